@@ -0,0 +1,96 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::ffi::c_void;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use qir_backend::*;
+
+/// Allocates the given number of qubits and puts the given number of them, counted from the last one, into a
+/// uniform superposition, so that the state has `2^superposed` non-zero amplitudes at the highest qubit locations.
+fn prepare(qubits: usize, superposed: usize) -> (Vec<*mut c_void>, Vec<*mut c_void>) {
+    let all: Vec<*mut c_void> = (0..qubits)
+        .map(|_| __quantum__rt__qubit_allocate())
+        .collect();
+    let superposed = all[qubits - superposed..].to_vec();
+    for &qubit in &superposed {
+        __quantum__qis__h__body(qubit);
+    }
+    // The sparse simulator queues H gates, so a T gate on every qubit applies them before the benchmark starts.
+    for &qubit in &superposed {
+        __quantum__qis__t__body(qubit);
+    }
+    (all, superposed)
+}
+
+/// Applies a T gate to every given qubit followed by a chain of CNOT gates between them, which keeps the number
+/// of non-zero amplitudes and therefore the storage of the state.
+fn layer(qubits: &[*mut c_void]) {
+    for &qubit in qubits {
+        __quantum__qis__t__body(qubit);
+    }
+    for pair in qubits.windows(2) {
+        __quantum__qis__cnot__body(pair[0], pair[1]);
+    }
+}
+
+fn release(qubits: Vec<*mut c_void>) {
+    for qubit in qubits {
+        __quantum__rt__qubit_release(qubit);
+    }
+}
+
+/// Benchmarks a layer of gates on states just below and just above the fraction of non-zero amplitudes from
+/// which they are stored densely, so that the sparse and the dense engine run on states of about the same size.
+pub fn dense_threshold(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Dense Threshold");
+    for qubits in [12, 16] {
+        // A quarter of the amplitudes is the threshold itself, which is still stored sparsely.
+        for (engine, superposed) in [("Sparse", qubits - 2), ("Dense", qubits - 1)] {
+            let (all, superposed) = prepare(qubits, superposed);
+            group.bench_function(BenchmarkId::new(engine, qubits), |b| {
+                b.iter(|| layer(&superposed));
+            });
+            release(all);
+        }
+    }
+    group.finish();
+}
+
+/// Benchmarks a layer of gates on the same amplitudes stored densely in a register of 16 qubits and sparsely in
+/// a register of 26 qubits, from which states are always stored sparsely. States that are dense enough to be
+/// stored densely near that limit take GiBs in either storage.
+pub fn dense_qubit_limit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Dense Qubit Limit");
+    group.sample_size(10);
+    for (engine, qubits) in [("Dense", 16), ("Sparse", 26)] {
+        let (all, superposed) = prepare(qubits, 16);
+        group.bench_function(BenchmarkId::new(engine, qubits), |b| {
+            b.iter(|| layer(&superposed));
+        });
+        release(all);
+    }
+    group.finish();
+}
+
+/// Benchmarks a layer of gates on the same sparse state at the highest locations of registers whose basis states
+/// are indexed by `u64`, `u128` and `BigUint` values.
+pub fn basis_index_widths(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Basis Index Widths");
+    for (index, qubits) in [("u64", 64), ("u128", 128), ("BigUint", 192)] {
+        let (all, superposed) = prepare(qubits, 10);
+        group.bench_function(BenchmarkId::new(index, qubits), |b| {
+            b.iter(|| layer(&superposed));
+        });
+        release(all);
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    dense_threshold,
+    dense_qubit_limit,
+    basis_index_widths
+);
+criterion_main!(benches);
@@ -0,0 +1,462 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Safe Rust API for the simulator, which checks every operation before applying it and reports invalid
+//! operations as errors instead of panicking.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use num_bigint::BigUint;
+use num_complex::Complex64;
+use qir_stdlib::Pauli;
+use rustc_hash::FxHashSet;
+
+use crate::{
+    density,
+    engine::{Engine, SimulatorKind},
+    mps,
+};
+
+/// The identifier of the next simulator that is created, which tells apart the qubits of different simulators.
+static NEXT_SIMULATOR: AtomicUsize = AtomicUsize::new(0);
+
+/// A handle to a qubit allocated by a `Simulator`, which is only valid for the simulator that allocated it and
+/// until it is released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Qubit {
+    id: usize,
+    owner: usize,
+}
+
+impl Qubit {
+    /// Returns the identifier of the qubit, which determines its bit in the indices of the state.
+    #[must_use]
+    pub fn id(self) -> usize {
+        self.id
+    }
+}
+
+/// A quantum simulator that is driven directly from Rust, independent of the simulator used by the QIR
+/// functions of this crate.
+///
+/// ```
+/// use qir_backend::Simulator;
+///
+/// let mut sim = Simulator::default();
+/// let (q0, q1) = (sim.allocate()?, sim.allocate()?);
+/// sim.h(q0)?;
+/// sim.mcx(&[q0], q1)?;
+/// assert_eq!(sim.measure(q0)?, sim.measure(q1)?);
+/// # Ok::<(), String>(())
+/// ```
+pub struct Simulator {
+    engine: Engine,
+    qubits: FxHashSet<usize>,
+    id: usize,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Simulator::new(SimulatorKind::default())
+    }
+}
+
+/// Generates the methods that apply single-qubit gates. Non-Clifford gates are given with their name.
+macro_rules! single_qubit_gates {
+    ($($(#[$meta:meta])* $name:ident $(($gate:literal))?;)*) => {
+        $(
+            $(#[$meta])*
+            /// # Errors
+            ///
+            /// Will return `Err` if the qubit is not allocated or if the simulator does not support the gate.
+            pub fn $name(&mut self, target: Qubit) -> Result<(), String> {
+                self.check(&[target])?;
+                $(self.check_clifford($gate)?;)?
+                self.engine.$name(target.id);
+                Ok(())
+            }
+        )*
+    };
+}
+
+/// Generates the methods that apply single-qubit rotations, which are given with their name.
+macro_rules! rotations {
+    ($($(#[$meta:meta])* $name:ident($gate:literal);)*) => {
+        $(
+            $(#[$meta])*
+            /// # Errors
+            ///
+            /// Will return `Err` if the qubit is not allocated or if the simulator does not support the rotation.
+            pub fn $name(&mut self, theta: f64, target: Qubit) -> Result<(), String> {
+                self.check(&[target])?;
+                self.check_clifford($gate)?;
+                self.engine.$name(theta, target.id);
+                Ok(())
+            }
+        )*
+    };
+}
+
+impl Simulator {
+    /// Creates a simulator of the given kind with no qubits allocated.
+    #[must_use]
+    pub fn new(kind: SimulatorKind) -> Self {
+        Simulator {
+            engine: Engine::new(kind),
+            qubits: FxHashSet::default(),
+            id: NEXT_SIMULATOR.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the kind of the simulator.
+    #[must_use]
+    pub fn kind(&self) -> SimulatorKind {
+        self.engine.kind()
+    }
+
+    /// Allocates a qubit in the |0⟩ state.
+    /// # Errors
+    ///
+    /// Will return `Err` if the simulator cannot hold another qubit.
+    pub fn allocate(&mut self) -> Result<Qubit, String> {
+        if self.kind() == SimulatorKind::DensityMatrix && self.qubits.len() >= density::MAX_QUBITS {
+            return Err(format!(
+                "The density matrix simulator supports at most {} qubits.",
+                density::MAX_QUBITS
+            ));
+        }
+        let id = self.engine.allocate();
+        self.qubits.insert(id);
+        Ok(Qubit { id, owner: self.id })
+    }
+
+    /// Releases the given qubit, measuring it in the process, after which its handle is no longer valid.
+    /// # Errors
+    ///
+    /// Will return `Err` if the qubit is not allocated.
+    pub fn release(&mut self, qubit: Qubit) -> Result<(), String> {
+        self.check(&[qubit])?;
+        self.engine.release(qubit.id);
+        self.qubits.remove(&qubit.id);
+        Ok(())
+    }
+
+    single_qubit_gates! {
+        /// Applies the Pauli X gate.
+        x;
+        /// Applies the Pauli Y gate.
+        y;
+        /// Applies the Pauli Z gate.
+        z;
+        /// Applies the Hadamard gate.
+        h;
+        /// Applies the S gate.
+        s;
+        /// Applies the adjoint of the S gate.
+        sadj;
+        /// Applies the T gate.
+        t("T");
+        /// Applies the adjoint of the T gate.
+        tadj("Adjoint T");
+    }
+
+    rotations! {
+        /// Applies a rotation about the X axis by the given angle.
+        rx("Rx");
+        /// Applies a rotation about the Y axis by the given angle.
+        ry("Ry");
+        /// Applies a rotation about the Z axis by the given angle.
+        rz("Rz");
+    }
+
+    /// Applies the X gate to the target if all controls are |1⟩.
+    /// # Errors
+    ///
+    /// Will return `Err` if any qubit is not allocated or appears more than once, or if the simulator does not
+    /// support the number of controls.
+    pub fn mcx(&mut self, ctls: &[Qubit], target: Qubit) -> Result<(), String> {
+        let qubits = [ctls, &[target]].concat();
+        self.check(&qubits)?;
+        if ctls.len() > 1 {
+            match self.kind() {
+                SimulatorKind::Stabilizer => self.check_clifford("multicontrolled X")?,
+                SimulatorKind::Mps { .. } => {
+                    return Err(
+                        "The MPS simulator does not support X gates with more than one control."
+                            .to_owned(),
+                    )
+                }
+                _ => (),
+            }
+        }
+        self.engine.mcx(&ids(ctls), target.id);
+        Ok(())
+    }
+
+    /// Applies the exponential of the multi-qubit Pauli operator with the given angle, exp(iθP), where the
+    /// Paulis act on the corresponding targets.
+    /// # Errors
+    ///
+    /// Will return `Err` if any target is not allocated or appears more than once, if the number of Paulis
+    /// differs from the number of targets, or if the simulator does not support the rotation.
+    pub fn exp(&mut self, paulis: &[Pauli], theta: f64, targets: &[Qubit]) -> Result<(), String> {
+        if paulis.len() != targets.len() {
+            return Err(format!(
+                "Expected as many Paulis as targets, but got {} Paulis and {} targets.",
+                paulis.len(),
+                targets.len()
+            ));
+        }
+        self.check(targets)?;
+        self.check_clifford("Exp")?;
+        self.engine.exp(paulis, theta, &ids(targets));
+        Ok(())
+    }
+
+    /// Measures the given qubit in the computational basis, collapsing the state based on the result, which is
+    /// `true` for |1⟩.
+    /// # Errors
+    ///
+    /// Will return `Err` if the qubit is not allocated.
+    pub fn measure(&mut self, qubit: Qubit) -> Result<bool, String> {
+        self.check(&[qubit])?;
+        Ok(self.engine.measure(qubit.id))
+    }
+
+    /// Measures the parity of the given qubits in the computational basis, collapsing the state based on the
+    /// result, which is `true` for odd parity.
+    /// # Errors
+    ///
+    /// Will return `Err` if there are no qubits, or if any qubit is not allocated or appears more than once.
+    pub fn joint_measure(&mut self, qubits: &[Qubit]) -> Result<bool, String> {
+        self.check_nonempty(qubits)?;
+        Ok(self.engine.joint_measure(&ids(qubits)))
+    }
+
+    /// Returns the probability that measuring the parity of the given qubits yields odd parity without
+    /// collapsing the state, which for a single qubit is the probability of measuring |1⟩.
+    /// # Errors
+    ///
+    /// Will return `Err` if there are no qubits, if any qubit is not allocated or appears more than once, or if
+    /// the simulator does not keep a state, such as resource estimation.
+    pub fn probability(&mut self, qubits: &[Qubit]) -> Result<f64, String> {
+        self.check_nonempty(qubits)?;
+        self.check_state("computing probabilities")?;
+        Ok(self.engine.joint_probability(&ids(qubits)))
+    }
+
+    /// Returns the expectation value ⟨ψ|P|ψ⟩ of the Pauli string P that applies the given Paulis to the given
+    /// qubits, which is computed exactly and leaves the state unchanged.
+    /// # Errors
+    ///
+    /// Will return `Err` if any qubit is not allocated or appears more than once, if the number of Paulis
+    /// differs from the number of qubits, or if the simulator does not keep a state, such as resource estimation.
+    pub fn expectation(&mut self, paulis: &[Pauli], qubits: &[Qubit]) -> Result<f64, String> {
+        self.hamiltonian_expectation(&[(1.0, paulis, qubits)])
+    }
+
+    /// Returns the expectation value of a weighted sum of Pauli strings, such as a Hamiltonian, where each term
+    /// is given by its coefficient, its Paulis and the qubits they apply to.
+    /// # Errors
+    ///
+    /// Will return `Err` if any qubit of a term is not allocated or appears more than once in the term, if the
+    /// number of Paulis of a term differs from its number of qubits, or if the simulator does not keep a state,
+    /// such as resource estimation.
+    pub fn hamiltonian_expectation(
+        &mut self,
+        terms: &[(f64, &[Pauli], &[Qubit])],
+    ) -> Result<f64, String> {
+        let mut pauli_lists = Vec::with_capacity(terms.len());
+        for (coefficient, paulis, qubits) in terms {
+            if paulis.len() != qubits.len() {
+                return Err(format!(
+                    "Expected as many Paulis as qubits, but got {} Paulis and {} qubits.",
+                    paulis.len(),
+                    qubits.len()
+                ));
+            }
+            self.check(qubits)?;
+            let pauli_list: Vec<(Pauli, usize)> = paulis
+                .iter()
+                .zip(qubits.iter())
+                .map(|(pauli, qubit)| (*pauli, qubit.id))
+                .collect();
+            pauli_lists.push((*coefficient, pauli_list));
+        }
+        self.check_state("computing probabilities")?;
+        Ok(pauli_lists
+            .iter()
+            .map(|(coefficient, pauli_list)| coefficient * self.engine.expectation(pauli_list))
+            .sum())
+    }
+
+    /// Returns a sorted copy of the non-zero amplitudes of the state, where bit i of an index is the value of
+    /// the qubit with identifier i, along with the number of allocated qubits. On the density matrix simulator,
+    /// the pairs are the non-zero entries of the density matrix instead, as for `capture_quantum_state`.
+    /// # Errors
+    ///
+    /// Will return `Err` if the simulator cannot capture the state, such as the stabilizer simulator.
+    pub fn state(&mut self) -> Result<(Vec<(BigUint, Complex64)>, usize), String> {
+        match self.kind() {
+            SimulatorKind::Stabilizer => {
+                return Err(
+                    "The stabilizer simulator does not support capturing the state vector."
+                        .to_owned(),
+                )
+            }
+            SimulatorKind::Mps { .. } if self.qubits.len() > mps::MAX_STATE_QUBITS => {
+                return Err(format!(
+                    "The MPS simulator can only capture the state of up to {} qubits.",
+                    mps::MAX_STATE_QUBITS
+                ))
+            }
+            _ => self.check_state("capturing the state")?,
+        }
+        Ok(self.engine.get_state())
+    }
+
+    /// Checks that the given qubits were allocated by this simulator, are not released and are distinct.
+    fn check(&self, qubits: &[Qubit]) -> Result<(), String> {
+        let mut seen = FxHashSet::default();
+        for qubit in qubits {
+            if qubit.owner != self.id {
+                return Err(format!(
+                    "Qubit {} was allocated by another simulator.",
+                    qubit.id
+                ));
+            }
+            if !self.qubits.contains(&qubit.id) {
+                return Err(format!("Qubit {} is not allocated.", qubit.id));
+            }
+            if !seen.insert(qubit.id) {
+                return Err(format!("Qubit {} is used more than once.", qubit.id));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_nonempty(&self, qubits: &[Qubit]) -> Result<(), String> {
+        if qubits.is_empty() {
+            return Err("Expected at least one qubit.".to_owned());
+        }
+        self.check(qubits)
+    }
+
+    /// Checks that the simulator supports the given non-Clifford gate, which the stabilizer simulator does not.
+    fn check_clifford(&self, gate: &str) -> Result<(), String> {
+        if self.kind() == SimulatorKind::Stabilizer {
+            return Err(format!(
+                "The stabilizer simulator does not support the non-Clifford gate {gate}."
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that the simulator keeps a state that the given operation can inspect, which resource estimation
+    /// does not.
+    fn check_state(&self, operation: &str) -> Result<(), String> {
+        if let SimulatorKind::Estimate { .. } = self.kind() {
+            return Err(format!("Resource estimation does not support {operation}."));
+        }
+        Ok(())
+    }
+}
+
+fn ids(qubits: &[Qubit]) -> Vec<usize> {
+    qubits.iter().map(|qubit| qubit.id).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Simulator;
+    use crate::SimulatorKind;
+    use num_bigint::BigUint;
+    use qir_stdlib::Pauli;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_bell_state() -> Result<(), String> {
+        let mut sim = Simulator::default();
+        let (q0, q1) = (sim.allocate()?, sim.allocate()?);
+        sim.h(q0)?;
+        sim.mcx(&[q0], q1)?;
+        let (state, qubit_count) = sim.state()?;
+        assert_eq!(qubit_count, 2);
+        assert_eq!(
+            state
+                .iter()
+                .map(|(index, _)| index.clone())
+                .collect::<Vec<_>>(),
+            [0_u32, 3].map(BigUint::from)
+        );
+        assert!((sim.probability(&[q0])? - 0.5).abs() < 1e-10);
+        assert!(sim.probability(&[q0, q1])?.abs() < 1e-10);
+        assert!((sim.expectation(&[Pauli::Y, Pauli::Y], &[q0, q1])? + 1.0).abs() < 1e-10);
+        assert!(
+            (sim.hamiltonian_expectation(&[
+                (0.5, &[Pauli::X, Pauli::X], &[q0, q1]),
+                (2.0, &[Pauli::Z], &[q1]),
+            ])? - 0.5)
+                .abs()
+                < 1e-10
+        );
+        let res = sim.measure(q0)?;
+        assert_eq!(sim.joint_measure(&[q1])?, res);
+        sim.release(q0)?;
+        sim.release(q1)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotations() -> Result<(), String> {
+        let mut sim = Simulator::default();
+        let (q0, q1) = (sim.allocate()?, sim.allocate()?);
+        sim.rx(PI / 2.0, q0)?;
+        assert!((sim.probability(&[q0])? - 0.5).abs() < 1e-10);
+        sim.rx(-PI / 2.0, q0)?;
+        // exp(-iπ/2 XX) flips both qubits up to a global phase.
+        sim.exp(&[Pauli::X, Pauli::X], -PI / 2.0, &[q0, q1])?;
+        assert!((sim.probability(&[q0])? - 1.0).abs() < 1e-10);
+        assert!((sim.probability(&[q1])? - 1.0).abs() < 1e-10);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_operations_return_errors() -> Result<(), String> {
+        let mut sim = Simulator::default();
+        let qubit = sim.allocate()?;
+        assert_eq!(
+            sim.mcx(&[qubit], qubit),
+            Err("Qubit 0 is used more than once.".to_owned())
+        );
+        assert!(sim.exp(&[Pauli::X], PI, &[]).is_err());
+        assert!(sim.joint_measure(&[]).is_err());
+        sim.release(qubit)?;
+        assert_eq!(sim.h(qubit), Err("Qubit 0 is not allocated.".to_owned()));
+        let other = Simulator::default().allocate()?;
+        assert_eq!(
+            sim.h(other),
+            Err("Qubit 0 was allocated by another simulator.".to_owned())
+        );
+
+        let mut sim = Simulator::new(SimulatorKind::Stabilizer);
+        let qubit = sim.allocate()?;
+        assert_eq!(
+            sim.rx(PI, qubit),
+            Err("The stabilizer simulator does not support the non-Clifford gate Rx.".to_owned())
+        );
+        assert!(sim.state().is_err());
+        sim.h(qubit)?;
+        assert!((sim.probability(&[qubit])? - 0.5).abs() < 1e-10);
+
+        let mut sim = Simulator::new(SimulatorKind::Mps { bond_dimension: 4 });
+        let qubits = [sim.allocate()?, sim.allocate()?, sim.allocate()?];
+        assert_eq!(
+            sim.mcx(&qubits[..2], qubits[2]),
+            Err(
+                "The MPS simulator does not support X gates with more than one control.".to_owned()
+            )
+        );
+        Ok(())
+    }
+}
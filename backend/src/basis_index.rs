@@ -0,0 +1,121 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use num_bigint::BigUint;
+use num_traits::Zero;
+use std::{
+    fmt::Display,
+    hash::Hash,
+    ops::{BitAnd, BitOr, BitXor},
+};
+
+/// `BasisIndex` trait allows for indexing the basis states of a sparse state vector, where bit i of an index is
+/// the value of the qubit at location i. Fixed-width integers avoid the heap allocations of `BigUint` but can
+/// only index states of as many qubit locations as they have bits.
+pub(crate) trait BasisIndex:
+    Clone
+    + Eq
+    + Hash
+    + Ord
+    + Display
+    + Zero
+    + for<'a> BitAnd<&'a Self, Output = Self>
+    + for<'a> BitOr<&'a Self, Output = Self>
+    + for<'a> BitXor<&'a Self, Output = Self>
+{
+    /// The number of qubit locations that an index can hold.
+    const CAPACITY: usize;
+
+    fn bit(&self, loc: u64) -> bool;
+
+    fn set_bit(&mut self, loc: u64, value: bool);
+
+    fn count_ones(&self) -> u64;
+
+    fn from_biguint(index: &BigUint) -> Self;
+
+    fn to_biguint(&self) -> BigUint;
+}
+
+impl BasisIndex for BigUint {
+    const CAPACITY: usize = usize::MAX;
+
+    fn bit(&self, loc: u64) -> bool {
+        BigUint::bit(self, loc)
+    }
+
+    fn set_bit(&mut self, loc: u64, value: bool) {
+        BigUint::set_bit(self, loc, value);
+    }
+
+    fn count_ones(&self) -> u64 {
+        BigUint::count_ones(self)
+    }
+
+    fn from_biguint(index: &BigUint) -> Self {
+        index.clone()
+    }
+
+    fn to_biguint(&self) -> BigUint {
+        self.clone()
+    }
+}
+
+macro_rules! fixed_width_basis_index {
+    ($($ty:ty),*) => {
+        $(
+            impl BasisIndex for $ty {
+                const CAPACITY: usize = <$ty>::BITS as usize;
+
+                fn bit(&self, loc: u64) -> bool {
+                    (*self >> loc) & 1 != 0
+                }
+
+                fn set_bit(&mut self, loc: u64, value: bool) {
+                    let mask: $ty = 1 << loc;
+                    if value {
+                        *self |= mask;
+                    } else {
+                        *self &= !mask;
+                    }
+                }
+
+                fn count_ones(&self) -> u64 {
+                    u64::from(<$ty>::count_ones(*self))
+                }
+
+                fn from_biguint(index: &BigUint) -> Self {
+                    <$ty>::try_from(index).unwrap_or_else(|_| {
+                        panic!("Basis index {index} does not fit into {} bits.", Self::CAPACITY)
+                    })
+                }
+
+                fn to_biguint(&self) -> BigUint {
+                    BigUint::from(*self)
+                }
+            }
+        )*
+    };
+}
+
+fixed_width_basis_index!(u64, u128);
+
+#[cfg(test)]
+mod tests {
+    use super::BasisIndex;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn test_fixed_width_indices_match_biguint() {
+        let mut big = BigUint::from(0b1011_u32);
+        let mut small = 0b1011_u128;
+        for (loc, value) in [(0, false), (70, true), (3, true), (2, true), (70, false)] {
+            BasisIndex::set_bit(&mut big, loc, value);
+            small.set_bit(loc, value);
+            assert_eq!(small.to_biguint(), big);
+            assert_eq!(u128::from_biguint(&big), small);
+            assert_eq!(BasisIndex::count_ones(&small), BasisIndex::count_ones(&big));
+            assert!((0..128).all(|loc| small.bit(loc) == BasisIndex::bit(&big, loc)));
+        }
+    }
+}
@@ -0,0 +1,577 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Dense state vector simulation, which stores every amplitude of the state in a vector indexed by the bits of
+//! the qubit locations. This avoids hashing the indices of the sparse simulator and is faster once a large
+//! fraction of the amplitudes is non-zero.
+
+use crate::{
+    basis_index::BasisIndex,
+    dump::StateDump,
+    nearly_zero::NearlyZero,
+    simulator::{check_for_duplicates, random_sample, FlushLevel, QuantumSim, SparseState},
+};
+use num_bigint::BigUint;
+use num_complex::Complex64;
+use num_traits::{One, Zero};
+use qir_stdlib::Pauli;
+use rustc_hash::FxHashMap;
+use std::{cmp::Ordering, f64::consts::FRAC_1_SQRT_2};
+
+/// A 2x2 matrix given by its rows.
+pub(crate) type Matrix = [[Complex64; 2]; 2];
+
+/// The `DenseSim` struct contains the dense state vector of a simulation.
+pub(crate) struct DenseSim {
+    /// The amplitudes of the state, where bit i of an index is the value of the qubit at location i.
+    state: Vec<Complex64>,
+
+    /// The mapping from qubit identifiers to locations, which are always the numbers 0 to n-1 for n qubits.
+    pub(crate) id_map: FxHashMap<usize, usize>,
+}
+
+impl DenseSim {
+    /// Converts the state of the given sparse simulator into a dense state, applying any queued operations.
+    pub(crate) fn from_sparse<K: BasisIndex>(sim: &mut QuantumSim<K>) -> Self {
+        let mut ids: Vec<usize> = sim.id_map.keys().copied().collect();
+        sim.flush_queue(&ids, FlushLevel::HRxRy);
+
+        // The sparse locations may have gaps, so the qubits are assigned to consecutive locations in order.
+        ids.sort_unstable_by_key(|id| sim.id_map[id]);
+        let locs: Vec<u64> = ids.iter().map(|id| sim.id_map[id] as u64).collect();
+        let mut state = vec![Complex64::zero(); 1 << ids.len()];
+        for (key, value) in &sim.state {
+            let index = locs.iter().enumerate().fold(0, |index, (new_loc, &loc)| {
+                index | usize::from(key.bit(loc)) << new_loc
+            });
+            state[index] = *value;
+        }
+
+        DenseSim {
+            state,
+            id_map: ids
+                .into_iter()
+                .enumerate()
+                .map(|(loc, id)| (id, loc))
+                .collect(),
+        }
+    }
+
+    /// Converts the dense state into a sparse simulator, dropping the amplitudes that are nearly zero. The
+    /// dense state never has more qubits than fit into a `u64` index.
+    pub(crate) fn to_sparse(&self) -> QuantumSim<u64> {
+        let state: SparseState<u64> = self
+            .state
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| !value.is_nearly_zero())
+            .map(|(index, value)| (index as u64, *value))
+            .collect();
+        QuantumSim::with_state(state, self.id_map.clone())
+    }
+
+    /// Returns the number of allocated qubits.
+    pub(crate) fn qubit_count(&self) -> usize {
+        self.id_map.len()
+    }
+
+    /// Checks whether the fraction of amplitudes that are not nearly zero is below the given threshold. The state
+    /// is only scanned until enough amplitudes are found, since this is checked after every operation.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub(crate) fn is_sparser_than(&self, threshold: f64) -> bool {
+        let limit = (threshold * self.state.len() as f64).ceil() as usize;
+        self.state
+            .iter()
+            .filter(|value| !value.is_nearly_zero())
+            .take(limit)
+            .count()
+            < limit
+    }
+
+    fn loc(&self, id: usize) -> usize {
+        *self
+            .id_map
+            .get(&id)
+            .unwrap_or_else(|| panic!("Unable to find qubit with id {id}"))
+    }
+
+    /// Returns the mask of the locations of the given qubits, which must be distinct.
+    fn mask(&self, ids: &[usize]) -> usize {
+        check_for_duplicates(ids);
+        ids.iter().fold(0, |mask, &id| mask | 1 << self.loc(id))
+    }
+
+    /// Allocates a fresh qubit, returning its identifier. Like the sparse simulator, this uses the lowest
+    /// available identifier.
+    #[must_use]
+    pub(crate) fn allocate(&mut self) -> usize {
+        let id = (0..=self.id_map.len())
+            .find(|id| !self.id_map.contains_key(id))
+            .expect("There should be an available qubit identifier.");
+        // The new qubit takes the highest location, so the amplitudes with it in |1⟩ are the new upper half.
+        self.id_map.insert(id, self.id_map.len());
+        self.state.resize(self.state.len() * 2, Complex64::zero());
+        id
+    }
+
+    /// Releases the given qubit, collapsing its state in the process and removing its location from the state.
+    /// # Panics
+    ///
+    /// The function will panic if the given id does not correpsond to an allocated qubit.
+    pub(crate) fn release(&mut self, id: usize) {
+        let loc = self.loc(id);
+        if self.measure(id) {
+            self.x(id);
+        }
+
+        let low_mask = (1 << loc) - 1;
+        self.state = (0..self.state.len() / 2)
+            .map(|index| self.state[(index & !low_mask) << 1 | (index & low_mask)])
+            .collect();
+        self.id_map.remove(&id);
+        for other_loc in self.id_map.values_mut() {
+            if *other_loc > loc {
+                *other_loc -= 1;
+            }
+        }
+    }
+
+    /// Returns a sorted copy of the non-zero amplitudes, indexed by the qubits in order of their identifiers,
+    /// along with the number of allocated qubits.
+    #[must_use]
+    pub(crate) fn get_state(&mut self) -> (Vec<(BigUint, Complex64)>, usize) {
+        let mut ids: Vec<usize> = self.id_map.keys().copied().collect();
+        ids.sort_unstable();
+        let locs: Vec<usize> = ids.iter().map(|&id| self.id_map[&id]).collect();
+        let mut state: Vec<(BigUint, Complex64)> = self
+            .state
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| !value.is_nearly_zero())
+            .map(|(index, value)| {
+                let mut key = BigUint::zero();
+                for (bit, loc) in locs.iter().enumerate() {
+                    if index & 1 << loc != 0 {
+                        key.set_bit(bit as u64, true);
+                    }
+                }
+                (key, *value)
+            })
+            .collect();
+        state.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        (state, ids.len())
+    }
+
+    /// Returns the current state vector as a table of the basis states with non-zero amplitudes.
+    pub(crate) fn dump(&mut self) -> String {
+        let (state, qubits) = self.get_state();
+        StateDump::new(state, qubits, 0.0).to_table()
+    }
+
+    fn parity_probability(&self, mask: usize) -> f64 {
+        self.state
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| (index & mask).count_ones() & 1 != 0)
+            .map(|(_, value)| value.norm_sqr())
+            .sum()
+    }
+
+    /// Removes the amplitudes whose parity at the given mask does not match the given value and normalizes the
+    /// state.
+    fn collapse(&mut self, mask: usize, val: bool) {
+        let mut norm = 0.0;
+        for (index, value) in self.state.iter_mut().enumerate() {
+            if ((index & mask).count_ones() & 1 != 0) == val {
+                norm += value.norm_sqr();
+            } else {
+                *value = Complex64::zero();
+            }
+        }
+        let scaling = 1.0 / norm.sqrt();
+        for value in &mut self.state {
+            *value *= scaling;
+        }
+    }
+
+    /// Checks the probability of parity measurement in the computational basis for the given set of
+    /// qubits.
+    /// # Panics
+    ///
+    /// This function will panic if the given ids do not all correspond to allocated qubits.
+    /// This function will panic if there are duplicate ids in the given list.
+    #[must_use]
+    pub(crate) fn joint_probability(&mut self, ids: &[usize]) -> f64 {
+        self.parity_probability(self.mask(ids))
+    }
+
+    /// Measures the qubit with the given id, collapsing the state based on the measured result.
+    /// # Panics
+    ///
+    /// This funciton will panic if the given identifier does not correspond to an allocated qubit.
+    #[must_use]
+    pub(crate) fn measure(&mut self, id: usize) -> bool {
+        self.joint_measure(&[id])
+    }
+
+    /// Performs a joint measurement to get the parity of the given qubits, collapsing the state
+    /// based on the measured result.
+    /// # Panics
+    ///
+    /// This function will panic if any of the given identifiers do not correspond to an allocated qubit.
+    /// This function will panic if any of the given identifiers are duplicates.
+    #[must_use]
+    pub(crate) fn joint_measure(&mut self, ids: &[usize]) -> bool {
+        let mask = self.mask(ids);
+        let res = random_sample() < self.parity_probability(mask);
+        self.collapse(mask, res);
+        res
+    }
+
+    /// Swaps the mapped ids for the given qubits.
+    pub(crate) fn swap_qubit_ids(&mut self, qubit1: usize, qubit2: usize) {
+        let (loc1, loc2) = (self.loc(qubit1), self.loc(qubit2));
+        self.id_map.insert(qubit1, loc2);
+        self.id_map.insert(qubit2, loc1);
+    }
+
+    /// Applies a trajectory of the amplitude damping channel with the given damping probability to the qubit
+    /// with the given id, like the sparse simulator.
+    /// # Panics
+    ///
+    /// This function will panic if the given identifier does not correspond to an allocated qubit.
+    pub(crate) fn amplitude_damping(&mut self, gamma: f64, id: usize) {
+        let mask = self.mask(&[id]);
+        let decay_probability = gamma * self.parity_probability(mask);
+        if random_sample() < decay_probability {
+            self.collapse(mask, true);
+            self.x(id);
+        } else {
+            let scaling = 1.0 / (1.0 - decay_probability).sqrt();
+            let damped_scaling = (1.0 - gamma).sqrt() * scaling;
+            for (index, value) in self.state.iter_mut().enumerate() {
+                *value *= if index & mask == 0 {
+                    scaling
+                } else {
+                    damped_scaling
+                };
+            }
+        }
+    }
+
+    /// Applies the given matrix to the target for the amplitudes where all controls are set.
+    fn controlled_matrix(&mut self, ctls: &[usize], target: usize, matrix: Matrix) {
+        let mut ids = ctls.to_vec();
+        ids.push(target);
+        let target_mask = 1 << self.loc(target);
+        let ctls_mask = self.mask(&ids) & !target_mask;
+        apply_controlled_matrix(&mut self.state, ctls_mask, target_mask, &matrix);
+    }
+
+    /// Multiplies the amplitudes where all controls and the target are set by the given phase.
+    pub(crate) fn mcphase(&mut self, ctls: &[usize], phase: Complex64, target: usize) {
+        let mut ids = ctls.to_vec();
+        ids.push(target);
+        let mask = self.mask(&ids);
+        for (index, value) in self.state.iter_mut().enumerate() {
+            if index & mask == mask {
+                *value *= phase;
+            }
+        }
+    }
+
+    pub(crate) fn x(&mut self, target: usize) {
+        self.mcx(&[], target);
+    }
+
+    pub(crate) fn mcx(&mut self, ctls: &[usize], target: usize) {
+        let (zero, one) = (Complex64::zero(), Complex64::one());
+        self.controlled_matrix(ctls, target, [[zero, one], [one, zero]]);
+    }
+
+    pub(crate) fn y(&mut self, target: usize) {
+        self.mcy(&[], target);
+    }
+
+    pub(crate) fn mcy(&mut self, ctls: &[usize], target: usize) {
+        let (zero, i) = (Complex64::zero(), Complex64::i());
+        self.controlled_matrix(ctls, target, [[zero, -i], [i, zero]]);
+    }
+
+    pub(crate) fn z(&mut self, target: usize) {
+        self.mcz(&[], target);
+    }
+
+    pub(crate) fn mcz(&mut self, ctls: &[usize], target: usize) {
+        self.mcphase(ctls, -Complex64::one(), target);
+    }
+
+    pub(crate) fn h(&mut self, target: usize) {
+        self.mch(&[], target);
+    }
+
+    pub(crate) fn mch(&mut self, ctls: &[usize], target: usize) {
+        let value = Complex64::new(FRAC_1_SQRT_2, 0.0);
+        self.controlled_matrix(ctls, target, [[value, value], [value, -value]]);
+    }
+
+    pub(crate) fn s(&mut self, target: usize) {
+        self.mcs(&[], target);
+    }
+
+    pub(crate) fn mcs(&mut self, ctls: &[usize], target: usize) {
+        self.mcphase(ctls, Complex64::i(), target);
+    }
+
+    pub(crate) fn sadj(&mut self, target: usize) {
+        self.mcsadj(&[], target);
+    }
+
+    pub(crate) fn mcsadj(&mut self, ctls: &[usize], target: usize) {
+        self.mcphase(ctls, -Complex64::i(), target);
+    }
+
+    pub(crate) fn t(&mut self, target: usize) {
+        self.mct(&[], target);
+    }
+
+    pub(crate) fn mct(&mut self, ctls: &[usize], target: usize) {
+        self.mcphase(ctls, Complex64::new(FRAC_1_SQRT_2, FRAC_1_SQRT_2), target);
+    }
+
+    pub(crate) fn tadj(&mut self, target: usize) {
+        self.mctadj(&[], target);
+    }
+
+    pub(crate) fn mctadj(&mut self, ctls: &[usize], target: usize) {
+        self.mcphase(ctls, Complex64::new(FRAC_1_SQRT_2, -FRAC_1_SQRT_2), target);
+    }
+
+    pub(crate) fn rx(&mut self, theta: f64, target: usize) {
+        self.mcrx(&[], theta, target);
+    }
+
+    pub(crate) fn mcrx(&mut self, ctls: &[usize], theta: f64, target: usize) {
+        self.controlled_matrix(ctls, target, rx_matrix(theta));
+    }
+
+    pub(crate) fn ry(&mut self, theta: f64, target: usize) {
+        self.mcry(&[], theta, target);
+    }
+
+    pub(crate) fn mcry(&mut self, ctls: &[usize], theta: f64, target: usize) {
+        self.controlled_matrix(ctls, target, ry_matrix(theta));
+    }
+
+    pub(crate) fn rz(&mut self, theta: f64, target: usize) {
+        self.mcrz(&[], theta, target);
+    }
+
+    pub(crate) fn mcrz(&mut self, ctls: &[usize], theta: f64, target: usize) {
+        self.controlled_matrix(ctls, target, rz_matrix(theta));
+    }
+
+    pub(crate) fn exp(&mut self, paulis: &[Pauli], theta: f64, targets: &[usize]) {
+        self.mcexp(&[], paulis, theta, targets);
+    }
+
+    /// Applies the exponential of the multi-qubit Pauli operator with the given angle, exp(iθP), for the
+    /// amplitudes where all controls are set.
+    pub(crate) fn mcexp(
+        &mut self,
+        ctls: &[usize],
+        paulis: &[Pauli],
+        theta: f64,
+        targets: &[usize],
+    ) {
+        let mut ids = ctls.to_vec();
+        ids.extend_from_slice(targets);
+        check_for_duplicates(&ids);
+        let ctls_mask = ctls.iter().fold(0, |mask, &id| mask | 1 << self.loc(id));
+
+        // P|k⟩ = i^y (-1)^|k ∧ yz| |k ⊕ xy⟩, where y is the number of Pauli-Y operators.
+        let (mut xy_mask, mut yz_mask, mut y_count) = (0, 0, 0);
+        for (pauli, &target) in paulis.iter().zip(targets) {
+            let bit = 1 << self.loc(target);
+            match pauli {
+                Pauli::I => (),
+                Pauli::X => xy_mask |= bit,
+                Pauli::Y => {
+                    xy_mask |= bit;
+                    yz_mask |= bit;
+                    y_count += 1;
+                }
+                Pauli::Z => yz_mask |= bit,
+            }
+        }
+        apply_pauli_exp(&mut self.state, ctls_mask, xy_mask, yz_mask, y_count, theta);
+    }
+}
+
+/// Applies the given matrix to the bit of the target mask for the entries of the state where all bits of the
+/// controls mask are set.
+pub(crate) fn apply_controlled_matrix(
+    state: &mut [Complex64],
+    ctls_mask: usize,
+    target_mask: usize,
+    matrix: &Matrix,
+) {
+    for index in 0..state.len() {
+        if index & target_mask == 0 && index & ctls_mask == ctls_mask {
+            let flipped = index | target_mask;
+            let (zero, one) = (state[index], state[flipped]);
+            state[index] = matrix[0][0] * zero + matrix[0][1] * one;
+            state[flipped] = matrix[1][0] * zero + matrix[1][1] * one;
+        }
+    }
+}
+
+/// Applies exp(iθP) for the entries of the state where all bits of the controls mask are set, where the Pauli
+/// operator P is given by the bits it flips, the bits whose value adds a phase of -1, and its number of Pauli-Y
+/// operators.
+pub(crate) fn apply_pauli_exp(
+    state: &mut [Complex64],
+    ctls_mask: usize,
+    xy_mask: usize,
+    yz_mask: usize,
+    y_count: u32,
+    theta: f64,
+) {
+    let id_coeff = Complex64::new(theta.cos(), 0.0);
+    let pauli_coeff = Complex64::new(0.0, theta.sin()) * Complex64::i().powu(y_count);
+    let sign = |index: usize| {
+        if (index & yz_mask).count_ones() & 1 == 0 {
+            1.0
+        } else {
+            -1.0
+        }
+    };
+
+    for index in 0..state.len() {
+        if index & ctls_mask != ctls_mask {
+            continue;
+        }
+        let flipped = index ^ xy_mask;
+        match index.cmp(&flipped) {
+            Ordering::Equal => state[index] *= id_coeff + pauli_coeff * sign(index),
+            Ordering::Less => {
+                let (value, flipped_value) = (state[index], state[flipped]);
+                state[index] = id_coeff * value + pauli_coeff * sign(flipped) * flipped_value;
+                state[flipped] = id_coeff * flipped_value + pauli_coeff * sign(index) * value;
+            }
+            // The pair was updated when visiting the lower index.
+            Ordering::Greater => (),
+        }
+    }
+}
+
+/// Returns the matrix of a rotation about the X axis by the given angle.
+pub(crate) fn rx_matrix(theta: f64) -> Matrix {
+    let (sin, cos) = (theta / 2.0).sin_cos();
+    [
+        [Complex64::new(cos, 0.0), Complex64::new(0.0, -sin)],
+        [Complex64::new(0.0, -sin), Complex64::new(cos, 0.0)],
+    ]
+}
+
+/// Returns the matrix of a rotation about the Y axis by the given angle.
+pub(crate) fn ry_matrix(theta: f64) -> Matrix {
+    let (sin, cos) = (theta / 2.0).sin_cos();
+    [
+        [Complex64::new(cos, 0.0), Complex64::new(-sin, 0.0)],
+        [Complex64::new(sin, 0.0), Complex64::new(cos, 0.0)],
+    ]
+}
+
+/// Returns the matrix of a rotation about the Z axis by the given angle.
+pub(crate) fn rz_matrix(theta: f64) -> Matrix {
+    [
+        [Complex64::new(0.0, -theta / 2.0).exp(), Complex64::zero()],
+        [Complex64::zero(), Complex64::new(0.0, theta / 2.0).exp()],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DenseSim;
+    use crate::{nearly_zero::NearlyZero, simulator::QuantumSim};
+    use qir_stdlib::Pauli;
+
+    fn assert_same_state(dense: &mut DenseSim, sparse: &mut QuantumSim<u64>) {
+        let (dense_state, dense_count) = dense.get_state();
+        let (sparse_state, sparse_count) = sparse.get_state();
+        assert_eq!(dense_count, sparse_count);
+        assert_eq!(dense_state.len(), sparse_state.len());
+        for ((dense_key, dense_value), (sparse_key, sparse_value)) in
+            dense_state.iter().zip(&sparse_state)
+        {
+            assert_eq!(dense_key, sparse_key);
+            assert!((dense_value - sparse_value).is_nearly_zero());
+        }
+    }
+
+    // Applies the same gates to a dense and a sparse simulator and verifies that their states match.
+    #[test]
+    fn test_gates_match_sparse() {
+        let mut sparse = QuantumSim::<u64>::default();
+        let qubits: Vec<usize> = (0..4).map(|_| sparse.allocate()).collect();
+        sparse.h(qubits[0]);
+        let mut dense = DenseSim::from_sparse(&mut sparse);
+        assert_same_state(&mut dense, &mut sparse);
+
+        macro_rules! apply {
+            ($($gate:ident($($arg:expr),*);)*) => {
+                $(
+                    dense.$gate($($arg),*);
+                    sparse.$gate($($arg),*);
+                    assert_same_state(&mut dense, &mut sparse);
+                )*
+            };
+        }
+        apply! {
+            h(qubits[1]);
+            mcx(&[qubits[0]], qubits[2]);
+            t(qubits[2]);
+            rx(0.3, qubits[3]);
+            mcry(&[qubits[1], qubits[2]], 1.1, qubits[3]);
+            s(qubits[0]);
+            mch(&[qubits[3]], qubits[0]);
+            y(qubits[1]);
+            mcrz(&[qubits[0]], -0.7, qubits[1]);
+            sadj(qubits[3]);
+            mcy(&[qubits[2]], qubits[0]);
+            mcz(&[qubits[0], qubits[1]], qubits[3]);
+            exp(&[Pauli::X, Pauli::Y, Pauli::Z], 0.4, &[qubits[0], qubits[1], qubits[3]]);
+            mcexp(&[qubits[2]], &[Pauli::Z, Pauli::Z], 0.9, &[qubits[0], qubits[1]]);
+            tadj(qubits[0]);
+            swap_qubit_ids(qubits[0], qubits[3]);
+            ry(2.0, qubits[0]);
+            mctadj(&[qubits[1]], qubits[3]);
+        }
+    }
+
+    #[test]
+    fn test_release_removes_location() {
+        let mut sparse = QuantumSim::<u64>::default();
+        let mut dense = DenseSim::from_sparse(&mut sparse);
+        let q0 = dense.allocate();
+        let q1 = dense.allocate();
+        let q2 = dense.allocate();
+        dense.x(q2);
+        dense.h(q0);
+        dense.release(q1);
+        assert_eq!(dense.qubit_count(), 2);
+        assert!((dense.joint_probability(&[q2]) - 1.0).is_nearly_zero());
+        assert!((dense.joint_probability(&[q0]) - 0.5).is_nearly_zero());
+        assert_eq!(dense.allocate(), q1);
+        assert!(dense.joint_probability(&[q1]).is_nearly_zero());
+
+        let mut sparse = dense.to_sparse();
+        assert!((sparse.joint_probability(&[q2]) - 1.0).is_nearly_zero());
+        assert!((sparse.joint_probability(&[q0]) - 0.5).is_nearly_zero());
+    }
+}
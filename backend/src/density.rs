@@ -0,0 +1,496 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Density matrix simulation, which tracks the mixed state of the qubits exactly. Noise is applied as quantum
+//! channels given by their Kraus operators instead of sampling errors, so that the state of a noisy program
+//! does not depend on the shot. The density matrix of n qubits has 4^n entries, which limits the simulation to
+//! few qubits.
+
+use crate::{
+    dense::{apply_controlled_matrix, apply_pauli_exp, rx_matrix, ry_matrix, rz_matrix, Matrix},
+    nearly_zero::NearlyZero,
+    noise::amplitude_damping_kraus,
+    simulator::{check_for_duplicates, random_sample},
+};
+use num_bigint::BigUint;
+use num_complex::Complex64;
+use num_traits::{One, Zero};
+use qir_stdlib::Pauli;
+use rustc_hash::FxHashMap;
+use std::{f64::consts::FRAC_1_SQRT_2, fmt::Write};
+
+/// The largest number of qubits, whose density matrix already takes 1 GiB.
+pub(crate) const MAX_QUBITS: usize = 13;
+
+/// The `DensityMatrixSim` struct contains the density matrix of a simulation.
+pub(crate) struct DensityMatrixSim {
+    /// The entries of the density matrix, where bit 2i of an index is the value of the qubit at location i in
+    /// the column and bit 2i + 1 is its value in the row.
+    state: Vec<Complex64>,
+
+    /// The mapping from qubit identifiers to locations, which are always the numbers 0 to n-1 for n qubits.
+    id_map: FxHashMap<usize, usize>,
+}
+
+impl Default for DensityMatrixSim {
+    fn default() -> Self {
+        DensityMatrixSim {
+            state: vec![Complex64::one()],
+            id_map: FxHashMap::default(),
+        }
+    }
+}
+
+/// Moves bit i of the given bits to bit 2i, which is the column bit of location i.
+fn spread(bits: usize) -> usize {
+    (0..usize::BITS as usize / 2)
+        .filter(|loc| bits & 1 << loc != 0)
+        .fold(0, |spread, loc| spread | 1 << (2 * loc))
+}
+
+/// Returns the complex conjugate of the given matrix.
+fn conj(matrix: &Matrix) -> Matrix {
+    matrix.map(|row| row.map(|value| value.conj()))
+}
+
+impl DensityMatrixSim {
+    fn loc(&self, id: usize) -> usize {
+        *self
+            .id_map
+            .get(&id)
+            .unwrap_or_else(|| panic!("Unable to find qubit with id {id}"))
+    }
+
+    /// Returns the mask of the locations of the given qubits, which must be distinct.
+    fn mask(&self, ids: &[usize]) -> usize {
+        check_for_duplicates(ids);
+        ids.iter().fold(0, |mask, &id| mask | 1 << self.loc(id))
+    }
+
+    /// Allocates a fresh qubit in |0⟩, returning its identifier. Like the sparse simulator, this uses the lowest
+    /// available identifier.
+    /// # Panics
+    ///
+    /// This function will panic if the maximum number of qubits is already allocated.
+    #[must_use]
+    pub(crate) fn allocate(&mut self) -> usize {
+        assert!(
+            self.id_map.len() < MAX_QUBITS,
+            "The density matrix simulator supports at most {MAX_QUBITS} qubits."
+        );
+        let id = (0..=self.id_map.len())
+            .find(|id| !self.id_map.contains_key(id))
+            .expect("There should be an available qubit identifier.");
+        // The new qubit takes the highest location, so the entries with it in |0⟩⟨0| are the existing ones.
+        self.id_map.insert(id, self.id_map.len());
+        self.state.resize(self.state.len() * 4, Complex64::zero());
+        id
+    }
+
+    /// Releases the given qubit, collapsing its state in the process and removing its location from the density
+    /// matrix.
+    /// # Panics
+    ///
+    /// The function will panic if the given id does not correpsond to an allocated qubit.
+    pub(crate) fn release(&mut self, id: usize) {
+        let loc = self.loc(id);
+        if self.measure(id) {
+            self.x(id);
+        }
+
+        let low_mask = (1 << (2 * loc)) - 1;
+        self.state = (0..self.state.len() / 4)
+            .map(|index| self.state[(index & !low_mask) << 2 | (index & low_mask)])
+            .collect();
+        self.id_map.remove(&id);
+        for other_loc in self.id_map.values_mut() {
+            if *other_loc > loc {
+                *other_loc -= 1;
+            }
+        }
+    }
+
+    /// Returns the entries of the density matrix that are not nearly zero with their row and column, indexed by
+    /// the qubits in order of their identifiers.
+    fn entries(&self) -> Vec<(usize, usize, Complex64)> {
+        let mut ids: Vec<usize> = self.id_map.keys().copied().collect();
+        ids.sort_unstable();
+        let locs: Vec<usize> = ids.iter().map(|&id| self.id_map[&id]).collect();
+        let mut entries: Vec<(usize, usize, Complex64)> = self
+            .state
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| !value.is_nearly_zero())
+            .map(|(index, value)| {
+                let (mut row, mut col) = (0, 0);
+                for (bit, loc) in locs.iter().enumerate() {
+                    row |= (index >> (2 * loc + 1) & 1) << bit;
+                    col |= (index >> (2 * loc) & 1) << bit;
+                }
+                (row, col, *value)
+            })
+            .collect();
+        entries.sort_unstable_by_key(|&(row, col, _)| (row, col));
+        entries
+    }
+
+    /// Returns a sorted copy of the entries of the density matrix that are not nearly zero, along with the number
+    /// of allocated qubits. For n qubits, the entry in row r and column c has the index r · 2^n + c.
+    #[must_use]
+    pub(crate) fn get_state(&mut self) -> (Vec<(BigUint, Complex64)>, usize) {
+        let qubits = self.id_map.len();
+        let state = self
+            .entries()
+            .into_iter()
+            .map(|(row, col, value)| (BigUint::from(row << qubits | col), value))
+            .collect();
+        (state, qubits)
+    }
+
+    /// Returns the current density matrix with integer labels for the rows and columns, skipping any entries
+    /// that are zero.
+    pub(crate) fn dump(&mut self) -> String {
+        let mut text = String::from("DENSITY MATRIX: [ ");
+        for (row, col, value) in self.entries() {
+            let _ = write!(text, "|{row}\u{27e9}\u{27e8}{col}|: {value}, ");
+        }
+        text.push(']');
+        text
+    }
+
+    /// Returns the probability that the parity of the locations in the given mask is odd, which is the sum of
+    /// the matching diagonal entries.
+    fn parity_probability(&self, mask: usize) -> f64 {
+        (0..1 << self.id_map.len())
+            .filter(|bits: &usize| (bits & mask).count_ones() & 1 != 0)
+            .map(|bits| self.state[3 * spread(bits)].re)
+            .sum()
+    }
+
+    /// Projects the density matrix onto the states whose parity at the given mask matches the given value and
+    /// normalizes its trace.
+    fn collapse(&mut self, mask: usize, val: bool) {
+        let probability = self.parity_probability(mask);
+        let scaling = 1.0 / if val { probability } else { 1.0 - probability };
+        let col_mask = spread(mask);
+        for (index, value) in self.state.iter_mut().enumerate() {
+            let row_parity = (index & col_mask << 1).count_ones() & 1 != 0;
+            let col_parity = (index & col_mask).count_ones() & 1 != 0;
+            if row_parity == val && col_parity == val {
+                *value *= scaling;
+            } else {
+                *value = Complex64::zero();
+            }
+        }
+    }
+
+    /// Checks the probability of parity measurement in the computational basis for the given set of
+    /// qubits.
+    /// # Panics
+    ///
+    /// This function will panic if the given ids do not all correspond to allocated qubits.
+    /// This function will panic if there are duplicate ids in the given list.
+    #[must_use]
+    pub(crate) fn joint_probability(&mut self, ids: &[usize]) -> f64 {
+        self.parity_probability(self.mask(ids))
+    }
+
+    /// Measures the qubit with the given id, collapsing the state based on the measured result.
+    /// # Panics
+    ///
+    /// This funciton will panic if the given identifier does not correspond to an allocated qubit.
+    #[must_use]
+    pub(crate) fn measure(&mut self, id: usize) -> bool {
+        self.joint_measure(&[id])
+    }
+
+    /// Performs a joint measurement to get the parity of the given qubits, collapsing the state
+    /// based on the measured result.
+    /// # Panics
+    ///
+    /// This function will panic if any of the given identifiers do not correspond to an allocated qubit.
+    /// This function will panic if any of the given identifiers are duplicates.
+    #[must_use]
+    pub(crate) fn joint_measure(&mut self, ids: &[usize]) -> bool {
+        let mask = self.mask(ids);
+        let res = random_sample() < self.parity_probability(mask);
+        self.collapse(mask, res);
+        res
+    }
+
+    /// Swaps the mapped ids for the given qubits.
+    pub(crate) fn swap_qubit_ids(&mut self, qubit1: usize, qubit2: usize) {
+        let (loc1, loc2) = (self.loc(qubit1), self.loc(qubit2));
+        self.id_map.insert(qubit1, loc2);
+        self.id_map.insert(qubit2, loc1);
+    }
+
+    /// Applies the quantum channel with the given Kraus operators to the qubit with the given id, which maps
+    /// the density matrix ρ to the sum of `KρK†` over the operators `K`.
+    /// # Panics
+    ///
+    /// This function will panic if the given identifier does not correspond to an allocated qubit.
+    pub(crate) fn apply_channel(&mut self, kraus: &[Matrix], id: usize) {
+        let target_mask = spread(1 << self.loc(id));
+        let mut state = vec![Complex64::zero(); self.state.len()];
+        for operator in kraus {
+            let mut term = self.state.clone();
+            apply_controlled_matrix(&mut term, 0, target_mask << 1, operator);
+            apply_controlled_matrix(&mut term, 0, target_mask, &conj(operator));
+            for (value, term_value) in state.iter_mut().zip(term) {
+                *value += term_value;
+            }
+        }
+        self.state = state;
+    }
+
+    /// Applies the amplitude damping channel with the given damping probability to the qubit with the given id.
+    /// Unlike the state vector simulators, which sample whether the qubit decays, this applies the channel
+    /// exactly.
+    /// # Panics
+    ///
+    /// This function will panic if the given identifier does not correspond to an allocated qubit.
+    pub(crate) fn amplitude_damping(&mut self, gamma: f64, id: usize) {
+        self.apply_channel(&amplitude_damping_kraus(gamma), id);
+    }
+
+    /// Applies the given matrix U to the target for the states where all controls are set, which maps the
+    /// density matrix ρ to `UρU†`.
+    fn controlled_matrix(&mut self, ctls: &[usize], target: usize, matrix: Matrix) {
+        let mut ids = ctls.to_vec();
+        ids.push(target);
+        let ctls_mask = spread(self.mask(&ids) & !(1 << self.loc(target)));
+        let target_mask = spread(1 << self.loc(target));
+        // Multiplying with U from the left transforms the rows, and multiplying with U† from the right
+        // transforms the columns with the complex conjugate of U.
+        apply_controlled_matrix(&mut self.state, ctls_mask << 1, target_mask << 1, &matrix);
+        apply_controlled_matrix(&mut self.state, ctls_mask, target_mask, &conj(&matrix));
+    }
+
+    pub(crate) fn mcphase(&mut self, ctls: &[usize], phase: Complex64, target: usize) {
+        let (zero, one) = (Complex64::zero(), Complex64::one());
+        self.controlled_matrix(ctls, target, [[one, zero], [zero, phase]]);
+    }
+
+    pub(crate) fn x(&mut self, target: usize) {
+        self.mcx(&[], target);
+    }
+
+    pub(crate) fn mcx(&mut self, ctls: &[usize], target: usize) {
+        let (zero, one) = (Complex64::zero(), Complex64::one());
+        self.controlled_matrix(ctls, target, [[zero, one], [one, zero]]);
+    }
+
+    pub(crate) fn y(&mut self, target: usize) {
+        self.mcy(&[], target);
+    }
+
+    pub(crate) fn mcy(&mut self, ctls: &[usize], target: usize) {
+        let (zero, i) = (Complex64::zero(), Complex64::i());
+        self.controlled_matrix(ctls, target, [[zero, -i], [i, zero]]);
+    }
+
+    pub(crate) fn z(&mut self, target: usize) {
+        self.mcz(&[], target);
+    }
+
+    pub(crate) fn mcz(&mut self, ctls: &[usize], target: usize) {
+        self.mcphase(ctls, -Complex64::one(), target);
+    }
+
+    pub(crate) fn h(&mut self, target: usize) {
+        self.mch(&[], target);
+    }
+
+    pub(crate) fn mch(&mut self, ctls: &[usize], target: usize) {
+        let value = Complex64::new(FRAC_1_SQRT_2, 0.0);
+        self.controlled_matrix(ctls, target, [[value, value], [value, -value]]);
+    }
+
+    pub(crate) fn s(&mut self, target: usize) {
+        self.mcs(&[], target);
+    }
+
+    pub(crate) fn mcs(&mut self, ctls: &[usize], target: usize) {
+        self.mcphase(ctls, Complex64::i(), target);
+    }
+
+    pub(crate) fn sadj(&mut self, target: usize) {
+        self.mcsadj(&[], target);
+    }
+
+    pub(crate) fn mcsadj(&mut self, ctls: &[usize], target: usize) {
+        self.mcphase(ctls, -Complex64::i(), target);
+    }
+
+    pub(crate) fn t(&mut self, target: usize) {
+        self.mct(&[], target);
+    }
+
+    pub(crate) fn mct(&mut self, ctls: &[usize], target: usize) {
+        self.mcphase(ctls, Complex64::new(FRAC_1_SQRT_2, FRAC_1_SQRT_2), target);
+    }
+
+    pub(crate) fn tadj(&mut self, target: usize) {
+        self.mctadj(&[], target);
+    }
+
+    pub(crate) fn mctadj(&mut self, ctls: &[usize], target: usize) {
+        self.mcphase(ctls, Complex64::new(FRAC_1_SQRT_2, -FRAC_1_SQRT_2), target);
+    }
+
+    pub(crate) fn rx(&mut self, theta: f64, target: usize) {
+        self.mcrx(&[], theta, target);
+    }
+
+    pub(crate) fn mcrx(&mut self, ctls: &[usize], theta: f64, target: usize) {
+        self.controlled_matrix(ctls, target, rx_matrix(theta));
+    }
+
+    pub(crate) fn ry(&mut self, theta: f64, target: usize) {
+        self.mcry(&[], theta, target);
+    }
+
+    pub(crate) fn mcry(&mut self, ctls: &[usize], theta: f64, target: usize) {
+        self.controlled_matrix(ctls, target, ry_matrix(theta));
+    }
+
+    pub(crate) fn rz(&mut self, theta: f64, target: usize) {
+        self.mcrz(&[], theta, target);
+    }
+
+    pub(crate) fn mcrz(&mut self, ctls: &[usize], theta: f64, target: usize) {
+        self.controlled_matrix(ctls, target, rz_matrix(theta));
+    }
+
+    pub(crate) fn exp(&mut self, paulis: &[Pauli], theta: f64, targets: &[usize]) {
+        self.mcexp(&[], paulis, theta, targets);
+    }
+
+    /// Applies the exponential of the multi-qubit Pauli operator with the given angle, exp(iθP), for the
+    /// states where all controls are set.
+    pub(crate) fn mcexp(
+        &mut self,
+        ctls: &[usize],
+        paulis: &[Pauli],
+        theta: f64,
+        targets: &[usize],
+    ) {
+        let mut ids = ctls.to_vec();
+        ids.extend_from_slice(targets);
+        check_for_duplicates(&ids);
+        let ctls_mask = spread(ctls.iter().fold(0, |mask, &id| mask | 1 << self.loc(id)));
+
+        let (mut xy_mask, mut yz_mask, mut y_count) = (0, 0, 0);
+        for (pauli, &target) in paulis.iter().zip(targets) {
+            let bit = spread(1 << self.loc(target));
+            match pauli {
+                Pauli::I => (),
+                Pauli::X => xy_mask |= bit,
+                Pauli::Y => {
+                    xy_mask |= bit;
+                    yz_mask |= bit;
+                    y_count += 1;
+                }
+                Pauli::Z => yz_mask |= bit,
+            }
+        }
+        // The complex conjugate of exp(iθP) is exp(-iθP*), where P* is P negated for an odd number of Pauli-Y
+        // operators.
+        let column_theta = if y_count % 2 == 0 { -theta } else { theta };
+        apply_pauli_exp(
+            &mut self.state,
+            ctls_mask << 1,
+            xy_mask << 1,
+            yz_mask << 1,
+            y_count,
+            theta,
+        );
+        apply_pauli_exp(
+            &mut self.state,
+            ctls_mask,
+            xy_mask,
+            yz_mask,
+            y_count,
+            column_theta,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DensityMatrixSim;
+    use crate::{dense::DenseSim, nearly_zero::NearlyZero, simulator::QuantumSim};
+    use num_complex::Complex64;
+    use qir_stdlib::Pauli;
+    use std::f64::consts::PI;
+
+    /// Checks that the density matrix is the outer product of the given pure state with itself.
+    fn assert_pure_state(density: &mut DensityMatrixSim, pure: &mut DenseSim) {
+        let (state, qubits) = pure.get_state();
+        let mut amplitudes = vec![Complex64::default(); 1 << qubits];
+        for (index, value) in state {
+            amplitudes[usize::try_from(index).unwrap()] = value;
+        }
+        for (row, col, value) in density.entries() {
+            assert!((value - amplitudes[row] * amplitudes[col].conj()).is_nearly_zero());
+        }
+        let products = amplitudes
+            .iter()
+            .flat_map(|row| amplitudes.iter().map(move |col| row * col.conj()))
+            .filter(|product| !product.is_nearly_zero())
+            .count();
+        assert_eq!(density.entries().len(), products);
+    }
+
+    #[test]
+    fn test_gates_match_pure_state() {
+        let mut density = DensityMatrixSim::default();
+        let mut pure = DenseSim::from_sparse(&mut QuantumSim::<u64>::default());
+        let qubits: Vec<usize> = (0..3).map(|_| density.allocate()).collect();
+        for _ in 0..3 {
+            let _ = pure.allocate();
+        }
+        macro_rules! apply {
+            ($($gate:ident($($arg:expr),*);)*) => {
+                $(
+                    density.$gate($($arg),*);
+                    pure.$gate($($arg),*);
+                    assert_pure_state(&mut density, &mut pure);
+                )*
+            };
+        }
+        apply! {
+            h(qubits[0]);
+            mcx(&[qubits[0]], qubits[1]);
+            ry(PI / 3.0, qubits[2]);
+            s(qubits[1]);
+            mcy(&[qubits[2]], qubits[0]);
+            mcrz(&[qubits[0], qubits[1]], PI / 5.0, qubits[2]);
+            t(qubits[2]);
+            exp(&[Pauli::X, Pauli::Y], PI / 7.0, &[qubits[0], qubits[2]]);
+            mcexp(&[qubits[1]], &[Pauli::Y, Pauli::Z], PI / 9.0, &[qubits[2], qubits[0]]);
+            swap_qubit_ids(qubits[0], qubits[2]);
+            rx(PI / 4.0, qubits[0]);
+        }
+        assert!((density.joint_probability(&[qubits[0], qubits[1]])
+            - pure.joint_probability(&[qubits[0], qubits[1]]))
+        .is_nearly_zero());
+    }
+
+    #[test]
+    fn test_amplitude_damping_mixes_state() {
+        let mut sim = DensityMatrixSim::default();
+        let q = sim.allocate();
+        sim.h(q);
+        sim.amplitude_damping(0.5, q);
+        assert!((sim.joint_probability(&[q]) - 0.25).abs() < 1e-10);
+        let entries = sim.entries();
+        assert_eq!(entries.len(), 4);
+        assert!((entries[1].2.re - 0.5 * 0.5_f64.sqrt()).abs() < 1e-10);
+        let res = sim.measure(q);
+        assert!((sim.joint_probability(&[q]) - if res { 1.0 } else { 0.0 }).abs() < 1e-10);
+        sim.release(q);
+        assert_eq!(sim.entries().len(), 1);
+    }
+}
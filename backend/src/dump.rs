@@ -0,0 +1,147 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Readable dumps of a state vector, as a table of basis states in ket notation or as JSON with the amplitude,
+//! probability and phase of every basis state.
+
+use std::fmt::Write;
+
+use num_bigint::BigUint;
+use num_complex::Complex64;
+use serde::Serialize;
+
+/// A snapshot of the amplitudes of a state vector, leaving out the basis states whose amplitudes are negligible.
+pub(crate) struct StateDump {
+    qubits: usize,
+    entries: Vec<DumpEntry>,
+}
+
+#[derive(Serialize)]
+struct DumpEntry {
+    /// The values of the qubits in the basis state, with qubit 0 as the leftmost bit.
+    basis_state: String,
+    real: f64,
+    imaginary: f64,
+    probability: f64,
+    /// The phase of the amplitude in radians.
+    phase: f64,
+}
+
+#[derive(Serialize)]
+struct JsonDump<'a> {
+    qubits: usize,
+    amplitudes: &'a [DumpEntry],
+}
+
+impl StateDump {
+    /// Creates a dump of the given sorted amplitudes of a state of the given number of qubits, where bit i of an
+    /// index is the value of qubit i, leaving out the amplitudes whose magnitude is at most the threshold.
+    pub(crate) fn new(
+        amplitudes: Vec<(BigUint, Complex64)>,
+        qubits: usize,
+        threshold: f64,
+    ) -> Self {
+        let entries = amplitudes
+            .into_iter()
+            .filter(|(_, amplitude)| amplitude.norm() > threshold)
+            .map(|(index, amplitude)| DumpEntry {
+                basis_state: (0..qubits)
+                    .map(|qubit| if index.bit(qubit as u64) { '1' } else { '0' })
+                    .collect(),
+                real: amplitude.re,
+                imaginary: amplitude.im,
+                probability: amplitude.norm_sqr(),
+                phase: amplitude.arg(),
+            })
+            .collect();
+        StateDump { qubits, entries }
+    }
+
+    /// Returns a table with a row for every basis state in ket notation, with its amplitude, probability and
+    /// phase.
+    pub(crate) fn to_table(&self) -> String {
+        let header = "Basis State";
+        let width = header.len().max(self.qubits + 2);
+        let mut table = format!(
+            "{header:<width$}  {:<17}  {:>11}  {:>7}\n",
+            "Amplitude", "Probability", "Phase"
+        );
+        for entry in &self.entries {
+            let ket = format!("|{}\u{27e9}", entry.basis_state);
+            let amplitude = format!("{:+.4}{:+.4}i", entry.real, entry.imaginary);
+            writeln!(
+                table,
+                "{ket:<width$}  {amplitude:<17}  {:>10.4}%  {:>7.4}",
+                entry.probability * 100.0,
+                entry.phase,
+            )
+            .expect("Writing to a string should succeed.");
+        }
+        table
+    }
+
+    /// Returns a JSON object with the number of qubits and the amplitude, probability and phase of every basis
+    /// state.
+    pub(crate) fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&JsonDump {
+            qubits: self.qubits,
+            amplitudes: &self.entries,
+        })
+        .expect("State dumps should serialize to JSON.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StateDump;
+    use num_bigint::BigUint;
+    use num_complex::Complex64;
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    fn bell_state() -> Vec<(BigUint, Complex64)> {
+        vec![
+            (BigUint::from(0_u8), Complex64::new(FRAC_1_SQRT_2, 0.0)),
+            (BigUint::from(2_u8), Complex64::new(1e-9, 0.0)),
+            (BigUint::from(3_u8), Complex64::new(0.0, -FRAC_1_SQRT_2)),
+        ]
+    }
+
+    #[test]
+    fn test_table() {
+        let dump = StateDump::new(bell_state(), 2, 1e-6);
+        assert_eq!(
+            dump.to_table(),
+            concat!(
+                "Basis State  Amplitude          Probability    Phase\n",
+                "|00\u{27e9}         +0.7071+0.0000i       50.0000%   0.0000\n",
+                "|11\u{27e9}         +0.0000-0.7071i       50.0000%  -1.5708\n",
+            )
+        );
+        let dump = StateDump::new(bell_state(), 2, 0.0);
+        assert!(dump.to_table().contains("|01\u{27e9}"));
+    }
+
+    #[test]
+    fn test_json() {
+        let dump = StateDump::new(
+            vec![(BigUint::from(1_u8), Complex64::new(0.0, 1.0))],
+            2,
+            0.0,
+        );
+        assert_eq!(
+            dump.to_json(),
+            r#"{
+  "qubits": 2,
+  "amplitudes": [
+    {
+      "basis_state": "10",
+      "real": 0.0,
+      "imaginary": 1.0,
+      "probability": 1.0,
+      "phase": 1.5707963267948966
+    }
+  ]
+}"#
+        );
+    }
+}
@@ -0,0 +1,386 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::str::FromStr;
+
+use num_bigint::BigUint;
+use num_complex::Complex64;
+use qir_stdlib::Pauli;
+
+use crate::{
+    basis_index::BasisIndex,
+    dense::DenseSim,
+    density::DensityMatrixSim,
+    estimate::{MeasurementPolicy, ResourceCounts, ResourceEstimator},
+    mps::{MpsSim, DEFAULT_BOND_DIMENSION},
+    sampling::{SampledSim, TerminalDistribution},
+    simulator::QuantumSim,
+    stabilizer::StabilizerSim,
+    trace::TracedSim,
+};
+
+/// The kinds of simulators that can execute the quantum intrinsics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SimulatorKind {
+    /// State vector simulation, which supports all intrinsics. The state is stored sparsely and switches to a
+    /// dense vector while a large fraction of the amplitudes is non-zero. Sparse states of up to 64 or 128 qubits
+    /// index their basis states with fixed-width integers.
+    #[default]
+    Sparse,
+    /// Stabilizer tableau simulation, which supports thousands of qubits but only Clifford gates and
+    /// measurements.
+    Stabilizer,
+    /// Density matrix simulation, which supports all intrinsics on few qubits and applies noise exactly.
+    DensityMatrix,
+    /// Matrix product state simulation, which supports hundreds of qubits in circuits with little entanglement
+    /// but no gates with more than one control. Bonds between neighboring qubits are truncated to the given
+    /// dimension, which introduces an error that is reported as the truncation error.
+    Mps { bond_dimension: usize },
+    /// Resource estimation, which counts the gates, measurements, qubits and depth of a program instead of
+    /// simulating it, so that programs of any size can be run. Measurements report outcomes according to the
+    /// given policy, and intrinsics that inspect the state are not supported.
+    Estimate { measurements: MeasurementPolicy },
+}
+
+impl FromStr for SimulatorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sparse" => Ok(SimulatorKind::Sparse),
+            "stabilizer" => Ok(SimulatorKind::Stabilizer),
+            "density-matrix" => Ok(SimulatorKind::DensityMatrix),
+            "mps" => Ok(SimulatorKind::Mps {
+                bond_dimension: DEFAULT_BOND_DIMENSION,
+            }),
+            "estimate" => Ok(SimulatorKind::Estimate {
+                measurements: MeasurementPolicy::default(),
+            }),
+            _ => Err(format!("Unsupported simulator '{s}'.")),
+        }
+    }
+}
+
+/// The number of qubits from which the state vector is always stored sparsely, since a dense state of 26
+/// qubits already takes 1 GiB.
+const MAX_DENSE_QUBITS: usize = 26;
+
+/// The fraction of non-zero amplitudes above which a sparse state vector is stored densely.
+const DENSE_THRESHOLD: f64 = 0.25;
+
+/// The fraction of non-zero amplitudes below which a dense state vector is stored sparsely again. It is lower
+/// than the threshold for dense storage so that states near the threshold do not switch on every operation.
+const SPARSE_THRESHOLD: f64 = 0.0625;
+
+/// The simulator that the quantum intrinsics are executed on.
+pub(crate) enum Engine {
+    Sparse(QuantumSim<u64>),
+    WideSparse(QuantumSim<u128>),
+    BigSparse(QuantumSim<BigUint>),
+    Dense(DenseSim),
+    Stabilizer(StabilizerSim),
+    DensityMatrix(DensityMatrixSim),
+    Mps(MpsSim),
+    Estimator(ResourceEstimator),
+    /// The shots of a program whose measurements are sampled from the distribution of an earlier shot.
+    Sampled(SampledSim),
+    /// A simulator whose operations are recorded as an `OpenQASM` 3 trace.
+    Traced(Box<TracedSim>),
+}
+
+/// Forwards the given functions to the simulator of the engine, switching between the storages of the state
+/// vector afterwards as needed.
+macro_rules! dispatch {
+    ($($(#[$meta:meta])*
+    fn $name:ident(&mut self $(, $arg:ident: $ty:ty)*) $(-> $ret:ty)?;)*) => {
+        $(
+            $(#[$meta])*
+            pub(crate) fn $name(&mut self $(, $arg: $ty)*) $(-> $ret)? {
+                let result = match self {
+                    Engine::Sparse(sim) => sim.$name($($arg),*),
+                    Engine::WideSparse(sim) => sim.$name($($arg),*),
+                    Engine::BigSparse(sim) => sim.$name($($arg),*),
+                    Engine::Dense(sim) => sim.$name($($arg),*),
+                    Engine::Stabilizer(sim) => sim.$name($($arg),*),
+                    Engine::DensityMatrix(sim) => sim.$name($($arg),*),
+                    Engine::Mps(sim) => sim.$name($($arg),*),
+                    Engine::Estimator(sim) => sim.$name($($arg),*),
+                    Engine::Sampled(sim) => sim.$name($($arg),*),
+                    Engine::Traced(sim) => sim.$name($($arg),*),
+                };
+                self.switch_storage();
+                result
+            }
+        )*
+    };
+}
+
+impl Engine {
+    /// Creates a simulator of the given kind with no qubits allocated.
+    pub(crate) fn new(kind: SimulatorKind) -> Self {
+        match kind {
+            SimulatorKind::Sparse => Engine::Sparse(QuantumSim::default()),
+            SimulatorKind::Stabilizer => Engine::Stabilizer(StabilizerSim::default()),
+            SimulatorKind::DensityMatrix => Engine::DensityMatrix(DensityMatrixSim::default()),
+            SimulatorKind::Mps { bond_dimension } => Engine::Mps(MpsSim::new(bond_dimension)),
+            SimulatorKind::Estimate { measurements } => {
+                Engine::Estimator(ResourceEstimator::new(measurements))
+            }
+        }
+    }
+
+    pub(crate) fn kind(&self) -> SimulatorKind {
+        match self {
+            Engine::Sparse(_)
+            | Engine::WideSparse(_)
+            | Engine::BigSparse(_)
+            | Engine::Dense(_)
+            | Engine::Sampled(_) => SimulatorKind::Sparse,
+            Engine::Stabilizer(_) => SimulatorKind::Stabilizer,
+            Engine::DensityMatrix(_) => SimulatorKind::DensityMatrix,
+            Engine::Mps(sim) => SimulatorKind::Mps {
+                bond_dimension: sim.bond_dimension(),
+            },
+            Engine::Estimator(sim) => SimulatorKind::Estimate {
+                measurements: sim.policy(),
+            },
+            Engine::Traced(sim) => sim.sim().kind(),
+        }
+    }
+
+    /// Returns the resources counted by the resource estimator, or `None` for the simulators.
+    pub(crate) fn resource_counts(&self) -> Option<ResourceCounts> {
+        match self {
+            Engine::Estimator(sim) => Some(sim.counts().clone()),
+            Engine::Traced(sim) => sim.sim().resource_counts(),
+            _ => None,
+        }
+    }
+
+    /// Returns the total weight of the singular values that the MPS simulator discarded, which is zero for all
+    /// other simulators since they simulate the state exactly.
+    pub(crate) fn truncation_error(&self) -> f64 {
+        match self {
+            Engine::Mps(sim) => sim.truncation_error(),
+            Engine::Traced(sim) => sim.sim().truncation_error(),
+            _ => 0.0,
+        }
+    }
+
+    /// Returns the distribution of the basis states of a state vector along with the identifiers of its qubits,
+    /// or `None` for the other simulators.
+    pub(crate) fn terminal_distribution(&mut self) -> Option<(TerminalDistribution, Vec<usize>)> {
+        let ids: Vec<usize> = match self {
+            Engine::Sparse(sim) => sim.id_map.keys().copied().collect(),
+            Engine::WideSparse(sim) => sim.id_map.keys().copied().collect(),
+            Engine::BigSparse(sim) => sim.id_map.keys().copied().collect(),
+            Engine::Dense(sim) => sim.id_map.keys().copied().collect(),
+            _ => return None,
+        };
+        Some((TerminalDistribution::new(self.get_state().0), ids))
+    }
+
+    /// Returns the expectation value of the Pauli string given by the Pauli and qubit id pairs without collapsing
+    /// the state. The qubits are rotated into the computational basis, where the expectation value follows from
+    /// the probability of odd parity, and rotated back afterwards. These rotations are not part of a trace.
+    pub(crate) fn expectation(&mut self, pauli_list: &[(Pauli, usize)]) -> f64 {
+        if let Engine::Traced(sim) = self {
+            return sim.sim_mut().expectation(pauli_list);
+        }
+        let pauli_list: Vec<(Pauli, usize)> = pauli_list
+            .iter()
+            .filter(|(pauli, _)| !matches!(pauli, Pauli::I))
+            .copied()
+            .collect();
+        if pauli_list.is_empty() {
+            return 1.0;
+        }
+        for &(pauli, qubit) in &pauli_list {
+            match pauli {
+                Pauli::X => self.h(qubit),
+                Pauli::Y => {
+                    self.sadj(qubit);
+                    self.h(qubit);
+                }
+                Pauli::I | Pauli::Z => (),
+            }
+        }
+        let qubits: Vec<usize> = pauli_list.iter().map(|(_, qubit)| *qubit).collect();
+        let expectation = 1.0 - 2.0 * self.joint_probability(&qubits);
+        for &(pauli, qubit) in &pauli_list {
+            match pauli {
+                Pauli::X => self.h(qubit),
+                Pauli::Y => {
+                    self.h(qubit);
+                    self.s(qubit);
+                }
+                Pauli::I | Pauli::Z => (),
+            }
+        }
+        expectation
+    }
+
+    /// Switches a state vector to dense storage when the fraction of non-zero amplitudes passes the dense
+    /// threshold, and back to sparse storage when it falls below the sparse threshold. Sparse states are indexed
+    /// by the narrowest integers that hold all qubit locations.
+    fn switch_storage(&mut self) {
+        match self {
+            Engine::Sparse(sim) => {
+                if is_dense(sim) {
+                    *self = Engine::Dense(DenseSim::from_sparse(sim));
+                } else if sim.id_map.len() > u64::CAPACITY {
+                    *self = Engine::from_sparse(sim);
+                }
+            }
+            Engine::WideSparse(sim) => {
+                if is_dense(sim) {
+                    *self = Engine::Dense(DenseSim::from_sparse(sim));
+                } else if sim.id_map.len() > u128::CAPACITY || fits::<u64, _>(sim) {
+                    *self = Engine::from_sparse(sim);
+                }
+            }
+            Engine::BigSparse(sim) => {
+                if is_dense(sim) {
+                    *self = Engine::Dense(DenseSim::from_sparse(sim));
+                } else if fits::<u128, _>(sim) {
+                    *self = Engine::from_sparse(sim);
+                }
+            }
+            Engine::Dense(sim) => {
+                if sim.qubit_count() >= MAX_DENSE_QUBITS || sim.is_sparser_than(SPARSE_THRESHOLD) {
+                    *self = Engine::Sparse(sim.to_sparse());
+                }
+            }
+            Engine::Stabilizer(_)
+            | Engine::DensityMatrix(_)
+            | Engine::Mps(_)
+            | Engine::Estimator(_)
+            | Engine::Sampled(_)
+            | Engine::Traced(_) => (),
+        }
+    }
+
+    /// Creates a sparse engine with the state of the given simulator, indexed by the narrowest integers that
+    /// hold all of its qubit locations. A newly allocated qubit takes the lowest free location, so the indices
+    /// are widened as soon as there are more qubits than they have bits.
+    fn from_sparse<K: BasisIndex>(sim: &mut QuantumSim<K>) -> Self {
+        if fits::<u64, _>(sim) {
+            Engine::Sparse(sim.convert())
+        } else if fits::<u128, _>(sim) {
+            Engine::WideSparse(sim.convert())
+        } else {
+            Engine::BigSparse(sim.convert())
+        }
+    }
+
+    dispatch! {
+        #[must_use]
+        fn allocate(&mut self) -> usize;
+        fn release(&mut self, id: usize);
+        #[must_use]
+        fn dump(&mut self) -> String;
+        #[must_use]
+        fn get_state(&mut self) -> (Vec<(BigUint, Complex64)>, usize);
+        #[must_use]
+        fn joint_probability(&mut self, ids: &[usize]) -> f64;
+        #[must_use]
+        fn measure(&mut self, id: usize) -> bool;
+        #[must_use]
+        fn joint_measure(&mut self, ids: &[usize]) -> bool;
+        fn swap_qubit_ids(&mut self, qubit1: usize, qubit2: usize);
+        fn amplitude_damping(&mut self, gamma: f64, id: usize);
+        fn x(&mut self, target: usize);
+        fn y(&mut self, target: usize);
+        fn z(&mut self, target: usize);
+        fn h(&mut self, target: usize);
+        fn s(&mut self, target: usize);
+        fn sadj(&mut self, target: usize);
+        fn t(&mut self, target: usize);
+        fn tadj(&mut self, target: usize);
+        fn mcx(&mut self, ctls: &[usize], target: usize);
+        fn mcy(&mut self, ctls: &[usize], target: usize);
+        fn mcz(&mut self, ctls: &[usize], target: usize);
+        fn mch(&mut self, ctls: &[usize], target: usize);
+        fn mcs(&mut self, ctls: &[usize], target: usize);
+        fn mcsadj(&mut self, ctls: &[usize], target: usize);
+        fn mct(&mut self, ctls: &[usize], target: usize);
+        fn mctadj(&mut self, ctls: &[usize], target: usize);
+        fn mcphase(&mut self, ctls: &[usize], phase: Complex64, target: usize);
+        fn rx(&mut self, theta: f64, target: usize);
+        fn ry(&mut self, theta: f64, target: usize);
+        fn rz(&mut self, theta: f64, target: usize);
+        fn mcrx(&mut self, ctls: &[usize], theta: f64, target: usize);
+        fn mcry(&mut self, ctls: &[usize], theta: f64, target: usize);
+        fn mcrz(&mut self, ctls: &[usize], theta: f64, target: usize);
+        fn exp(&mut self, paulis: &[Pauli], theta: f64, targets: &[usize]);
+        fn mcexp(&mut self, ctls: &[usize], paulis: &[Pauli], theta: f64, targets: &[usize]);
+    }
+}
+
+/// Checks whether the fraction of non-zero amplitudes of the given sparse state passes the dense threshold.
+#[allow(clippy::cast_precision_loss)]
+fn is_dense<K: BasisIndex>(sim: &QuantumSim<K>) -> bool {
+    let qubits = sim.id_map.len();
+    qubits < MAX_DENSE_QUBITS
+        && sim.state.len() as f64 > DENSE_THRESHOLD * (1_usize << qubits) as f64
+}
+
+/// Checks whether all qubit locations of the given sparse state fit into indices of type `L`. The locations are
+/// only inspected if there are few enough qubits, which keeps the check cheap for large registers.
+fn fits<L: BasisIndex, K: BasisIndex>(sim: &QuantumSim<K>) -> bool {
+    sim.id_map.len() <= L::CAPACITY && sim.id_map.values().all(|&loc| loc < L::CAPACITY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Engine, SimulatorKind};
+
+    #[test]
+    fn test_switches_between_sparse_and_dense() {
+        let mut engine = Engine::new(SimulatorKind::Sparse);
+        let qubits: Vec<usize> = (0..6).map(|_| engine.allocate()).collect();
+        for &qubit in &qubits {
+            engine.h(qubit);
+        }
+        // The H gates are queued by the sparse simulator until the state is inspected.
+        assert!(matches!(engine, Engine::Sparse(_)));
+        assert!((engine.joint_probability(&qubits) - 0.5).abs() < 1e-10);
+        assert!(matches!(engine, Engine::Dense(_)));
+        assert_eq!(engine.get_state().0.len(), 64);
+        for &qubit in &qubits {
+            let _ = engine.measure(qubit);
+        }
+        assert!(matches!(engine, Engine::Sparse(_)));
+        assert_eq!(engine.get_state().0.len(), 1);
+    }
+
+    #[test]
+    fn test_widens_and_narrows_indices() {
+        let mut engine = Engine::new(SimulatorKind::Sparse);
+        let ctl = engine.allocate();
+        engine.h(ctl);
+        let mut targets = vec![];
+        for (count, expected) in [(63, "u64"), (64, "u128"), (128, "BigUint")] {
+            while targets.len() < count {
+                let target = engine.allocate();
+                engine.mcx(&[ctl], target);
+                targets.push(target);
+            }
+            let width = match engine {
+                Engine::Sparse(_) => "u64",
+                Engine::WideSparse(_) => "u128",
+                Engine::BigSparse(_) => "BigUint",
+                _ => "other",
+            };
+            assert_eq!(width, expected);
+            assert!((engine.joint_probability(&[ctl, targets[count - 1]])).abs() < 1e-10);
+        }
+        for target in targets.drain(32..) {
+            engine.release(target);
+        }
+        // Releasing the qubits measures them, which collapses the remaining qubits to the same value.
+        assert!(matches!(engine, Engine::Sparse(_)));
+        assert_eq!(engine.get_state().0.len(), 1);
+        assert!((engine.joint_probability(&[ctl, targets[31]])).abs() < 1e-10);
+    }
+}
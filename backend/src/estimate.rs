@@ -0,0 +1,384 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Resource estimation, which counts the operations of a program instead of simulating them. Since no state is
+//! kept, programs of any size can be estimated, while measurement outcomes follow a fixed policy.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    str::FromStr,
+};
+
+use num_bigint::BigUint;
+use num_complex::Complex64;
+use qir_stdlib::Pauli;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+
+use crate::simulator::{check_for_duplicates, random_sample};
+
+/// The outcomes reported by measurements during resource estimation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MeasurementPolicy {
+    /// Every measurement reports `Zero`.
+    #[default]
+    Zero,
+    /// Every measurement reports `One`.
+    One,
+    /// Every measurement reports `Zero` or `One` with equal probability, drawn from the random number generator
+    /// of the simulator.
+    Random,
+}
+
+impl FromStr for MeasurementPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zero" => Ok(MeasurementPolicy::Zero),
+            "one" => Ok(MeasurementPolicy::One),
+            "random" => Ok(MeasurementPolicy::Random),
+            _ => Err(format!("Unsupported measurement policy '{s}'.")),
+        }
+    }
+}
+
+/// The resources counted for a program.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ResourceCounts {
+    /// The largest number of qubits allocated at the same time.
+    pub peak_qubits: u64,
+    /// The number of gates of each kind, where the kind of a gate with controls is prefixed with `c` for one
+    /// control, `cc` for two controls and `mc` for more controls.
+    pub gates: BTreeMap<String, u64>,
+    /// The number of T and adjoint T gates without controls.
+    pub t_count: u64,
+    /// The number of rotations, i.e., Rx, Ry, Rz, phase and Pauli exponential gates with any number of controls.
+    pub rotation_count: u64,
+    /// The number of single-qubit and joint measurements.
+    pub measurement_count: u64,
+    /// The number of layers of the circuit, where every gate and measurement takes one layer on its qubits after
+    /// the previous operations on them.
+    pub depth: u64,
+}
+
+/// The `ResourceEstimator` struct counts the operations applied to it in place of a simulator. Qubit identifiers
+/// are assigned like in the sparse simulator, so that released identifiers are reused.
+pub(crate) struct ResourceEstimator {
+    policy: MeasurementPolicy,
+    counts: ResourceCounts,
+    /// The identifiers below `next` that are not allocated.
+    free: BTreeSet<usize>,
+    next: usize,
+    /// The layer of the last operation on every qubit identifier, which is kept when a qubit is released since
+    /// reusing it continues after that operation.
+    layers: FxHashMap<usize, u64>,
+}
+
+impl ResourceEstimator {
+    /// Creates a resource estimator with no qubits allocated, whose measurements follow the given policy.
+    pub(crate) fn new(policy: MeasurementPolicy) -> Self {
+        ResourceEstimator {
+            policy,
+            counts: ResourceCounts::default(),
+            free: BTreeSet::new(),
+            next: 0,
+            layers: FxHashMap::default(),
+        }
+    }
+
+    pub(crate) fn policy(&self) -> MeasurementPolicy {
+        self.policy
+    }
+
+    pub(crate) fn counts(&self) -> &ResourceCounts {
+        &self.counts
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn live_qubits(&self) -> u64 {
+        (self.next - self.free.len()) as u64
+    }
+
+    /// Places an operation on the given qubits in the layer after the last operation on any of them.
+    fn schedule(&mut self, qubits: &[usize]) {
+        check_for_duplicates(qubits);
+        let layer = qubits
+            .iter()
+            .map(|qubit| self.layers.get(qubit).copied().unwrap_or_default())
+            .max()
+            .unwrap_or_default()
+            + 1;
+        for &qubit in qubits {
+            self.layers.insert(qubit, layer);
+        }
+        self.counts.depth = self.counts.depth.max(layer);
+    }
+
+    /// Counts a gate of the given kind with the given controls on the given targets.
+    fn count(&mut self, gate: &str, ctls: &[usize], targets: &[usize]) {
+        let prefix = match ctls.len() {
+            0 => "",
+            1 => "c",
+            2 => "cc",
+            _ => "mc",
+        };
+        *self
+            .counts
+            .gates
+            .entry(format!("{prefix}{gate}"))
+            .or_default() += 1;
+        if ctls.is_empty() && matches!(gate, "t" | "tadj") {
+            self.counts.t_count += 1;
+        }
+        if matches!(gate, "rx" | "ry" | "rz" | "phase" | "exp") {
+            self.counts.rotation_count += 1;
+        }
+        let qubits: Vec<usize> = ctls.iter().chain(targets).copied().collect();
+        self.schedule(&qubits);
+    }
+
+    fn outcome(&self) -> bool {
+        match self.policy {
+            MeasurementPolicy::Zero => false,
+            MeasurementPolicy::One => true,
+            MeasurementPolicy::Random => random_sample() < 0.5,
+        }
+    }
+
+    fn unsupported(operation: &str) -> ! {
+        panic!("Resource estimation does not support {operation}.")
+    }
+
+    /// Allocates a fresh qubit, returning its identifier. Note that this will use the lowest available
+    /// identifier, like the sparse simulator.
+    #[must_use]
+    pub(crate) fn allocate(&mut self) -> usize {
+        let id = self.free.pop_first().unwrap_or_else(|| {
+            self.next += 1;
+            self.next - 1
+        });
+        self.counts.peak_qubits = self.counts.peak_qubits.max(self.live_qubits());
+        id
+    }
+
+    /// Releases the given qubit.
+    /// # Panics
+    ///
+    /// The function will panic if the given id does not correspond to an allocated qubit.
+    pub(crate) fn release(&mut self, id: usize) {
+        assert!(
+            id < self.next && self.free.insert(id),
+            "Unable to find qubit with id {id}"
+        );
+    }
+
+    /// Counts a measurement of the given qubit and returns the outcome given by the measurement policy.
+    #[must_use]
+    pub(crate) fn measure(&mut self, id: usize) -> bool {
+        self.joint_measure(&[id])
+    }
+
+    /// Counts a joint measurement of the given qubits and returns the outcome given by the measurement policy.
+    #[must_use]
+    pub(crate) fn joint_measure(&mut self, ids: &[usize]) -> bool {
+        self.counts.measurement_count += 1;
+        self.schedule(ids);
+        self.outcome()
+    }
+
+    pub(crate) fn swap_qubit_ids(&mut self, qubit1: usize, qubit2: usize) {
+        self.count("swap", &[], &[qubit1, qubit2]);
+    }
+
+    pub(crate) fn x(&mut self, target: usize) {
+        self.count("x", &[], &[target]);
+    }
+
+    pub(crate) fn y(&mut self, target: usize) {
+        self.count("y", &[], &[target]);
+    }
+
+    pub(crate) fn z(&mut self, target: usize) {
+        self.count("z", &[], &[target]);
+    }
+
+    pub(crate) fn h(&mut self, target: usize) {
+        self.count("h", &[], &[target]);
+    }
+
+    pub(crate) fn s(&mut self, target: usize) {
+        self.count("s", &[], &[target]);
+    }
+
+    pub(crate) fn sadj(&mut self, target: usize) {
+        self.count("sadj", &[], &[target]);
+    }
+
+    pub(crate) fn t(&mut self, target: usize) {
+        self.count("t", &[], &[target]);
+    }
+
+    pub(crate) fn tadj(&mut self, target: usize) {
+        self.count("tadj", &[], &[target]);
+    }
+
+    pub(crate) fn mcx(&mut self, ctls: &[usize], target: usize) {
+        self.count("x", ctls, &[target]);
+    }
+
+    pub(crate) fn mcy(&mut self, ctls: &[usize], target: usize) {
+        self.count("y", ctls, &[target]);
+    }
+
+    pub(crate) fn mcz(&mut self, ctls: &[usize], target: usize) {
+        self.count("z", ctls, &[target]);
+    }
+
+    pub(crate) fn mch(&mut self, ctls: &[usize], target: usize) {
+        self.count("h", ctls, &[target]);
+    }
+
+    pub(crate) fn mcs(&mut self, ctls: &[usize], target: usize) {
+        self.count("s", ctls, &[target]);
+    }
+
+    pub(crate) fn mcsadj(&mut self, ctls: &[usize], target: usize) {
+        self.count("sadj", ctls, &[target]);
+    }
+
+    pub(crate) fn mct(&mut self, ctls: &[usize], target: usize) {
+        self.count("t", ctls, &[target]);
+    }
+
+    pub(crate) fn mctadj(&mut self, ctls: &[usize], target: usize) {
+        self.count("tadj", ctls, &[target]);
+    }
+
+    pub(crate) fn mcphase(&mut self, ctls: &[usize], _phase: Complex64, target: usize) {
+        self.count("phase", ctls, &[target]);
+    }
+
+    pub(crate) fn rx(&mut self, _theta: f64, target: usize) {
+        self.count("rx", &[], &[target]);
+    }
+
+    pub(crate) fn ry(&mut self, _theta: f64, target: usize) {
+        self.count("ry", &[], &[target]);
+    }
+
+    pub(crate) fn rz(&mut self, _theta: f64, target: usize) {
+        self.count("rz", &[], &[target]);
+    }
+
+    pub(crate) fn mcrx(&mut self, ctls: &[usize], _theta: f64, target: usize) {
+        self.count("rx", ctls, &[target]);
+    }
+
+    pub(crate) fn mcry(&mut self, ctls: &[usize], _theta: f64, target: usize) {
+        self.count("ry", ctls, &[target]);
+    }
+
+    pub(crate) fn mcrz(&mut self, ctls: &[usize], _theta: f64, target: usize) {
+        self.count("rz", ctls, &[target]);
+    }
+
+    pub(crate) fn exp(&mut self, _paulis: &[Pauli], _theta: f64, targets: &[usize]) {
+        self.count("exp", &[], targets);
+    }
+
+    pub(crate) fn mcexp(
+        &mut self,
+        ctls: &[usize],
+        _paulis: &[Pauli],
+        _theta: f64,
+        targets: &[usize],
+    ) {
+        self.count("exp", ctls, targets);
+    }
+}
+
+/// The operations that inspect the state or apply noise, which panic since no state is kept.
+#[allow(clippy::unused_self)]
+impl ResourceEstimator {
+    pub(crate) fn dump(&mut self) -> String {
+        Self::unsupported("dumping the state")
+    }
+
+    #[must_use]
+    pub(crate) fn get_state(&mut self) -> (Vec<(BigUint, Complex64)>, usize) {
+        Self::unsupported("capturing the state")
+    }
+
+    #[must_use]
+    pub(crate) fn joint_probability(&mut self, _ids: &[usize]) -> f64 {
+        Self::unsupported("computing probabilities")
+    }
+
+    pub(crate) fn amplitude_damping(&mut self, _gamma: f64, _id: usize) {
+        Self::unsupported("noise");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MeasurementPolicy, ResourceEstimator};
+
+    #[test]
+    fn test_counts_and_depth() {
+        let mut sim = ResourceEstimator::new(MeasurementPolicy::One);
+        let q0 = sim.allocate();
+        let q1 = sim.allocate();
+        let q2 = sim.allocate();
+        sim.h(q0);
+        sim.t(q1);
+        sim.mcx(&[q0], q1);
+        sim.mcx(&[q0, q1], q2);
+        sim.mct(&[q2], q0);
+        sim.rz(0.5, q2);
+        sim.exp(&[], 0.5, &[q0, q1]);
+        assert!(sim.measure(q0));
+        sim.release(q2);
+        let q3 = sim.allocate();
+        assert_eq!(q3, q2);
+        sim.x(q3);
+
+        let counts = sim.counts();
+        assert_eq!(counts.peak_qubits, 3);
+        let gates: Vec<(&str, u64)> = counts
+            .gates
+            .iter()
+            .map(|(gate, count)| (gate.as_str(), *count))
+            .collect();
+        assert_eq!(
+            gates,
+            [
+                ("ccx", 1),
+                ("ct", 1),
+                ("cx", 1),
+                ("exp", 1),
+                ("h", 1),
+                ("rz", 1),
+                ("t", 1),
+                ("x", 1)
+            ]
+        );
+        assert_eq!(counts.t_count, 1);
+        assert_eq!(counts.rotation_count, 2);
+        assert_eq!(counts.measurement_count, 1);
+        // The gates from h to the measurement follow each other, except for rz next to exp and x next to the measurement.
+        assert_eq!(counts.depth, 6);
+    }
+
+    #[test]
+    fn test_measurement_policies() {
+        let mut sim = ResourceEstimator::new(MeasurementPolicy::Zero);
+        let qubit = sim.allocate();
+        sim.x(qubit);
+        assert!(!sim.measure(qubit));
+        assert!(!sim.joint_measure(&[qubit]));
+        assert_eq!(sim.counts().measurement_count, 2);
+        assert_eq!("random".parse(), Ok(MeasurementPolicy::Random));
+        assert!("maybe".parse::<MeasurementPolicy>().is_err());
+    }
+}
@@ -7,9 +7,8 @@
 // in favor of having high level languages decompose into CNOT and single qubit rotations (see
 // https://github.com/microsoft/qsharp-runtime/issues/999 and https://github.com/microsoft/QuantumLibraries/issues/579).
 
-use num_bigint::BigUint;
 use num_complex::Complex64;
-use num_traits::{One, Zero};
+use num_traits::One;
 use qir_stdlib::{
     arrays::{QirArray, __quantum__rt__array_get_element_ptr_1d, __quantum__rt__array_get_size_1d},
     tuples::{__quantum__rt__tuple_create, __quantum__rt__tuple_update_reference_count},
@@ -22,19 +21,27 @@ use std::{
 };
 
 use crate::{
+    basis_index::BasisIndex,
     ensure_sufficient_qubits,
     nearly_zero::NearlyZero,
     simulator::{FlushLevel, QuantumSim, SparseState},
     SIM_STATE,
 };
 
+qir_stdlib::exports! {
+    fn __quantum__qis__exp__body(paulis: *const QirArray, theta: c_double, qubits: *const QirArray);
+    fn __quantum__qis__exp__adj(paulis: *const QirArray, theta: c_double, qubits: *const QirArray);
+    fn __quantum__qis__exp__ctl(ctls: *const QirArray, arg_tuple: *mut *const Vec<u8>);
+    fn __quantum__qis__exp__ctladj(ctls: *const QirArray, arg_tuple: *mut *const Vec<u8>);
+}
+
 /// QIR API for applying an exponential of a multi-qubit rotation about the given Pauli axes with the given angle and qubits.
 /// # Safety
 ///
 /// This function should only be called with arrays and tuples created by the QIR runtime library.
 #[allow(clippy::cast_ptr_alignment)]
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__qis__exp__body(
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__qis__exp__body(
     paulis: *const QirArray,
     theta: c_double,
     qubits: *const QirArray,
@@ -60,6 +67,7 @@ pub unsafe extern "C" fn __quantum__qis__exp__body(
             .collect();
 
         state.sim.exp(&paulis, theta, &targets);
+        state.apply_noise("exp", &targets);
     });
 }
 
@@ -67,8 +75,8 @@ pub unsafe extern "C" fn __quantum__qis__exp__body(
 /// # Safety
 ///
 /// This function should only be called with arrays and tuples created by the QIR runtime library.
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__qis__exp__adj(
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__qis__exp__adj(
     paulis: *const QirArray,
     theta: c_double,
     qubits: *const QirArray,
@@ -89,11 +97,8 @@ struct ExpArgs {
 ///
 /// This function should only be called with arrays and tuples created by the QIR runtime library.
 #[allow(clippy::cast_ptr_alignment)]
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__qis__exp__ctl(
-    ctls: *const QirArray,
-    arg_tuple: *mut *const Vec<u8>,
-) {
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__qis__exp__ctl(ctls: *const QirArray, arg_tuple: *mut *const Vec<u8>) {
     SIM_STATE.with(|sim_state| {
         let state = &mut *sim_state.borrow_mut();
         let args = *arg_tuple.cast::<ExpArgs>();
@@ -128,6 +133,9 @@ pub unsafe extern "C" fn __quantum__qis__exp__ctl(
             .collect();
 
         state.sim.mcexp(&ctls, &paulis, args.theta, &targets);
+        let mut qubits = ctls;
+        qubits.extend(targets);
+        state.apply_noise("exp", &qubits);
     });
 }
 
@@ -136,11 +144,8 @@ pub unsafe extern "C" fn __quantum__qis__exp__ctl(
 ///
 /// This function should only be called with arrays and tuples created by the QIR runtime library.
 #[allow(clippy::cast_ptr_alignment)]
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__qis__exp__ctladj(
-    ctls: *const QirArray,
-    arg_tuple: *mut *const Vec<u8>,
-) {
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__qis__exp__ctladj(ctls: *const QirArray, arg_tuple: *mut *const Vec<u8>) {
     let args = *arg_tuple.cast::<ExpArgs>();
     let new_args = ExpArgs {
         paulis: args.paulis,
@@ -153,7 +158,7 @@ pub unsafe extern "C" fn __quantum__qis__exp__ctladj(
     __quantum__rt__tuple_update_reference_count(new_arg_tuple, -1);
 }
 
-impl QuantumSim {
+impl<K: BasisIndex> QuantumSim<K> {
     pub(crate) fn exp(&mut self, paulis: &[Pauli], theta: f64, targets: &[usize]) {
         self.mcexp(&[], paulis, theta, targets);
     }
@@ -214,8 +219,8 @@ impl QuantumSim {
         let id_coeff = Complex64::new(theta.cos(), 0.0);
         let pauli_coeff = Complex64::new(0.0, theta.sin());
 
-        let mut xy_mask = BigUint::zero();
-        let mut yz_mask = BigUint::zero();
+        let mut xy_mask = K::zero();
+        let mut yz_mask = K::zero();
         let mut y_count = 0_u64;
         for i in 0..paulis.len() {
             match paulis[i] {
@@ -240,7 +245,7 @@ impl QuantumSim {
                     SparseState::default(),
                     |mut accum, (index, value)| {
                         if ctls.iter().all(|c| index.bit(*c))
-                            && (&index & &yz_mask).count_ones() & 1 != 0
+                            && (index.clone() & &yz_mask).count_ones() & 1 != 0
                         {
                             accum.insert(index, value * id_coeff);
                         }
@@ -253,7 +258,7 @@ impl QuantumSim {
                     SparseState::default(),
                     |mut accum, (index, value)| {
                         if ctls.iter().all(|c| index.bit(*c))
-                            && (&index & &yz_mask).count_ones() & 1 != 0
+                            && (index.clone() & &yz_mask).count_ones() & 1 != 0
                         {
                             accum.insert(index, value * pauli_coeff);
                         }
@@ -299,20 +304,20 @@ impl QuantumSim {
             let mut new_state = SparseState::default();
             for (index, value) in &self.state {
                 if ctls.iter().all(|c| index.bit(*c)) {
-                    let alt_index = index ^ &xy_mask;
+                    let alt_index = index.clone() ^ &xy_mask;
                     if !self.state.contains_key(&alt_index) {
                         new_state.insert(index.clone(), value * id_coeff);
                         new_state.insert(
                             alt_index,
                             value
-                                * if (index & &yz_mask).count_ones() & 1 == 0 {
+                                * if (index.clone() & &yz_mask).count_ones() & 1 == 0 {
                                     pauli_coeff
                                 } else {
                                     -pauli_coeff
                                 },
                         );
                     } else if index < &alt_index {
-                        let parity = (index & &yz_mask).count_ones() & 1 != 0;
+                        let parity = (index.clone() & &yz_mask).count_ones() & 1 != 0;
                         let alt_value = self.state[&alt_index] as Complex64;
 
                         let new_value = value * id_coeff
@@ -349,7 +354,7 @@ mod tests {
 
     #[test]
     fn test_exp_from_cnot() {
-        let sim = &mut QuantumSim::default();
+        let sim = &mut QuantumSim::<u64>::default();
         let (control, target, paired) = (sim.allocate(), sim.allocate(), sim.allocate());
 
         // Entangle the check qubit `paired` with both `control` and `target`
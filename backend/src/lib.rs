@@ -8,88 +8,437 @@
 //! programs. This includes a broad set of quantum intrinsic operations for sparse state simulation,
 //! based on the design from
 //! <a href="https://arxiv.org/abs/2105.01533">Leveraging state sparsity for more efficient quantum simulations</a>.
+//! Programs that only use Clifford gates can instead be simulated on a stabilizer tableau, small noisy
+//! programs on a density matrix, and large programs with little entanglement on a matrix product state,
+//! selected with `set_simulator_kind`, which also selects resource estimation to count the operations of programs
+//! that are too large to simulate. A thread can hold several simulators that are created, bound and
+//! destroyed with `create_simulator`, `bind_simulator` and `destroy_simulator`, where the QIR functions execute
+//! on the bound simulator. Besides the QIR functions, the `Simulator` type drives a simulator directly from Rust.
+//! The operations executed by a shot can be recorded as an `OpenQASM` 3 program with `set_tracing`.
 
 pub mod result_bool;
 
 pub mod exp;
 
+pub mod estimate;
+
+pub mod noise;
+
+pub mod registry;
+
+mod api;
+mod basis_index;
+mod dense;
+mod density;
+mod dump;
+mod engine;
+mod mps;
 mod nearly_zero;
+mod sampling;
 mod simulator;
+mod stabilizer;
+mod trace;
 
 use bitvec::prelude::*;
+use dump::StateDump;
+use engine::Engine;
+use estimate::ResourceCounts;
 use nearly_zero::NearlyZero;
+use noise::NoiseModel;
 use num_bigint::BigUint;
 use num_complex::Complex64;
-use simulator::QuantumSim;
+use rustc_hash::FxHashMap;
+use sampling::{SampledSim, TerminalSampling};
 use std::cell::RefCell;
 use std::convert::TryInto;
 use std::ffi::c_char;
 use std::ffi::c_double;
-use std::ffi::{c_void, CString};
+use std::ffi::{c_void, CStr, CString};
+use std::fs;
 use std::mem::size_of;
+use std::path::Path;
+use std::rc::Rc;
+use trace::TracedSim;
 
 use result_bool::{
     __quantum__rt__result_equal, __quantum__rt__result_get_one, __quantum__rt__result_get_zero,
 };
 
+pub use api::{Qubit, Simulator};
+pub use engine::SimulatorKind;
 pub use qir_stdlib::{
     arrays::*, bigints::*, callables::*, math::*, output_recording::*, range_support::*,
     strings::*, tuples::*, *,
 };
 
+qir_stdlib::exports! {
+    fn __quantum__rt__initialize(tag: *mut c_char);
+    fn __quantum__qis__h__body(qubit: *mut c_void);
+    fn __quantum__qis__s__body(qubit: *mut c_void);
+    fn __quantum__qis__s__adj(qubit: *mut c_void);
+    fn __quantum__qis__t__body(qubit: *mut c_void);
+    fn __quantum__qis__t__adj(qubit: *mut c_void);
+    fn __quantum__qis__x__body(qubit: *mut c_void);
+    fn __quantum__qis__y__body(qubit: *mut c_void);
+    fn __quantum__qis__z__body(qubit: *mut c_void);
+    fn __quantum__qis__cnot__body(control: *mut c_void, target: *mut c_void);
+    fn __quantum__qis__cx__body(control: *mut c_void, target: *mut c_void);
+    fn __quantum__qis__cy__body(control: *mut c_void, target: *mut c_void);
+    fn __quantum__qis__cz__body(control: *mut c_void, target: *mut c_void);
+    fn __quantum__qis__ccx__body(
+        control_1: *mut c_void,
+        control_2: *mut c_void,
+        target: *mut c_void,
+    );
+    fn __quantum__qis__rx__body(theta: c_double, qubit: *mut c_void);
+    fn __quantum__qis__ry__body(theta: c_double, qubit: *mut c_void);
+    fn __quantum__qis__rz__body(theta: c_double, qubit: *mut c_void);
+    fn __quantum__qis__h__ctl(ctls: *const QirArray, qubit: *mut c_void);
+    fn __quantum__qis__s__ctl(ctls: *const QirArray, qubit: *mut c_void);
+    fn __quantum__qis__s__ctladj(ctls: *const QirArray, qubit: *mut c_void);
+    fn __quantum__qis__t__ctl(ctls: *const QirArray, qubit: *mut c_void);
+    fn __quantum__qis__t__ctladj(ctls: *const QirArray, qubit: *mut c_void);
+    fn __quantum__qis__x__ctl(ctls: *const QirArray, qubit: *mut c_void);
+    fn __quantum__qis__y__ctl(ctls: *const QirArray, qubit: *mut c_void);
+    fn __quantum__qis__z__ctl(ctls: *const QirArray, qubit: *mut c_void);
+    fn __quantum__qis__rx__ctl(ctls: *const QirArray, arg_tuple: *mut *const Vec<u8>);
+    fn __quantum__qis__ry__ctl(ctls: *const QirArray, arg_tuple: *mut *const Vec<u8>);
+    fn __quantum__qis__rz__ctl(ctls: *const QirArray, arg_tuple: *mut *const Vec<u8>);
+    fn __quantum__qis__rxx__body(theta: c_double, qubit1: *mut c_void, qubit2: *mut c_void);
+    fn __quantum__qis__ryy__body(theta: c_double, qubit1: *mut c_void, qubit2: *mut c_void);
+    fn __quantum__qis__rzz__body(theta: c_double, qubit1: *mut c_void, qubit2: *mut c_void);
+    fn __quantum__qis__r__body(pauli: Pauli, theta: c_double, qubit: *mut c_void);
+    fn __quantum__qis__r__adj(pauli: Pauli, theta: c_double, qubit: *mut c_void);
+    fn __quantum__qis__r__ctl(ctls: *const QirArray, arg_tuple: *mut *const Vec<u8>);
+    fn __quantum__qis__r__ctladj(ctls: *const QirArray, arg_tuple: *mut *const Vec<u8>);
+    fn __quantum__qis__swap__body(qubit1: *mut c_void, qubit2: *mut c_void);
+    fn __quantum__qis__reset__body(qubit: *mut c_void);
+    fn __quantum__qis__mresetz__body(qubit: *mut c_void) -> *mut c_void;
+    fn __quantum__qis__mz__body(qubit: *mut c_void, result: *mut c_void);
+    fn __quantum__qis__read_result__body(result: *mut c_void) -> bool;
+    fn __quantum__qis__m__body(qubit: *mut c_void) -> *mut c_void;
+    fn __quantum__qis__measure__body(
+        paulis: *const QirArray,
+        qubits: *const QirArray,
+    ) -> *mut c_void;
+    fn __quantum__qis__expectation__body(
+        paulis: *const QirArray,
+        qubits: *const QirArray,
+    ) -> c_double;
+    fn __quantum__qis__hamiltonianexpectation__body(
+        coefficients: *const QirArray,
+        paulis: *const QirArray,
+        qubits: *const QirArray,
+    ) -> c_double;
+    fn __quantum__qis__assertmeasurementprobability__body(
+        paulis: *const QirArray,
+        qubits: *const QirArray,
+        result: *mut c_void,
+        prob: c_double,
+        msg: *const CString,
+        tol: c_double,
+    );
+    fn __quantum__qis__assertmeasurementprobability__ctl(
+        ctls: *const QirArray,
+        arg_tuple: *mut *const Vec<u8>,
+    );
+    fn __quantum__rt__result_record_output(result: *mut c_void, tag: *mut c_char) => Labeled;
+    fn __quantum__rt__qubit_allocate() -> *mut c_void;
+    fn __quantum__rt__qubit_allocate_array(size: u64) -> *const QirArray;
+    fn __quantum__rt__qubit_release_array(arr: *const QirArray);
+    fn __quantum__rt__qubit_release(qubit: *mut c_void);
+    fn __quantum__rt__qubit_to_string(qubit: *mut c_void) -> *const CString;
+    fn __quantum__qis__dumpmachine__body(location: *mut c_void);
+}
+
 // Additional test infrastructure is available in matrix_testing that allows comparing the transformations
 // implemented here with direct matrix application to the state vector.
 #[cfg(test)]
 mod matrix_testing;
 
 struct SimulatorState {
-    pub sim: QuantumSim,
+    pub sim: Engine,
     pub res: BitVec,
     pub max_qubit_id: usize,
+    pub noise: Option<NoiseModel>,
+    /// The number of composite gates being applied, whose noise replaces the noise of the gates they consist of.
+    pub composite_depth: usize,
+    /// Whether the measurements of the shots are sampled from the distribution captured in an earlier shot.
+    pub terminal: TerminalSampling,
+    /// Whether the operations of the shots are recorded as an `OpenQASM` 3 trace.
+    pub tracing: bool,
+    /// The magnitude up to which amplitudes are left out when the state is dumped.
+    pub dump_threshold: f64,
+}
+
+impl SimulatorState {
+    fn new(kind: SimulatorKind) -> Self {
+        SimulatorState {
+            sim: Engine::new(kind),
+            res: bitvec![],
+            max_qubit_id: 0,
+            noise: None,
+            composite_depth: 0,
+            terminal: TerminalSampling::Off,
+            tracing: false,
+            dump_threshold: 0.0,
+        }
+    }
+
+    /// Applies the noise of the given gate to the given qubits if a noise model is set, unless the gate is part
+    /// of a composite gate.
+    fn apply_noise(&mut self, gate: &str, qubits: &[usize]) {
+        if self.composite_depth == 0 {
+            if let Some(noise) = &self.noise {
+                noise.apply(&mut self.sim, gate, qubits);
+            }
+        }
+    }
+
+    /// Captures the distribution of the basis states at the first measurement of a shot if terminal measurements
+    /// are sampled, and replaces the simulation with one that samples the measurements from it.
+    fn capture_terminal_distribution(&mut self) {
+        if let TerminalSampling::Capture = self.terminal {
+            if let Some((distribution, ids)) = self.sim.terminal_distribution() {
+                let distribution = Rc::new(distribution);
+                self.sim = Engine::Sampled(SampledSim::new(Rc::clone(&distribution), ids));
+                self.terminal = TerminalSampling::Sample(distribution);
+            }
+        }
+    }
+
+    /// Returns the result reported by the given measurement of the given qubits, which differs from the measured
+    /// result if readout errors occur.
+    fn readout(&self, gate: &str, qubits: &[usize], result: bool) -> bool {
+        match &self.noise {
+            Some(noise) => noise.readout(gate, qubits, result),
+            None => result,
+        }
+    }
+}
+
+/// A handle to one of the simulators of a thread, of which one is bound at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SimulatorHandle(usize);
+
+/// The simulators of a thread other than the bound one, which is kept in `SIM_STATE` so that the intrinsics do
+/// not need to look it up.
+struct SimulatorInstances {
+    bound: SimulatorHandle,
+    next: usize,
+    unbound: FxHashMap<SimulatorHandle, SimulatorState>,
 }
 
 thread_local! {
-    static SIM_STATE: RefCell<SimulatorState> = RefCell::new(SimulatorState {
-        sim: QuantumSim::default(),
-        res: bitvec![],
-        max_qubit_id: 0
+    static SIM_STATE: RefCell<SimulatorState> = RefCell::new(SimulatorState::new(SimulatorKind::Sparse));
+    static SIM_INSTANCES: RefCell<SimulatorInstances> = RefCell::new(SimulatorInstances {
+        bound: SimulatorHandle(0),
+        next: 1,
+        unbound: FxHashMap::default(),
     });
 }
 
+/// Returns the handle of the simulator that the intrinsics on the current thread are executed on. Every
+/// thread starts with a sparse simulator bound.
+#[must_use]
+pub fn bound_simulator() -> SimulatorHandle {
+    SIM_INSTANCES.with(|instances| instances.borrow().bound)
+}
+
+/// Creates a simulator of the given kind on the current thread without binding it, returning its handle.
+#[allow(clippy::must_use_candidate)]
+pub fn create_simulator(kind: SimulatorKind) -> SimulatorHandle {
+    SIM_INSTANCES.with(|instances| {
+        let instances = &mut *instances.borrow_mut();
+        let handle = SimulatorHandle(instances.next);
+        instances.next += 1;
+        instances.unbound.insert(handle, SimulatorState::new(kind));
+        handle
+    })
+}
+
+/// Binds the simulator with the given handle, so that the intrinsics on the current thread are executed on it,
+/// returning the handle of the previously bound simulator. Every simulator keeps its own state, kind and noise
+/// model while another one is bound, but all simulators of a thread share the random number generator.
+/// # Errors
+///
+/// Will return `Err` if there is no simulator with the given handle on the current thread.
+pub fn bind_simulator(handle: SimulatorHandle) -> Result<SimulatorHandle, String> {
+    SIM_INSTANCES.with(|instances| {
+        let instances = &mut *instances.borrow_mut();
+        let previous = instances.bound;
+        if handle != previous {
+            let mut state = instances
+                .unbound
+                .remove(&handle)
+                .ok_or_else(|| format!("There is no simulator with handle {}.", handle.0))?;
+            SIM_STATE.with(|sim_state| std::mem::swap(&mut *sim_state.borrow_mut(), &mut state));
+            instances.unbound.insert(previous, state);
+            instances.bound = handle;
+        }
+        Ok(previous)
+    })
+}
+
+/// Destroys the simulator with the given handle, releasing its state.
+/// # Errors
+///
+/// Will return `Err` if the simulator is bound or if there is no simulator with the given handle on the
+/// current thread.
+pub fn destroy_simulator(handle: SimulatorHandle) -> Result<(), String> {
+    SIM_INSTANCES.with(|instances| {
+        let instances = &mut *instances.borrow_mut();
+        if handle == instances.bound {
+            return Err(format!(
+                "The simulator with handle {} is bound and cannot be destroyed.",
+                handle.0
+            ));
+        }
+        instances
+            .unbound
+            .remove(&handle)
+            .map(drop)
+            .ok_or_else(|| format!("There is no simulator with handle {}.", handle.0))
+    })
+}
+
 /// Sets the seed for the pseudo-random number generator used during measurements.
 pub fn set_rng_seed(seed: u64) {
     simulator::set_rng_seed(seed);
 }
 
+/// Sets the kind of the simulator bound on the current thread, which executes the quantum intrinsics, returning
+/// the previous kind. Changing the kind discards the current simulation, and the kind is kept when the
+/// execution environment is initialized.
+#[allow(clippy::must_use_candidate)]
+pub fn set_simulator_kind(kind: SimulatorKind) -> SimulatorKind {
+    SIM_STATE.with(|sim_state| {
+        let state = &mut *sim_state.borrow_mut();
+        let previous = state.sim.kind();
+        if kind != previous {
+            state.sim = Engine::new(kind);
+            state.res = bitvec![];
+            state.max_qubit_id = 0;
+        }
+        previous
+    })
+}
+
+/// Sets the noise model of the simulator bound on the current thread, returning the previous noise model. The
+/// noise model is kept when the execution environment is initialized, and `None` simulates without noise.
+#[allow(clippy::must_use_candidate)]
+pub fn set_noise_model(model: Option<NoiseModel>) -> Option<NoiseModel> {
+    SIM_STATE.with(|sim_state| std::mem::replace(&mut sim_state.borrow_mut().noise, model))
+}
+
+/// Returns the truncation error of the simulator bound on the current thread, which is the total weight of the
+/// singular values discarded by the matrix product state simulator to keep its bonds within the bond dimension.
+/// All other simulators simulate the state exactly and report zero.
+#[must_use]
+pub fn truncation_error() -> f64 {
+    SIM_STATE.with(|sim_state| sim_state.borrow().sim.truncation_error())
+}
+
+/// Returns the resources counted since the execution environment was initialized if the simulator bound on the
+/// current thread is a resource estimator, or `None` otherwise.
+#[must_use]
+pub fn resource_counts() -> Option<ResourceCounts> {
+    SIM_STATE.with(|sim_state| sim_state.borrow().sim.resource_counts())
+}
+
+/// Sets whether the measurements of the shots on the current thread are sampled, returning the previous setting.
+/// When enabled, the next shot captures the distribution of the basis states at its first measurement, and the
+/// outcomes of all `__quantum__qis__mz__body` measurements of that shot and the following ones are drawn from
+/// it instead of being simulated, skipping the gates of the following shots. This is only valid for programs
+/// without noise that apply no gates after their first measurement and run the same gates in every shot, and
+/// enabling it again discards the captured distribution.
+#[allow(clippy::must_use_candidate)]
+pub fn set_terminal_sampling(enabled: bool) -> bool {
+    SIM_STATE.with(|sim_state| {
+        let state = &mut *sim_state.borrow_mut();
+        let previous = !matches!(state.terminal, TerminalSampling::Off);
+        state.terminal = if enabled {
+            TerminalSampling::Capture
+        } else {
+            TerminalSampling::Off
+        };
+        previous
+    })
+}
+
+/// Sets whether the operations of the shots on the current thread are recorded as an `OpenQASM` 3 trace, returning
+/// the previous setting. The setting takes effect when the execution environment is initialized, after which
+/// `trace_qasm` returns the trace of the shot.
+#[allow(clippy::must_use_candidate)]
+pub fn set_tracing(enabled: bool) -> bool {
+    SIM_STATE.with(|sim_state| std::mem::replace(&mut sim_state.borrow_mut().tracing, enabled))
+}
+
+/// Sets the magnitude up to which amplitudes are left out when `__quantum__qis__dumpmachine__body` dumps the state
+/// of the simulator bound on the current thread, returning the previous threshold.
+#[allow(clippy::must_use_candidate)]
+pub fn set_dump_threshold(threshold: f64) -> f64 {
+    SIM_STATE
+        .with(|sim_state| std::mem::replace(&mut sim_state.borrow_mut().dump_threshold, threshold))
+}
+
+/// Returns the `OpenQASM` 3 program with the gates and measurements executed on the simulator bound on the current
+/// thread since the execution environment was initialized, or `None` if the operations are not traced. Gates
+/// without an equivalent in the standard gate library are decomposed, and the outcomes of the measurements are
+/// given in comments.
+#[must_use]
+pub fn trace_qasm() -> Option<String> {
+    SIM_STATE.with(|sim_state| match &sim_state.borrow().sim {
+        Engine::Traced(sim) => Some(sim.to_qasm()),
+        _ => None,
+    })
+}
+
 /// Initializes the execution environment.
-#[no_mangle]
-pub extern "C" fn __quantum__rt__initialize(_: *mut c_char) {
+#[allow(non_snake_case)]
+pub fn __quantum__rt__initialize(_: *mut c_char) {
     SIM_STATE.with(|sim_state| {
         let state = &mut *sim_state.borrow_mut();
-        state.sim = QuantumSim::default();
+        state.sim = match &state.terminal {
+            TerminalSampling::Sample(distribution) => {
+                Engine::Sampled(SampledSim::new(Rc::clone(distribution), []))
+            }
+            _ => Engine::new(state.sim.kind()),
+        };
+        if state.tracing {
+            let sim = std::mem::replace(&mut state.sim, Engine::new(SimulatorKind::Sparse));
+            state.sim = Engine::Traced(Box::new(TracedSim::new(sim)));
+        }
         state.res = bitvec![];
         state.max_qubit_id = 0;
+        state.composite_depth = 0;
+    });
+}
+
+/// Applies a gate that is implemented with other gates, applying the noise of the gate to the given qubits
+/// instead of the noise of the gates it consists of.
+fn composite_gate(gate: &str, qubits: &[usize], apply: impl FnOnce()) {
+    SIM_STATE.with(|sim_state| sim_state.borrow_mut().composite_depth += 1);
+    apply();
+    SIM_STATE.with(|sim_state| {
+        let state = &mut *sim_state.borrow_mut();
+        state.composite_depth -= 1;
+        state.apply_noise(gate, qubits);
     });
 }
 
-fn ensure_sufficient_qubits(sim: &mut QuantumSim, qubit_id: usize, max: &mut usize) {
+fn ensure_sufficient_qubits(sim: &mut Engine, qubit_id: usize, max: &mut usize) {
     while qubit_id + 1 > *max {
         let _ = sim.allocate();
         *max += 1;
     }
 }
 
-/// Maps the given qubits from the given Pauli basis into the computational basis, returning the
-/// unwrapped `QirArray`s into a vector of matching Pauli and qubit id tuples.
+/// Reads the pairs of Paulis and qubit ids from the given arrays, failing if the arrays differ in size.
 #[allow(clippy::cast_ptr_alignment)]
-unsafe fn map_to_z_basis(
-    state: &mut SimulatorState,
-    paulis: *const QirArray,
-    qubits: *const QirArray,
-) -> Vec<(Pauli, usize)> {
+unsafe fn read_pauli_list(paulis: *const QirArray, qubits: *const QirArray) -> Vec<(Pauli, usize)> {
     let paulis_size = __quantum__rt__array_get_size_1d(paulis);
-    let qubits_size = __quantum__rt__array_get_size_1d(qubits);
-    if paulis_size != qubits_size {
+    if paulis_size != __quantum__rt__array_get_size_1d(qubits) {
         __quantum__rt__fail(__quantum__rt__string_create(
             CString::new("Pauli array and Qubit array must be the same size.")
                 .expect("Unable to allocate memory for failure message string.")
@@ -97,21 +446,31 @@ unsafe fn map_to_z_basis(
                 .as_ptr() as *mut c_char,
         ));
     }
-
-    let combined_list: Vec<(Pauli, usize)> = (0..paulis_size)
-        .filter_map(|index| {
-            let p =
-                *__quantum__rt__array_get_element_ptr_1d(paulis, index).cast::<Pauli>() as Pauli;
-            let q = *__quantum__rt__array_get_element_ptr_1d(qubits, index).cast::<*mut c_void>()
-                as usize;
-            if let Pauli::I = p {
-                None
-            } else {
-                ensure_sufficient_qubits(&mut state.sim, q, &mut state.max_qubit_id);
-                Some((p, q))
-            }
+    (0..paulis_size)
+        .map(|index| {
+            (
+                *__quantum__rt__array_get_element_ptr_1d(paulis, index).cast::<Pauli>(),
+                *__quantum__rt__array_get_element_ptr_1d(qubits, index).cast::<*mut c_void>()
+                    as usize,
+            )
         })
+        .collect()
+}
+
+/// Maps the given qubits from the given Pauli basis into the computational basis, returning the
+/// unwrapped `QirArray`s into a vector of matching Pauli and qubit id tuples.
+unsafe fn map_to_z_basis(
+    state: &mut SimulatorState,
+    paulis: *const QirArray,
+    qubits: *const QirArray,
+) -> Vec<(Pauli, usize)> {
+    let combined_list: Vec<(Pauli, usize)> = read_pauli_list(paulis, qubits)
+        .into_iter()
+        .filter(|(p, _)| !matches!(p, Pauli::I))
         .collect();
+    for (_, q) in &combined_list {
+        ensure_sufficient_qubits(&mut state.sim, *q, &mut state.max_qubit_id);
+    }
 
     for (pauli, qubit) in &combined_list {
         match pauli {
@@ -148,13 +507,14 @@ macro_rules! single_qubit_gate {
     ($(#[$meta:meta])*
     $qir_name:ident, $gate:expr) => {
         $(#[$meta])*
-        #[no_mangle]
-        pub extern "C" fn $qir_name(qubit: *mut c_void) {
+        #[allow(non_snake_case)]
+        pub fn $qir_name(qubit: *mut c_void) {
             SIM_STATE.with(|sim_state| {
                 let state = &mut *sim_state.borrow_mut();
                 ensure_sufficient_qubits(&mut state.sim, qubit as usize, &mut state.max_qubit_id);
 
                 $gate(&mut state.sim, qubit as usize);
+                state.apply_noise(noise::gate_name(stringify!($qir_name)), &[qubit as usize]);
             });
         }
     };
@@ -163,56 +523,60 @@ macro_rules! single_qubit_gate {
 single_qubit_gate!(
     /// QIR API for performing the H gate on the given qubit.
     __quantum__qis__h__body,
-    QuantumSim::h
+    Engine::h
 );
 single_qubit_gate!(
     /// QIR API for performing the S gate on the given qubit.
     __quantum__qis__s__body,
-    QuantumSim::s
+    Engine::s
 );
 single_qubit_gate!(
     /// QIR API for performing the Adjoint S gate on the given qubit.
     __quantum__qis__s__adj,
-    QuantumSim::sadj
+    Engine::sadj
 );
 single_qubit_gate!(
     /// QIR API for performing the T gate on the given qubit.
     __quantum__qis__t__body,
-    QuantumSim::t
+    Engine::t
 );
 single_qubit_gate!(
     /// QIR API for performing the Adjoint T gate on the given qubit.
     __quantum__qis__t__adj,
-    QuantumSim::tadj
+    Engine::tadj
 );
 single_qubit_gate!(
     /// QIR API for performing the X gate on the given qubit.
     __quantum__qis__x__body,
-    QuantumSim::x
+    Engine::x
 );
 single_qubit_gate!(
     /// QIR API for performing the Y gate on the given qubit.
     __quantum__qis__y__body,
-    QuantumSim::y
+    Engine::y
 );
 single_qubit_gate!(
     /// QIR API for performing the Z gate on the given qubit.
     __quantum__qis__z__body,
-    QuantumSim::z
+    Engine::z
 );
 
 macro_rules! controlled_qubit_gate {
     ($(#[$meta:meta])*
     $qir_name:ident, $gate:expr, 1) => {
         $(#[$meta])*
-        #[no_mangle]
-        pub extern "C" fn $qir_name(control: *mut c_void, target: *mut c_void) {
+        #[allow(non_snake_case)]
+        pub fn $qir_name(control: *mut c_void, target: *mut c_void) {
             SIM_STATE.with(|sim_state| {
                 let state = &mut *sim_state.borrow_mut();
                 ensure_sufficient_qubits(&mut state.sim, target as usize, &mut state.max_qubit_id);
                 ensure_sufficient_qubits(&mut state.sim, control as usize, &mut state.max_qubit_id);
 
                 $gate(&mut state.sim, &[control as usize], target as usize);
+                state.apply_noise(
+                    noise::gate_name(stringify!($qir_name)),
+                    &[control as usize, target as usize],
+                );
             });
         }
     };
@@ -220,8 +584,8 @@ macro_rules! controlled_qubit_gate {
     ($(#[$meta:meta])*
     $qir_name:ident, $gate:expr, 2) => {
         $(#[$meta])*
-        #[no_mangle]
-        pub extern "C" fn $qir_name(
+        #[allow(non_snake_case)]
+        pub fn $qir_name(
             control_1: *mut c_void,
             control_2: *mut c_void,
             target: *mut c_void,
@@ -233,6 +597,10 @@ macro_rules! controlled_qubit_gate {
                 ensure_sufficient_qubits(&mut state.sim, control_2 as usize, &mut state.max_qubit_id);
 
                 $gate(&mut state.sim, &[control_1 as usize, control_2 as usize], target as usize);
+                state.apply_noise(
+                    noise::gate_name(stringify!($qir_name)),
+                    &[control_1 as usize, control_2 as usize, target as usize],
+                );
             });
         }
     };
@@ -241,31 +609,31 @@ macro_rules! controlled_qubit_gate {
 controlled_qubit_gate!(
     /// QIR API for performing the CNOT gate with the given qubits.
     __quantum__qis__cnot__body,
-    QuantumSim::mcx,
+    Engine::mcx,
     1
 );
 controlled_qubit_gate!(
     /// QIR API for performing the CNOT gate with the given qubits.
     __quantum__qis__cx__body,
-    QuantumSim::mcx,
+    Engine::mcx,
     1
 );
 controlled_qubit_gate!(
     /// QIR API for performing the CCNOT gate with the given qubits.
     __quantum__qis__ccx__body,
-    QuantumSim::mcx,
+    Engine::mcx,
     2
 );
 controlled_qubit_gate!(
     /// QIR API for performing the CY gate with the given qubits.
     __quantum__qis__cy__body,
-    QuantumSim::mcy,
+    Engine::mcy,
     1
 );
 controlled_qubit_gate!(
     /// QIR API for performing the CZ gate with the given qubits.
     __quantum__qis__cz__body,
-    QuantumSim::mcz,
+    Engine::mcz,
     1
 );
 
@@ -273,13 +641,14 @@ macro_rules! single_qubit_rotation {
     ($(#[$meta:meta])*
     $qir_name:ident, $gate:expr) => {
         $(#[$meta])*
-        #[no_mangle]
-        pub extern "C" fn $qir_name(theta: c_double, qubit: *mut c_void) {
+        #[allow(non_snake_case)]
+        pub fn $qir_name(theta: c_double, qubit: *mut c_void) {
             SIM_STATE.with(|sim_state| {
                 let state = &mut *sim_state.borrow_mut();
                 ensure_sufficient_qubits(&mut state.sim, qubit as usize, &mut state.max_qubit_id);
 
                 $gate(&mut state.sim, theta, qubit as usize);
+                state.apply_noise(noise::gate_name(stringify!($qir_name)), &[qubit as usize]);
             });
         }
     };
@@ -288,17 +657,17 @@ macro_rules! single_qubit_rotation {
 single_qubit_rotation!(
     /// QIR API for applying a Pauli-X rotation with the given angle and qubit.
     __quantum__qis__rx__body,
-    QuantumSim::rx
+    Engine::rx
 );
 single_qubit_rotation!(
     /// QIR API for applying a Pauli-Y rotation with the given angle and qubit.
     __quantum__qis__ry__body,
-    QuantumSim::ry
+    Engine::ry
 );
 single_qubit_rotation!(
     /// QIR API for applying a Pauli-Z rotation with the given angle and qubit.
     __quantum__qis__rz__body,
-    QuantumSim::rz
+    Engine::rz
 );
 
 macro_rules! multicontrolled_qubit_gate {
@@ -308,8 +677,8 @@ macro_rules! multicontrolled_qubit_gate {
         /// # Safety
         ///
         /// This function should only be called with arrays and tuples created by the QIR runtime library.
-        #[no_mangle]
-        pub unsafe extern "C" fn $qir_name(ctls: *const QirArray, qubit: *mut c_void) {
+        #[allow(non_snake_case)]
+        pub unsafe fn $qir_name(ctls: *const QirArray, qubit: *mut c_void) {
             SIM_STATE.with(|sim_state| {
                 let state = &mut *sim_state.borrow_mut();
                 ensure_sufficient_qubits(&mut state.sim, qubit as usize, &mut state.max_qubit_id);
@@ -324,6 +693,9 @@ macro_rules! multicontrolled_qubit_gate {
                     .collect();
 
                 $gate(&mut state.sim, &ctls_list, qubit as usize);
+                let mut qubits = ctls_list;
+                qubits.push(qubit as usize);
+                state.apply_noise(noise::gate_name(stringify!($qir_name)), &qubits);
             });
         }
     };
@@ -332,42 +704,42 @@ macro_rules! multicontrolled_qubit_gate {
 multicontrolled_qubit_gate!(
     /// QIR API for performing the multicontrolled H gate with the given qubits.
     __quantum__qis__h__ctl,
-    QuantumSim::mch
+    Engine::mch
 );
 multicontrolled_qubit_gate!(
     /// QIR API for performing the multicontrolled S gate with the given qubits.
     __quantum__qis__s__ctl,
-    QuantumSim::mcs
+    Engine::mcs
 );
 multicontrolled_qubit_gate!(
     /// QIR API for performing the multicontrolled Adjoint S gate with the given qubits.
     __quantum__qis__s__ctladj,
-    QuantumSim::mcsadj
+    Engine::mcsadj
 );
 multicontrolled_qubit_gate!(
     /// QIR API for performing the multicontrolled T gate with the given qubits.
     __quantum__qis__t__ctl,
-    QuantumSim::mct
+    Engine::mct
 );
 multicontrolled_qubit_gate!(
     /// QIR API for performing the multicontrolled Adjoint T gate with the given qubits.
     __quantum__qis__t__ctladj,
-    QuantumSim::mctadj
+    Engine::mctadj
 );
 multicontrolled_qubit_gate!(
     /// QIR API for performing the multicontrolled X gate with the given qubits.
     __quantum__qis__x__ctl,
-    QuantumSim::mcx
+    Engine::mcx
 );
 multicontrolled_qubit_gate!(
     /// QIR API for performing the multicontrolled Y gate with the given qubits.
     __quantum__qis__y__ctl,
-    QuantumSim::mcy
+    Engine::mcy
 );
 multicontrolled_qubit_gate!(
     /// QIR API for performing the multicontrolled Z gate with the given qubits.
     __quantum__qis__z__ctl,
-    QuantumSim::mcz
+    Engine::mcz
 );
 
 #[derive(Copy, Clone)]
@@ -384,8 +756,8 @@ macro_rules! multicontrolled_qubit_rotation {
         /// # Safety
         ///
         /// This function should only be called with arrays and tuples created by the QIR runtime library.
-        #[no_mangle]
-        pub unsafe extern "C" fn $qir_name(
+        #[allow(non_snake_case)]
+        pub unsafe fn $qir_name(
             ctls: *const QirArray,
             arg_tuple: *mut *const Vec<u8>,
         ) {
@@ -411,6 +783,9 @@ macro_rules! multicontrolled_qubit_rotation {
                     args.theta,
                     args.qubit as usize,
                 );
+                let mut qubits = ctls_list;
+                qubits.push(args.qubit as usize);
+                state.apply_noise(noise::gate_name(stringify!($qir_name)), &qubits);
             });
         }
     };
@@ -419,78 +794,72 @@ macro_rules! multicontrolled_qubit_rotation {
 multicontrolled_qubit_rotation!(
     /// QIR API for applying a multicontrolled Pauli-X rotation with the given angle and qubit.
     __quantum__qis__rx__ctl,
-    QuantumSim::mcrx
+    Engine::mcrx
 );
 multicontrolled_qubit_rotation!(
     /// QIR API for applying a multicontrolled Pauli-Y rotation with the given angle and qubit.
     __quantum__qis__ry__ctl,
-    QuantumSim::mcry
+    Engine::mcry
 );
 multicontrolled_qubit_rotation!(
     /// QIR API for applying a multicontrolled Pauli-Z rotation with the given angle and qubit.
     __quantum__qis__rz__ctl,
-    QuantumSim::mcrz
+    Engine::mcrz
 );
 
 /// QIR API for applying a joint rotation Pauli-Y rotation with the given angle for the two target qubit.
-#[no_mangle]
-pub extern "C" fn __quantum__qis__rxx__body(
-    theta: c_double,
-    qubit1: *mut c_void,
-    qubit2: *mut c_void,
-) {
-    __quantum__qis__h__body(qubit1);
+#[allow(non_snake_case)]
+pub fn __quantum__qis__rxx__body(theta: c_double, qubit1: *mut c_void, qubit2: *mut c_void) {
+    composite_gate("rxx", &[qubit1 as usize, qubit2 as usize], || {
+        __quantum__qis__h__body(qubit1);
 
-    __quantum__qis__h__body(qubit2);
+        __quantum__qis__h__body(qubit2);
 
-    __quantum__qis__rzz__body(theta, qubit1, qubit2);
+        __quantum__qis__rzz__body(theta, qubit1, qubit2);
 
-    __quantum__qis__h__body(qubit2);
+        __quantum__qis__h__body(qubit2);
 
-    __quantum__qis__h__body(qubit1);
+        __quantum__qis__h__body(qubit1);
+    });
 }
 
 /// QIR API for applying a joint rotation Pauli-Y rotation with the given angle for the two target qubit.
-#[no_mangle]
-pub extern "C" fn __quantum__qis__ryy__body(
-    theta: c_double,
-    qubit1: *mut c_void,
-    qubit2: *mut c_void,
-) {
-    __quantum__qis__h__body(qubit1);
-    __quantum__qis__s__body(qubit1);
-    __quantum__qis__h__body(qubit1);
-
-    __quantum__qis__h__body(qubit2);
-    __quantum__qis__s__body(qubit2);
-    __quantum__qis__h__body(qubit2);
-
-    __quantum__qis__rzz__body(theta, qubit1, qubit2);
-
-    __quantum__qis__h__body(qubit2);
-    __quantum__qis__s__adj(qubit2);
-    __quantum__qis__h__body(qubit2);
-
-    __quantum__qis__h__body(qubit1);
-    __quantum__qis__s__adj(qubit1);
-    __quantum__qis__h__body(qubit1);
+#[allow(non_snake_case)]
+pub fn __quantum__qis__ryy__body(theta: c_double, qubit1: *mut c_void, qubit2: *mut c_void) {
+    composite_gate("ryy", &[qubit1 as usize, qubit2 as usize], || {
+        __quantum__qis__h__body(qubit1);
+        __quantum__qis__s__body(qubit1);
+        __quantum__qis__h__body(qubit1);
+
+        __quantum__qis__h__body(qubit2);
+        __quantum__qis__s__body(qubit2);
+        __quantum__qis__h__body(qubit2);
+
+        __quantum__qis__rzz__body(theta, qubit1, qubit2);
+
+        __quantum__qis__h__body(qubit2);
+        __quantum__qis__s__adj(qubit2);
+        __quantum__qis__h__body(qubit2);
+
+        __quantum__qis__h__body(qubit1);
+        __quantum__qis__s__adj(qubit1);
+        __quantum__qis__h__body(qubit1);
+    });
 }
 
 /// QIR API for applying a joint rotation Pauli-Z rotation with the given angle for the two target qubit.
-#[no_mangle]
-pub extern "C" fn __quantum__qis__rzz__body(
-    theta: c_double,
-    qubit1: *mut c_void,
-    qubit2: *mut c_void,
-) {
-    __quantum__qis__cx__body(qubit2, qubit1);
-    __quantum__qis__rz__body(theta, qubit1);
-    __quantum__qis__cx__body(qubit2, qubit1);
+#[allow(non_snake_case)]
+pub fn __quantum__qis__rzz__body(theta: c_double, qubit1: *mut c_void, qubit2: *mut c_void) {
+    composite_gate("rzz", &[qubit1 as usize, qubit2 as usize], || {
+        __quantum__qis__cx__body(qubit2, qubit1);
+        __quantum__qis__rz__body(theta, qubit1);
+        __quantum__qis__cx__body(qubit2, qubit1);
+    });
 }
 
 /// QIR API for applying a rotation about the given Pauli axis with the given angle and qubit.
-#[no_mangle]
-pub extern "C" fn __quantum__qis__r__body(pauli: Pauli, theta: c_double, qubit: *mut c_void) {
+#[allow(non_snake_case)]
+pub fn __quantum__qis__r__body(pauli: Pauli, theta: c_double, qubit: *mut c_void) {
     match pauli {
         Pauli::I => (),
         Pauli::X => __quantum__qis__rx__body(theta, qubit),
@@ -500,8 +869,8 @@ pub extern "C" fn __quantum__qis__r__body(pauli: Pauli, theta: c_double, qubit:
 }
 
 /// QIR API for applying an adjoint rotation about the given Pauli axis with the given angle and qubit.
-#[no_mangle]
-pub extern "C" fn __quantum__qis__r__adj(pauli: Pauli, theta: c_double, qubit: *mut c_void) {
+#[allow(non_snake_case)]
+pub fn __quantum__qis__r__adj(pauli: Pauli, theta: c_double, qubit: *mut c_void) {
     __quantum__qis__r__body(pauli, -theta, qubit);
 }
 
@@ -518,11 +887,8 @@ struct PauliRotationArgs {
 ///
 /// This function should only be called with arrays and tuples created by the QIR runtime library.
 #[allow(clippy::cast_ptr_alignment)]
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__qis__r__ctl(
-    ctls: *const QirArray,
-    arg_tuple: *mut *const Vec<u8>,
-) {
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__qis__r__ctl(ctls: *const QirArray, arg_tuple: *mut *const Vec<u8>) {
     let args = *arg_tuple.cast::<PauliRotationArgs>();
     let rot_args = RotationArgs {
         theta: args.theta,
@@ -562,6 +928,9 @@ pub unsafe extern "C" fn __quantum__qis__r__ctl(
                             *head,
                         );
                     }
+                    let mut qubits = ctls_list;
+                    qubits.push(args.qubit as usize);
+                    state.apply_noise("r", &qubits);
                 });
             }
         }
@@ -574,11 +943,8 @@ pub unsafe extern "C" fn __quantum__qis__r__ctl(
 /// # Safety
 ///
 /// This function should only be called with arrays and tuples created by the QIR runtime library.
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__qis__r__ctladj(
-    ctls: *const QirArray,
-    arg_tuple: *mut *const Vec<u8>,
-) {
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__qis__r__ctladj(ctls: *const QirArray, arg_tuple: *mut *const Vec<u8>) {
     let args = *arg_tuple.cast::<PauliRotationArgs>();
     let new_args = PauliRotationArgs {
         pauli: args.pauli,
@@ -592,20 +958,21 @@ pub unsafe extern "C" fn __quantum__qis__r__ctladj(
 }
 
 /// QIR API for applying a SWAP gate to the given qubits.
-#[no_mangle]
-pub extern "C" fn __quantum__qis__swap__body(qubit1: *mut c_void, qubit2: *mut c_void) {
+#[allow(non_snake_case)]
+pub fn __quantum__qis__swap__body(qubit1: *mut c_void, qubit2: *mut c_void) {
     SIM_STATE.with(|sim_state| {
         let state = &mut *sim_state.borrow_mut();
         ensure_sufficient_qubits(&mut state.sim, qubit1 as usize, &mut state.max_qubit_id);
         ensure_sufficient_qubits(&mut state.sim, qubit2 as usize, &mut state.max_qubit_id);
 
         state.sim.swap_qubit_ids(qubit1 as usize, qubit2 as usize);
+        state.apply_noise("swap", &[qubit1 as usize, qubit2 as usize]);
     });
 }
 
 /// QIR API for resetting the given qubit in the computational basis.
-#[no_mangle]
-pub extern "C" fn __quantum__qis__reset__body(qubit: *mut c_void) {
+#[allow(non_snake_case)]
+pub fn __quantum__qis__reset__body(qubit: *mut c_void) {
     SIM_STATE.with(|sim_state| {
         let state = &mut *sim_state.borrow_mut();
         ensure_sufficient_qubits(&mut state.sim, qubit as usize, &mut state.max_qubit_id);
@@ -613,18 +980,23 @@ pub extern "C" fn __quantum__qis__reset__body(qubit: *mut c_void) {
         if state.sim.measure(qubit as usize) {
             state.sim.x(qubit as usize);
         }
+        state.apply_noise("reset", &[qubit as usize]);
     });
 }
 
 /// QIR API for measuring the given qubit and then resetting it in the computational basis.
-#[no_mangle]
-pub extern "C" fn __quantum__qis__mresetz__body(qubit: *mut c_void) -> *mut c_void {
+#[allow(non_snake_case)]
+pub fn __quantum__qis__mresetz__body(qubit: *mut c_void) -> *mut c_void {
     SIM_STATE.with(|sim_state| {
         let state = &mut *sim_state.borrow_mut();
         ensure_sufficient_qubits(&mut state.sim, qubit as usize, &mut state.max_qubit_id);
+        state.apply_noise("mresetz", &[qubit as usize]);
 
-        if state.sim.measure(qubit as usize) {
+        let res = state.sim.measure(qubit as usize);
+        if res {
             state.sim.x(qubit as usize);
+        }
+        if state.readout("mresetz", &[qubit as usize], res) {
             __quantum__rt__result_get_one()
         } else {
             __quantum__rt__result_get_zero()
@@ -633,8 +1005,8 @@ pub extern "C" fn __quantum__qis__mresetz__body(qubit: *mut c_void) -> *mut c_vo
 }
 
 /// QIR API for measuring the given qubit in the computation basis and storing the measured value with the given result identifier.
-#[no_mangle]
-pub extern "C" fn __quantum__qis__mz__body(qubit: *mut c_void, result: *mut c_void) {
+#[allow(non_snake_case)]
+pub fn __quantum__qis__mz__body(qubit: *mut c_void, result: *mut c_void) {
     SIM_STATE.with(|sim_state| {
         let state = &mut *sim_state.borrow_mut();
         let res_id = result as usize;
@@ -643,19 +1015,22 @@ pub extern "C" fn __quantum__qis__mz__body(qubit: *mut c_void, result: *mut c_vo
         if state.res.len() < res_id + 1 {
             state.res.resize(res_id + 1, false);
         }
+        state.apply_noise("mz", &[qubit as usize]);
+        state.capture_terminal_distribution();
 
+        let res = state.sim.measure(qubit as usize);
         *state
             .res
             .get_mut(res_id)
             .expect("Result with given id missing after expansion.") =
-            state.sim.measure(qubit as usize);
+            state.readout("mz", &[qubit as usize], res);
     });
 }
 
 /// QIR API that reads the Boolean value corresponding to the given result identifier, where true
 /// indicates a |1⟩ state and false indicates a |0⟩ state.
-#[no_mangle]
-pub extern "C" fn __quantum__qis__read_result__body(result: *mut c_void) -> bool {
+#[allow(non_snake_case)]
+pub fn __quantum__qis__read_result__body(result: *mut c_void) -> bool {
     SIM_STATE.with(|sim_state| {
         let res = &mut sim_state.borrow_mut().res;
         let res_id = result as usize;
@@ -671,13 +1046,15 @@ pub extern "C" fn __quantum__qis__read_result__body(result: *mut c_void) -> bool
 }
 
 /// QIR API that measures a given qubit in the computational basis, returning a runtime managed result value.
-#[no_mangle]
-pub extern "C" fn __quantum__qis__m__body(qubit: *mut c_void) -> *mut c_void {
+#[allow(non_snake_case)]
+pub fn __quantum__qis__m__body(qubit: *mut c_void) -> *mut c_void {
     SIM_STATE.with(|sim_state| {
         let state = &mut *sim_state.borrow_mut();
         ensure_sufficient_qubits(&mut state.sim, qubit as usize, &mut state.max_qubit_id);
+        state.apply_noise("m", &[qubit as usize]);
 
-        if state.sim.measure(qubit as usize) {
+        let res = state.sim.measure(qubit as usize);
+        if state.readout("m", &[qubit as usize], res) {
             __quantum__rt__result_get_one()
         } else {
             __quantum__rt__result_get_zero()
@@ -693,8 +1070,9 @@ pub extern "C" fn __quantum__qis__m__body(qubit: *mut c_void) -> *mut c_void {
 ///
 /// This function will panic if the provided paulis and qubits arrays are not of the same size.
 #[allow(clippy::cast_ptr_alignment)]
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__qis__measure__body(
+#[must_use]
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__qis__measure__body(
     paulis: *const QirArray,
     qubits: *const QirArray,
 ) -> *mut c_void {
@@ -702,17 +1080,14 @@ pub unsafe extern "C" fn __quantum__qis__measure__body(
         let mut state = sim_state.borrow_mut();
 
         let combined_list = map_to_z_basis(&mut state, paulis, qubits);
+        let measured_qubits: Vec<usize> = combined_list.iter().map(|(_, q)| *q).collect();
+        state.apply_noise("measure", &measured_qubits);
 
-        let res = state.sim.joint_measure(
-            &combined_list
-                .iter()
-                .map(|(_, q)| *q)
-                .collect::<Vec<usize>>(),
-        );
+        let res = state.sim.joint_measure(&measured_qubits);
 
         unmap_from_z_basis(&mut state, combined_list);
 
-        if res {
+        if state.readout("measure", &measured_qubits, res) {
             __quantum__rt__result_get_one()
         } else {
             __quantum__rt__result_get_zero()
@@ -720,6 +1095,117 @@ pub unsafe extern "C" fn __quantum__qis__measure__body(
     })
 }
 
+/// Returns the expectation value of the Pauli string given by the Pauli and qubit id pairs on the bound
+/// simulator.
+fn pauli_expectation(state: &mut SimulatorState, pauli_list: &[(Pauli, usize)]) -> f64 {
+    for (_, qubit) in pauli_list {
+        ensure_sufficient_qubits(&mut state.sim, *qubit, &mut state.max_qubit_id);
+    }
+    state.sim.expectation(pauli_list)
+}
+
+/// Rust API for the expectation value ⟨ψ|P|ψ⟩ of the Pauli string P that applies the given Paulis to the given
+/// qubits. The value is computed exactly and the state is left unchanged, unlike estimating it from
+/// measurements.
+/// # Panics
+///
+/// This function will panic if the number of Paulis differs from the number of qubits, or if a qubit appears
+/// more than once.
+#[must_use]
+pub fn expectation(paulis: &[Pauli], qubits: &[*mut c_void]) -> f64 {
+    hamiltonian_expectation(&[(1.0, paulis, qubits)])
+}
+
+/// Rust API for the expectation value of a weighted sum of Pauli strings, such as a Hamiltonian, where each
+/// term is given by its coefficient, its Paulis and the qubits they apply to.
+/// # Panics
+///
+/// This function will panic if the number of Paulis of a term differs from its number of qubits, or if a qubit
+/// appears more than once in a term.
+#[must_use]
+pub fn hamiltonian_expectation(terms: &[(f64, &[Pauli], &[*mut c_void])]) -> f64 {
+    SIM_STATE.with(|sim_state| {
+        let state = &mut *sim_state.borrow_mut();
+        terms
+            .iter()
+            .map(|(coefficient, paulis, qubits)| {
+                assert_eq!(
+                    paulis.len(),
+                    qubits.len(),
+                    "Pauli array and Qubit array must be the same size."
+                );
+                let pauli_list: Vec<(Pauli, usize)> = paulis
+                    .iter()
+                    .zip(qubits.iter())
+                    .map(|(pauli, qubit)| (*pauli, *qubit as usize))
+                    .collect();
+                coefficient * pauli_expectation(state, &pauli_list)
+            })
+            .sum()
+    })
+}
+
+/// QIR API for the exact expectation value of the Pauli string that applies the given Paulis to the given
+/// qubits, which leaves the state unchanged.
+/// # Safety
+///
+/// This function should only be called with arrays created by the QIR runtime library.
+#[must_use]
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__qis__expectation__body(
+    paulis: *const QirArray,
+    qubits: *const QirArray,
+) -> c_double {
+    let pauli_list = read_pauli_list(paulis, qubits);
+    SIM_STATE.with(|sim_state| pauli_expectation(&mut sim_state.borrow_mut(), &pauli_list))
+}
+
+/// QIR API for the exact expectation value of a weighted sum of Pauli strings, such as a Hamiltonian. The
+/// coefficients are given as an array of doubles, and the Paulis and qubits of the terms as arrays of arrays.
+/// # Safety
+///
+/// This function should only be called with arrays created by the QIR runtime library.
+#[allow(clippy::cast_ptr_alignment)]
+#[must_use]
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__qis__hamiltonianexpectation__body(
+    coefficients: *const QirArray,
+    paulis: *const QirArray,
+    qubits: *const QirArray,
+) -> c_double {
+    let count = __quantum__rt__array_get_size_1d(coefficients);
+    if count != __quantum__rt__array_get_size_1d(paulis)
+        || count != __quantum__rt__array_get_size_1d(qubits)
+    {
+        __quantum__rt__fail(__quantum__rt__string_create(
+            CString::new("Coefficient array, Pauli array and Qubit array must be the same size.")
+                .expect("Unable to allocate memory for failure message string.")
+                .as_bytes_with_nul()
+                .as_ptr() as *mut c_char,
+        ));
+    }
+    let terms: Vec<(f64, Vec<(Pauli, usize)>)> = (0..count)
+        .map(|index| {
+            (
+                *__quantum__rt__array_get_element_ptr_1d(coefficients, index).cast::<c_double>(),
+                read_pauli_list(
+                    *__quantum__rt__array_get_element_ptr_1d(paulis, index)
+                        .cast::<*const QirArray>(),
+                    *__quantum__rt__array_get_element_ptr_1d(qubits, index)
+                        .cast::<*const QirArray>(),
+                ),
+            )
+        })
+        .collect();
+    SIM_STATE.with(|sim_state| {
+        let state = &mut *sim_state.borrow_mut();
+        terms
+            .iter()
+            .map(|(coefficient, pauli_list)| coefficient * pauli_expectation(state, pauli_list))
+            .sum()
+    })
+}
+
 /// Rust API for checking internal simulator state and returning true only if the given qubit is in exactly the |0⟩ state.
 pub fn qubit_is_zero(qubit: *mut c_void) -> bool {
     SIM_STATE.with(|sim_state| {
@@ -738,8 +1224,8 @@ pub fn qubit_is_zero(qubit: *mut c_void) -> bool {
 /// # Safety
 ///
 /// This function should only be called with arrays created by the QIR runtime library.
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__qis__assertmeasurementprobability__body(
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__qis__assertmeasurementprobability__body(
     paulis: *const QirArray,
     qubits: *const QirArray,
     result: *mut c_void,
@@ -788,8 +1274,8 @@ struct AssertMeasurementProbabilityArgs {
 /// # Safety
 ///
 /// This function should only be called with arrays created by the QIR runtime library.
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__qis__assertmeasurementprobability__ctl(
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__qis__assertmeasurementprobability__ctl(
     _ctls: *const QirArray,
     arg_tuple: *mut *const Vec<u8>,
 ) {
@@ -807,39 +1293,28 @@ pub unsafe extern "C" fn __quantum__qis__assertmeasurementprobability__ctl(
 pub mod legacy {
     use std::ffi::c_void;
 
-    use crate::{
-        result_bool::{__quantum__rt__result_equal, __quantum__rt__result_get_one},
-        SIM_STATE,
-    };
+    use qir_stdlib::output_recording::{record_output, OutputRecord, OutputValue};
 
-    #[allow(non_snake_case)]
-    pub extern "C" fn __quantum__rt__result_record_output(result: *mut c_void) {
-        SIM_STATE.with(|sim_state| {
-            let res = &mut sim_state.borrow_mut().res;
-            let res_id = result as usize;
-            let b = if res.is_empty() {
-                // No static measurements have been used, so default to dynamic handling.
-                __quantum__rt__result_equal(result, __quantum__rt__result_get_one())
-            } else {
-                if res.len() < res_id + 1 {
-                    res.resize(res_id + 1, false);
-                }
-                *res.get(res_id)
-                    .expect("Result with given id missing after expansion.")
-            };
+    use crate::read_recorded_result;
 
-            println!("RESULT\t{}", if b { "1" } else { "0" });
-        });
+    qir_stdlib::exports! {
+        fn __quantum__rt__result_record_output(result: *mut c_void) => Legacy;
+    }
+
+    #[allow(non_snake_case)]
+    pub fn __quantum__rt__result_record_output(result: *mut c_void) {
+        record_output(OutputRecord::LegacyValue(OutputValue::Result(
+            read_recorded_result(result),
+        )));
     }
 }
 
-/// QIR API for recording the given result into the program output.
-#[no_mangle]
-pub extern "C" fn __quantum__rt__result_record_output(result: *mut c_void, tag: *mut c_char) {
+/// Reads the value of the given result for output recording, supporting both static and dynamic results.
+fn read_recorded_result(result: *mut c_void) -> bool {
     SIM_STATE.with(|sim_state| {
         let res = &mut sim_state.borrow_mut().res;
         let res_id = result as usize;
-        let b = if res.is_empty() {
+        if res.is_empty() {
             // No static measurements have been used, so default to dynamic handling.
             __quantum__rt__result_equal(result, __quantum__rt__result_get_one())
         } else {
@@ -848,38 +1323,33 @@ pub extern "C" fn __quantum__rt__result_record_output(result: *mut c_void, tag:
             }
             *res.get(res_id)
                 .expect("Result with given id missing after expansion.")
-        };
+        }
+    })
+}
 
-        let val: i64 = i64::from(b);
-        output("RESULT", &val, tag, &mut std::io::stdout()).expect("Failed to write result output");
-    });
+/// QIR API for recording the given result into the program output.
+#[allow(non_snake_case)]
+pub fn __quantum__rt__result_record_output(result: *mut c_void, tag: *mut c_char) {
+    record_tagged_result(read_recorded_result(result), tag);
 }
 
-#[cfg(windows)]
-const LINE_ENDING: &[u8] = b"\r\n";
-#[cfg(not(windows))]
-const LINE_ENDING: &[u8] = b"\n";
-
-fn output(
-    ty: &str,
-    val: &dyn std::fmt::Display,
-    tag: *mut c_char,
-    output: &mut impl std::io::Write,
-) -> std::io::Result<()> {
-    output.write_fmt(format_args!("OUTPUT\t{ty}\t{val}"))?;
-    if !tag.is_null() {
-        output.write_all(b"\t")?;
-        unsafe {
-            output.write_all(CString::from_raw(tag).as_bytes())?;
-        }
-    }
-    output.write_all(LINE_ENDING)?;
-    Ok(())
+fn record_tagged_result(val: bool, tag: *mut c_char) {
+    let tag = if tag.is_null() {
+        None
+    } else {
+        // The tag is owned by the caller, so it is only borrowed here.
+        unsafe { Some(CStr::from_ptr(tag).to_string_lossy().into_owned()) }
+    };
+    record_output(OutputRecord::Value {
+        value: OutputValue::Result(val),
+        tag,
+    });
 }
 
 /// QIR API that allocates the next available qubit in the simulation.
-#[no_mangle]
-pub extern "C" fn __quantum__rt__qubit_allocate() -> *mut c_void {
+#[must_use]
+#[allow(non_snake_case)]
+pub fn __quantum__rt__qubit_allocate() -> *mut c_void {
     SIM_STATE.with(|sim_state| {
         let mut state = sim_state.borrow_mut();
         let qubit_id = state.sim.allocate();
@@ -894,8 +1364,9 @@ pub extern "C" fn __quantum__rt__qubit_allocate() -> *mut c_void {
 
 /// QIR API for allocating the given number of qubits in the simulation, returning them as a runtime managed array.
 #[allow(clippy::cast_ptr_alignment)]
-#[no_mangle]
-pub extern "C" fn __quantum__rt__qubit_allocate_array(size: u64) -> *const QirArray {
+#[must_use]
+#[allow(non_snake_case)]
+pub fn __quantum__rt__qubit_allocate_array(size: u64) -> *const QirArray {
     let arr = __quantum__rt__array_create_1d(
         size_of::<usize>()
             .try_into()
@@ -916,8 +1387,8 @@ pub extern "C" fn __quantum__rt__qubit_allocate_array(size: u64) -> *const QirAr
 ///
 /// This function should only be called with arrays created by `__quantum__rt__qubit_allocate_array`.
 #[allow(clippy::cast_ptr_alignment)]
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__qubit_release_array(arr: *const QirArray) {
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__qubit_release_array(arr: *const QirArray) {
     for index in 0..__quantum__rt__array_get_size_1d(arr) {
         let elem = __quantum__rt__array_get_element_ptr_1d(arr, index).cast::<*mut c_void>();
         __quantum__rt__qubit_release(*elem);
@@ -926,8 +1397,8 @@ pub unsafe extern "C" fn __quantum__rt__qubit_release_array(arr: *const QirArray
 }
 
 /// QIR API for releasing the given qubit from the simulation.
-#[no_mangle]
-pub extern "C" fn __quantum__rt__qubit_release(qubit: *mut c_void) {
+#[allow(non_snake_case)]
+pub fn __quantum__rt__qubit_release(qubit: *mut c_void) {
     SIM_STATE.with(|sim_state| {
         let mut state = sim_state.borrow_mut();
         state.sim.release(qubit as usize);
@@ -935,8 +1406,8 @@ pub extern "C" fn __quantum__rt__qubit_release(qubit: *mut c_void) {
 }
 
 /// QIR API for getting the string interpretation of a qubit identifier.
-#[no_mangle]
-pub extern "C" fn __quantum__rt__qubit_to_string(qubit: *mut c_void) -> *const CString {
+#[allow(non_snake_case)]
+pub fn __quantum__rt__qubit_to_string(qubit: *mut c_void) -> *const CString {
     unsafe {
         __quantum__rt__string_create(
             CString::new(format!("{}", qubit as usize))
@@ -950,6 +1421,8 @@ pub extern "C" fn __quantum__rt__qubit_to_string(qubit: *mut c_void) -> *const C
 /// Rust API for getting a snapshot of current quantum state. The state is a sorted copy of
 /// the current sparse state represented by a vector of pairs of indices and complex numbers along
 /// with the total number of currently allocated qubits to help in interpreting the state.
+/// On the density matrix simulator, the pairs are the non-zero entries of the density matrix instead,
+/// where the entry in row r and column c of a density matrix of n qubits has the index r · 2^n + c.
 #[must_use]
 pub fn capture_quantum_state() -> (Vec<(BigUint, Complex64)>, usize) {
     SIM_STATE.with(|sim_state| {
@@ -958,45 +1431,116 @@ pub fn capture_quantum_state() -> (Vec<(BigUint, Complex64)>, usize) {
     })
 }
 
-/// QIR API for dumping full internal simulator state.
-#[no_mangle]
-pub extern "C" fn __quantum__qis__dumpmachine__body(location: *mut c_void) {
-    if !location.is_null() {
-        unimplemented!("Dump to location is not implemented.")
-    }
+/// QIR API for dumping full internal simulator state. Without a location, the state is recorded as a message to
+/// the output sink of the current thread, as a table with the amplitude, probability and phase of every basis
+/// state in ket notation, where qubit 0 is the leftmost bit. It is preceded by a message with the measurement
+/// results so far, if any. Otherwise the location is a `%String*` with the path of the file to which the state is
+/// written, as JSON if the path has a `.json` extension and as the table otherwise. The file only contains the
+/// state, without the measurement results, and nothing is recorded to the output sink. Basis states whose
+/// amplitudes have at most the magnitude set with `set_dump_threshold` are left out. The stabilizer and density
+/// matrix simulators record their own representation of the state and cannot write it to a file.
+///
+/// # Panics
+///
+/// This function will panic if the state cannot be written to the location.
+#[allow(non_snake_case)]
+pub fn __quantum__qis__dumpmachine__body(location: *mut c_void) {
+    // The location is owned by the caller, so it is only borrowed here.
+    let path = (!location.is_null())
+        .then(|| unsafe { (*location.cast::<CString>()).to_string_lossy().into_owned() });
     SIM_STATE.with(|sim_state| {
-        let mut state = sim_state.borrow_mut();
+        let state = &mut *sim_state.borrow_mut();
+        let simulator = match state.sim.kind() {
+            SimulatorKind::Stabilizer => Some("stabilizer"),
+            SimulatorKind::DensityMatrix => Some("density matrix"),
+            _ => None,
+        };
+        if let Some(simulator) = simulator {
+            assert!(
+                path.is_none(),
+                "The {simulator} simulator does not support dumping the state to a file."
+            );
+            record_global_results(&state.res);
+            let dump = state.sim.dump();
+            record_output(OutputRecord::Message(dump));
+            return;
+        }
 
-        if !state.res.is_empty() {
-            println!("Global Results: {}", state.res);
+        let (amplitudes, qubits) = state.sim.get_state();
+        let dump = StateDump::new(amplitudes, qubits, state.dump_threshold);
+        match path {
+            None => {
+                record_global_results(&state.res);
+                let table = dump.to_table();
+                record_output(OutputRecord::Message(
+                    table.trim_end_matches('\n').to_owned(),
+                ));
+            }
+            Some(path) => {
+                let contents = if Path::new(&path)
+                    .extension()
+                    .map_or(false, |ext| ext == "json")
+                {
+                    dump.to_json()
+                } else {
+                    dump.to_table()
+                };
+                if let Err(e) = fs::write(&path, contents) {
+                    panic!("Failed to write the state to '{path}': {e}");
+                }
+            }
         }
-        state.sim.dump();
     });
 }
 
+/// Records the results of the measurements so far as a message, unless nothing was measured.
+fn record_global_results(res: &BitVec) {
+    if !res.is_empty() {
+        record_output(OutputRecord::Message(format!("Global Results: {res}")));
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{f64::consts::PI, ffi::c_void, ptr::null_mut};
+    use std::{
+        f64::consts::PI,
+        ffi::{c_void, CString},
+        mem::size_of,
+        path::{Path, PathBuf},
+        ptr::null_mut,
+    };
 
     use crate::{
-        __quantum__qis__cnot__body, __quantum__qis__cx__body, __quantum__qis__cz__body,
-        __quantum__qis__dumpmachine__body, __quantum__qis__h__body, __quantum__qis__m__body,
+        __quantum__qis__ccx__body, __quantum__qis__cnot__body, __quantum__qis__cx__body,
+        __quantum__qis__cz__body, __quantum__qis__dumpmachine__body,
+        __quantum__qis__expectation__body, __quantum__qis__h__body,
+        __quantum__qis__hamiltonianexpectation__body, __quantum__qis__m__body,
         __quantum__qis__mresetz__body, __quantum__qis__mz__body, __quantum__qis__read_result__body,
         __quantum__qis__rx__body, __quantum__qis__rxx__body, __quantum__qis__ry__body,
         __quantum__qis__ryy__body, __quantum__qis__rz__body, __quantum__qis__rzz__body,
-        __quantum__qis__s__adj, __quantum__qis__s__body, __quantum__qis__x__body,
-        __quantum__rt__qubit_allocate, __quantum__rt__qubit_allocate_array,
-        __quantum__rt__qubit_release, __quantum__rt__qubit_release_array,
-        __quantum__rt__result_equal, capture_quantum_state, map_to_z_basis, qubit_is_zero,
-        result_bool::__quantum__rt__result_get_one, result_bool::__quantum__rt__result_get_zero,
-        unmap_from_z_basis, SIM_STATE,
+        __quantum__qis__s__adj, __quantum__qis__s__body, __quantum__qis__t__body,
+        __quantum__qis__x__body, __quantum__rt__initialize, __quantum__rt__qubit_allocate,
+        __quantum__rt__qubit_allocate_array, __quantum__rt__qubit_release,
+        __quantum__rt__qubit_release_array, __quantum__rt__result_equal, bind_simulator,
+        bound_simulator, capture_quantum_state, create_simulator, destroy_simulator,
+        engine::Engine,
+        estimate::{MeasurementPolicy, ResourceCounts},
+        expectation, hamiltonian_expectation, map_to_z_basis,
+        noise::NoiseModel,
+        qubit_is_zero, resource_counts,
+        result_bool::__quantum__rt__result_get_one,
+        result_bool::__quantum__rt__result_get_zero,
+        set_dump_threshold, set_noise_model, set_output_sink, set_rng_seed, set_simulator_kind,
+        set_terminal_sampling, set_tracing, trace_qasm, truncation_error, unmap_from_z_basis,
+        OutputBuffer, OutputRecord, SimulatorKind, SIM_STATE,
     };
     use num_bigint::BigUint;
     use qir_stdlib::{
         arrays::{
-            __quantum__rt__array_create_1d, __quantum__rt__array_get_element_ptr_1d,
+            QirArray, __quantum__rt__array_create_1d, __quantum__rt__array_get_element_ptr_1d,
             __quantum__rt__array_update_reference_count,
         },
+        strings::{__quantum__rt__string_create, __quantum__rt__string_update_reference_count},
         Pauli,
     };
 
@@ -1058,6 +1602,79 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_expectation() {
+        let q0 = __quantum__rt__qubit_allocate();
+        let q1 = __quantum__rt__qubit_allocate();
+        __quantum__qis__h__body(q0);
+        __quantum__qis__cx__body(q0, q1);
+        for (paulis, expected) in [
+            ([Pauli::Z, Pauli::Z], 1.0),
+            ([Pauli::X, Pauli::X], 1.0),
+            ([Pauli::Y, Pauli::Y], -1.0),
+            ([Pauli::Z, Pauli::I], 0.0),
+            ([Pauli::I, Pauli::I], 1.0),
+        ] {
+            assert!((expectation(&paulis, &[q0, q1]) - expected).abs() < 1e-10);
+        }
+        assert!(
+            (hamiltonian_expectation(&[
+                (0.5, &[Pauli::Z, Pauli::Z], &[q0, q1]),
+                (-2.0, &[Pauli::Y, Pauli::Y], &[q0, q1]),
+                (3.0, &[Pauli::X], &[q1]),
+            ]) - 2.5)
+                .abs()
+                < 1e-10
+        );
+
+        // The expectation values do not collapse the state.
+        __quantum__qis__cx__body(q0, q1);
+        __quantum__qis__h__body(q0);
+        assert!(qubit_is_zero(q0));
+        assert!(qubit_is_zero(q1));
+        __quantum__rt__qubit_release(q0);
+        __quantum__rt__qubit_release(q1);
+    }
+
+    #[allow(clippy::cast_ptr_alignment)]
+    #[test]
+    fn test_expectation_intrinsics() {
+        unsafe fn array<T>(items: &[T]) -> *const QirArray {
+            let array = __quantum__rt__array_create_1d(
+                u32::try_from(size_of::<T>()).unwrap(),
+                items.len() as u64,
+            );
+            for (index, item) in items.iter().enumerate() {
+                std::ptr::copy_nonoverlapping(
+                    item,
+                    __quantum__rt__array_get_element_ptr_1d(array, index as u64).cast::<T>(),
+                    1,
+                );
+            }
+            array
+        }
+
+        let q0 = __quantum__rt__qubit_allocate();
+        let q1 = __quantum__rt__qubit_allocate();
+        __quantum__qis__x__body(q0);
+        __quantum__qis__h__body(q1);
+        unsafe {
+            let qubits = array(&[q0, q1]);
+            let paulis = array(&[Pauli::Z, Pauli::X]);
+            assert!((__quantum__qis__expectation__body(paulis, qubits) + 1.0).abs() < 1e-10);
+            let hamiltonian = __quantum__qis__hamiltonianexpectation__body(
+                array(&[0.5_f64, 0.25]),
+                array(&[paulis, array(&[Pauli::Z])]),
+                array(&[qubits, array(&[q1])]),
+            );
+            assert!((hamiltonian + 0.5).abs() < 1e-10);
+        }
+        __quantum__qis__h__body(q1);
+        __quantum__qis__x__body(q0);
+        __quantum__rt__qubit_release(q0);
+        __quantum__rt__qubit_release(q1);
+    }
+
     #[allow(clippy::cast_ptr_alignment)]
     #[test]
     fn test_map_unmap_are_adjoint() {
@@ -1276,6 +1893,402 @@ mod tests {
         assert!(qubit_is_zero(qubit));
     }
 
+    #[test]
+    fn test_noise_model() {
+        let noise = NoiseModel::from_toml(
+            "[gates.x]\nbit_flip = 1.0\n[gates.h]\namplitude_damping = 1.0\n[gates.m]\nreadout_error = 1.0\n",
+        )
+        .expect("noise model should parse");
+        assert_eq!(set_noise_model(Some(noise)), None);
+        let qubit = __quantum__rt__qubit_allocate();
+        __quantum__qis__x__body(qubit);
+        assert!(qubit_is_zero(qubit));
+        __quantum__qis__h__body(qubit);
+        assert!(qubit_is_zero(qubit));
+        assert!(__quantum__rt__result_equal(
+            __quantum__rt__result_get_one(),
+            __quantum__qis__m__body(qubit)
+        ));
+        assert!(qubit_is_zero(qubit));
+        assert!(__quantum__rt__result_equal(
+            __quantum__rt__result_get_zero(),
+            __quantum__qis__mresetz__body(qubit)
+        ));
+        // The noise of the gates that implement RXX is not applied.
+        let qubit2 = __quantum__rt__qubit_allocate();
+        __quantum__qis__rxx__body(PI, qubit, qubit2);
+        assert!(!qubit_is_zero(qubit));
+        assert!(!qubit_is_zero(qubit2));
+        assert!(set_noise_model(None).is_some());
+        __quantum__qis__x__body(qubit);
+        __quantum__qis__x__body(qubit2);
+        assert!(qubit_is_zero(qubit));
+        assert!(qubit_is_zero(qubit2));
+        __quantum__rt__qubit_release(qubit);
+        __quantum__rt__qubit_release(qubit2);
+    }
+
+    #[test]
+    fn test_density_matrix_simulator() {
+        assert_eq!(
+            set_simulator_kind(SimulatorKind::DensityMatrix),
+            SimulatorKind::Sparse
+        );
+        let qubit = __quantum__rt__qubit_allocate();
+        let qubit2 = __quantum__rt__qubit_allocate();
+        __quantum__qis__h__body(qubit);
+        __quantum__qis__cx__body(qubit, qubit2);
+        let (state, qubit_count) = capture_quantum_state();
+        assert_eq!(qubit_count, 2);
+        assert_eq!(
+            state
+                .iter()
+                .map(|(index, _)| index.clone())
+                .collect::<Vec<_>>(),
+            [0_u32, 3, 12, 15].map(BigUint::from)
+        );
+        assert!(state
+            .iter()
+            .all(|(_, value)| (value.re - 0.5).abs() < 1e-10));
+
+        // Noise is applied exactly, so the bit flip mixes the state instead of flipping it in some shots.
+        let noise = NoiseModel::from_toml("[gates.x]\nbit_flip = 0.25\n")
+            .expect("noise model should parse");
+        set_noise_model(Some(noise));
+        __quantum__qis__mresetz__body(qubit);
+        __quantum__qis__mresetz__body(qubit2);
+        __quantum__qis__x__body(qubit);
+        let (state, _) = capture_quantum_state();
+        assert_eq!(
+            state
+                .iter()
+                .map(|(index, _)| index.clone())
+                .collect::<Vec<_>>(),
+            [0_u32, 5].map(BigUint::from)
+        );
+        assert!((state[0].1.re - 0.25).abs() < 1e-10);
+        assert!((state[1].1.re - 0.75).abs() < 1e-10);
+        set_noise_model(None);
+        __quantum__rt__qubit_release(qubit);
+        __quantum__rt__qubit_release(qubit2);
+        assert_eq!(
+            set_simulator_kind(SimulatorKind::Sparse),
+            SimulatorKind::DensityMatrix
+        );
+    }
+
+    #[test]
+    fn test_multiple_simulators() {
+        let first = bound_simulator();
+        let qubit = __quantum__rt__qubit_allocate();
+        __quantum__qis__x__body(qubit);
+
+        // The second simulator has its own qubits and kind, and does not see the state of the first one.
+        let second = create_simulator(SimulatorKind::Stabilizer);
+        assert_eq!(bind_simulator(second), Ok(first));
+        assert_eq!(
+            set_simulator_kind(SimulatorKind::Stabilizer),
+            SimulatorKind::Stabilizer
+        );
+        let qubit2 = __quantum__rt__qubit_allocate();
+        assert!(qubit_is_zero(qubit2));
+        __quantum__qis__h__body(qubit2);
+        assert_eq!(
+            destroy_simulator(second),
+            Err("The simulator with handle 1 is bound and cannot be destroyed.".to_owned())
+        );
+
+        assert_eq!(bind_simulator(first), Ok(second));
+        assert_eq!(
+            set_simulator_kind(SimulatorKind::Sparse),
+            SimulatorKind::Sparse
+        );
+        assert!(!qubit_is_zero(qubit));
+        __quantum__qis__x__body(qubit);
+        __quantum__rt__qubit_release(qubit);
+
+        assert_eq!(bind_simulator(second), Ok(first));
+        let res = __quantum__qis__mresetz__body(qubit2);
+        assert!(
+            __quantum__rt__result_equal(res, __quantum__rt__result_get_zero())
+                || __quantum__rt__result_equal(res, __quantum__rt__result_get_one())
+        );
+        __quantum__rt__qubit_release(qubit2);
+        assert_eq!(bind_simulator(first), Ok(second));
+        assert_eq!(destroy_simulator(second), Ok(()));
+        assert_eq!(
+            bind_simulator(second),
+            Err("There is no simulator with handle 1.".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_terminal_sampling() {
+        let (q0, q1) = (null_mut(), 1 as *mut c_void);
+        let (r0, r1) = (null_mut(), 1 as *mut c_void);
+        assert!(!set_terminal_sampling(true));
+        let mut ones = 0;
+        for shot in 0..200 {
+            set_rng_seed(shot);
+            __quantum__rt__initialize(null_mut());
+            __quantum__qis__h__body(q0);
+            __quantum__qis__cx__body(q0, q1);
+            __quantum__qis__mz__body(q0, r0);
+            __quantum__qis__mz__body(q1, r1);
+            let res = __quantum__qis__read_result__body(r0);
+            assert_eq!(__quantum__qis__read_result__body(r1), res);
+            ones += usize::from(res);
+        }
+        assert!((50..150).contains(&ones));
+        // The shots after the first one skip the gates and sample the measurements.
+        SIM_STATE.with(|sim_state| {
+            assert!(matches!(sim_state.borrow().sim, Engine::Sampled(_)));
+        });
+        assert!(set_terminal_sampling(false));
+    }
+
+    #[test]
+    fn test_terminal_sampling_of_unused_qubits() {
+        let (q0, q1) = (null_mut(), 1 as *mut c_void);
+        let (r0, r1) = (null_mut(), 1 as *mut c_void);
+        set_terminal_sampling(true);
+        for _ in 0..3 {
+            __quantum__rt__initialize(null_mut());
+            __quantum__qis__x__body(q0);
+            __quantum__qis__mz__body(q0, r0);
+            // The second qubit is only allocated by its measurement, after the distribution is captured.
+            __quantum__qis__mz__body(q1, r1);
+            assert!(__quantum__qis__read_result__body(r0));
+            assert!(!__quantum__qis__read_result__body(r1));
+        }
+        set_terminal_sampling(false);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Gates cannot be applied after the first measurement when measurements are sampled."
+    )]
+    fn test_terminal_sampling_rejects_gates_after_measurements() {
+        set_terminal_sampling(true);
+        __quantum__qis__mz__body(null_mut(), null_mut());
+        __quantum__qis__x__body(null_mut());
+    }
+
+    #[test]
+    fn test_mps_simulator() {
+        assert_eq!(
+            set_simulator_kind(SimulatorKind::Mps { bond_dimension: 4 }),
+            SimulatorKind::Sparse
+        );
+        let qubits: Vec<*mut c_void> = (0..300).map(|_| __quantum__rt__qubit_allocate()).collect();
+        __quantum__qis__h__body(qubits[0]);
+        for pair in qubits.windows(2) {
+            __quantum__qis__cx__body(pair[0], pair[1]);
+        }
+        // The first and last qubits are far apart, so the gate swaps them next to each other.
+        __quantum__qis__cz__body(qubits[0], qubits[299]);
+        assert!(truncation_error() < 1e-10);
+        let res = __quantum__qis__m__body(qubits[0]);
+        for &qubit in &qubits {
+            assert!(__quantum__rt__result_equal(
+                res,
+                __quantum__qis__mresetz__body(qubit)
+            ));
+            __quantum__rt__qubit_release(qubit);
+        }
+        assert_eq!(
+            set_simulator_kind(SimulatorKind::Sparse),
+            SimulatorKind::Mps { bond_dimension: 4 }
+        );
+    }
+
+    #[test]
+    fn test_stabilizer_simulator() {
+        assert_eq!(
+            set_simulator_kind(SimulatorKind::Stabilizer),
+            SimulatorKind::Sparse
+        );
+        let qubits: Vec<*mut c_void> = (0..500).map(|_| __quantum__rt__qubit_allocate()).collect();
+        __quantum__qis__h__body(qubits[0]);
+        for pair in qubits.windows(2) {
+            __quantum__qis__cx__body(pair[0], pair[1]);
+        }
+        let res = __quantum__qis__m__body(qubits[0]);
+        for &qubit in &qubits {
+            assert!(__quantum__rt__result_equal(
+                res,
+                __quantum__qis__mresetz__body(qubit)
+            ));
+            __quantum__rt__qubit_release(qubit);
+        }
+        assert_eq!(
+            set_simulator_kind(SimulatorKind::Sparse),
+            SimulatorKind::Stabilizer
+        );
+    }
+
+    #[test]
+    fn test_tracing() {
+        assert!(!set_tracing(true));
+        assert_eq!(trace_qasm(), None);
+        __quantum__rt__initialize(null_mut());
+        let q0 = __quantum__rt__qubit_allocate();
+        let q1 = __quantum__rt__qubit_allocate();
+        __quantum__qis__h__body(q0);
+        __quantum__qis__cx__body(q0, q1);
+        __quantum__qis__rx__body(0.5, q1);
+        let res = __quantum__qis__m__body(q0);
+        let one = __quantum__rt__result_equal(res, __quantum__rt__result_get_one());
+        __quantum__rt__qubit_release(q0);
+        __quantum__rt__qubit_release(q1);
+        assert_eq!(
+            trace_qasm(),
+            Some(format!(
+                "OPENQASM 3.0;\ninclude \"stdgates.inc\";\nqubit[2] q;\nbit[1] c;\nh q[0];\ncx q[0], q[1];\nrx(0.5) q[1];\nc[0] = measure q[0]; // {}\n",
+                u8::from(one)
+            ))
+        );
+        assert!(set_tracing(false));
+        __quantum__rt__initialize(null_mut());
+        assert_eq!(trace_qasm(), None);
+    }
+
+    #[test]
+    fn test_dumpmachine_records_messages() {
+        let buffer = OutputBuffer::new();
+        let previous = set_output_sink(Box::new(buffer.clone()));
+        __quantum__rt__initialize(null_mut());
+        let q0 = __quantum__rt__qubit_allocate();
+        __quantum__qis__x__body(q0);
+        __quantum__qis__mz__body(q0, null_mut());
+        __quantum__qis__dumpmachine__body(null_mut());
+        set_output_sink(previous);
+        let records = buffer.take();
+        assert_eq!(
+            records.first(),
+            Some(&OutputRecord::Message("Global Results: [1]".to_owned()))
+        );
+        match records.get(1) {
+            Some(OutputRecord::Message(table)) => {
+                assert!(table.starts_with("Basis State  Amplitude"));
+                assert!(table.contains("|1\u{27e9}"));
+                assert!(!table.ends_with('\n'));
+            }
+            record => panic!("Expected the state table as message, got {record:?}"),
+        }
+        assert_eq!(records.len(), 2);
+        __quantum__qis__x__body(q0);
+        __quantum__rt__qubit_release(q0);
+    }
+
+    /// Files that are removed when dropped, so that a test does not leave them behind if an assertion fails.
+    struct RemoveOnDrop(Vec<PathBuf>);
+
+    impl Drop for RemoveOnDrop {
+        fn drop(&mut self) {
+            for path in &self.0 {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dumpmachine_to_file() {
+        let dump = |path: &Path| {
+            let path = CString::new(path.to_str().expect("Path should be valid UTF-8"))
+                .expect("Path should not contain null bytes");
+            unsafe {
+                let location = __quantum__rt__string_create(path.as_ptr().cast_mut());
+                __quantum__qis__dumpmachine__body(location.cast_mut().cast());
+                __quantum__rt__string_update_reference_count(location, -1);
+            }
+        };
+        let json = std::env::temp_dir().join(format!("dumpmachine_{}.json", std::process::id()));
+        let table = std::env::temp_dir().join(format!("dumpmachine_{}.txt", std::process::id()));
+        let _dumps = RemoveOnDrop(vec![json.clone(), table.clone()]);
+
+        __quantum__rt__initialize(null_mut());
+        let q0 = __quantum__rt__qubit_allocate();
+        let q1 = __quantum__rt__qubit_allocate();
+        __quantum__qis__x__body(q0);
+        __quantum__qis__ry__body(0.001, q1);
+        dump(&json);
+        dump(&table);
+        let contents = std::fs::read_to_string(&json).expect("State should be written as JSON");
+        let value: serde_json::Value =
+            serde_json::from_str(&contents).expect("Dump should be valid JSON");
+        assert_eq!(value["qubits"], 2);
+        assert_eq!(value["amplitudes"][0]["basis_state"], "10");
+        assert_eq!(value["amplitudes"][1]["basis_state"], "11");
+        let contents = std::fs::read_to_string(&table).expect("State should be written as a table");
+        assert!(contents.starts_with("Basis State  Amplitude"));
+        assert!(contents.contains("|10\u{27e9}"));
+
+        assert!(set_dump_threshold(0.01).abs() < f64::EPSILON);
+        dump(&json);
+        let contents = std::fs::read_to_string(&json).expect("State should be written as JSON");
+        let value: serde_json::Value =
+            serde_json::from_str(&contents).expect("Dump should be valid JSON");
+        assert_eq!(value["amplitudes"].as_array().map(Vec::len), Some(1));
+        assert!((set_dump_threshold(0.0) - 0.01).abs() < f64::EPSILON);
+
+        __quantum__qis__ry__body(-0.001, q1);
+        __quantum__qis__x__body(q0);
+        __quantum__rt__qubit_release(q0);
+        __quantum__rt__qubit_release(q1);
+    }
+
+    #[test]
+    fn test_resource_estimation() {
+        let kind = SimulatorKind::Estimate {
+            measurements: MeasurementPolicy::One,
+        };
+        set_simulator_kind(kind);
+        assert!(resource_counts().is_some());
+        let qubits: Vec<*mut c_void> = (0..1000).map(|_| __quantum__rt__qubit_allocate()).collect();
+        __quantum__qis__h__body(qubits[0]);
+        for pair in qubits.windows(2) {
+            __quantum__qis__cx__body(pair[0], pair[1]);
+        }
+        __quantum__qis__t__body(qubits[0]);
+        __quantum__qis__rx__body(0.5, qubits[1]);
+        assert!(__quantum__rt__result_equal(
+            __quantum__qis__m__body(qubits[999]),
+            __quantum__rt__result_get_one()
+        ));
+        for &qubit in &qubits[500..] {
+            __quantum__rt__qubit_release(qubit);
+        }
+        let ancilla = __quantum__rt__qubit_allocate();
+        __quantum__qis__ccx__body(qubits[0], qubits[1], ancilla);
+
+        let counts = resource_counts().expect("Resources should be counted.");
+        assert_eq!(counts.peak_qubits, 1000);
+        assert_eq!(counts.gates["h"], 1);
+        assert_eq!(counts.gates["cx"], 999);
+        assert_eq!(counts.gates["ccx"], 1);
+        assert_eq!(counts.t_count, 1);
+        assert_eq!(counts.rotation_count, 1);
+        assert_eq!(counts.measurement_count, 1);
+        // The h gate, the chain of cx gates and the measurement of the last qubit follow each other.
+        assert_eq!(counts.depth, 1001);
+
+        __quantum__rt__initialize(null_mut());
+        assert_eq!(resource_counts(), Some(ResourceCounts::default()));
+        assert_eq!(set_simulator_kind(SimulatorKind::Sparse), kind);
+        assert_eq!(resource_counts(), None);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The stabilizer simulator does not support the non-Clifford gate Rx."
+    )]
+    fn test_stabilizer_simulator_rejects_rotations() {
+        set_simulator_kind(SimulatorKind::Stabilizer);
+        let qubit = __quantum__rt__qubit_allocate();
+        __quantum__qis__rx__body(PI, qubit);
+    }
+
     #[test]
     fn test_capture_quantum_state() {
         let qubit = __quantum__rt__qubit_allocate();
@@ -3,6 +3,7 @@
 
 use core::f64::consts::FRAC_1_SQRT_2;
 use ndarray::{array, s, Array2};
+use num_bigint::BigUint;
 use num_complex::Complex64;
 use num_traits::One;
 use num_traits::ToPrimitive;
@@ -10,9 +11,9 @@ use num_traits::Zero;
 use rustc_hash::FxHashMap;
 
 use crate::nearly_zero::NearlyZero;
-use crate::simulator::{FlushLevel, QuantumSim};
+use crate::simulator::{check_for_duplicates, FlushLevel, QuantumSim};
 
-impl QuantumSim {
+impl QuantumSim<BigUint> {
     /// Applies the given unitary to the given targets, extending the unitary to accomodate controls if any.
     /// # Panics
     ///
@@ -52,7 +53,7 @@ impl QuantumSim {
             // Extend the provided unitary by inserting it into an identity matrix.
             unitary = controlled(&unitary, ctrls.len().try_into().unwrap());
         }
-        Self::check_for_duplicates(&targets);
+        check_for_duplicates(&targets);
 
         self.flush_queue(&targets, FlushLevel::HRxRy);
 
@@ -382,10 +383,10 @@ mod tests {
     /// Utility for testing operation equivalence.
     fn assert_operation_equal_referenced<F1, F2>(mut op: F1, mut reference: F2, count: usize)
     where
-        F1: FnMut(&mut QuantumSim, &[usize]),
-        F2: FnMut(&mut QuantumSim, &[usize]),
+        F1: FnMut(&mut QuantumSim<BigUint>, &[usize]),
+        F2: FnMut(&mut QuantumSim<BigUint>, &[usize]),
     {
-        let mut sim = QuantumSim::default();
+        let mut sim = QuantumSim::<BigUint>::default();
 
         // Allocte the control we use to verify behavior.
         let ctl = sim.allocate();
@@ -0,0 +1,800 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Matrix product state simulation, which stores the state as a chain of tensors, one per qubit, connected by
+//! bonds of limited dimension. Gates on neighboring qubits are applied by contracting their tensors and
+//! splitting the result with a singular value decomposition, where the smallest singular values are discarded
+//! when the bond dimension would exceed its maximum. Gates on distant qubits first move one of the qubits next
+//! to the other with swaps. This allows simulating hundreds of qubits in shallow circuits with little
+//! entanglement, and the discarded weight of the singular values is reported as the truncation error.
+//! Gates with more than one control are not supported and cause a panic.
+
+use crate::{
+    dense::{rx_matrix, ry_matrix, rz_matrix, Matrix},
+    dump::StateDump,
+    simulator::{check_for_duplicates, random_sample},
+};
+use ndarray::{Array2, Array3};
+use num_bigint::BigUint;
+use num_complex::Complex64;
+use num_traits::{One, Zero};
+use qir_stdlib::Pauli;
+use rustc_hash::FxHashMap;
+use std::f64::consts::FRAC_1_SQRT_2;
+
+/// The maximum bond dimension of the MPS simulator unless configured otherwise.
+pub(crate) const DEFAULT_BOND_DIMENSION: usize = 64;
+
+/// The largest number of qubits whose state vector can be captured.
+pub(crate) const MAX_STATE_QUBITS: usize = 26;
+
+/// Singular values below this fraction of the largest one are treated as zero.
+const SINGULAR_VALUE_CUTOFF: f64 = 1e-13;
+
+/// A 4x4 matrix acting on two neighboring qubits, where the first qubit is the high bit of the indices.
+type TwoQubitMatrix = [[Complex64; 4]; 4];
+
+/// The `MpsSim` struct contains the matrix product state of a simulation.
+pub(crate) struct MpsSim {
+    /// The tensor of each position in the chain, indexed by the left bond, the value of the qubit and the right
+    /// bond. The tensors left of the orthogonality center are left-orthonormal and those right of it are
+    /// right-orthonormal, so the center tensor alone determines the norm and local probabilities.
+    tensors: Vec<Array3<Complex64>>,
+
+    /// The mapping from qubit identifiers to positions in the chain.
+    id_map: FxHashMap<usize, usize>,
+
+    /// The position of the orthogonality center.
+    center: usize,
+
+    /// The maximum dimension of a bond.
+    bond_dimension: usize,
+
+    /// The total weight of the singular values discarded to keep the bonds within the maximum dimension.
+    truncation_error: f64,
+}
+
+/// Computes the singular value decomposition A = UΣV† of the given matrix with the one-sided Jacobi method.
+/// Returns U, the singular values in decreasing order and V†, where singular values that are nearly zero and
+/// their vectors are dropped.
+#[allow(clippy::many_single_char_names)]
+fn svd(mut a: Array2<Complex64>) -> (Array2<Complex64>, Vec<f64>, Array2<Complex64>) {
+    let (rows, cols) = a.dim();
+    let mut v = Array2::<Complex64>::eye(cols);
+    for _ in 0..100 {
+        let mut rotated = false;
+        for p in 0..cols {
+            for q in p + 1..cols {
+                let (mut alpha, mut beta, mut gamma) = (0.0, 0.0, Complex64::zero());
+                for i in 0..rows {
+                    alpha += a[[i, p]].norm_sqr();
+                    beta += a[[i, q]].norm_sqr();
+                    gamma += a[[i, p]].conj() * a[[i, q]];
+                }
+                if gamma.norm() <= f64::EPSILON * (alpha * beta).sqrt() || gamma.norm() == 0.0 {
+                    continue;
+                }
+                rotated = true;
+                // Rotating the phase of column q makes the product of the columns real, so that a real Jacobi
+                // rotation orthogonalizes them.
+                let phase = (gamma / gamma.norm()).conj();
+                let ratio = (beta - alpha) / (2.0 * gamma.norm());
+                let t = ratio.signum() / (ratio.abs() + (1.0 + ratio * ratio).sqrt());
+                let c = 1.0 / (1.0 + t * t).sqrt();
+                let s = c * t;
+                for matrix in [&mut a, &mut v] {
+                    for i in 0..matrix.nrows() {
+                        let (column_p, column_q) = (matrix[[i, p]], matrix[[i, q]] * phase);
+                        matrix[[i, p]] = column_p * c - column_q * s;
+                        matrix[[i, q]] = column_p * s + column_q * c;
+                    }
+                }
+            }
+        }
+        if !rotated {
+            break;
+        }
+    }
+
+    let norms: Vec<f64> = (0..cols)
+        .map(|j| {
+            a.column(j)
+                .iter()
+                .map(Complex64::norm_sqr)
+                .sum::<f64>()
+                .sqrt()
+        })
+        .collect();
+    let mut order: Vec<usize> = (0..cols).collect();
+    order.sort_unstable_by(|&i, &j| norms[j].total_cmp(&norms[i]));
+    let largest = order.first().map_or(0.0, |&j| norms[j]);
+    order.retain(|&j| norms[j] > SINGULAR_VALUE_CUTOFF * largest);
+
+    let u = Array2::from_shape_fn((rows, order.len()), |(i, k)| {
+        a[[i, order[k]]] / norms[order[k]]
+    });
+    let vt = Array2::from_shape_fn((order.len(), cols), |(k, j)| v[[j, order[k]]].conj());
+    (u, order.iter().map(|&j| norms[j]).collect(), vt)
+}
+
+/// Returns the matrix that applies the given matrix to one of two neighboring qubits if the other is |1⟩.
+fn controlled(matrix: &Matrix, control_first: bool) -> TwoQubitMatrix {
+    let mut result = [[Complex64::zero(); 4]; 4];
+    for (index, row) in result.iter_mut().enumerate() {
+        row[index] = Complex64::one();
+    }
+    for (row, matrix_row) in matrix.iter().enumerate() {
+        for (col, value) in matrix_row.iter().enumerate() {
+            // The index of the target qubit in the four basis states where the control is |1⟩.
+            let (row, col) = if control_first {
+                (2 | row, 2 | col)
+            } else {
+                ((row << 1) | 1, (col << 1) | 1)
+            };
+            result[row][col] = *value;
+        }
+    }
+    result
+}
+
+/// Returns the matrix that swaps two neighboring qubits.
+fn swap_matrix() -> TwoQubitMatrix {
+    let mut result = [[Complex64::zero(); 4]; 4];
+    for (index, swapped) in [0, 2, 1, 3].into_iter().enumerate() {
+        result[index][swapped] = Complex64::one();
+    }
+    result
+}
+
+impl MpsSim {
+    /// Creates a matrix product state with no qubits allocated and the given maximum bond dimension.
+    pub(crate) fn new(bond_dimension: usize) -> Self {
+        assert!(bond_dimension > 0, "The bond dimension must be positive.");
+        MpsSim {
+            tensors: Vec::new(),
+            id_map: FxHashMap::default(),
+            center: 0,
+            bond_dimension,
+            truncation_error: 0.0,
+        }
+    }
+
+    pub(crate) fn bond_dimension(&self) -> usize {
+        self.bond_dimension
+    }
+
+    /// Returns the total weight of the singular values that were discarded to keep the bonds within the maximum
+    /// dimension, which bounds the error of the simulated state.
+    pub(crate) fn truncation_error(&self) -> f64 {
+        self.truncation_error
+    }
+
+    fn pos(&self, id: usize) -> usize {
+        *self
+            .id_map
+            .get(&id)
+            .unwrap_or_else(|| panic!("Unable to find qubit with id {id}"))
+    }
+
+    fn unsupported(gate: &str) -> ! {
+        panic!("The MPS simulator does not support {gate} with more than one control.")
+    }
+
+    /// Moves the orthogonality center to the given position by splitting the tensors between the current and
+    /// the new center.
+    fn move_center(&mut self, pos: usize) {
+        while self.center < pos {
+            let tensor = &self.tensors[self.center];
+            let (left, _, right) = tensor.dim();
+            let matrix = Array2::from_shape_fn((left * 2, right), |(row, col)| {
+                tensor[[row / 2, row % 2, col]]
+            });
+            let (u, singular_values, vt) = svd(matrix);
+            let bond = singular_values.len();
+            self.tensors[self.center] =
+                Array3::from_shape_fn((left, 2, bond), |(l, p, k)| u[[l * 2 + p, k]]);
+            let next = &self.tensors[self.center + 1];
+            let (_, _, next_right) = next.dim();
+            self.tensors[self.center + 1] =
+                Array3::from_shape_fn((bond, 2, next_right), |(k, p, r)| {
+                    (0..right)
+                        .map(|j| singular_values[k] * vt[[k, j]] * next[[j, p, r]])
+                        .sum()
+                });
+            self.center += 1;
+        }
+        while self.center > pos {
+            let tensor = &self.tensors[self.center];
+            let (left, _, right) = tensor.dim();
+            let matrix = Array2::from_shape_fn((left, 2 * right), |(row, col)| {
+                tensor[[row, col / right, col % right]]
+            });
+            let (u, singular_values, vt) = svd(matrix);
+            let bond = singular_values.len();
+            self.tensors[self.center] =
+                Array3::from_shape_fn((bond, 2, right), |(k, p, r)| vt[[k, p * right + r]]);
+            let previous = &self.tensors[self.center - 1];
+            let (previous_left, _, _) = previous.dim();
+            self.tensors[self.center - 1] =
+                Array3::from_shape_fn((previous_left, 2, bond), |(l, p, k)| {
+                    (0..left)
+                        .map(|j| previous[[l, p, j]] * u[[j, k]] * singular_values[k])
+                        .sum()
+                });
+            self.center -= 1;
+        }
+    }
+
+    /// Allocates a fresh qubit at the end of the chain, returning its identifier. Like the sparse simulator,
+    /// this uses the lowest available identifier.
+    #[must_use]
+    pub(crate) fn allocate(&mut self) -> usize {
+        let id = (0..=self.id_map.len())
+            .find(|id| !self.id_map.contains_key(id))
+            .expect("There should be an available qubit identifier.");
+        let mut tensor = Array3::zeros((1, 2, 1));
+        tensor[[0, 0, 0]] = Complex64::one();
+        self.id_map.insert(id, self.tensors.len());
+        self.tensors.push(tensor);
+        id
+    }
+
+    /// Releases the given qubit, collapsing its state in the process and removing it from the chain.
+    /// # Panics
+    ///
+    /// The function will panic if the given id does not correpsond to an allocated qubit.
+    pub(crate) fn release(&mut self, id: usize) {
+        let pos = self.pos(id);
+        if self.measure(id) {
+            self.x(id);
+        }
+
+        // The qubit is in |0⟩, so its tensor reduces to a matrix that is merged into a neighbor.
+        let tensor = self.tensors.remove(pos);
+        let (left, _, right) = tensor.dim();
+        if pos < self.tensors.len() {
+            let next = &self.tensors[pos];
+            let (_, _, next_right) = next.dim();
+            self.tensors[pos] = Array3::from_shape_fn((left, 2, next_right), |(l, p, r)| {
+                (0..right)
+                    .map(|j| tensor[[l, 0, j]] * next[[j, p, r]])
+                    .sum()
+            });
+        } else if pos > 0 {
+            let previous = &self.tensors[pos - 1];
+            let (previous_left, _, _) = previous.dim();
+            self.tensors[pos - 1] =
+                Array3::from_shape_fn((previous_left, 2, right), |(l, p, r)| {
+                    (0..left)
+                        .map(|j| previous[[l, p, j]] * tensor[[j, 0, r]])
+                        .sum()
+                });
+            self.center = pos - 1;
+        } else {
+            self.center = 0;
+        }
+
+        self.id_map.remove(&id);
+        for other_pos in self.id_map.values_mut() {
+            if *other_pos > pos {
+                *other_pos -= 1;
+            }
+        }
+    }
+
+    /// Returns a sorted copy of the non-zero amplitudes, indexed by the qubits in order of their identifiers,
+    /// along with the number of allocated qubits.
+    /// # Panics
+    ///
+    /// This function will panic if too many qubits are allocated to capture the state vector.
+    #[must_use]
+    pub(crate) fn get_state(&mut self) -> (Vec<(BigUint, Complex64)>, usize) {
+        assert!(
+            self.tensors.len() <= MAX_STATE_QUBITS,
+            "The MPS simulator can only capture the state of up to {MAX_STATE_QUBITS} qubits."
+        );
+        // The amplitudes of the first positions in the chain, indexed by their values and the open bond.
+        let mut amplitudes = Array2::from_elem((1, 1), Complex64::one());
+        for (pos, tensor) in self.tensors.iter().enumerate() {
+            let (left, _, right) = tensor.dim();
+            amplitudes = Array2::from_shape_fn((amplitudes.nrows() * 2, right), |(index, r)| {
+                let (rest, value) = (index & !(1 << pos), (index >> pos) & 1);
+                (0..left)
+                    .map(|l| amplitudes[[rest, l]] * tensor[[l, value, r]])
+                    .sum()
+            });
+        }
+
+        let mut ids: Vec<usize> = self.id_map.keys().copied().collect();
+        ids.sort_unstable();
+        let positions: Vec<usize> = ids.iter().map(|&id| self.id_map[&id]).collect();
+        let mut state: Vec<(BigUint, Complex64)> = amplitudes
+            .column(0)
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| value.norm_sqr() > 1e-20)
+            .map(|(index, value)| {
+                let mut key = BigUint::zero();
+                for (bit, pos) in positions.iter().enumerate() {
+                    if index & (1 << pos) != 0 {
+                        key.set_bit(bit as u64, true);
+                    }
+                }
+                (key, *value)
+            })
+            .collect();
+        state.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        (state, ids.len())
+    }
+
+    /// Returns the current state vector as a table of the basis states with non-zero amplitudes.
+    pub(crate) fn dump(&mut self) -> String {
+        let (state, qubits) = self.get_state();
+        StateDump::new(state, qubits, 0.0).to_table()
+    }
+
+    /// Returns the probability of the qubit at the given position being |1⟩, moving the orthogonality center
+    /// to it.
+    fn probability(&mut self, pos: usize) -> f64 {
+        self.move_center(pos);
+        let tensor = &self.tensors[pos];
+        let (left, _, right) = tensor.dim();
+        let one: f64 = (0..left)
+            .flat_map(|l| (0..right).map(move |r| tensor[[l, 1, r]].norm_sqr()))
+            .sum();
+        let total: f64 = tensor.iter().map(Complex64::norm_sqr).sum();
+        one / total
+    }
+
+    /// Projects the qubit at the orthogonality center onto the given value and normalizes the state.
+    fn collapse(&mut self, pos: usize, val: bool) {
+        self.move_center(pos);
+        let tensor = &mut self.tensors[pos];
+        let mut norm = 0.0;
+        for ((_, value, _), entry) in tensor.indexed_iter_mut() {
+            if (value == 1) == val {
+                norm += entry.norm_sqr();
+            } else {
+                *entry = Complex64::zero();
+            }
+        }
+        let scaling = 1.0 / norm.sqrt();
+        tensor.mapv_inplace(|entry| entry * scaling);
+    }
+
+    /// Computes the parity of the given qubits into the last of them, applies the given function to the
+    /// position of that qubit and uncomputes the parity.
+    fn with_parity<T>(&mut self, ids: &[usize], f: impl FnOnce(&mut Self, usize) -> T) -> T {
+        check_for_duplicates(ids);
+        let (&target, ctls) = ids.split_last().expect("Qubit list cannot be empty.");
+        for &ctl in ctls {
+            self.mcx(&[ctl], target);
+        }
+        let result = f(self, self.pos(target));
+        for &ctl in ctls.iter().rev() {
+            self.mcx(&[ctl], target);
+        }
+        result
+    }
+
+    /// Checks the probability of parity measurement in the computational basis for the given set of
+    /// qubits.
+    /// # Panics
+    ///
+    /// This function will panic if the given ids do not all correspond to allocated qubits.
+    /// This function will panic if there are duplicate ids in the given list.
+    #[must_use]
+    pub(crate) fn joint_probability(&mut self, ids: &[usize]) -> f64 {
+        self.with_parity(ids, Self::probability)
+    }
+
+    /// Measures the qubit with the given id, collapsing the state based on the measured result.
+    /// # Panics
+    ///
+    /// This funciton will panic if the given identifier does not correspond to an allocated qubit.
+    #[must_use]
+    pub(crate) fn measure(&mut self, id: usize) -> bool {
+        self.joint_measure(&[id])
+    }
+
+    /// Performs a joint measurement to get the parity of the given qubits, collapsing the state
+    /// based on the measured result.
+    /// # Panics
+    ///
+    /// This function will panic if any of the given identifiers do not correspond to an allocated qubit.
+    /// This function will panic if any of the given identifiers are duplicates.
+    #[must_use]
+    pub(crate) fn joint_measure(&mut self, ids: &[usize]) -> bool {
+        self.with_parity(ids, |sim, pos| {
+            let res = random_sample() < sim.probability(pos);
+            sim.collapse(pos, res);
+            res
+        })
+    }
+
+    /// Swaps the mapped ids for the given qubits.
+    pub(crate) fn swap_qubit_ids(&mut self, qubit1: usize, qubit2: usize) {
+        let (pos1, pos2) = (self.pos(qubit1), self.pos(qubit2));
+        self.id_map.insert(qubit1, pos2);
+        self.id_map.insert(qubit2, pos1);
+    }
+
+    /// Applies a trajectory of the amplitude damping channel with the given damping probability to the qubit
+    /// with the given id, like the sparse simulator.
+    /// # Panics
+    ///
+    /// This function will panic if the given identifier does not correspond to an allocated qubit.
+    pub(crate) fn amplitude_damping(&mut self, gamma: f64, id: usize) {
+        let pos = self.pos(id);
+        let decay_probability = gamma * self.probability(pos);
+        if random_sample() < decay_probability {
+            self.collapse(pos, true);
+            self.x(id);
+        } else {
+            let scaling = 1.0 / (1.0 - decay_probability).sqrt();
+            let damped_scaling = (1.0 - gamma).sqrt() * scaling;
+            for ((_, value, _), entry) in self.tensors[pos].indexed_iter_mut() {
+                *entry *= if value == 0 { scaling } else { damped_scaling };
+            }
+        }
+    }
+
+    /// Applies the given matrix to the qubit at the given position, which keeps the orthogonality of its tensor.
+    fn apply_single(&mut self, pos: usize, matrix: &Matrix) {
+        let tensor = &mut self.tensors[pos];
+        let (left, _, right) = tensor.dim();
+        for l in 0..left {
+            for r in 0..right {
+                let (zero, one) = (tensor[[l, 0, r]], tensor[[l, 1, r]]);
+                tensor[[l, 0, r]] = matrix[0][0] * zero + matrix[0][1] * one;
+                tensor[[l, 1, r]] = matrix[1][0] * zero + matrix[1][1] * one;
+            }
+        }
+    }
+
+    /// Applies the given matrix to the qubits at the given position and the next one, truncating the bond
+    /// between them to the maximum bond dimension.
+    fn apply_pair(&mut self, pos: usize, matrix: &TwoQubitMatrix) {
+        self.move_center(pos);
+        let (first, second) = (&self.tensors[pos], &self.tensors[pos + 1]);
+        let ((left, _, bond), (_, _, right)) = (first.dim(), second.dim());
+        let pair = Array2::from_shape_fn((left * 2, 2 * right), |(row, col)| {
+            let (l, r) = (row / 2, col % right);
+            let value = ((row % 2) << 1) | (col / right);
+            (0..4)
+                .map(|index| {
+                    matrix[value][index]
+                        * (0..bond)
+                            .map(|k| first[[l, index >> 1, k]] * second[[k, index & 1, r]])
+                            .sum::<Complex64>()
+                })
+                .sum()
+        });
+
+        let (u, mut singular_values, vt) = svd(pair);
+        let total: f64 = singular_values.iter().map(|value| value * value).sum();
+        let bond = singular_values.len().min(self.bond_dimension);
+        let kept: f64 = singular_values[..bond]
+            .iter()
+            .map(|value| value * value)
+            .sum();
+        self.truncation_error += (total - kept) / total;
+        singular_values.truncate(bond);
+        let scaling = (total / kept).sqrt();
+
+        self.tensors[pos] = Array3::from_shape_fn((left, 2, bond), |(l, p, k)| u[[l * 2 + p, k]]);
+        self.tensors[pos + 1] = Array3::from_shape_fn((bond, 2, right), |(k, p, r)| {
+            singular_values[k] * scaling * vt[[k, p * right + r]]
+        });
+        self.center = pos + 1;
+    }
+
+    /// Applies the given matrix to the target if the control is |1⟩, first swapping the control along the chain
+    /// until it is next to the target.
+    fn apply_controlled(&mut self, ctl: usize, target: usize, matrix: &Matrix) {
+        check_for_duplicates(&[ctl, target]);
+        let target_pos = self.pos(target);
+        loop {
+            let ctl_pos = self.pos(ctl);
+            let next_pos = if ctl_pos < target_pos {
+                ctl_pos + 1
+            } else {
+                ctl_pos - 1
+            };
+            if next_pos == target_pos {
+                break;
+            }
+            let neighbor = *self
+                .id_map
+                .iter()
+                .find(|(_, &pos)| pos == next_pos)
+                .expect("Every position should hold a qubit.")
+                .0;
+            self.apply_pair(ctl_pos.min(next_pos), &swap_matrix());
+            self.id_map.insert(ctl, next_pos);
+            self.id_map.insert(neighbor, ctl_pos);
+        }
+        let ctl_pos = self.pos(ctl);
+        self.apply_pair(
+            ctl_pos.min(target_pos),
+            &controlled(matrix, ctl_pos < target_pos),
+        );
+    }
+
+    /// Applies the given matrix to the target with at most one control.
+    fn apply_with_controls(&mut self, gate: &str, ctls: &[usize], target: usize, matrix: &Matrix) {
+        match ctls {
+            [] => self.apply_single(self.pos(target), matrix),
+            [ctl] => self.apply_controlled(*ctl, target, matrix),
+            _ => Self::unsupported(gate),
+        }
+    }
+
+    pub(crate) fn mcphase(&mut self, ctls: &[usize], phase: Complex64, target: usize) {
+        let (zero, one) = (Complex64::zero(), Complex64::one());
+        self.apply_with_controls("phase gates", ctls, target, &[[one, zero], [zero, phase]]);
+    }
+
+    pub(crate) fn x(&mut self, target: usize) {
+        self.mcx(&[], target);
+    }
+
+    pub(crate) fn mcx(&mut self, ctls: &[usize], target: usize) {
+        let (zero, one) = (Complex64::zero(), Complex64::one());
+        self.apply_with_controls("X gates", ctls, target, &[[zero, one], [one, zero]]);
+    }
+
+    pub(crate) fn y(&mut self, target: usize) {
+        self.mcy(&[], target);
+    }
+
+    pub(crate) fn mcy(&mut self, ctls: &[usize], target: usize) {
+        let (zero, i) = (Complex64::zero(), Complex64::i());
+        self.apply_with_controls("Y gates", ctls, target, &[[zero, -i], [i, zero]]);
+    }
+
+    pub(crate) fn z(&mut self, target: usize) {
+        self.mcz(&[], target);
+    }
+
+    pub(crate) fn mcz(&mut self, ctls: &[usize], target: usize) {
+        self.mcphase(ctls, -Complex64::one(), target);
+    }
+
+    pub(crate) fn h(&mut self, target: usize) {
+        self.mch(&[], target);
+    }
+
+    pub(crate) fn mch(&mut self, ctls: &[usize], target: usize) {
+        let value = Complex64::new(FRAC_1_SQRT_2, 0.0);
+        self.apply_with_controls("H gates", ctls, target, &[[value, value], [value, -value]]);
+    }
+
+    pub(crate) fn s(&mut self, target: usize) {
+        self.mcs(&[], target);
+    }
+
+    pub(crate) fn mcs(&mut self, ctls: &[usize], target: usize) {
+        self.mcphase(ctls, Complex64::i(), target);
+    }
+
+    pub(crate) fn sadj(&mut self, target: usize) {
+        self.mcsadj(&[], target);
+    }
+
+    pub(crate) fn mcsadj(&mut self, ctls: &[usize], target: usize) {
+        self.mcphase(ctls, -Complex64::i(), target);
+    }
+
+    pub(crate) fn t(&mut self, target: usize) {
+        self.mct(&[], target);
+    }
+
+    pub(crate) fn mct(&mut self, ctls: &[usize], target: usize) {
+        self.mcphase(ctls, Complex64::new(FRAC_1_SQRT_2, FRAC_1_SQRT_2), target);
+    }
+
+    pub(crate) fn tadj(&mut self, target: usize) {
+        self.mctadj(&[], target);
+    }
+
+    pub(crate) fn mctadj(&mut self, ctls: &[usize], target: usize) {
+        self.mcphase(ctls, Complex64::new(FRAC_1_SQRT_2, -FRAC_1_SQRT_2), target);
+    }
+
+    pub(crate) fn rx(&mut self, theta: f64, target: usize) {
+        self.mcrx(&[], theta, target);
+    }
+
+    pub(crate) fn mcrx(&mut self, ctls: &[usize], theta: f64, target: usize) {
+        self.apply_with_controls("Rx gates", ctls, target, &rx_matrix(theta));
+    }
+
+    pub(crate) fn ry(&mut self, theta: f64, target: usize) {
+        self.mcry(&[], theta, target);
+    }
+
+    pub(crate) fn mcry(&mut self, ctls: &[usize], theta: f64, target: usize) {
+        self.apply_with_controls("Ry gates", ctls, target, &ry_matrix(theta));
+    }
+
+    pub(crate) fn rz(&mut self, theta: f64, target: usize) {
+        self.mcrz(&[], theta, target);
+    }
+
+    pub(crate) fn mcrz(&mut self, ctls: &[usize], theta: f64, target: usize) {
+        self.apply_with_controls("Rz gates", ctls, target, &rz_matrix(theta));
+    }
+
+    /// Applies the exponential of the multi-qubit Pauli operator with the given angle, exp(iθP), by rotating
+    /// the qubits into the Z basis and rotating the parity of the qubits.
+    pub(crate) fn exp(&mut self, paulis: &[Pauli], theta: f64, targets: &[usize]) {
+        check_for_duplicates(targets);
+        let qubits: Vec<(Pauli, usize)> = paulis
+            .iter()
+            .zip(targets)
+            .filter(|(pauli, _)| !matches!(pauli, Pauli::I))
+            .map(|(pauli, target)| (*pauli, *target))
+            .collect();
+        // The Pauli operator is the identity, so the rotation is a global phase.
+        if qubits.is_empty() {
+            return;
+        }
+
+        for &(pauli, target) in &qubits {
+            match pauli {
+                Pauli::X => self.h(target),
+                Pauli::Y => {
+                    self.sadj(target);
+                    self.h(target);
+                }
+                Pauli::I | Pauli::Z => (),
+            }
+        }
+        let ids: Vec<usize> = qubits.iter().map(|(_, target)| *target).collect();
+        // exp(iθZ) is the Rz rotation by -2θ.
+        self.with_parity(&ids, |sim, pos| {
+            sim.apply_single(pos, &rz_matrix(-2.0 * theta));
+        });
+        for &(pauli, target) in &qubits {
+            match pauli {
+                Pauli::X => self.h(target),
+                Pauli::Y => {
+                    self.h(target);
+                    self.s(target);
+                }
+                Pauli::I | Pauli::Z => (),
+            }
+        }
+    }
+
+    /// Applies the exponential of the multi-qubit Pauli operator if no controls are given.
+    /// # Panics
+    ///
+    /// This function will panic if any controls are given.
+    pub(crate) fn mcexp(
+        &mut self,
+        ctls: &[usize],
+        paulis: &[Pauli],
+        theta: f64,
+        targets: &[usize],
+    ) {
+        if !ctls.is_empty() {
+            Self::unsupported("Exp gates");
+        }
+        self.exp(paulis, theta, targets);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{svd, MpsSim};
+    use crate::{nearly_zero::NearlyZero, simulator::QuantumSim};
+    use ndarray::Array2;
+    use num_complex::Complex64;
+    use qir_stdlib::Pauli;
+    use std::f64::consts::PI;
+
+    fn assert_same_state(mps: &mut MpsSim, sparse: &mut QuantumSim<u64>) {
+        let (mps_state, mps_count) = mps.get_state();
+        let (sparse_state, sparse_count) = sparse.get_state();
+        assert_eq!(mps_count, sparse_count);
+        assert_eq!(mps_state.len(), sparse_state.len());
+        for ((mps_key, mps_value), (sparse_key, sparse_value)) in
+            mps_state.iter().zip(&sparse_state)
+        {
+            assert_eq!(mps_key, sparse_key);
+            assert!((mps_value - sparse_value).is_nearly_zero());
+        }
+    }
+
+    #[test]
+    #[allow(clippy::cast_precision_loss)]
+    fn test_svd() {
+        let matrix = Array2::from_shape_fn((3, 4), |(i, j)| {
+            Complex64::new((i * 4 + j) as f64 / 3.0, (i as f64 - j as f64).sin())
+        });
+        let (u, singular_values, vt) = svd(matrix.clone());
+        assert!(singular_values.windows(2).all(|pair| pair[0] >= pair[1]));
+        let product = u
+            .dot(&Array2::from_diag(
+                &singular_values
+                    .iter()
+                    .map(|value| Complex64::new(*value, 0.0))
+                    .collect::<ndarray::Array1<_>>(),
+            ))
+            .dot(&vt);
+        for (expected, actual) in matrix.iter().zip(product.iter()) {
+            assert!((expected - actual).is_nearly_zero());
+        }
+    }
+
+    #[test]
+    fn test_gates_match_sparse() {
+        let mut mps = MpsSim::new(16);
+        let mut sparse = QuantumSim::<u64>::default();
+        let qubits: Vec<usize> = (0..5).map(|_| mps.allocate()).collect();
+        for _ in 0..5 {
+            let _ = sparse.allocate();
+        }
+        macro_rules! apply {
+            ($($gate:ident($($arg:expr),*);)*) => {
+                $(
+                    mps.$gate($($arg),*);
+                    sparse.$gate($($arg),*);
+                    assert_same_state(&mut mps, &mut sparse);
+                )*
+            };
+        }
+        apply! {
+            h(qubits[0]);
+            mcx(&[qubits[0]], qubits[4]);
+            ry(PI / 3.0, qubits[2]);
+            mcy(&[qubits[4]], qubits[1]);
+            s(qubits[1]);
+            mcrz(&[qubits[2]], PI / 5.0, qubits[0]);
+            t(qubits[3]);
+            mch(&[qubits[1]], qubits[3]);
+            exp(&[Pauli::X, Pauli::Y, Pauli::Z], PI / 7.0, &[qubits[0], qubits[2], qubits[4]]);
+            swap_qubit_ids(qubits[0], qubits[3]);
+            mcrx(&[qubits[3]], PI / 4.0, qubits[0]);
+        }
+        assert!((mps.joint_probability(&[qubits[0], qubits[4]])
+            - sparse.joint_probability(&[qubits[0], qubits[4]]))
+        .is_nearly_zero());
+        let res = mps.joint_measure(&[qubits[1], qubits[2]]);
+        assert!(
+            (mps.joint_probability(&[qubits[1], qubits[2]]) - if res { 1.0 } else { 0.0 })
+                .is_nearly_zero()
+        );
+        assert!(mps.truncation_error().is_nearly_zero());
+        for qubit in qubits {
+            mps.release(qubit);
+        }
+        assert_eq!(mps.get_state().0.len(), 1);
+    }
+
+    #[test]
+    fn test_long_chain_with_truncation() {
+        let mut mps = MpsSim::new(2);
+        let qubits: Vec<usize> = (0..200).map(|_| mps.allocate()).collect();
+        mps.h(qubits[0]);
+        for pair in qubits.windows(2) {
+            mps.mcx(&[pair[0]], pair[1]);
+        }
+        // A GHZ state has bond dimension 2, so it is represented exactly.
+        assert!(mps.truncation_error().is_nearly_zero());
+        let res = mps.measure(qubits[0]);
+        assert!(qubits.iter().all(|&qubit| mps.measure(qubit) == res));
+
+        // Entangling neighboring qubits in several layers needs a larger bond dimension.
+        for layer in 1..4 {
+            for pair in qubits[..6].windows(2) {
+                mps.ry(PI / f64::from(layer + 2), pair[0]);
+                mps.mcrx(&[pair[0]], PI / 3.0, pair[1]);
+                mps.mcx(&[pair[1]], pair[0]);
+            }
+        }
+        assert!(mps.truncation_error() > 0.0);
+    }
+}
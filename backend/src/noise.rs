@@ -0,0 +1,347 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Noise models for the simulator. Noise is simulated with quantum trajectories: after every operation, the
+//! error channels of the operation are sampled for each qubit it acts on, and the sampled errors are applied to
+//! the state. The density matrix simulator instead applies the channels exactly by their Kraus operators.
+//! Measurements are affected by the channels before the measurement and by readout errors, which flip the
+//! reported result without changing the state.
+
+use std::{collections::HashMap, ffi::OsStr, fs, path::Path};
+
+use num_complex::Complex64;
+use num_traits::{One, Zero};
+use serde::Deserialize;
+
+use crate::{dense::Matrix, engine::Engine, simulator::random_sample};
+
+/// The error channels applied to a qubit, given by the probability of each error.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+#[allow(clippy::module_name_repetitions)]
+pub struct NoiseChannels {
+    /// The probability of an X, Y or Z error, each of which is equally likely.
+    pub depolarizing: f64,
+    /// The probability of an X error.
+    pub bit_flip: f64,
+    /// The probability of a Z error.
+    pub phase_flip: f64,
+    /// The probability of decaying from |1⟩ to |0⟩.
+    pub amplitude_damping: f64,
+    /// The probability of reporting the opposite result when measuring.
+    pub readout_error: f64,
+}
+
+impl NoiseChannels {
+    fn validate(&self) -> Result<(), String> {
+        for (name, probability) in [
+            ("depolarizing", self.depolarizing),
+            ("bit_flip", self.bit_flip),
+            ("phase_flip", self.phase_flip),
+            ("amplitude_damping", self.amplitude_damping),
+            ("readout_error", self.readout_error),
+        ] {
+            if !(0.0..=1.0).contains(&probability) {
+                return Err(format!(
+                    "Probability of {name} noise must be between 0 and 1, but is {probability}."
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the Kraus operators of each channel with a non-zero probability, in the order in which the
+    /// channels are applied.
+    fn kraus_operators(&self) -> Vec<Vec<Matrix>> {
+        let (zero, one, i) = (Complex64::zero(), Complex64::one(), Complex64::i());
+        let identity = [[one, zero], [zero, one]];
+        let x = [[zero, one], [one, zero]];
+        let y = [[zero, -i], [i, zero]];
+        let z = [[one, zero], [zero, -one]];
+        let scale = |matrix: Matrix, probability: f64| {
+            matrix.map(|row| row.map(|value| value * probability.sqrt()))
+        };
+
+        let mut channels = vec![];
+        if self.depolarizing > 0.0 {
+            channels.push(vec![
+                scale(identity, 1.0 - self.depolarizing),
+                scale(x, self.depolarizing / 3.0),
+                scale(y, self.depolarizing / 3.0),
+                scale(z, self.depolarizing / 3.0),
+            ]);
+        }
+        if self.bit_flip > 0.0 {
+            channels.push(vec![
+                scale(identity, 1.0 - self.bit_flip),
+                scale(x, self.bit_flip),
+            ]);
+        }
+        if self.phase_flip > 0.0 {
+            channels.push(vec![
+                scale(identity, 1.0 - self.phase_flip),
+                scale(z, self.phase_flip),
+            ]);
+        }
+        if self.amplitude_damping > 0.0 {
+            channels.push(amplitude_damping_kraus(self.amplitude_damping).to_vec());
+        }
+        channels
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn apply(&self, sim: &mut Engine, qubit: usize) {
+        if let Engine::DensityMatrix(sim) = sim {
+            for kraus in self.kraus_operators() {
+                sim.apply_channel(&kraus, qubit);
+            }
+            return;
+        }
+        if self.depolarizing > 0.0 {
+            let sample = random_sample();
+            if sample < self.depolarizing {
+                // The sample is uniformly distributed below the probability, so it also selects the error.
+                match (3.0 * sample / self.depolarizing) as u32 {
+                    0 => sim.x(qubit),
+                    1 => sim.y(qubit),
+                    _ => sim.z(qubit),
+                }
+            }
+        }
+        if self.bit_flip > 0.0 && random_sample() < self.bit_flip {
+            sim.x(qubit);
+        }
+        if self.phase_flip > 0.0 && random_sample() < self.phase_flip {
+            sim.z(qubit);
+        }
+        if self.amplitude_damping > 0.0 {
+            sim.amplitude_damping(self.amplitude_damping, qubit);
+        }
+    }
+
+    fn readout(&self, result: bool) -> bool {
+        if self.readout_error > 0.0 && random_sample() < self.readout_error {
+            !result
+        } else {
+            result
+        }
+    }
+}
+
+/// A noise model given by the error channels of each gate and each qubit. Gates are named by the operation in
+/// the name of their intrinsic, e.g., `h` for `__quantum__qis__h__body` and its controlled and adjoint variants,
+/// and `mz` for `__quantum__qis__mz__body`. The channels of a gate, or the default channels for gates without
+/// their own channels, are applied to every qubit the gate acts on, followed by the channels of the qubit.
+///
+/// Noise models can be loaded from JSON or TOML, such as
+///
+/// ```toml
+/// [default]
+/// depolarizing = 0.001
+///
+/// [gates.cx]
+/// depolarizing = 0.01
+///
+/// [gates.mz]
+/// readout_error = 0.02
+///
+/// [qubits.3]
+/// amplitude_damping = 0.005
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(try_from = "SerializedNoiseModel")]
+#[allow(clippy::module_name_repetitions)]
+pub struct NoiseModel {
+    /// The channels of the gates without their own channels.
+    pub default: NoiseChannels,
+    /// The channels of each gate by gate name.
+    pub gates: HashMap<String, NoiseChannels>,
+    /// The channels of each qubit by qubit id.
+    pub qubits: HashMap<usize, NoiseChannels>,
+}
+
+/// The serialized form of a noise model, whose qubit ids are keys and therefore strings.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SerializedNoiseModel {
+    default: NoiseChannels,
+    gates: HashMap<String, NoiseChannels>,
+    qubits: HashMap<String, NoiseChannels>,
+}
+
+impl TryFrom<SerializedNoiseModel> for NoiseModel {
+    type Error = String;
+
+    fn try_from(model: SerializedNoiseModel) -> Result<Self, Self::Error> {
+        let qubits = model
+            .qubits
+            .into_iter()
+            .map(|(id, channels)| {
+                id.parse()
+                    .map(|id| (id, channels))
+                    .map_err(|_| format!("Invalid qubit id '{id}' in noise model."))
+            })
+            .collect::<Result<_, _>>()?;
+        let model = NoiseModel {
+            default: model.default,
+            gates: model.gates,
+            qubits,
+        };
+        model.default.validate()?;
+        for channels in model.gates.values().chain(model.qubits.values()) {
+            channels.validate()?;
+        }
+        Ok(model)
+    }
+}
+
+impl NoiseModel {
+    /// Parses a noise model from JSON.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the JSON does not describe a noise model or a probability is not between 0 and 1.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Invalid noise model: {e}"))
+    }
+
+    /// Parses a noise model from TOML.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the TOML does not describe a noise model or a probability is not between 0 and 1.
+    pub fn from_toml(toml: &str) -> Result<Self, String> {
+        toml::from_str(toml).map_err(|e| format!("Invalid noise model: {e}"))
+    }
+
+    /// Loads a noise model from a .json or .toml file.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if
+    /// - `path` does not have either a .json or .toml as an extension
+    /// - `path` cannot be read
+    /// - `path` does not contain a valid noise model
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(OsStr::to_str);
+        let parse = match extension {
+            Some("json") => Self::from_json,
+            Some("toml") => Self::from_toml,
+            _ => {
+                return Err(format!(
+                    "Unsupported noise model file extension '{extension:?}'."
+                ))
+            }
+        };
+        parse(&fs::read_to_string(path).map_err(|e| e.to_string())?)
+    }
+
+    fn channels<'a>(&'a self, gate: &str, qubit: usize) -> impl Iterator<Item = &'a NoiseChannels> {
+        [
+            Some(self.gates.get(gate).unwrap_or(&self.default)),
+            self.qubits.get(&qubit),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Samples and applies the errors of the given gate to each of the given qubits.
+    pub(crate) fn apply(&self, sim: &mut Engine, gate: &str, qubits: &[usize]) {
+        for &qubit in qubits {
+            for channels in self.channels(gate, qubit) {
+                channels.apply(sim, qubit);
+            }
+        }
+    }
+
+    /// Samples the readout errors of the given measurement of the given qubits and returns the reported result.
+    pub(crate) fn readout(&self, gate: &str, qubits: &[usize], result: bool) -> bool {
+        qubits.iter().fold(result, |result, &qubit| {
+            self.channels(gate, qubit)
+                .fold(result, |result, channels| channels.readout(result))
+        })
+    }
+}
+
+/// Returns the Kraus operators of the amplitude damping channel with the given damping probability.
+pub(crate) fn amplitude_damping_kraus(gamma: f64) -> [Matrix; 2] {
+    let (zero, one) = (Complex64::zero(), Complex64::one());
+    [
+        [
+            [one, zero],
+            [zero, Complex64::new((1.0 - gamma).sqrt(), 0.0)],
+        ],
+        [[zero, Complex64::new(gamma.sqrt(), 0.0)], [zero, zero]],
+    ]
+}
+
+/// Returns the name of the gate implemented by the given intrinsic, e.g., `h` for `__quantum__qis__h__ctl`.
+pub(crate) fn gate_name(intrinsic: &str) -> &str {
+    let name = intrinsic.trim_start_matches("__quantum__qis__");
+    name.split_once("__").map_or(name, |(gate, _)| gate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{gate_name, NoiseChannels, NoiseModel};
+
+    #[test]
+    fn test_gate_name() {
+        assert_eq!(gate_name("__quantum__qis__h__body"), "h");
+        assert_eq!(gate_name("__quantum__qis__rx__ctl"), "rx");
+        assert_eq!(
+            gate_name("__quantum__qis__read_result__body"),
+            "read_result"
+        );
+    }
+
+    #[test]
+    fn test_load_toml_and_json() {
+        let toml = NoiseModel::from_toml(
+            "[default]\ndepolarizing = 0.1\n[gates.cx]\nbit_flip = 0.2\n[qubits.3]\nreadout_error = 0.3\n",
+        )
+        .expect("TOML noise model should parse");
+        let json = NoiseModel::from_json(
+            r#"{"default": {"depolarizing": 0.1}, "gates": {"cx": {"bit_flip": 0.2}}, "qubits": {"3": {"readout_error": 0.3}}}"#,
+        )
+        .expect("JSON noise model should parse");
+        assert_eq!(toml, json);
+        assert_eq!(
+            toml.default,
+            NoiseChannels {
+                depolarizing: 0.1,
+                ..NoiseChannels::default()
+            }
+        );
+        assert_eq!(
+            toml.gates["cx"],
+            NoiseChannels {
+                bit_flip: 0.2,
+                ..NoiseChannels::default()
+            }
+        );
+        assert_eq!(
+            toml.qubits[&3],
+            NoiseChannels {
+                readout_error: 0.3,
+                ..NoiseChannels::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_noise_models() {
+        assert!(NoiseModel::from_json(r#"{"qubits": {"a": {}}}"#)
+            .unwrap_err()
+            .starts_with("Invalid noise model: Invalid qubit id 'a' in noise model."));
+        assert!(NoiseModel::from_json(r#"{"gates": {"h": {"bit_flip": 1.5}}}"#)
+            .unwrap_err()
+            .starts_with(
+                "Invalid noise model: Probability of bit_flip noise must be between 0 and 1, but is 1.5."
+            ));
+        assert!(NoiseModel::from_json(r#"{"default": {"bitflip": 0.1}}"#).is_err());
+        assert_eq!(NoiseModel::from_json("{}"), Ok(NoiseModel::default()));
+        assert!(NoiseChannels::default().readout(true));
+    }
+}
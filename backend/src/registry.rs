@@ -0,0 +1,40 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! The registry of the functions that the backend exports for QIR programs. Runners bind the declarations of a
+//! program based on this registry, which lists every function declared with `qir_stdlib::exports!`.
+
+pub use qir_stdlib::registry::{ExportedFunction, OutputSchema};
+
+/// Returns the functions exported by the backend, including the runtime functions of `qir_stdlib`. A name
+/// occurs more than once if the function has variants with different numbers of parameters.
+#[must_use]
+pub fn exported_functions() -> Vec<ExportedFunction> {
+    [
+        crate::exports::functions(),
+        crate::exp::exports::functions(),
+        crate::legacy::exports::functions(),
+        crate::result_bool::exports::functions(),
+        qir_stdlib::registry::exported_functions(),
+    ]
+    .concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::exported_functions;
+    use std::collections::HashSet;
+
+    #[test]
+    fn registry_has_unique_variants() {
+        let mut variants = HashSet::new();
+        for function in exported_functions() {
+            assert!(
+                variants.insert((function.name, function.param_count)),
+                "Function '{}' is registered twice with {} parameters.",
+                function.name,
+                function.param_count
+            );
+        }
+    }
+}
@@ -5,28 +5,38 @@
 use qir_stdlib::strings::__quantum__rt__string_create;
 use std::ffi::{c_char, c_void, CString};
 
-#[no_mangle]
-pub extern "C" fn __quantum__rt__result_get_zero() -> *mut c_void {
+qir_stdlib::exports! {
+    fn __quantum__rt__result_get_zero() -> *mut c_void;
+    fn __quantum__rt__result_get_one() -> *mut c_void;
+    fn __quantum__rt__result_equal(r1: *mut c_void, r2: *mut c_void) -> bool;
+    fn __quantum__rt__result_update_reference_count(res: *mut c_void, update: i32);
+    fn __quantum__rt__result_to_string(res: *mut c_void) -> *const CString;
+}
+
+#[must_use]
+#[allow(non_snake_case)]
+pub fn __quantum__rt__result_get_zero() -> *mut c_void {
     std::ptr::null_mut()
 }
 
-#[no_mangle]
-pub extern "C" fn __quantum__rt__result_get_one() -> *mut c_void {
+#[must_use]
+#[allow(non_snake_case)]
+pub fn __quantum__rt__result_get_one() -> *mut c_void {
     1 as *mut c_void
 }
 
-#[no_mangle]
-pub extern "C" fn __quantum__rt__result_equal(r1: *mut c_void, r2: *mut c_void) -> bool {
+#[allow(non_snake_case)]
+pub fn __quantum__rt__result_equal(r1: *mut c_void, r2: *mut c_void) -> bool {
     r1 == r2
 }
 
-#[no_mangle]
-pub extern "C" fn __quantum__rt__result_update_reference_count(_res: *mut c_void, _update: i32) {
+#[allow(non_snake_case)]
+pub fn __quantum__rt__result_update_reference_count(_res: *mut c_void, _update: i32) {
     // no-op
 }
 
-#[no_mangle]
-pub extern "C" fn __quantum__rt__result_to_string(res: *mut c_void) -> *const CString {
+#[allow(non_snake_case)]
+pub fn __quantum__rt__result_to_string(res: *mut c_void) -> *const CString {
     unsafe {
         __quantum__rt__string_create(
             CString::new(
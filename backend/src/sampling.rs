@@ -0,0 +1,192 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Sampling of terminal measurements, which draws the outcomes of all measurements of a shot at once from the
+//! distribution of the basis states when the first measurement happens. Programs whose measurements all come
+//! after the last gate thereby only need to be simulated once, and the remaining shots skip the gates.
+
+use std::rc::Rc;
+
+use num_bigint::BigUint;
+use num_complex::Complex64;
+use qir_stdlib::Pauli;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::simulator::random_sample;
+
+/// Whether and how the measurements of a shot are sampled.
+#[derive(Default)]
+pub(crate) enum TerminalSampling {
+    /// Measurements are simulated.
+    #[default]
+    Off,
+    /// The distribution is captured at the first measurement of the next shot.
+    Capture,
+    /// The measurements of every shot are sampled from the captured distribution.
+    Sample(Rc<TerminalDistribution>),
+}
+
+/// The probability distribution of the basis states of a state vector.
+pub(crate) struct TerminalDistribution {
+    /// The basis states with non-zero probability, where bit i of an index is the value of the qubit with the
+    /// i-th smallest identifier.
+    states: Vec<BigUint>,
+    /// The cumulative probabilities of the basis states.
+    cumulative: Vec<f64>,
+}
+
+impl TerminalDistribution {
+    pub(crate) fn new(state: Vec<(BigUint, Complex64)>) -> Self {
+        let mut total = 0.0;
+        let (states, cumulative) = state
+            .into_iter()
+            .map(|(index, value)| {
+                total += value.norm_sqr();
+                (index, total)
+            })
+            .unzip();
+        TerminalDistribution { states, cumulative }
+    }
+
+    fn sample(&self) -> BigUint {
+        let total = self.cumulative.last().copied().unwrap_or_default();
+        let threshold = random_sample() * total;
+        let index = self
+            .cumulative
+            .partition_point(|&probability| probability <= threshold)
+            .min(self.states.len() - 1);
+        self.states[index].clone()
+    }
+}
+
+/// The `SampledSim` struct replaces the simulation of a shot whose measurements are sampled. Gates before the
+/// first measurement are skipped, and all measurements read the basis state drawn at the first measurement.
+pub(crate) struct SampledSim {
+    distribution: Rc<TerminalDistribution>,
+    ids: FxHashSet<usize>,
+    /// The drawn basis state and the bit of every qubit in it, once the first measurement happened.
+    sample: Option<(BigUint, FxHashMap<usize, u64>)>,
+}
+
+/// Generates the gates, which are skipped since they happen before the first measurement.
+macro_rules! skipped_gates {
+    ($(fn $name:ident(&mut self $(, $arg:ident: $ty:ty)*);)*) => {
+        $(
+            pub(crate) fn $name(&mut self $(, $arg: $ty)*) {
+                $(let _ = $arg;)*
+                self.check_unmeasured();
+            }
+        )*
+    };
+}
+
+impl SampledSim {
+    /// Creates a sampled simulation from the given distribution, where the given qubits are already allocated.
+    pub(crate) fn new(
+        distribution: Rc<TerminalDistribution>,
+        ids: impl IntoIterator<Item = usize>,
+    ) -> Self {
+        SampledSim {
+            distribution,
+            ids: ids.into_iter().collect(),
+            sample: None,
+        }
+    }
+
+    fn check_unmeasured(&self) {
+        assert!(
+            self.sample.is_none(),
+            "Gates cannot be applied after the first measurement when measurements are sampled."
+        );
+    }
+
+    fn unsupported(operation: &str) -> ! {
+        panic!("Sampled measurements do not support {operation}.")
+    }
+
+    #[must_use]
+    pub(crate) fn allocate(&mut self) -> usize {
+        let id = (0..=self.ids.len())
+            .find(|id| !self.ids.contains(id))
+            .expect("There should be an available qubit identifier.");
+        self.ids.insert(id);
+        id
+    }
+
+    pub(crate) fn release(&mut self, id: usize) {
+        assert!(self.ids.remove(&id), "Unable to find qubit with id {id}");
+    }
+
+    /// Returns the value of the given qubit in the drawn basis state, drawing it at the first measurement. Qubits
+    /// that are missing from the drawn state were allocated after the first measurement, without any gates applied
+    /// to them, so they are measured as zero.
+    #[must_use]
+    pub(crate) fn measure(&mut self, id: usize) -> bool {
+        let distribution = &self.distribution;
+        let ids = &self.ids;
+        let (state, bits) = self.sample.get_or_insert_with(|| {
+            let mut sorted: Vec<usize> = ids.iter().copied().collect();
+            sorted.sort_unstable();
+            let bits = sorted.into_iter().zip(0..).collect();
+            (distribution.sample(), bits)
+        });
+        bits.get(&id).map_or(false, |bit| state.bit(*bit))
+    }
+
+    #[must_use]
+    pub(crate) fn joint_measure(&mut self, ids: &[usize]) -> bool {
+        ids.iter()
+            .fold(false, |parity, &id| parity ^ self.measure(id))
+    }
+
+    skipped_gates! {
+        fn swap_qubit_ids(&mut self, qubit1: usize, qubit2: usize);
+        fn x(&mut self, target: usize);
+        fn y(&mut self, target: usize);
+        fn z(&mut self, target: usize);
+        fn h(&mut self, target: usize);
+        fn s(&mut self, target: usize);
+        fn sadj(&mut self, target: usize);
+        fn t(&mut self, target: usize);
+        fn tadj(&mut self, target: usize);
+        fn mcx(&mut self, ctls: &[usize], target: usize);
+        fn mcy(&mut self, ctls: &[usize], target: usize);
+        fn mcz(&mut self, ctls: &[usize], target: usize);
+        fn mch(&mut self, ctls: &[usize], target: usize);
+        fn mcs(&mut self, ctls: &[usize], target: usize);
+        fn mcsadj(&mut self, ctls: &[usize], target: usize);
+        fn mct(&mut self, ctls: &[usize], target: usize);
+        fn mctadj(&mut self, ctls: &[usize], target: usize);
+        fn mcphase(&mut self, ctls: &[usize], phase: Complex64, target: usize);
+        fn rx(&mut self, theta: f64, target: usize);
+        fn ry(&mut self, theta: f64, target: usize);
+        fn rz(&mut self, theta: f64, target: usize);
+        fn mcrx(&mut self, ctls: &[usize], theta: f64, target: usize);
+        fn mcry(&mut self, ctls: &[usize], theta: f64, target: usize);
+        fn mcrz(&mut self, ctls: &[usize], theta: f64, target: usize);
+        fn exp(&mut self, paulis: &[Pauli], theta: f64, targets: &[usize]);
+        fn mcexp(&mut self, ctls: &[usize], paulis: &[Pauli], theta: f64, targets: &[usize]);
+    }
+}
+
+/// The operations that inspect the state or apply noise, which panic since the state is not simulated.
+#[allow(clippy::unused_self)]
+impl SampledSim {
+    pub(crate) fn dump(&mut self) -> String {
+        Self::unsupported("dumping the state")
+    }
+
+    #[must_use]
+    pub(crate) fn get_state(&mut self) -> (Vec<(BigUint, Complex64)>, usize) {
+        Self::unsupported("capturing the state")
+    }
+
+    #[must_use]
+    pub(crate) fn joint_probability(&mut self, _ids: &[usize]) -> f64 {
+        Self::unsupported("computing probabilities")
+    }
+
+    pub(crate) fn amplitude_damping(&mut self, _gamma: f64, _id: usize) {
+        Self::unsupported("noise");
+    }
+}
@@ -1,7 +1,7 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
-use crate::nearly_zero::NearlyZero;
+use crate::{basis_index::BasisIndex, dump::StateDump, nearly_zero::NearlyZero};
 use num_bigint::BigUint;
 use num_complex::Complex64;
 use num_traits::{One, Zero};
@@ -9,13 +9,14 @@ use rand::{rngs::StdRng, Rng, SeedableRng};
 use rustc_hash::{FxHashMap, FxHashSet};
 use std::{cell::RefCell, f64::consts::FRAC_1_SQRT_2};
 
-pub type SparseState = FxHashMap<BigUint, Complex64>;
+pub type SparseState<K> = FxHashMap<K, Complex64>;
 
 /// The `QuantumSim` struct contains the necessary state for tracking the simulation. Each instance of a
-/// `QuantumSim` represents an independant simulation.
-pub(crate) struct QuantumSim {
+/// `QuantumSim` represents an independant simulation. The basis states are indexed by `K`, which must be able to
+/// hold the bits of all qubit locations.
+pub(crate) struct QuantumSim<K: BasisIndex> {
     /// The structure that describes the current quantum state.
-    pub(crate) state: FxHashMap<BigUint, Complex64>,
+    pub(crate) state: SparseState<K>,
 
     /// The mapping from qubit identifiers to internal state locations.
     pub(crate) id_map: FxHashMap<usize, usize>,
@@ -38,6 +39,25 @@ pub(crate) fn set_rng_seed(seed: u64) {
     RNG.with(|rng| rng.replace(StdRng::seed_from_u64(seed)));
 }
 
+/// Draws a uniformly distributed sample from [0, 1) using the simulator's random number generator.
+pub(crate) fn random_sample() -> f64 {
+    RNG.with(|rng| rng.borrow_mut().gen::<f64>())
+}
+
+/// Verifies that the given list of qubit ids does not contain any duplicate entries.
+/// # Panics
+///
+/// This function will panic if there are duplicate ids in the given list.
+pub(crate) fn check_for_duplicates(ids: &[usize]) {
+    let mut unique = FxHashSet::default();
+    for id in ids.iter() {
+        assert!(
+            unique.insert(id),
+            "Duplicate qubit id '{id}' found in application."
+        );
+    }
+}
+
 /// Levels for flushing of queued gates.
 #[derive(Debug, Copy, Clone)]
 pub(crate) enum FlushLevel {
@@ -46,19 +66,19 @@ pub(crate) enum FlushLevel {
     HRxRy,
 }
 
-impl Default for QuantumSim {
+impl<K: BasisIndex> Default for QuantumSim<K> {
     fn default() -> Self {
         Self::new()
     }
 }
 
 /// Provides the common set of functionality across all quantum simulation types.
-impl QuantumSim {
+impl<K: BasisIndex> QuantumSim<K> {
     /// Creates a new sparse state quantum simulator object with empty initial state (no qubits allocated, no operations buffered).
     #[must_use]
     fn new() -> Self {
         let mut initial_state = FxHashMap::default();
-        initial_state.insert(BigUint::zero(), Complex64::one());
+        initial_state.insert(K::zero(), Complex64::one());
 
         QuantumSim {
             state: initial_state,
@@ -69,6 +89,35 @@ impl QuantumSim {
         }
     }
 
+    /// Creates a new sparse state quantum simulator object with the given state and mapping from qubit
+    /// identifiers to state locations, with no operations buffered.
+    pub(crate) fn with_state(state: SparseState<K>, id_map: FxHashMap<usize, usize>) -> Self {
+        QuantumSim {
+            state,
+            id_map,
+            ..Self::new()
+        }
+    }
+
+    /// Moves the state and queued operations into a simulator whose basis states are indexed by `L`, leaving
+    /// this simulator without any qubits.
+    /// # Panics
+    ///
+    /// This function will panic if a qubit location does not fit into the indices of type `L`.
+    pub(crate) fn convert<L: BasisIndex>(&mut self) -> QuantumSim<L> {
+        QuantumSim {
+            state: self
+                .state
+                .drain()
+                .map(|(index, value)| (L::from_biguint(&index.to_biguint()), value))
+                .collect(),
+            id_map: std::mem::take(&mut self.id_map),
+            h_flag: std::mem::take(&mut self.h_flag),
+            rx_queue: std::mem::take(&mut self.rx_queue),
+            ry_queue: std::mem::take(&mut self.ry_queue),
+        }
+    }
+
     /// Returns a sorted copy of the current sparse state as a vector of pairs of indices and complex numbers, along with
     /// the total number of currently allocated qubits to help in interpreting the sparse state.
     #[must_use]
@@ -93,7 +142,11 @@ impl QuantumSim {
             }
         });
 
-        let mut state = self.state.clone().drain().collect::<Vec<_>>();
+        let mut state = self
+            .state
+            .iter()
+            .map(|(index, value)| (index.to_biguint(), *value))
+            .collect::<Vec<_>>();
         state.sort_unstable_by(|a, b| a.0.cmp(&b.0));
         (state, sorted_keys.len())
     }
@@ -157,50 +210,10 @@ impl QuantumSim {
         }
     }
 
-    /// Prints the current state vector to standard output with integer labels for the states, skipping any
-    /// states with zero amplitude.
-    pub(crate) fn dump(&mut self) {
-        // Swap all the entries in the state to be ordered by qubit identifier. This makes
-        // interpreting the state easier for external consumers that don't have access to the id map.
-        let mut sorted_keys: Vec<usize> = self.id_map.keys().copied().collect();
-        self.flush_queue(&sorted_keys, FlushLevel::HRxRy);
-
-        sorted_keys.sort_unstable();
-        sorted_keys.iter().enumerate().for_each(|(index, &key)| {
-            if index != self.id_map[&key] {
-                self.swap_qubit_state(self.id_map[&key], index);
-                let swapped_key = *self
-                    .id_map
-                    .iter()
-                    .find(|(_, &value)| value == index)
-                    .unwrap()
-                    .0;
-                *(self.id_map.get_mut(&swapped_key).unwrap()) = self.id_map[&key];
-                *(self.id_map.get_mut(&key).unwrap()) = index;
-            }
-        });
-
-        self.dump_impl(false);
-    }
-
-    /// Utility function that performs the actual output of state (and optionally map) to screen. Can
-    /// be called internally from other functions to aid in debugging and does not perform any modification
-    /// of the internal structures.
-    fn dump_impl(&self, print_id_map: bool) {
-        if print_id_map {
-            println!("MAP: {:?}", self.id_map);
-        };
-        print!("STATE: [ ");
-        let mut sorted_keys = self.state.keys().collect::<Vec<_>>();
-        sorted_keys.sort_unstable();
-        for key in sorted_keys {
-            print!(
-                "|{}\u{27e9}: {}, ",
-                key,
-                self.state.get(key).map_or_else(Complex64::zero, |v| *v)
-            );
-        }
-        println!("]");
+    /// Returns the current state vector as a table of the basis states with non-zero amplitudes.
+    pub(crate) fn dump(&mut self) -> String {
+        let (state, qubits) = self.get_state();
+        StateDump::new(state, qubits, 0.0).to_table()
     }
 
     /// Checks the probability of parity measurement in the computational basis for the given set of
@@ -213,7 +226,7 @@ impl QuantumSim {
     pub(crate) fn joint_probability(&mut self, ids: &[usize]) -> f64 {
         self.flush_queue(ids, FlushLevel::HRxRy);
 
-        Self::check_for_duplicates(ids);
+        check_for_duplicates(ids);
         let locs: Vec<usize> = ids
             .iter()
             .map(|id| {
@@ -246,8 +259,7 @@ impl QuantumSim {
     /// Utility that performs the actual measurement and collapse of the state for the given
     /// location.
     fn measure_impl(&mut self, loc: usize) -> bool {
-        let random_sample = RNG.with(|rng| rng.borrow_mut().gen::<f64>());
-        let res = random_sample < self.check_joint_probability(&[loc]);
+        let res = random_sample() < self.check_joint_probability(&[loc]);
         self.collapse(loc, res);
         res
     }
@@ -262,7 +274,7 @@ impl QuantumSim {
     pub(crate) fn joint_measure(&mut self, ids: &[usize]) -> bool {
         self.flush_queue(ids, FlushLevel::HRxRy);
 
-        Self::check_for_duplicates(ids);
+        check_for_duplicates(ids);
         let locs: Vec<usize> = ids
             .iter()
             .map(|id| {
@@ -273,8 +285,7 @@ impl QuantumSim {
             })
             .collect();
 
-        let random_sample = RNG.with(|rng| rng.borrow_mut().gen::<f64>());
-        let res = random_sample < self.check_joint_probability(&locs);
+        let res = random_sample() < self.check_joint_probability(&locs);
         self.joint_collapse(&locs, res);
         res
     }
@@ -283,11 +294,9 @@ impl QuantumSim {
     /// are set. This corresponds to the probability of jointly measuring those qubits in the computational
     /// basis.
     fn check_joint_probability(&self, locs: &[usize]) -> f64 {
-        let mask = locs.iter().fold(BigUint::zero(), |accum, loc| {
-            accum | (BigUint::one() << loc)
-        });
+        let mask = Self::mask(locs);
         self.state.iter().fold(0.0_f64, |accum, (index, val)| {
-            if (index & &mask).count_ones() & 1 > 0 {
+            if (index.clone() & &mask).count_ones() & 1 > 0 {
                 accum + val.norm_sqr()
             } else {
                 accum
@@ -295,6 +304,14 @@ impl QuantumSim {
         })
     }
 
+    /// Utility to get the index with the bits at the given locations set.
+    fn mask(locs: &[usize]) -> K {
+        locs.iter().fold(K::zero(), |mut accum, &loc| {
+            accum.set_bit(loc as u64, true);
+            accum
+        })
+    }
+
     /// Utility to collapse the probability at the given location based on the boolean value. This means
     /// that if the given value is 'true' then all keys in the sparse state where the given location
     /// has a zero bit will be reduced to zero and removed. Then the sparse state is normalized.
@@ -306,14 +323,12 @@ impl QuantumSim {
     /// The entries that do not correspond to the given boolean value are removed, and then the whole
     /// state is normalized.
     fn joint_collapse(&mut self, locs: &[usize], val: bool) {
-        let mask = locs.iter().fold(BigUint::zero(), |accum, loc| {
-            accum | (BigUint::one() << loc)
-        });
+        let mask = Self::mask(locs);
 
         let mut new_state = FxHashMap::default();
         let mut scaling_denominator = 0.0;
         for (k, v) in self.state.drain() {
-            if ((&k & &mask).count_ones() & 1 > 0) == val {
+            if ((k.clone() & &mask).count_ones() & 1 > 0) == val {
                 new_state.insert(k, v);
                 scaling_denominator += v.norm_sqr();
             }
@@ -329,6 +344,41 @@ impl QuantumSim {
         }
     }
 
+    /// Applies a trajectory of the amplitude damping channel with the given damping probability to the qubit
+    /// with the given id. The qubit decays to |0⟩ with the damping probability times the probability of the
+    /// qubit being in |1⟩, and otherwise its |1⟩ amplitudes are damped and the state is normalized.
+    /// # Panics
+    ///
+    /// This function will panic if the given identifier does not correspond to an allocated qubit.
+    pub(crate) fn amplitude_damping(&mut self, gamma: f64, id: usize) {
+        self.flush_queue(&[id], FlushLevel::HRxRy);
+        let loc = *self
+            .id_map
+            .get(&id)
+            .unwrap_or_else(|| panic!("Unable to find qubit with id {id}"));
+
+        let decay_probability = gamma * self.check_joint_probability(&[loc]);
+        if random_sample() < decay_probability {
+            self.collapse(loc, true);
+            self.x(id);
+        } else {
+            let scaling = 1.0 / (1.0 - decay_probability).sqrt();
+            let damped_scaling = (1.0 - gamma).sqrt() * scaling;
+            let mut new_state = FxHashMap::default();
+            for (k, v) in self.state.drain() {
+                let scaled_value = v * if k.bit(loc as u64) {
+                    damped_scaling
+                } else {
+                    scaling
+                };
+                if !scaled_value.is_nearly_zero() {
+                    new_state.insert(k, scaled_value);
+                }
+            }
+            self.state = new_state;
+        }
+    }
+
     /// Swaps the mapped ids for the given qubits.
     pub(crate) fn swap_qubit_ids(&mut self, qubit1: usize, qubit2: usize) {
         // Must also swap any queued operations.
@@ -392,22 +442,12 @@ impl QuantumSim {
             });
     }
 
-    pub(crate) fn check_for_duplicates(ids: &[usize]) {
-        let mut unique = FxHashSet::default();
-        for id in ids.iter() {
-            assert!(
-                unique.insert(id),
-                "Duplicate qubit id '{id}' found in application."
-            );
-        }
-    }
-
     /// Verifies that the given target and list of controls does not contain any duplicate entries, and returns
     /// those values mapped to internal identifiers and converted to `u64`.
     fn resolve_and_check_qubits(&self, target: usize, ctls: &[usize]) -> (u64, Vec<u64>) {
         let mut ids = ctls.to_owned();
         ids.push(target);
-        Self::check_for_duplicates(&ids);
+        check_for_duplicates(&ids);
 
         let target = *self
             .id_map
@@ -467,7 +507,7 @@ impl QuantumSim {
     /// size of the state vector the same.
     fn controlled_gate<F>(&mut self, ctls: &[usize], target: usize, mut op: F)
     where
-        F: FnMut((BigUint, Complex64), u64) -> (BigUint, Complex64),
+        F: FnMut((K, Complex64), u64) -> (K, Complex64),
     {
         let (target, ctls) = self.resolve_and_check_qubits(target, ctls);
 
@@ -488,7 +528,7 @@ impl QuantumSim {
     }
 
     /// Performs the Pauli-X transformation on a single state.
-    fn x_transform((mut index, val): (BigUint, Complex64), target: u64) -> (BigUint, Complex64) {
+    fn x_transform((mut index, val): (K, Complex64), target: u64) -> (K, Complex64) {
         index.set_bit(target, !index.bit(target));
         (index, val)
     }
@@ -541,10 +581,7 @@ impl QuantumSim {
     }
 
     /// Performs the Pauli-Y transformation on a single state.
-    fn y_transform(
-        (mut index, mut val): (BigUint, Complex64),
-        target: u64,
-    ) -> (BigUint, Complex64) {
+    fn y_transform((mut index, mut val): (K, Complex64), target: u64) -> (K, Complex64) {
         index.set_bit(target, !index.bit(target));
         val *= if index.bit(target) {
             Complex64::i()
@@ -591,9 +628,9 @@ impl QuantumSim {
     /// Performs a phase transformation (a rotation in the computational basis) on a single state.
     fn phase_transform(
         phase: Complex64,
-        (index, val): (BigUint, Complex64),
+        (index, val): (K, Complex64),
         target: u64,
-    ) -> (BigUint, Complex64) {
+    ) -> (K, Complex64) {
         let val = val
             * if index.bit(target) {
                 phase
@@ -613,7 +650,7 @@ impl QuantumSim {
     }
 
     /// Performs the Pauli-Z transformation on a single state.
-    fn z_transform((index, val): (BigUint, Complex64), target: u64) -> (BigUint, Complex64) {
+    fn z_transform((index, val): (K, Complex64), target: u64) -> (K, Complex64) {
         Self::phase_transform(-Complex64::one(), (index, val), target)
     }
 
@@ -687,7 +724,7 @@ impl QuantumSim {
     }
 
     /// Performs the S transformation on a single state.
-    fn s_transform((index, val): (BigUint, Complex64), target: u64) -> (BigUint, Complex64) {
+    fn s_transform((index, val): (K, Complex64), target: u64) -> (K, Complex64) {
         Self::phase_transform(Complex64::i(), (index, val), target)
     }
 
@@ -705,7 +742,7 @@ impl QuantumSim {
     }
 
     /// Performs the adjoint S transformation on a signle state.
-    fn sadj_transform((index, val): (BigUint, Complex64), target: u64) -> (BigUint, Complex64) {
+    fn sadj_transform((index, val): (K, Complex64), target: u64) -> (K, Complex64) {
         Self::phase_transform(-Complex64::i(), (index, val), target)
     }
 
@@ -723,7 +760,7 @@ impl QuantumSim {
     }
 
     /// Performs the T transformation on a single state.
-    fn t_transform((index, val): (BigUint, Complex64), target: u64) -> (BigUint, Complex64) {
+    fn t_transform((index, val): (K, Complex64), target: u64) -> (K, Complex64) {
         Self::phase_transform(
             Complex64::new(FRAC_1_SQRT_2, FRAC_1_SQRT_2),
             (index, val),
@@ -745,7 +782,7 @@ impl QuantumSim {
     }
 
     /// Performs the adjoint T transformation to a single state.
-    fn tadj_transform((index, val): (BigUint, Complex64), target: u64) -> (BigUint, Complex64) {
+    fn tadj_transform((index, val): (K, Complex64), target: u64) -> (K, Complex64) {
         Self::phase_transform(
             Complex64::new(FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
             (index, val),
@@ -767,11 +804,7 @@ impl QuantumSim {
     }
 
     /// Performs the Rz transformation with the given angle to a single state.
-    fn rz_transform(
-        (index, val): (BigUint, Complex64),
-        theta: f64,
-        target: u64,
-    ) -> (BigUint, Complex64) {
+    fn rz_transform((index, val): (K, Complex64), theta: f64, target: u64) -> (K, Complex64) {
         let val = val
             * Complex64::exp(Complex64::new(
                 0.0,
@@ -832,12 +865,12 @@ impl QuantumSim {
         // This operation cannot be done in-place so create a new empty state vector to populate.
         let mut new_state = SparseState::default();
 
-        let mut flipped = BigUint::zero();
+        let mut flipped = K::zero();
         flipped.set_bit(target, true);
 
         for (index, value) in &self.state {
             if ctls.iter().all(|c| index.bit(*c)) {
-                let flipped_index = index ^ &flipped;
+                let flipped_index = index.clone() ^ &flipped;
                 if !self.state.contains_key(&flipped_index) {
                     // The state vector does not have an entry for the state where the target is flipped
                     // and all other qubits are the same, meaning there is no superposition for this state.
@@ -868,8 +901,10 @@ impl QuantumSim {
 
                     let new_val = (value - flipped_value) as Complex64;
                     if !new_val.is_nearly_zero() {
-                        new_state
-                            .insert(index | &flipped, new_val * std::f64::consts::FRAC_1_SQRT_2);
+                        new_state.insert(
+                            index.clone() | &flipped,
+                            new_val * std::f64::consts::FRAC_1_SQRT_2,
+                        );
                     }
                 }
             } else {
@@ -906,12 +941,12 @@ impl QuantumSim {
             let (target, ctls) = self.resolve_and_check_qubits(target, ctls);
             let mut new_state = SparseState::default();
             let m10 = m01 * if sign_flip { -1.0 } else { 1.0 };
-            let mut flipped = BigUint::zero();
+            let mut flipped = K::zero();
             flipped.set_bit(target, true);
 
             for (index, value) in &self.state {
                 if ctls.iter().all(|c| index.bit(*c)) {
-                    let flipped_index = index ^ &flipped;
+                    let flipped_index = index.clone() ^ &flipped;
                     if !self.state.contains_key(&flipped_index) {
                         // The state vector doesn't have an entry for the flipped target bit, so there
                         // isn't a superposition. Calculate the superposition using the matrix entries.
@@ -1011,7 +1046,7 @@ mod tests {
     // Test that basic allocation and release of qubits doesn't fail.
     #[test]
     fn test_alloc_release() {
-        let sim = &mut QuantumSim::default();
+        let sim = &mut QuantumSim::<u64>::default();
         for i in 0..16 {
             assert_eq!(sim.allocate(), i);
         }
@@ -1033,7 +1068,7 @@ mod tests {
     /// Verifies that application of gates to a qubit results in the correct probabilities.
     #[test]
     fn test_probability() {
-        let mut sim = QuantumSim::default();
+        let mut sim = QuantumSim::<u64>::default();
         let q = sim.allocate();
         let extra = sim.allocate();
         assert!(almost_equal(0.0, sim.joint_probability(&[q])));
@@ -1061,7 +1096,7 @@ mod tests {
     /// can be operationally reset back into the ground state.
     #[test]
     fn test_measure() {
-        let mut sim = QuantumSim::default();
+        let mut sim = QuantumSim::<u64>::default();
         let q = sim.allocate();
         let extra = sim.allocate();
         assert!(!sim.measure(q));
@@ -1089,7 +1124,7 @@ mod tests {
     // and start in a zero state.
     #[test]
     fn test_out_of_order_release() {
-        let sim = &mut QuantumSim::default();
+        let sim = &mut QuantumSim::<u64>::default();
         for i in 0..5 {
             assert_eq!(sim.allocate(), i);
             sim.x(i);
@@ -1122,7 +1157,7 @@ mod tests {
     /// qubits.
     #[test]
     fn test_joint_probability() {
-        let mut sim = QuantumSim::default();
+        let mut sim = QuantumSim::<u64>::default();
         let q0 = sim.allocate();
         let q1 = sim.allocate();
         assert!(almost_equal(0.0, sim.joint_probability(&[q0, q1])));
@@ -1142,7 +1177,7 @@ mod tests {
     /// qubits.
     #[test]
     fn test_joint_measurement() {
-        let mut sim = QuantumSim::default();
+        let mut sim = QuantumSim::<u64>::default();
         let q0 = sim.allocate();
         let q1 = sim.allocate();
         assert!(!sim.joint_measure(&[q0, q1]));
@@ -1165,7 +1200,7 @@ mod tests {
     /// Test multiple controls.
     #[test]
     fn test_multiple_controls() {
-        let mut sim = QuantumSim::default();
+        let mut sim = QuantumSim::<u64>::default();
         let q0 = sim.allocate();
         let q1 = sim.allocate();
         let q2 = sim.allocate();
@@ -1195,7 +1230,7 @@ mod tests {
     #[test]
     #[should_panic(expected = "Duplicate qubit id '0' found in application.")]
     fn test_duplicate_target() {
-        let mut sim = QuantumSim::new();
+        let mut sim = QuantumSim::<u64>::new();
         let q = sim.allocate();
         sim.mcx(&[q], q);
     }
@@ -1204,7 +1239,7 @@ mod tests {
     #[test]
     #[should_panic(expected = "Duplicate qubit id '1' found in application.")]
     fn test_duplicate_control() {
-        let mut sim = QuantumSim::new();
+        let mut sim = QuantumSim::<u64>::new();
         let q = sim.allocate();
         let c = sim.allocate();
         sim.mcx(&[c, c], q);
@@ -1214,7 +1249,7 @@ mod tests {
     #[test]
     #[should_panic(expected = "Duplicate qubit id '0' found in application.")]
     fn test_target_in_control() {
-        let mut sim = QuantumSim::new();
+        let mut sim = QuantumSim::<u64>::new();
         let q = sim.allocate();
         let c = sim.allocate();
         sim.mcx(&[c, q], q);
@@ -1223,7 +1258,7 @@ mod tests {
     /// Large, entangled state handling.
     #[test]
     fn test_large_state() {
-        let mut sim = QuantumSim::new();
+        let mut sim = QuantumSim::<BigUint>::new();
         let ctl = sim.allocate();
         sim.h(ctl);
         for _ in 0..4999 {
@@ -1240,7 +1275,7 @@ mod tests {
     #[test]
     fn test_seeded_rng() {
         set_rng_seed(42);
-        let mut sim = QuantumSim::new();
+        let mut sim = QuantumSim::<u64>::new();
         let q = sim.allocate();
         let mut val1 = 0_u64;
         for i in 0..64 {
@@ -1250,7 +1285,7 @@ mod tests {
             }
         }
         set_rng_seed(42);
-        let mut sim = QuantumSim::new();
+        let mut sim = QuantumSim::<u64>::new();
         let q = sim.allocate();
         let mut val2 = 0_u64;
         for i in 0..64 {
@@ -1265,8 +1300,8 @@ mod tests {
     /// Utility for testing operation equivalence.
     fn assert_operation_equal_referenced<F1, F2>(mut op: F1, mut reference: F2, count: usize)
     where
-        F1: FnMut(&mut QuantumSim, &[usize]),
-        F2: FnMut(&mut QuantumSim, &[usize]),
+        F1: FnMut(&mut QuantumSim<u64>, &[usize]),
+        F2: FnMut(&mut QuantumSim<u64>, &[usize]),
     {
         enum QueuedOp {
             NoOp,
@@ -1276,7 +1311,7 @@ mod tests {
         }
 
         for inner_op in [QueuedOp::NoOp, QueuedOp::H, QueuedOp::Rx, QueuedOp::Ry] {
-            let mut sim = QuantumSim::default();
+            let mut sim = QuantumSim::<u64>::default();
 
             // Allocte the control we use to verify behavior.
             let ctl = sim.allocate();
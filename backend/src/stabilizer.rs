@@ -0,0 +1,625 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Stabilizer simulation based on the tableau representation from
+//! <a href="https://arxiv.org/abs/quant-ph/0406196">Improved simulation of stabilizer circuits</a>.
+//! The state of n qubits is described by n stabilizer and n destabilizer generators, so Clifford gates take
+//! O(n) and measurements O(n²) time, which allows simulating thousands of qubits. Gates outside of the
+//! Clifford group cannot be simulated and cause a panic.
+
+use crate::simulator::{check_for_duplicates, random_sample};
+use num_bigint::BigUint;
+use num_complex::Complex64;
+use qir_stdlib::Pauli;
+use std::fmt::Write;
+
+/// A Pauli operator with a sign, given by the bits of its X and Z components for each qubit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PauliRow {
+    x: Vec<u64>,
+    z: Vec<u64>,
+    negative: bool,
+}
+
+impl PauliRow {
+    fn identity(words: usize) -> Self {
+        PauliRow {
+            x: vec![0; words],
+            z: vec![0; words],
+            negative: false,
+        }
+    }
+
+    fn x_bit(&self, qubit: usize) -> bool {
+        self.x[qubit / 64] & (1 << (qubit % 64)) != 0
+    }
+
+    fn z_bit(&self, qubit: usize) -> bool {
+        self.z[qubit / 64] & (1 << (qubit % 64)) != 0
+    }
+
+    fn set_x(&mut self, qubit: usize, value: bool) {
+        let mask = 1 << (qubit % 64);
+        if value {
+            self.x[qubit / 64] |= mask;
+        } else {
+            self.x[qubit / 64] &= !mask;
+        }
+    }
+
+    fn set_z(&mut self, qubit: usize, value: bool) {
+        let mask = 1 << (qubit % 64);
+        if value {
+            self.z[qubit / 64] |= mask;
+        } else {
+            self.z[qubit / 64] &= !mask;
+        }
+    }
+
+    /// Returns whether this operator anticommutes with the other one.
+    fn anticommutes(&self, other: &PauliRow) -> bool {
+        let mut parity = 0;
+        for i in 0..self.x.len() {
+            parity ^= ((self.x[i] & other.z[i]) ^ (self.z[i] & other.x[i])).count_ones();
+        }
+        parity & 1 != 0
+    }
+
+    /// Multiplies this operator by the other one from the left, tracking the sign of the product. The sign is
+    /// only meaningful if the operators commute, since the product is not Hermitian otherwise.
+    fn multiply(&mut self, other: &PauliRow) {
+        // The exponent of i in the phase of the product, where each qubit contributes -1, 0 or 1.
+        let mut exponent = 2 * i64::from(self.negative) + 2 * i64::from(other.negative);
+        for i in 0..self.x.len() {
+            let (x1, z1, x2, z2) = (other.x[i], other.z[i], self.x[i], self.z[i]);
+            let positive = (x1 & z1 & !x2 & z2) | (x1 & !z1 & x2 & z2) | (!x1 & z1 & x2 & !z2);
+            let negative = (x1 & z1 & x2 & !z2) | (x1 & !z1 & !x2 & z2) | (!x1 & z1 & x2 & z2);
+            exponent += i64::from(positive.count_ones()) - i64::from(negative.count_ones());
+            self.x[i] ^= x1;
+            self.z[i] ^= z1;
+        }
+        self.negative = exponent.rem_euclid(4) == 2;
+    }
+}
+
+/// The `StabilizerSim` struct contains the tableau of a stabilizer simulation. Qubit identifiers are used as
+/// the columns of the tableau.
+pub(crate) struct StabilizerSim {
+    /// The destabilizer generators, where the i-th destabilizer anticommutes only with the i-th stabilizer.
+    destabilizers: Vec<PauliRow>,
+
+    /// The stabilizer generators of the state.
+    stabilizers: Vec<PauliRow>,
+
+    /// Whether the qubit with a given identifier is allocated.
+    allocated: Vec<bool>,
+}
+
+impl Default for StabilizerSim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StabilizerSim {
+    /// Creates a new stabilizer simulator object with no qubits allocated.
+    #[must_use]
+    fn new() -> Self {
+        StabilizerSim {
+            destabilizers: Vec::new(),
+            stabilizers: Vec::new(),
+            allocated: Vec::new(),
+        }
+    }
+
+    fn qubit_count(&self) -> usize {
+        self.allocated.len()
+    }
+
+    fn rows_mut(&mut self) -> impl Iterator<Item = &mut PauliRow> {
+        self.destabilizers
+            .iter_mut()
+            .chain(self.stabilizers.iter_mut())
+    }
+
+    /// Adds a column for a new qubit in the |0⟩ state to the tableau.
+    fn add_qubit(&mut self) -> usize {
+        let qubit = self.qubit_count();
+        if qubit % 64 == 0 {
+            for row in self.rows_mut() {
+                row.x.push(0);
+                row.z.push(0);
+            }
+        }
+        let words = qubit / 64 + 1;
+        let mut destabilizer = PauliRow::identity(words);
+        destabilizer.set_x(qubit, true);
+        self.destabilizers.push(destabilizer);
+        let mut stabilizer = PauliRow::identity(words);
+        stabilizer.set_z(qubit, true);
+        self.stabilizers.push(stabilizer);
+        self.allocated.push(false);
+        qubit
+    }
+
+    /// Allocates a fresh qubit, returning its identifier. Note that this will use the lowest available
+    /// identifier, like the sparse simulator.
+    #[must_use]
+    pub(crate) fn allocate(&mut self) -> usize {
+        let qubit = match self.allocated.iter().position(|allocated| !allocated) {
+            Some(qubit) => qubit,
+            None => self.add_qubit(),
+        };
+        self.allocated[qubit] = true;
+        qubit
+    }
+
+    /// Releases the given qubit, resetting it to the |0⟩ state so that its column can be reused.
+    /// # Panics
+    ///
+    /// The function will panic if the given id does not correpsond to an allocated qubit.
+    pub(crate) fn release(&mut self, id: usize) {
+        if self.measure(id) {
+            self.x(id);
+        }
+        self.allocated[id] = false;
+    }
+
+    /// Verifies that the given qubits are allocated and distinct.
+    fn check_qubits(&self, ids: &[usize]) {
+        for &id in ids {
+            assert!(
+                self.allocated.get(id).copied().unwrap_or_default(),
+                "Unable to find qubit with id {id}"
+            );
+        }
+        check_for_duplicates(ids);
+    }
+
+    fn non_clifford(gate: &str) -> ! {
+        panic!("The stabilizer simulator does not support the non-Clifford gate {gate}.")
+    }
+
+    fn pauli_string(&self, row: &PauliRow) -> String {
+        let mut string = String::from(if row.negative { "-" } else { "+" });
+        string.extend((0..self.qubit_count()).map(|qubit| {
+            match (row.x_bit(qubit), row.z_bit(qubit)) {
+                (false, false) => 'I',
+                (true, false) => 'X',
+                (true, true) => 'Y',
+                (false, true) => 'Z',
+            }
+        }));
+        string
+    }
+
+    /// Returns the stabilizer generators of the current state, with one Pauli for each qubit in order of the
+    /// qubit identifiers.
+    pub(crate) fn dump(&mut self) -> String {
+        let mut text = String::from("STABILIZERS: [ ");
+        for stabilizer in &self.stabilizers {
+            let _ = write!(text, "{}, ", self.pauli_string(stabilizer));
+        }
+        text.push(']');
+        text
+    }
+
+    /// Returns the product of Pauli-Z operators on the given qubits.
+    fn z_product(&self, ids: &[usize]) -> PauliRow {
+        let mut row = PauliRow::identity((self.qubit_count() + 63) / 64);
+        for &id in ids {
+            row.set_z(id, true);
+        }
+        row
+    }
+
+    /// Returns whether the given Pauli operator, which must commute with all stabilizers, has a negative sign
+    /// when written as a product of the stabilizers.
+    fn deterministic_outcome(&self, pauli: &PauliRow) -> bool {
+        let mut product = PauliRow::identity(pauli.x.len());
+        for (destabilizer, stabilizer) in self.destabilizers.iter().zip(&self.stabilizers) {
+            if destabilizer.anticommutes(pauli) {
+                product.multiply(stabilizer);
+            }
+        }
+        product.negative
+    }
+
+    /// Measures the given Pauli operator, updating the tableau based on the measured result.
+    fn measure_pauli(&mut self, mut pauli: PauliRow) -> bool {
+        let Some(pivot) = self
+            .stabilizers
+            .iter()
+            .position(|stabilizer| stabilizer.anticommutes(&pauli))
+        else {
+            return self.deterministic_outcome(&pauli);
+        };
+
+        let pivot_row = self.stabilizers[pivot].clone();
+        let pivot_index = self.qubit_count() + pivot;
+        for (index, row) in self.rows_mut().enumerate() {
+            if index != pivot_index && row.anticommutes(&pauli) {
+                row.multiply(&pivot_row);
+            }
+        }
+        let res = random_sample() < 0.5;
+        pauli.negative = res;
+        self.destabilizers[pivot] = pivot_row;
+        self.stabilizers[pivot] = pauli;
+        res
+    }
+
+    /// Checks the probability of parity measurement in the computational basis for the given set of
+    /// qubits, which is either 0, 1/2 or 1.
+    /// # Panics
+    ///
+    /// This function will panic if the given ids do not all correspond to allocated qubits.
+    /// This function will panic if there are duplicate ids in the given list.
+    #[must_use]
+    pub(crate) fn joint_probability(&mut self, ids: &[usize]) -> f64 {
+        self.check_qubits(ids);
+        let pauli = self.z_product(ids);
+        if self
+            .stabilizers
+            .iter()
+            .any(|stabilizer| stabilizer.anticommutes(&pauli))
+        {
+            0.5
+        } else if self.deterministic_outcome(&pauli) {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Measures the qubit with the given id, collapsing the state based on the measured result.
+    /// # Panics
+    ///
+    /// This funciton will panic if the given identifier does not correspond to an allocated qubit.
+    #[must_use]
+    pub(crate) fn measure(&mut self, id: usize) -> bool {
+        self.joint_measure(&[id])
+    }
+
+    /// Performs a joint measurement to get the parity of the given qubits, collapsing the state
+    /// based on the measured result.
+    /// # Panics
+    ///
+    /// This function will panic if any of the given identifiers do not correspond to an allocated qubit.
+    /// This function will panic if any of the given identifiers are duplicates.
+    #[must_use]
+    pub(crate) fn joint_measure(&mut self, ids: &[usize]) -> bool {
+        self.check_qubits(ids);
+        self.measure_pauli(self.z_product(ids))
+    }
+
+    /// Swaps the states of the two given qubits.
+    pub(crate) fn swap_qubit_ids(&mut self, qubit1: usize, qubit2: usize) {
+        self.check_qubits(&[qubit1, qubit2]);
+        for row in self.rows_mut() {
+            let (x1, z1) = (row.x_bit(qubit1), row.z_bit(qubit1));
+            row.set_x(qubit1, row.x_bit(qubit2));
+            row.set_z(qubit1, row.z_bit(qubit2));
+            row.set_x(qubit2, x1);
+            row.set_z(qubit2, z1);
+        }
+    }
+
+    /// Applies the X gate to the given qubit.
+    pub(crate) fn x(&mut self, target: usize) {
+        self.check_qubits(&[target]);
+        for row in self.rows_mut() {
+            row.negative ^= row.z_bit(target);
+        }
+    }
+
+    /// Applies the Y gate to the given qubit.
+    pub(crate) fn y(&mut self, target: usize) {
+        self.check_qubits(&[target]);
+        for row in self.rows_mut() {
+            row.negative ^= row.x_bit(target) ^ row.z_bit(target);
+        }
+    }
+
+    /// Applies the Z gate to the given qubit.
+    pub(crate) fn z(&mut self, target: usize) {
+        self.check_qubits(&[target]);
+        for row in self.rows_mut() {
+            row.negative ^= row.x_bit(target);
+        }
+    }
+
+    /// Applies the H gate to the given qubit.
+    pub(crate) fn h(&mut self, target: usize) {
+        self.check_qubits(&[target]);
+        for row in self.rows_mut() {
+            let (x, z) = (row.x_bit(target), row.z_bit(target));
+            row.negative ^= x && z;
+            row.set_x(target, z);
+            row.set_z(target, x);
+        }
+    }
+
+    /// Applies the S gate to the given qubit.
+    pub(crate) fn s(&mut self, target: usize) {
+        self.check_qubits(&[target]);
+        for row in self.rows_mut() {
+            let (x, z) = (row.x_bit(target), row.z_bit(target));
+            row.negative ^= x && z;
+            row.set_z(target, x ^ z);
+        }
+    }
+
+    /// Applies the Adjoint S gate to the given qubit.
+    pub(crate) fn sadj(&mut self, target: usize) {
+        self.check_qubits(&[target]);
+        for row in self.rows_mut() {
+            let (x, z) = (row.x_bit(target), row.z_bit(target));
+            row.negative ^= x && !z;
+            row.set_z(target, x ^ z);
+        }
+    }
+
+    /// Applies the CNOT gate with the given control and target.
+    fn cx(&mut self, ctl: usize, target: usize) {
+        self.check_qubits(&[ctl, target]);
+        for row in self.rows_mut() {
+            let (xc, zc) = (row.x_bit(ctl), row.z_bit(ctl));
+            let (xt, zt) = (row.x_bit(target), row.z_bit(target));
+            row.negative ^= xc && zt && (xt == zc);
+            row.set_x(target, xt ^ xc);
+            row.set_z(ctl, zc ^ zt);
+        }
+    }
+
+    /// Applies the X gate to the given target with at most one control.
+    /// # Panics
+    ///
+    /// This function will panic if more than one control is given.
+    pub(crate) fn mcx(&mut self, ctls: &[usize], target: usize) {
+        match ctls {
+            [] => self.x(target),
+            &[ctl] => self.cx(ctl, target),
+            _ => Self::non_clifford("multicontrolled X"),
+        }
+    }
+
+    /// Applies the Y gate to the given target with at most one control.
+    /// # Panics
+    ///
+    /// This function will panic if more than one control is given.
+    pub(crate) fn mcy(&mut self, ctls: &[usize], target: usize) {
+        match ctls {
+            [] => self.y(target),
+            &[ctl] => {
+                self.check_qubits(&[ctl, target]);
+                self.sadj(target);
+                self.cx(ctl, target);
+                self.s(target);
+            }
+            _ => Self::non_clifford("multicontrolled Y"),
+        }
+    }
+
+    /// Applies the Z gate to the given target with at most one control.
+    /// # Panics
+    ///
+    /// This function will panic if more than one control is given.
+    pub(crate) fn mcz(&mut self, ctls: &[usize], target: usize) {
+        match ctls {
+            [] => self.z(target),
+            &[ctl] => {
+                self.check_qubits(&[ctl, target]);
+                self.h(target);
+                self.cx(ctl, target);
+                self.h(target);
+            }
+            _ => Self::non_clifford("multicontrolled Z"),
+        }
+    }
+
+    /// Applies the H gate to the given target if no controls are given.
+    /// # Panics
+    ///
+    /// This function will panic if any controls are given.
+    pub(crate) fn mch(&mut self, ctls: &[usize], target: usize) {
+        if !ctls.is_empty() {
+            Self::non_clifford("controlled H");
+        }
+        self.h(target);
+    }
+
+    /// Applies the S gate to the given target if no controls are given.
+    /// # Panics
+    ///
+    /// This function will panic if any controls are given.
+    pub(crate) fn mcs(&mut self, ctls: &[usize], target: usize) {
+        if !ctls.is_empty() {
+            Self::non_clifford("controlled S");
+        }
+        self.s(target);
+    }
+
+    /// Applies the Adjoint S gate to the given target if no controls are given.
+    /// # Panics
+    ///
+    /// This function will panic if any controls are given.
+    pub(crate) fn mcsadj(&mut self, ctls: &[usize], target: usize) {
+        if !ctls.is_empty() {
+            Self::non_clifford("controlled Adjoint S");
+        }
+        self.sadj(target);
+    }
+}
+
+/// The operations that the stabilizer simulator does not support, which panic when applied.
+#[allow(clippy::unused_self)]
+impl StabilizerSim {
+    /// The state vector of a stabilizer simulation is not tracked.
+    /// # Panics
+    ///
+    /// This function always panics.
+    pub(crate) fn get_state(&mut self) -> (Vec<(BigUint, Complex64)>, usize) {
+        panic!("The stabilizer simulator does not support capturing the state vector.")
+    }
+
+    /// Amplitude damping is not a Pauli channel and cannot be simulated with stabilizers.
+    /// # Panics
+    ///
+    /// This function always panics.
+    pub(crate) fn amplitude_damping(&mut self, _gamma: f64, _id: usize) {
+        panic!("The stabilizer simulator does not support amplitude damping noise.")
+    }
+
+    pub(crate) fn t(&mut self, _target: usize) {
+        Self::non_clifford("T");
+    }
+
+    pub(crate) fn mct(&mut self, _ctls: &[usize], _target: usize) {
+        Self::non_clifford("T");
+    }
+
+    pub(crate) fn tadj(&mut self, _target: usize) {
+        Self::non_clifford("Adjoint T");
+    }
+
+    pub(crate) fn mctadj(&mut self, _ctls: &[usize], _target: usize) {
+        Self::non_clifford("Adjoint T");
+    }
+
+    pub(crate) fn mcphase(&mut self, _ctls: &[usize], _phase: Complex64, _target: usize) {
+        Self::non_clifford("controlled phase");
+    }
+
+    pub(crate) fn rx(&mut self, _theta: f64, _target: usize) {
+        Self::non_clifford("Rx");
+    }
+
+    pub(crate) fn mcrx(&mut self, _ctls: &[usize], _theta: f64, _target: usize) {
+        Self::non_clifford("Rx");
+    }
+
+    pub(crate) fn ry(&mut self, _theta: f64, _target: usize) {
+        Self::non_clifford("Ry");
+    }
+
+    pub(crate) fn mcry(&mut self, _ctls: &[usize], _theta: f64, _target: usize) {
+        Self::non_clifford("Ry");
+    }
+
+    pub(crate) fn rz(&mut self, _theta: f64, _target: usize) {
+        Self::non_clifford("Rz");
+    }
+
+    pub(crate) fn mcrz(&mut self, _ctls: &[usize], _theta: f64, _target: usize) {
+        Self::non_clifford("Rz");
+    }
+
+    pub(crate) fn exp(&mut self, _paulis: &[Pauli], _theta: f64, _targets: &[usize]) {
+        Self::non_clifford("Exp");
+    }
+
+    pub(crate) fn mcexp(
+        &mut self,
+        _ctls: &[usize],
+        _paulis: &[Pauli],
+        _theta: f64,
+        _targets: &[usize],
+    ) {
+        Self::non_clifford("Exp");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StabilizerSim;
+
+    #[test]
+    fn test_bell_pair() {
+        let mut sim = StabilizerSim::default();
+        let q0 = sim.allocate();
+        let q1 = sim.allocate();
+        sim.h(q0);
+        sim.mcx(&[q0], q1);
+        assert!((sim.joint_probability(&[q0]) - 0.5).abs() < f64::EPSILON);
+        assert!(sim.joint_probability(&[q0, q1]).abs() < f64::EPSILON);
+        let res = sim.measure(q0);
+        assert_eq!(sim.measure(q1), res);
+        sim.release(q0);
+        sim.release(q1);
+    }
+
+    #[test]
+    fn test_clifford_identities() {
+        let mut sim = StabilizerSim::default();
+        let q0 = sim.allocate();
+        let q1 = sim.allocate();
+        // HZH = X
+        sim.h(q0);
+        sim.z(q0);
+        sim.h(q0);
+        assert!(sim.measure(q0));
+        // S·S = Z, which only changes the sign of |+⟩.
+        sim.h(q1);
+        sim.s(q1);
+        sim.s(q1);
+        sim.h(q1);
+        assert!(sim.measure(q1));
+        sim.s(q1);
+        sim.sadj(q1);
+        assert!(sim.measure(q1));
+        // Y|1⟩ = -i|0⟩ and CY/CZ act on the target only if the control is set.
+        sim.y(q0);
+        assert!(!sim.measure(q0));
+        sim.mcy(&[q1], q0);
+        assert!(sim.measure(q0));
+        sim.h(q0);
+        sim.mcz(&[q1], q0);
+        sim.h(q0);
+        assert!(!sim.measure(q0));
+        sim.swap_qubit_ids(q0, q1);
+        assert!(sim.measure(q0));
+        assert!(!sim.measure(q1));
+    }
+
+    #[test]
+    fn test_large_ghz_state() {
+        let mut sim = StabilizerSim::default();
+        let qubits: Vec<usize> = (0..1000).map(|_| sim.allocate()).collect();
+        sim.h(qubits[0]);
+        for pair in qubits.windows(2) {
+            sim.mcx(&[pair[0]], pair[1]);
+        }
+        let res = sim.measure(qubits[500]);
+        for &qubit in &qubits {
+            assert_eq!(sim.measure(qubit), res);
+        }
+        assert!(!sim.joint_measure(&qubits[..2]));
+    }
+
+    #[test]
+    fn test_release_resets_qubit() {
+        let mut sim = StabilizerSim::default();
+        let q0 = sim.allocate();
+        sim.x(q0);
+        sim.release(q0);
+        assert_eq!(sim.allocate(), q0);
+        assert!(sim.joint_probability(&[q0]).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    #[should_panic(expected = "The stabilizer simulator does not support the non-Clifford gate T.")]
+    fn test_non_clifford_gate() {
+        let mut sim = StabilizerSim::default();
+        let q0 = sim.allocate();
+        sim.t(q0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate qubit id '0' found in application.")]
+    fn test_duplicate_qubits() {
+        let mut sim = StabilizerSim::default();
+        let q0 = sim.allocate();
+        sim.mcx(&[q0], q0);
+    }
+}
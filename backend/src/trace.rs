@@ -0,0 +1,409 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Tracing of the executed operations as an `OpenQASM` 3 program. Every gate applied to the traced simulator is
+//! written as a gate from the standard gate library, where gates without an equivalent are decomposed, and every
+//! measurement is written along with its outcome. Since the trace follows one execution, it contains the branches
+//! that were taken and resolves all angles to their values.
+
+use std::fmt::Write;
+
+use num_bigint::BigUint;
+use num_complex::Complex64;
+use qir_stdlib::Pauli;
+use rustc_hash::FxHashSet;
+
+use crate::engine::Engine;
+
+/// The `TracedSim` struct records the operations applied to it before forwarding them to the simulator it wraps.
+/// Qubit identifiers are used as indices into a single qubit register, and the outcome of every measurement is
+/// stored in its own bit of a single bit register.
+pub(crate) struct TracedSim {
+    sim: Engine,
+    /// The number of qubit identifiers that were allocated at some point.
+    qubits: usize,
+    /// The qubit identifiers that were released, which are reset when they are allocated again.
+    released: FxHashSet<usize>,
+    /// The number of measurements.
+    bits: usize,
+    /// The statements of the trace.
+    statements: Vec<String>,
+}
+
+fn qubit_list(qubits: &[usize]) -> String {
+    qubits
+        .iter()
+        .map(|qubit| format!("q[{qubit}]"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Returns the name of the given gate with the given number of controls, which uses the name from the standard
+/// gate library if there is one and a control modifier otherwise.
+fn controlled_name(gate: &str, controls: usize) -> String {
+    let base = gate.split('(').next().unwrap_or_default();
+    match (controls, base) {
+        (0, _) => gate.to_owned(),
+        (1, "x" | "y" | "z" | "h" | "p" | "rx" | "ry" | "rz") => format!("c{gate}"),
+        (2, "x") => "ccx".to_owned(),
+        (1, _) => format!("ctrl @ {gate}"),
+        _ => format!("ctrl({controls}) @ {gate}"),
+    }
+}
+
+impl TracedSim {
+    /// Creates a traced simulation of the given simulator, which must not have any qubits allocated.
+    pub(crate) fn new(sim: Engine) -> Self {
+        TracedSim {
+            sim,
+            qubits: 0,
+            released: FxHashSet::default(),
+            bits: 0,
+            statements: Vec::new(),
+        }
+    }
+
+    /// Returns the simulator that the operations are forwarded to.
+    pub(crate) fn sim(&self) -> &Engine {
+        &self.sim
+    }
+
+    pub(crate) fn sim_mut(&mut self) -> &mut Engine {
+        &mut self.sim
+    }
+
+    /// Renders the trace as an `OpenQASM` 3 program.
+    pub(crate) fn to_qasm(&self) -> String {
+        let mut qasm = "OPENQASM 3.0;\ninclude \"stdgates.inc\";\n".to_owned();
+        if self.qubits > 0 {
+            let _ = writeln!(qasm, "qubit[{}] q;", self.qubits);
+        }
+        if self.bits > 0 {
+            let _ = writeln!(qasm, "bit[{}] c;", self.bits);
+        }
+        for statement in &self.statements {
+            qasm.push_str(statement);
+            qasm.push('\n');
+        }
+        qasm
+    }
+
+    /// Records the given gate with the given controls on the given targets.
+    fn gate(&mut self, gate: &str, ctls: &[usize], targets: &[usize]) {
+        let qubits: Vec<usize> = ctls.iter().chain(targets).copied().collect();
+        self.statements.push(format!(
+            "{} {};",
+            controlled_name(gate, ctls.len()),
+            qubit_list(&qubits)
+        ));
+    }
+
+    /// Records the exponential of the given Pauli operator, exp(iθP), by rotating the qubits into the Z basis,
+    /// computing their parity into the last qubit and rotating it. Only the rotation is controlled, since the
+    /// other gates cancel out if the controls are not satisfied.
+    fn exp_gates(&mut self, ctls: &[usize], paulis: &[Pauli], theta: f64, targets: &[usize]) {
+        let qubits: Vec<(Pauli, usize)> = paulis
+            .iter()
+            .zip(targets)
+            .filter(|(pauli, _)| !matches!(pauli, Pauli::I))
+            .map(|(pauli, target)| (*pauli, *target))
+            .collect();
+        let Some(&(_, last)) = qubits.last() else {
+            // The Pauli operator is the identity, so the rotation is a (controlled) global phase.
+            match ctls.split_last() {
+                Some((ctl, rest)) => self.gate(&format!("p({theta})"), rest, &[*ctl]),
+                None => self.statements.push(format!("gphase({theta});")),
+            }
+            return;
+        };
+        for &(pauli, target) in &qubits {
+            match pauli {
+                Pauli::X => self.gate("h", &[], &[target]),
+                Pauli::Y => {
+                    self.gate("sdg", &[], &[target]);
+                    self.gate("h", &[], &[target]);
+                }
+                Pauli::I | Pauli::Z => (),
+            }
+        }
+        for &(_, target) in &qubits[..qubits.len() - 1] {
+            self.gate("x", &[target], &[last]);
+        }
+        self.gate(&format!("rz({})", -2.0 * theta), ctls, &[last]);
+        for &(_, target) in qubits[..qubits.len() - 1].iter().rev() {
+            self.gate("x", &[target], &[last]);
+        }
+        for &(pauli, target) in &qubits {
+            match pauli {
+                Pauli::X => self.gate("h", &[], &[target]),
+                Pauli::Y => {
+                    self.gate("h", &[], &[target]);
+                    self.gate("s", &[], &[target]);
+                }
+                Pauli::I | Pauli::Z => (),
+            }
+        }
+    }
+
+    /// Records a measurement of the given qubit with the given outcome.
+    fn measurement(&mut self, id: usize, result: bool) {
+        self.statements.push(format!(
+            "c[{}] = measure q[{id}]; // {}",
+            self.bits,
+            u8::from(result)
+        ));
+        self.bits += 1;
+    }
+
+    #[must_use]
+    pub(crate) fn allocate(&mut self) -> usize {
+        let id = self.sim.allocate();
+        if self.released.remove(&id) {
+            self.statements.push(format!("reset q[{id}];"));
+        }
+        self.qubits = self.qubits.max(id + 1);
+        id
+    }
+
+    pub(crate) fn release(&mut self, id: usize) {
+        self.sim.release(id);
+        self.released.insert(id);
+    }
+
+    pub(crate) fn dump(&mut self) -> String {
+        self.sim.dump()
+    }
+
+    #[must_use]
+    pub(crate) fn get_state(&mut self) -> (Vec<(BigUint, Complex64)>, usize) {
+        self.sim.get_state()
+    }
+
+    #[must_use]
+    pub(crate) fn joint_probability(&mut self, ids: &[usize]) -> f64 {
+        self.sim.joint_probability(ids)
+    }
+
+    #[must_use]
+    pub(crate) fn measure(&mut self, id: usize) -> bool {
+        let result = self.sim.measure(id);
+        self.measurement(id, result);
+        result
+    }
+
+    /// Measures the parity of the given qubits, which is recorded by computing the parity into the last qubit,
+    /// measuring it and uncomputing the parity.
+    #[must_use]
+    pub(crate) fn joint_measure(&mut self, ids: &[usize]) -> bool {
+        let result = self.sim.joint_measure(ids);
+        if let Some((&last, rest)) = ids.split_last() {
+            for &id in rest {
+                self.gate("x", &[id], &[last]);
+            }
+            self.measurement(last, result);
+            for &id in rest.iter().rev() {
+                self.gate("x", &[id], &[last]);
+            }
+        }
+        result
+    }
+
+    pub(crate) fn swap_qubit_ids(&mut self, qubit1: usize, qubit2: usize) {
+        self.sim.swap_qubit_ids(qubit1, qubit2);
+        self.gate("swap", &[], &[qubit1, qubit2]);
+    }
+
+    /// Applies amplitude damping, which has no equivalent gate and is recorded as a comment.
+    pub(crate) fn amplitude_damping(&mut self, gamma: f64, id: usize) {
+        self.sim.amplitude_damping(gamma, id);
+        self.statements
+            .push(format!("// amplitude damping({gamma}) q[{id}];"));
+    }
+
+    pub(crate) fn x(&mut self, target: usize) {
+        self.sim.x(target);
+        self.gate("x", &[], &[target]);
+    }
+
+    pub(crate) fn y(&mut self, target: usize) {
+        self.sim.y(target);
+        self.gate("y", &[], &[target]);
+    }
+
+    pub(crate) fn z(&mut self, target: usize) {
+        self.sim.z(target);
+        self.gate("z", &[], &[target]);
+    }
+
+    pub(crate) fn h(&mut self, target: usize) {
+        self.sim.h(target);
+        self.gate("h", &[], &[target]);
+    }
+
+    pub(crate) fn s(&mut self, target: usize) {
+        self.sim.s(target);
+        self.gate("s", &[], &[target]);
+    }
+
+    pub(crate) fn sadj(&mut self, target: usize) {
+        self.sim.sadj(target);
+        self.gate("sdg", &[], &[target]);
+    }
+
+    pub(crate) fn t(&mut self, target: usize) {
+        self.sim.t(target);
+        self.gate("t", &[], &[target]);
+    }
+
+    pub(crate) fn tadj(&mut self, target: usize) {
+        self.sim.tadj(target);
+        self.gate("tdg", &[], &[target]);
+    }
+
+    pub(crate) fn mcx(&mut self, ctls: &[usize], target: usize) {
+        self.sim.mcx(ctls, target);
+        self.gate("x", ctls, &[target]);
+    }
+
+    pub(crate) fn mcy(&mut self, ctls: &[usize], target: usize) {
+        self.sim.mcy(ctls, target);
+        self.gate("y", ctls, &[target]);
+    }
+
+    pub(crate) fn mcz(&mut self, ctls: &[usize], target: usize) {
+        self.sim.mcz(ctls, target);
+        self.gate("z", ctls, &[target]);
+    }
+
+    pub(crate) fn mch(&mut self, ctls: &[usize], target: usize) {
+        self.sim.mch(ctls, target);
+        self.gate("h", ctls, &[target]);
+    }
+
+    pub(crate) fn mcs(&mut self, ctls: &[usize], target: usize) {
+        self.sim.mcs(ctls, target);
+        self.gate("s", ctls, &[target]);
+    }
+
+    pub(crate) fn mcsadj(&mut self, ctls: &[usize], target: usize) {
+        self.sim.mcsadj(ctls, target);
+        self.gate("sdg", ctls, &[target]);
+    }
+
+    pub(crate) fn mct(&mut self, ctls: &[usize], target: usize) {
+        self.sim.mct(ctls, target);
+        self.gate("t", ctls, &[target]);
+    }
+
+    pub(crate) fn mctadj(&mut self, ctls: &[usize], target: usize) {
+        self.sim.mctadj(ctls, target);
+        self.gate("tdg", ctls, &[target]);
+    }
+
+    /// Applies the given phase to the target if all controls are set, which is recorded as a phase gate.
+    pub(crate) fn mcphase(&mut self, ctls: &[usize], phase: Complex64, target: usize) {
+        self.sim.mcphase(ctls, phase, target);
+        self.gate(&format!("p({})", phase.arg()), ctls, &[target]);
+    }
+
+    pub(crate) fn rx(&mut self, theta: f64, target: usize) {
+        self.sim.rx(theta, target);
+        self.gate(&format!("rx({theta})"), &[], &[target]);
+    }
+
+    pub(crate) fn ry(&mut self, theta: f64, target: usize) {
+        self.sim.ry(theta, target);
+        self.gate(&format!("ry({theta})"), &[], &[target]);
+    }
+
+    pub(crate) fn rz(&mut self, theta: f64, target: usize) {
+        self.sim.rz(theta, target);
+        self.gate(&format!("rz({theta})"), &[], &[target]);
+    }
+
+    pub(crate) fn mcrx(&mut self, ctls: &[usize], theta: f64, target: usize) {
+        self.sim.mcrx(ctls, theta, target);
+        self.gate(&format!("rx({theta})"), ctls, &[target]);
+    }
+
+    pub(crate) fn mcry(&mut self, ctls: &[usize], theta: f64, target: usize) {
+        self.sim.mcry(ctls, theta, target);
+        self.gate(&format!("ry({theta})"), ctls, &[target]);
+    }
+
+    pub(crate) fn mcrz(&mut self, ctls: &[usize], theta: f64, target: usize) {
+        self.sim.mcrz(ctls, theta, target);
+        self.gate(&format!("rz({theta})"), ctls, &[target]);
+    }
+
+    pub(crate) fn exp(&mut self, paulis: &[Pauli], theta: f64, targets: &[usize]) {
+        self.sim.exp(paulis, theta, targets);
+        self.exp_gates(&[], paulis, theta, targets);
+    }
+
+    pub(crate) fn mcexp(
+        &mut self,
+        ctls: &[usize],
+        paulis: &[Pauli],
+        theta: f64,
+        targets: &[usize],
+    ) {
+        self.sim.mcexp(ctls, paulis, theta, targets);
+        self.exp_gates(ctls, paulis, theta, targets);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TracedSim;
+    use crate::engine::{Engine, SimulatorKind};
+    use qir_stdlib::Pauli;
+
+    #[test]
+    fn test_trace() {
+        let mut sim = TracedSim::new(Engine::new(SimulatorKind::Sparse));
+        let q0 = sim.allocate();
+        let q1 = sim.allocate();
+        let q2 = sim.allocate();
+        sim.h(q0);
+        sim.mcx(&[q0], q1);
+        sim.mcx(&[q0, q1], q2);
+        sim.mcsadj(&[q2], q0);
+        sim.mcrz(&[q0, q1], 0.5, q2);
+        sim.exp(&[Pauli::X, Pauli::I, Pauli::Z], 0.25, &[q0, q1, q2]);
+        let parity = sim.joint_measure(&[q0, q1]);
+        let result = sim.measure(q2);
+        sim.release(q2);
+        let q3 = sim.allocate();
+        sim.x(q3);
+
+        assert_eq!(
+            sim.to_qasm(),
+            format!(
+                "OPENQASM 3.0;
+include \"stdgates.inc\";
+qubit[3] q;
+bit[2] c;
+h q[0];
+cx q[0], q[1];
+ccx q[0], q[1], q[2];
+ctrl @ sdg q[2], q[0];
+ctrl(2) @ rz(0.5) q[0], q[1], q[2];
+h q[0];
+cx q[0], q[2];
+rz(-0.5) q[2];
+cx q[0], q[2];
+h q[0];
+cx q[0], q[1];
+c[0] = measure q[1]; // {}
+cx q[0], q[1];
+c[1] = measure q[2]; // {}
+reset q[2];
+x q[2];
+",
+                u8::from(parity),
+                u8::from(result)
+            )
+        );
+    }
+}
@@ -0,0 +1,435 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::{
+    cell::Cell,
+    ffi::CStr,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use inkwell::{
+    context::Context,
+    module::Module,
+    types::{AnyTypeEnum, BasicType, BasicTypeEnum},
+    values::{BasicMetadataValueEnum, BasicValueEnum, FunctionValue, PointerValue},
+    AddressSpace,
+};
+use qir_backend::arrays::{
+    QirArray, __quantum__rt__array_create_1d, __quantum__rt__array_get_element_ptr_1d,
+    __quantum__rt__array_update_reference_count,
+};
+
+use crate::value_flow::ValueFlow;
+
+/// A typed value passed as argument to an entry point parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Argument {
+    Int(i64),
+    Double(f64),
+    Bool(bool),
+    /// An array of arguments, which must all have the same type.
+    Array(Vec<Argument>),
+}
+
+/// Parses an argument without knowing the parameter type. Integers are parsed as `Int`, other numbers
+/// as `Double`, `true` and `false` as `Bool`, and comma separated values in brackets as `Array`.
+impl FromStr for Argument {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(items) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            return split_items(items)?
+                .into_iter()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map(Argument::Array);
+        }
+        match s {
+            "true" => Ok(Argument::Bool(true)),
+            "false" => Ok(Argument::Bool(false)),
+            _ => s
+                .parse()
+                .map(Argument::Int)
+                .or_else(|_| s.parse().map(Argument::Double))
+                .map_err(|_| format!("Unable to parse argument value '{s}'.")),
+        }
+    }
+}
+
+/// Splits the comma separated items of an array, keeping nested arrays together.
+fn split_items(items: &str) -> Result<Vec<&str>, String> {
+    if items.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut parts = Vec::new();
+    let mut depth = 0_usize;
+    let mut start = 0;
+    for (index, c) in items.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| format!("Unbalanced brackets in array '[{items}]'."))?;
+            }
+            ',' if depth == 0 => {
+                parts.push(&items[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(format!("Unbalanced brackets in array '[{items}]'."));
+    }
+    parts.push(&items[start..]);
+    Ok(parts)
+}
+
+/// The arguments for the parameters of an entry point.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arguments {
+    /// Typed arguments in parameter order.
+    Positional(Vec<Argument>),
+    /// Arguments given as text by parameter name, which are parsed according to the parameter types.
+    Named(Vec<(String, String)>),
+}
+
+impl Default for Arguments {
+    fn default() -> Self {
+        Arguments::Positional(Vec::new())
+    }
+}
+
+impl Arguments {
+    pub(crate) fn is_empty(&self) -> bool {
+        match self {
+            Arguments::Positional(args) => args.is_empty(),
+            Arguments::Named(args) => args.is_empty(),
+        }
+    }
+}
+
+/// The parameter types of an entry point for which arguments can be passed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ParameterKind {
+    Int,
+    Double,
+    Bool,
+    /// A runtime array with elements of the given kind.
+    Array(Box<ParameterKind>),
+}
+
+impl Display for ParameterKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParameterKind::Int => write!(f, "Int"),
+            ParameterKind::Double => write!(f, "Double"),
+            ParameterKind::Bool => write!(f, "Bool"),
+            ParameterKind::Array(element) => write!(f, "{element}[]"),
+        }
+    }
+}
+
+/// Determines the kind of a parameter of the given type, whose values are given by the given pointers if it is
+/// an array. The element type of an array is not part of the signature, so it is taken from how the program
+/// accesses the elements of the array.
+fn parameter_kind<'ctx>(
+    context: &Context,
+    flow: &ValueFlow<'ctx>,
+    name: &str,
+    ty: BasicTypeEnum<'ctx>,
+    values: Vec<PointerValue<'ctx>>,
+) -> Result<ParameterKind, String> {
+    match ty {
+        BasicTypeEnum::IntType(int_type) if int_type.get_bit_width() == 1 => {
+            Ok(ParameterKind::Bool)
+        }
+        BasicTypeEnum::IntType(int_type) if int_type.get_bit_width() == 64 => {
+            Ok(ParameterKind::Int)
+        }
+        BasicTypeEnum::FloatType(float_type) if float_type == context.f64_type() => {
+            Ok(ParameterKind::Double)
+        }
+        BasicTypeEnum::PointerType(pointer_type) => match pointer_type.get_element_type() {
+            AnyTypeEnum::StructType(struct_type)
+                if struct_type.get_name().map(CStr::to_bytes) == Some(b"Array") =>
+            {
+                let (element_type, elements) = flow.element_type(values).ok_or_else(|| {
+                    format!("Unable to determine the element type of array parameter '{name}'.")
+                })?;
+                parameter_kind(context, flow, name, element_type, elements)
+                    .map(|element| ParameterKind::Array(Box::new(element)))
+            }
+            _ => Err(format!(
+                "Entry point parameter '{name}' has an unsupported type."
+            )),
+        },
+        _ => Err(format!(
+            "Entry point parameter '{name}' has an unsupported type."
+        )),
+    }
+}
+
+fn parameter_name(param: BasicValueEnum) -> String {
+    let name = match &param {
+        BasicValueEnum::ArrayValue(value) => value.get_name(),
+        BasicValueEnum::IntValue(value) => value.get_name(),
+        BasicValueEnum::FloatValue(value) => value.get_name(),
+        BasicValueEnum::PointerValue(value) => value.get_name(),
+        BasicValueEnum::StructValue(value) => value.get_name(),
+        BasicValueEnum::VectorValue(value) => value.get_name(),
+    };
+    name.to_string_lossy().into_owned()
+}
+
+/// Determines the names and kinds of the parameters of the entry point.
+///
+/// # Errors
+///
+/// Will return `Err` if a parameter has an unsupported type, or if it is an array whose element type cannot be
+/// determined from the program.
+pub(crate) fn parameters<'ctx>(
+    context: &Context,
+    flow: &ValueFlow<'ctx>,
+    entry_point: FunctionValue<'ctx>,
+) -> Result<Vec<(String, ParameterKind)>, String> {
+    entry_point
+        .get_params()
+        .into_iter()
+        .map(|param| {
+            let name = parameter_name(param);
+            let values = match param {
+                BasicValueEnum::PointerValue(pointer) => vec![pointer],
+                _ => Vec::new(),
+            };
+            let kind = parameter_kind(context, flow, &name, param.get_type(), values)?;
+            Ok((name, kind))
+        })
+        .collect()
+}
+
+/// Checks the given arguments against the parameters of an entry point and returns them in parameter order
+/// along with the parameter kinds, parsing named arguments according to the parameter kinds.
+pub(crate) fn resolve_arguments(
+    params: &[(String, ParameterKind)],
+    args: &Arguments,
+) -> Result<Vec<(ParameterKind, Argument)>, String> {
+    let args = match args {
+        Arguments::Positional(args) => {
+            if args.len() != params.len() {
+                return Err(format!(
+                    "Entry point expects {} arguments, but {} were given.",
+                    params.len(),
+                    args.len()
+                ));
+            }
+            params
+                .iter()
+                .zip(args)
+                .map(|((name, kind), arg)| check_argument(name, kind, arg.clone()))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+        Arguments::Named(args) => {
+            for (index, (name, _)) in args.iter().enumerate() {
+                if !params.iter().any(|(param, _)| param == name) {
+                    return Err(format!("Entry point has no parameter named '{name}'."));
+                }
+                if args[..index].iter().any(|(other, _)| other == name) {
+                    return Err(format!(
+                        "Argument for parameter '{name}' is given more than once."
+                    ));
+                }
+            }
+            params
+                .iter()
+                .map(|(name, kind)| {
+                    let (_, value) = args
+                        .iter()
+                        .find(|(arg, _)| arg == name)
+                        .ok_or_else(|| format!("Missing argument for parameter '{name}'."))?;
+                    let arg = parse_value(kind, value).ok_or_else(|| {
+                        format!("Invalid value '{value}' for parameter '{name}'.")
+                    })?;
+                    check_argument(name, kind, arg)
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+    Ok(params
+        .iter()
+        .map(|(_, kind)| kind.clone())
+        .zip(args)
+        .collect())
+}
+
+/// Parses a value given as text according to the parameter kind, or returns `None` if it is not a valid value
+/// of that kind.
+fn parse_value(kind: &ParameterKind, value: &str) -> Option<Argument> {
+    let value = value.trim();
+    match kind {
+        ParameterKind::Int => value.parse().ok().map(Argument::Int),
+        ParameterKind::Double => value.parse().ok().map(Argument::Double),
+        ParameterKind::Bool => value.parse().ok().map(Argument::Bool),
+        ParameterKind::Array(element) => {
+            let items = value.strip_prefix('[')?.strip_suffix(']')?;
+            split_items(items)
+                .ok()?
+                .into_iter()
+                .map(|item| parse_value(element, item))
+                .collect::<Option<_>>()
+                .map(Argument::Array)
+        }
+    }
+}
+
+/// Checks that the argument matches the parameter kind, converting integers to doubles where needed, including
+/// the elements of arrays.
+fn check_argument(name: &str, kind: &ParameterKind, arg: Argument) -> Result<Argument, String> {
+    convert_argument(kind, arg)
+        .ok_or_else(|| format!("Argument for parameter '{name}' must be of type {kind}."))
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn convert_argument(kind: &ParameterKind, arg: Argument) -> Option<Argument> {
+    match (kind, arg) {
+        (ParameterKind::Int, arg @ Argument::Int(_))
+        | (ParameterKind::Double, arg @ Argument::Double(_))
+        | (ParameterKind::Bool, arg @ Argument::Bool(_)) => Some(arg),
+        (ParameterKind::Double, Argument::Int(val)) => Some(Argument::Double(val as f64)),
+        (ParameterKind::Array(element), Argument::Array(items)) => items
+            .into_iter()
+            .map(|item| convert_argument(element, item))
+            .collect::<Option<_>>()
+            .map(Argument::Array),
+        _ => None,
+    }
+}
+
+/// Runtime arrays created for the arguments of an entry point, which are released when dropped.
+#[derive(Default)]
+pub(crate) struct ArgumentArrays(Vec<*const QirArray>);
+
+impl Drop for ArgumentArrays {
+    fn drop(&mut self) {
+        for array in self.0.drain(..) {
+            unsafe { __quantum__rt__array_update_reference_count(array, -1) };
+        }
+    }
+}
+
+impl ArgumentArrays {
+    /// Creates a runtime array with the given items, which have been checked against the element kind.
+    #[allow(clippy::cast_ptr_alignment)]
+    fn create(&mut self, element: &ParameterKind, items: &[Argument]) -> *const QirArray {
+        let elem_size = match element {
+            ParameterKind::Bool => 1,
+            _ => 8,
+        };
+        let array = __quantum__rt__array_create_1d(elem_size, items.len() as u64);
+        self.0.push(array);
+        for (index, item) in items.iter().enumerate() {
+            unsafe {
+                let elem = __quantum__rt__array_get_element_ptr_1d(array, index as u64);
+                match (element, item) {
+                    (_, Argument::Int(val)) => elem.cast::<i64>().write_unaligned(*val),
+                    (_, Argument::Double(val)) => elem.cast::<f64>().write_unaligned(*val),
+                    (_, Argument::Bool(val)) => *elem = i8::from(*val),
+                    (ParameterKind::Array(inner), Argument::Array(items)) => {
+                        let inner = self.create(inner, items);
+                        elem.cast::<*const QirArray>().write_unaligned(inner);
+                    }
+                    (_, Argument::Array(_)) => {
+                        unreachable!(
+                            "Array items should have been checked against the element kind."
+                        )
+                    }
+                }
+            }
+        }
+        array
+    }
+}
+
+/// Storage for the arguments of an entry point, from which the entry point wrapper loads them. Every argument
+/// occupies a 64-bit slot, where doubles are stored by their bit pattern and arrays by their address.
+pub(crate) struct ArgumentSlots(Box<[Cell<u64>]>);
+
+impl ArgumentSlots {
+    /// Stores the arguments in the slots, creating runtime arrays for the array arguments.
+    #[allow(clippy::cast_sign_loss)]
+    pub(crate) fn store(&self, args: &[(ParameterKind, Argument)], arrays: &mut ArgumentArrays) {
+        for (slot, (kind, arg)) in self.0.iter().zip(args) {
+            slot.set(match (kind, arg) {
+                (_, Argument::Int(val)) => *val as u64,
+                (_, Argument::Double(val)) => val.to_bits(),
+                (_, Argument::Bool(val)) => u64::from(*val),
+                (ParameterKind::Array(element), Argument::Array(items)) => {
+                    arrays.create(element, items) as usize as u64
+                }
+                (_, Argument::Array(_)) => {
+                    unreachable!("Arguments should have been checked against the parameter kinds.")
+                }
+            });
+        }
+    }
+}
+
+/// Adds a function without parameters to the module that calls the entry point with the arguments stored in
+/// the returned slots and returns its return value, which allows the execution engine to run entry points with
+/// parameters. Since the arguments are loaded when the function runs, the entry point can be called with
+/// different arguments without compiling it again.
+pub(crate) fn build_entry_point_wrapper<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    entry_point: FunctionValue<'ctx>,
+) -> (FunctionValue<'ctx>, ArgumentSlots) {
+    let param_types = entry_point.get_type().get_param_types();
+    let slots = ArgumentSlots(vec![Cell::new(0); param_types.len()].into_boxed_slice());
+
+    let fn_type = match entry_point.get_type().get_return_type() {
+        Some(return_type) => return_type.fn_type(&[], false),
+        None => context.void_type().fn_type(&[], false),
+    };
+    let wrapper = module.add_function(
+        &format!(
+            "__qir_runner__{}__wrapper",
+            entry_point.get_name().to_string_lossy()
+        ),
+        fn_type,
+        None,
+    );
+    let builder = context.create_builder();
+    builder.position_at_end(context.append_basic_block(wrapper, "entry"));
+
+    let i64_type = context.i64_type();
+    let mut values: Vec<BasicMetadataValueEnum> = Vec::new();
+    for (slot, param_type) in slots.0.iter().zip(param_types) {
+        let slot = i64_type
+            .const_int(slot.as_ptr() as usize as u64, false)
+            .const_to_pointer(i64_type.ptr_type(AddressSpace::default()));
+        let raw = builder.build_load(slot, "").into_int_value();
+        let value: BasicMetadataValueEnum = match param_type {
+            BasicTypeEnum::IntType(int_type) if int_type.get_bit_width() == 1 => {
+                builder.build_int_truncate(raw, int_type, "").into()
+            }
+            BasicTypeEnum::FloatType(float_type) => {
+                builder.build_bitcast(raw, float_type, "").into()
+            }
+            BasicTypeEnum::PointerType(pointer_type) => {
+                builder.build_int_to_ptr(raw, pointer_type, "").into()
+            }
+            _ => raw.into(),
+        };
+        values.push(value);
+    }
+
+    let call = builder.build_call(entry_point, &values, "");
+    match call.try_as_basic_value().left() {
+        Some(value) => builder.build_return(Some(&value)),
+        None => builder.build_return(None),
+    };
+    (wrapper, slots)
+}
@@ -0,0 +1,126 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! The functions exported by the backend do not unwind when they fail, but capture the failure and return, so that
+//! the shot must be stopped by the program itself. Every call of the program is followed by a check that returns
+//! from the calling function if a failure has been captured, which stops the shot at its first failure.
+
+use inkwell::{
+    attributes::{Attribute, AttributeLoc},
+    basic_block::BasicBlock,
+    builder::Builder,
+    context::Context,
+    module::Module,
+    types::BasicTypeEnum,
+    values::{BasicValueEnum, FunctionValue, InstructionOpcode, InstructionValue},
+};
+use qir_backend::failures::has_failed;
+
+use crate::{
+    module_functions,
+    value_flow::{instructions, operand},
+};
+
+/// The function called by the checks, which is bound to `failed`.
+pub(crate) const CHECK_FUNCTION: &str = "__qir_runner__failed";
+
+/// Returns whether a failure has been captured on the current thread.
+pub(crate) extern "C" fn failed() -> bool {
+    has_failed()
+}
+
+/// Adds a check after every call in the functions of the module, which returns a zero value from the function
+/// if a failure has been captured.
+pub(crate) fn add_failure_checks<'ctx>(context: &'ctx Context, module: &Module<'ctx>) {
+    let check = module.add_function(
+        CHECK_FUNCTION,
+        context.bool_type().fn_type(&[], false),
+        None,
+    );
+    let builder = context.create_builder();
+    let noreturn = Attribute::get_named_enum_kind_id("noreturn");
+    for function in module_functions(module) {
+        // Functions that fail return after all, e.g., `__quantum__rt__fail`.
+        function.remove_enum_attribute(AttributeLoc::Function, noreturn);
+        let calls: Vec<_> = instructions(function)
+            .into_iter()
+            .filter(|inst| is_checked_call(*inst))
+            .collect();
+        if calls.is_empty() {
+            continue;
+        }
+
+        let exit = context.append_basic_block(function, "failed");
+        builder.position_at_end(exit);
+        match function.get_type().get_return_type() {
+            Some(return_type) => builder.build_return(Some(&zero(return_type))),
+            None => builder.build_return(None),
+        };
+        for call in calls {
+            check_after(context, &builder, call, check, exit);
+        }
+    }
+}
+
+/// Returns whether the instruction calls a function that may fail, which excludes the LLVM intrinsics and the
+/// check itself.
+fn is_checked_call(inst: InstructionValue) -> bool {
+    if inst.get_opcode() != InstructionOpcode::Call {
+        return false;
+    }
+    let Some(BasicValueEnum::PointerValue(callee)) = operand(inst, inst.get_num_operands() - 1) else {
+        return true;
+    };
+    let name = callee.get_name().to_str().unwrap_or_default();
+    !name.starts_with("llvm.") && name != CHECK_FUNCTION
+}
+
+/// Moves the instructions of the block of the call up to and including the call into a new block, which
+/// continues with the rest of the block unless a failure has been captured.
+fn check_after<'ctx>(
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+    call: InstructionValue<'ctx>,
+    check: FunctionValue<'ctx>,
+    exit: BasicBlock<'ctx>,
+) {
+    let block = call.get_parent().expect("Call should be in a block.");
+    let head = context.prepend_basic_block(block, "");
+
+    // The branches to the block are redirected to the new block. Without its terminator, the block has no
+    // successors, so their phis keep referring to the block, which still branches to them.
+    let terminator = block
+        .get_terminator()
+        .expect("Block should have a terminator.");
+    terminator.remove_from_basic_block();
+    block.replace_all_uses_with(&head);
+    builder.position_at_end(block);
+    builder.insert_instruction(&terminator, None);
+
+    builder.position_at_end(head);
+    while let Some(inst) = block.get_first_instruction() {
+        inst.remove_from_basic_block();
+        builder.insert_instruction(&inst, None);
+        if inst == call {
+            break;
+        }
+    }
+    let failed = builder
+        .build_call(check, &[], "")
+        .try_as_basic_value()
+        .left()
+        .expect("Check should return a value.")
+        .into_int_value();
+    builder.build_conditional_branch(failed, exit, block);
+}
+
+fn zero(ty: BasicTypeEnum) -> BasicValueEnum {
+    match ty {
+        BasicTypeEnum::ArrayType(ty) => ty.const_zero().into(),
+        BasicTypeEnum::FloatType(ty) => ty.const_zero().into(),
+        BasicTypeEnum::IntType(ty) => ty.const_zero().into(),
+        BasicTypeEnum::PointerType(ty) => ty.const_zero().into(),
+        BasicTypeEnum::StructType(ty) => ty.const_zero().into(),
+        BasicTypeEnum::VectorType(ty) => ty.const_zero().into(),
+    }
+}
@@ -0,0 +1,432 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::{fmt::Write, str::FromStr};
+
+use qir_backend::output_recording::{LegacyMarker, OutputRecord, OutputValue};
+use serde_json::{json, Map, Value};
+
+use crate::{results::outcomes, ShotResult};
+
+/// The formats in which shot results can be rendered.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// The tab separated START/METADATA/OUTPUT/END text protocol.
+    #[default]
+    Text,
+    /// A JSON array with one object per shot, where array and tuple outputs contain their items.
+    Json,
+    /// A CSV table with one row per shot and one column per tagged output, along with a column with the failure
+    /// message of each shot if a shot failed. The names of the columns that are not named after a tag start with
+    /// `#`, and tags that start with `#` are written with another `#` in front.
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!("Unsupported output format '{s}'.")),
+        }
+    }
+}
+
+/// Renders the given shot results in the given format.
+///
+/// # Errors
+///
+/// Will return `Err` if the results are rendered as CSV and a shot records the same tag more than once.
+pub fn render_results(results: &[ShotResult], format: OutputFormat) -> Result<String, String> {
+    match format {
+        OutputFormat::Text => Ok(results.iter().map(ToString::to_string).collect()),
+        OutputFormat::Json => {
+            let shots: Vec<Value> = results.iter().map(ShotResult::to_json).collect();
+            let mut json =
+                serde_json::to_string_pretty(&shots).expect("Shot results should serialize");
+            json.push('\n');
+            Ok(json)
+        }
+        OutputFormat::Csv => render_csv(results),
+    }
+}
+
+impl ShotResult {
+    /// Converts the shot into a JSON object with its metadata, its messages, its outputs, and its failure
+    /// message if it failed. Arrays and tuples are nested based on their element counts or, for legacy output,
+    /// their start and end markers.
+    #[must_use]
+    pub fn to_json(&self) -> Value {
+        let metadata: Map<String, Value> = self
+            .metadata
+            .iter()
+            .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+            .collect();
+        let mut json = json!({
+            "metadata": metadata,
+            "outputs": nest_outputs(&self.output),
+            "messages": self.messages().collect::<Vec<_>>(),
+        });
+        if let Some(failure) = &self.failure {
+            json["failure"] = Value::String(failure.clone());
+        }
+        json
+    }
+}
+
+/// An array or tuple whose items are still being collected.
+struct Container {
+    value: Map<String, Value>,
+    items: Vec<Value>,
+    /// The number of items still expected, or `None` if the container is closed by a legacy end marker.
+    remaining: Option<i64>,
+}
+
+impl Container {
+    fn marker_based(ty: &str) -> Self {
+        let mut value = Map::new();
+        value.insert("type".to_owned(), Value::String(ty.to_owned()));
+        Container {
+            value,
+            items: Vec::new(),
+            remaining: None,
+        }
+    }
+
+    fn close(mut self) -> Value {
+        self.value
+            .insert("items".to_owned(), Value::Array(self.items));
+        Value::Object(self.value)
+    }
+}
+
+fn nest_outputs(records: &[OutputRecord]) -> Vec<Value> {
+    let mut root = Vec::new();
+    let mut stack: Vec<Container> = Vec::new();
+
+    for record in records {
+        match record {
+            OutputRecord::Value { value, tag } => push_value(&mut root, &mut stack, value, tag),
+            OutputRecord::LegacyValue(value) => push_value(&mut root, &mut stack, value, &None),
+            OutputRecord::LegacyMarker(LegacyMarker::ArrayStart) => {
+                stack.push(Container::marker_based("ARRAY"));
+            }
+            OutputRecord::LegacyMarker(LegacyMarker::TupleStart) => {
+                stack.push(Container::marker_based("TUPLE"));
+            }
+            OutputRecord::LegacyMarker(LegacyMarker::ArrayEnd | LegacyMarker::TupleEnd) => {
+                // Close any count based containers left open inside, then the marker based one.
+                while let Some(container) = stack.pop() {
+                    let is_marker_based = container.remaining.is_none();
+                    let closed = container.close();
+                    append_item(&mut root, &mut stack, closed);
+                    if is_marker_based {
+                        break;
+                    }
+                }
+            }
+            OutputRecord::Info(_) | OutputRecord::Message(_) => {}
+        }
+    }
+
+    // Containers that did not receive all of their items are closed with the items recorded so far.
+    while let Some(container) = stack.pop() {
+        let closed = container.close();
+        match stack.last_mut() {
+            Some(parent) => parent.items.push(closed),
+            None => root.push(closed),
+        }
+    }
+    root
+}
+
+fn push_value(
+    root: &mut Vec<Value>,
+    stack: &mut Vec<Container>,
+    value: &OutputValue,
+    tag: &Option<String>,
+) {
+    let mut object = Map::new();
+    object.insert(
+        "type".to_owned(),
+        Value::String(value.type_name().to_owned()),
+    );
+    if let Some(tag) = tag {
+        object.insert("tag".to_owned(), Value::String(tag.clone()));
+    }
+    match value {
+        OutputValue::Array(count) | OutputValue::Tuple(count) => {
+            if *count > 0 {
+                stack.push(Container {
+                    value: object,
+                    items: Vec::new(),
+                    remaining: Some(*count),
+                });
+            } else {
+                object.insert("items".to_owned(), Value::Array(Vec::new()));
+                append_item(root, stack, Value::Object(object));
+            }
+        }
+        _ => {
+            object.insert("value".to_owned(), json_value(value));
+            append_item(root, stack, Value::Object(object));
+        }
+    }
+}
+
+/// Appends the item to the innermost open container, closing every container that becomes full.
+fn append_item(root: &mut Vec<Value>, stack: &mut Vec<Container>, item: Value) {
+    let mut item = item;
+    loop {
+        let Some(parent) = stack.last_mut() else {
+            root.push(item);
+            return;
+        };
+        parent.items.push(item);
+        match parent.remaining.as_mut() {
+            Some(remaining) => {
+                *remaining -= 1;
+                if *remaining > 0 {
+                    return;
+                }
+            }
+            None => return,
+        }
+        item = stack.pop().expect("Parent container should exist").close();
+    }
+}
+
+fn json_value(value: &OutputValue) -> Value {
+    match value {
+        OutputValue::Int(val) | OutputValue::Array(val) | OutputValue::Tuple(val) => json!(val),
+        OutputValue::Double(val) => json!(val),
+        OutputValue::Bool(val) => json!(val),
+        OutputValue::Result(val) => json!(i64::from(*val)),
+    }
+}
+
+/// Renders one row per shot, with one column per distinct output tag in order of first appearance.
+/// Untagged values use `#` followed by their position among the values of the shot as column name.
+fn render_csv(results: &[ShotResult]) -> Result<String, String> {
+    // The failure column is only rendered if a shot failed.
+    if results.iter().any(|shot| shot.failure.is_some()) {
+        render_table(
+            &["shot", "failure"],
+            results.iter().enumerate().map(|(index, shot)| {
+                let failure = shot.failure.clone().unwrap_or_default();
+                (vec![index.to_string(), failure], shot)
+            }),
+        )
+    } else {
+        render_table(
+            &["shot"],
+            results
+                .iter()
+                .enumerate()
+                .map(|(index, shot)| (vec![index.to_string()], shot)),
+        )
+    }
+}
+
+/// A column name along with the rendered value for that column.
+type NamedValue = (String, String);
+
+/// Renders a table with the given leading columns followed by one column per output. The names of the leading
+/// columns and of the columns of untagged values start with `#`, which tags escape with another `#`, so that
+/// they cannot be confused with the columns of tags.
+fn render_table<'a>(
+    leading_columns: &[&str],
+    rows: impl Iterator<Item = (Vec<String>, &'a ShotResult)>,
+) -> Result<String, String> {
+    let rows = rows
+        .map(|(leading, shot)| {
+            let mut values: Vec<NamedValue> = Vec::new();
+            for (index, (value, tag)) in shot.values().enumerate() {
+                let column = match tag {
+                    Some(tag) if tag.starts_with('#') => format!("#{tag}"),
+                    Some(tag) => tag.to_owned(),
+                    None => format!("#{index}"),
+                };
+                if values.iter().any(|(name, _)| *name == column) {
+                    return Err(format!(
+                        "Cannot render the tag '{}' as a column, since a shot records it more than once.",
+                        tag.unwrap_or_default()
+                    ));
+                }
+                values.push((column, value.to_string()));
+            }
+            Ok((leading, values))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut columns: Vec<&str> = Vec::new();
+    for (column, _) in rows.iter().flat_map(|(_, values)| values) {
+        if !columns.contains(&column.as_str()) {
+            columns.push(column);
+        }
+    }
+
+    let mut csv = String::new();
+    let header: Vec<String> = leading_columns
+        .iter()
+        .map(|column| csv_field(&format!("#{column}")))
+        .chain(columns.iter().map(|column| csv_field(column)))
+        .collect();
+    let _ = writeln!(csv, "{}", header.join(","));
+    for (leading, values) in &rows {
+        let fields: Vec<String> = leading
+            .iter()
+            .map(|field| csv_field(field))
+            .chain(columns.iter().map(|column| {
+                values
+                    .iter()
+                    .find(|(name, _)| name == column)
+                    .map_or_else(String::new, |(_, value)| csv_field(value))
+            }))
+            .collect();
+        let _ = writeln!(csv, "{}", fields.join(","));
+    }
+    Ok(csv)
+}
+
+/// Renders the distinct outputs of the given shots along with their counts and probabilities, ordered
+/// by decreasing count. Failed shots are counted apart from completed shots with the same output, and their
+/// failure message is rendered with their output.
+///
+/// # Errors
+///
+/// Will return `Err` if the histogram is rendered as CSV and a shot records the same tag more than once.
+pub fn render_histogram(results: &[ShotResult], format: OutputFormat) -> Result<String, String> {
+    let outcomes = outcomes(results);
+    #[allow(clippy::cast_precision_loss)]
+    let probability = |count: usize| count as f64 / results.len() as f64;
+    // Probabilities are printed like recorded doubles.
+    let probability_text = |count: usize| OutputValue::Double(probability(count)).to_string();
+    match format {
+        OutputFormat::Text => {
+            let mut text = String::new();
+            for (shot, count) in outcomes {
+                let values: Vec<&OutputValue> = shot.values().map(|(value, _)| value).collect();
+                let _ = write!(
+                    text,
+                    "{count}\t{}\t{}",
+                    probability_text(count),
+                    outcome_text(&values)
+                );
+                if let Some(failure) = &shot.failure {
+                    let _ = write!(text, "\tFAILURE\t{}", failure.escape_default());
+                }
+                let _ = writeln!(text);
+            }
+            Ok(text)
+        }
+        OutputFormat::Json => {
+            let entries: Vec<Value> = outcomes
+                .into_iter()
+                .map(|(shot, count)| {
+                    let mut entry = json!({
+                        "count": count,
+                        "probability": probability(count),
+                        "outputs": nest_outputs(&shot.output),
+                    });
+                    if let Some(failure) = &shot.failure {
+                        entry["failure"] = Value::String(failure.clone());
+                    }
+                    entry
+                })
+                .collect();
+            let mut json =
+                serde_json::to_string_pretty(&entries).expect("Histogram should serialize");
+            json.push('\n');
+            Ok(json)
+        }
+        // The failure column is only rendered if a shot failed.
+        OutputFormat::Csv if outcomes.iter().any(|(shot, _)| shot.failure.is_some()) => {
+            render_table(
+                &["count", "probability", "failure"],
+                outcomes.into_iter().map(|(shot, count)| {
+                    let failure = shot.failure.clone().unwrap_or_default();
+                    (
+                        vec![count.to_string(), probability_text(count), failure],
+                        shot,
+                    )
+                }),
+            )
+        }
+        OutputFormat::Csv => render_table(
+            &["count", "probability"],
+            outcomes
+                .into_iter()
+                .map(|(shot, count)| (vec![count.to_string(), probability_text(count)], shot)),
+        ),
+    }
+}
+
+/// Renders the resources counted for the given shots as a JSON array with one report per shot, which also
+/// contains the failure message of a failed shot. Shots that were not run with resource estimation are rendered
+/// as `null`.
+#[must_use]
+pub fn render_estimates(results: &[ShotResult]) -> String {
+    let reports: Vec<Value> = results
+        .iter()
+        .map(|shot| {
+            let mut report = json!(shot.resources);
+            if let (Some(failure), Value::Object(report)) = (&shot.failure, &mut report) {
+                report.insert("failure".to_owned(), Value::String(failure.clone()));
+            }
+            report
+        })
+        .collect();
+    let mut json =
+        serde_json::to_string_pretty(&reports).expect("Resource counts should serialize");
+    json.push('\n');
+    json
+}
+
+/// Renders the values on a single line, with arrays in brackets and tuples in parentheses.
+fn outcome_text(values: &[&OutputValue]) -> String {
+    fn write_item(values: &[&OutputValue], index: &mut usize, text: &mut String) {
+        let value = values[*index];
+        *index += 1;
+        let (open, close, count) = match value {
+            OutputValue::Array(count) => ('[', ']', *count),
+            OutputValue::Tuple(count) => ('(', ')', *count),
+            _ => {
+                let _ = write!(text, "{value}");
+                return;
+            }
+        };
+        text.push(open);
+        let mut item = 0;
+        while item < count && *index < values.len() {
+            if item > 0 {
+                text.push_str(", ");
+            }
+            write_item(values, index, text);
+            item += 1;
+        }
+        text.push(close);
+    }
+
+    let mut text = String::new();
+    let mut index = 0;
+    while index < values.len() {
+        if index > 0 {
+            text.push_str(", ");
+        }
+        write_item(values, &mut index, &mut text);
+    }
+    text
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
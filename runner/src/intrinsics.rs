@@ -0,0 +1,215 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::{
+    ffi::{c_char, c_void, CStr},
+    path::Path,
+    slice,
+    sync::Arc,
+};
+
+use libloading::Library;
+
+/// The name of the function that plugins export to describe the intrinsics they provide. It has the
+/// C signature `const IntrinsicEntry* qir_runner_intrinsics(size_t* count)` and returns a table of
+/// `count` entries that must stay valid while the plugin is loaded.
+pub const PLUGIN_TABLE_SYMBOL: &str = "qir_runner_intrinsics";
+
+/// The signature of the function that plugins export as `PLUGIN_TABLE_SYMBOL`.
+type PluginTable = unsafe extern "C" fn(*mut usize) -> *const IntrinsicEntry;
+
+/// An entry of the intrinsic table exported by a plugin.
+#[repr(C)]
+pub struct IntrinsicEntry {
+    /// The null-terminated name of the intrinsic as declared in QIR, e.g., `__quantum__qis__foo__body`.
+    pub name: *const c_char,
+    /// The number of parameters of the intrinsic.
+    pub param_count: u32,
+    /// The address of the `extern "C"` function that implements the intrinsic.
+    pub function: *const c_void,
+}
+
+/// A function provided for an intrinsic that the runner does not implement itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Intrinsic {
+    pub(crate) name: String,
+    pub(crate) param_count: u32,
+    pub(crate) address: usize,
+}
+
+/// Custom intrinsics that are bound along with the built-in functions when a program is compiled. They can be
+/// registered as `extern "C"` functions or loaded from plugins, which are shared libraries that export an
+/// intrinsic table named by `PLUGIN_TABLE_SYMBOL`.
+#[derive(Debug, Clone, Default)]
+pub struct Intrinsics {
+    functions: Vec<Intrinsic>,
+    // The plugins stay loaded as long as functions from them can be bound.
+    libraries: Vec<Arc<Library>>,
+}
+
+impl Intrinsics {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the function at the given address for the intrinsic with the given name and number of
+    /// parameters, replacing any function previously registered for it.
+    ///
+    /// # Safety
+    ///
+    /// `function` must point to an `extern "C"` function whose signature matches the declaration of the
+    /// intrinsic in the programs that are run, and it must stay valid while those programs run.
+    pub unsafe fn register(&mut self, name: &str, param_count: u32, function: *const c_void) {
+        self.functions.retain(|intrinsic| intrinsic.name != name);
+        self.functions.push(Intrinsic {
+            name: name.to_owned(),
+            param_count,
+            address: function as usize,
+        });
+    }
+
+    /// Loads the plugin at the given path and registers the intrinsics from its intrinsic table.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if
+    /// - `path` cannot be loaded as a shared library.
+    /// - The library does not export an intrinsic table.
+    /// - The table contains an entry without a name or function, or a name that is not valid UTF-8.
+    ///
+    /// # Safety
+    ///
+    /// Loading the library runs its initialization code, and the functions in its table must satisfy the
+    /// requirements of `register`.
+    pub unsafe fn load_plugin(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let library = Library::new(path)
+            .map_err(|e| format!("Failed to load plugin '{}': {e}", path.display()))?;
+        let table = *library
+            .get::<PluginTable>(PLUGIN_TABLE_SYMBOL.as_bytes())
+            .map_err(|e| format!("Failed to load plugin '{}': {e}", path.display()))?;
+        self.register_table(table, path)?;
+        self.libraries.push(Arc::new(library));
+        Ok(())
+    }
+
+    /// Registers the intrinsics from the table returned by the given table function of the plugin at the
+    /// given path. No intrinsic is registered if an entry of the table is invalid.
+    unsafe fn register_table(&mut self, table: PluginTable, path: &Path) -> Result<(), String> {
+        let mut count = 0;
+        let entries = table(&mut count);
+        let entries = if entries.is_null() || count == 0 {
+            &[]
+        } else {
+            slice::from_raw_parts(entries, count)
+        };
+        let mut intrinsics = Vec::with_capacity(entries.len());
+        for entry in entries {
+            if entry.name.is_null() {
+                return Err(format!(
+                    "Plugin '{}' contains an intrinsic without a name.",
+                    path.display()
+                ));
+            }
+            let name = CStr::from_ptr(entry.name).to_str().map_err(|_| {
+                format!(
+                    "Plugin '{}' contains an intrinsic name that is not valid UTF-8.",
+                    path.display()
+                )
+            })?;
+            if entry.function.is_null() {
+                return Err(format!(
+                    "Plugin '{}' contains no function for intrinsic '{name}'.",
+                    path.display()
+                ));
+            }
+            intrinsics.push((name, entry));
+        }
+        for (name, entry) in intrinsics {
+            self.register(name, entry.param_count, entry.function);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn functions(&self) -> &[Intrinsic] {
+        &self.functions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Intrinsic, IntrinsicEntry, Intrinsics};
+    use std::{ffi::c_void, path::Path, ptr::null};
+
+    extern "C" fn intrinsic() {}
+
+    /// Returns a table with the given entries, which stays valid for the rest of the test run.
+    unsafe fn table(entries: Vec<IntrinsicEntry>, count: *mut usize) -> *const IntrinsicEntry {
+        let entries = Vec::leak(entries);
+        *count = entries.len();
+        entries.as_ptr()
+    }
+
+    unsafe extern "C" fn valid_table(count: *mut usize) -> *const IntrinsicEntry {
+        table(
+            vec![IntrinsicEntry {
+                name: b"__quantum__qis__foo__body\0".as_ptr().cast(),
+                param_count: 2,
+                function: intrinsic as *const c_void,
+            }],
+            count,
+        )
+    }
+
+    unsafe extern "C" fn unnamed_table(count: *mut usize) -> *const IntrinsicEntry {
+        table(
+            vec![
+                IntrinsicEntry {
+                    name: b"__quantum__qis__foo__body\0".as_ptr().cast(),
+                    param_count: 2,
+                    function: intrinsic as *const c_void,
+                },
+                IntrinsicEntry {
+                    name: null(),
+                    param_count: 0,
+                    function: intrinsic as *const c_void,
+                },
+            ],
+            count,
+        )
+    }
+
+    unsafe extern "C" fn empty_table(count: *mut usize) -> *const IntrinsicEntry {
+        *count = 0;
+        null()
+    }
+
+    #[test]
+    fn test_register_table() {
+        let mut intrinsics = Intrinsics::new();
+        unsafe { intrinsics.register_table(valid_table, Path::new("plugin.so")) }
+            .expect("Valid tables should be registered.");
+        assert_eq!(
+            intrinsics.functions(),
+            [Intrinsic {
+                name: "__quantum__qis__foo__body".to_owned(),
+                param_count: 2,
+                address: intrinsic as *const c_void as usize,
+            }]
+        );
+        unsafe { intrinsics.register_table(empty_table, Path::new("plugin.so")) }
+            .expect("Empty tables should be registered.");
+        assert_eq!(intrinsics.functions().len(), 1);
+    }
+
+    #[test]
+    fn test_register_table_rejects_entries_without_name() {
+        let mut intrinsics = Intrinsics::new();
+        assert_eq!(
+            unsafe { intrinsics.register_table(unnamed_table, Path::new("plugin.so")) },
+            Err("Plugin 'plugin.so' contains an intrinsic without a name.".to_owned())
+        );
+        assert!(intrinsics.functions().is_empty());
+    }
+}
@@ -0,0 +1,540 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::{ffi::OsStr, fs, ops::Range, path::Path, ptr::null_mut, str::FromStr};
+
+use inkwell::{
+    attributes::AttributeLoc,
+    context::Context,
+    execution_engine::ExecutionEngine,
+    memory_buffer::MemoryBuffer,
+    module::Module,
+    targets::{InitializationConfig, Target, TargetMachine},
+    values::{FunctionValue, GenericValue},
+    OptimizationLevel,
+};
+use qir_backend::{
+    __quantum__rt__initialize,
+    failures::{catch_failure, has_failed, set_capturing, take_failure},
+    noise::NoiseModel,
+    output_recording::{set_output_sink, OutputBuffer, OutputSink},
+    resource_counts, set_dump_threshold, set_noise_model, set_rng_seed, set_simulator_kind,
+    set_terminal_sampling, set_tracing, trace_qasm, truncation_error, SimulatorKind,
+};
+
+use crate::{
+    args::{self, ArgumentArrays, ArgumentSlots, Arguments, ParameterKind},
+    bind_functions, choose_entry_point, failure_checks,
+    intrinsics::Intrinsics,
+    module_functions,
+    qasm::Circuit,
+    return_value::{self, ReturnKind},
+    run_basic_passes_on, sampling,
+    value_flow::ValueFlow,
+    ShotResult,
+};
+
+/// The serialized form of a program.
+#[derive(Clone, Copy)]
+pub(crate) enum ProgramSource<'a> {
+    Bitcode(&'a [u8]),
+    Ir(&'a [u8]),
+    Qasm(&'a [u8]),
+}
+
+/// Compiles QIR and `OpenQASM` programs for repeated execution. The runner owns the LLVM context of the programs it
+/// compiles, so it must outlive them.
+///
+/// ```ignore
+/// let runner = Runner::new();
+/// let program = runner.load_file("program.bc")?;
+/// for angle in [0.1, 0.2, 0.3] {
+///     let args = Arguments::Positional(vec![Argument::Double(angle)]);
+///     let results = program.execute(None, &args, 100)?;
+/// }
+/// ```
+pub struct Runner {
+    context: Context,
+    intrinsics: Intrinsics,
+}
+
+impl Default for Runner {
+    fn default() -> Self {
+        Runner {
+            context: Context::create(),
+            intrinsics: Intrinsics::default(),
+        }
+    }
+}
+
+impl Runner {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a runner that binds the given custom intrinsics in the programs it compiles, in addition
+    /// to the built-in functions.
+    #[must_use]
+    pub fn with_intrinsics(intrinsics: Intrinsics) -> Self {
+        Runner {
+            context: Context::create(),
+            intrinsics,
+        }
+    }
+
+    /// Loads and compiles the QIR or `OpenQASM` file at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if
+    /// - `path` does not exist or the user does not have permission to read it.
+    /// - `path` does not contain a valid module or `OpenQASM` program
+    /// - `path` does not have a .ll, .bc or .qasm extension
+    /// - The module cannot be compiled or declares functions that are neither built in nor registered.
+    pub fn load_file(&self, path: impl AsRef<Path>) -> Result<CompiledProgram, String> {
+        let path = path.as_ref();
+        if path.extension().and_then(OsStr::to_str) == Some("qasm") {
+            let bytes = fs::read(path).map_err(|e| e.to_string())?;
+            return self.compile(ProgramSource::Qasm(&bytes));
+        }
+        CompiledProgram::new(
+            &self.context,
+            load_file(path, &self.context)?,
+            &self.intrinsics,
+        )
+    }
+
+    /// Loads and compiles the given QIR bitcode.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if
+    /// - `bytes` does not contain a valid bitcode module
+    /// - The module cannot be compiled or declares functions that are neither built in nor registered.
+    pub fn load_bitcode(&self, bytes: &[u8]) -> Result<CompiledProgram, String> {
+        self.compile(ProgramSource::Bitcode(bytes))
+    }
+
+    /// Parses the given `OpenQASM` 2.0 or 3.0 program. The program is run without an entry point or arguments,
+    /// and the classical registers are recorded as output at the end of every shot.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `source` is not a valid `OpenQASM` program or uses unsupported features.
+    pub fn load_qasm(&self, source: &str) -> Result<CompiledProgram, String> {
+        self.compile(ProgramSource::Qasm(source.as_bytes()))
+    }
+
+    pub(crate) fn compile(&self, source: ProgramSource) -> Result<CompiledProgram, String> {
+        let module = match source {
+            ProgramSource::Bitcode(bytes) => {
+                let buffer = MemoryBuffer::create_from_memory_range(bytes, "");
+                Module::parse_bitcode_from_buffer(&buffer, &self.context)
+                    .map_err(|e| e.to_string())?
+            }
+            ProgramSource::Ir(bytes) => {
+                let buffer = MemoryBuffer::create_from_memory_range_copy(bytes, "");
+                self.context
+                    .create_module_from_ir(buffer)
+                    .map_err(|e| e.to_string())?
+            }
+            ProgramSource::Qasm(bytes) => {
+                let source = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+                return Ok(CompiledProgram::with_program(Program::Qasm(
+                    Circuit::parse(source)?,
+                )));
+            }
+        };
+        CompiledProgram::new(&self.context, module, &self.intrinsics)
+    }
+}
+
+fn load_file(path: impl AsRef<Path>, context: &Context) -> Result<Module, String> {
+    let path = path.as_ref();
+    let extension = path.extension().and_then(OsStr::to_str);
+
+    match extension {
+        Some("ll") => MemoryBuffer::create_from_file(path)
+            .and_then(|buffer| context.create_module_from_ir(buffer))
+            .map_err(|e| e.to_string()),
+        Some("bc") => Module::parse_bitcode_from_path(path, context).map_err(|e| e.to_string()),
+        _ => Err(format!("Unsupported file extension '{extension:?}'.")),
+    }
+}
+
+/// What to do with the remaining shots after a shot fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailurePolicy {
+    /// Run the remaining shots and report the failure with the failed shot.
+    #[default]
+    Continue,
+    /// Stop after the failed shot, which is the last shot reported.
+    Stop,
+}
+
+impl FromStr for FailurePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "continue" => Ok(FailurePolicy::Continue),
+            "stop" => Ok(FailurePolicy::Stop),
+            _ => Err(format!("Unsupported failure policy '{s}'.")),
+        }
+    }
+}
+
+/// An entry point of a compiled program.
+struct EntryPoint<'ctx> {
+    function: FunctionValue<'ctx>,
+    /// The string attributes of the entry point, which are reported as metadata of every shot.
+    metadata: Vec<(String, String)>,
+    /// The names and kinds of the parameters, or why arguments cannot be passed to them.
+    parameters: Result<Vec<(String, ParameterKind)>, String>,
+    /// How the return value is recorded, or why it cannot be recorded.
+    return_kind: Result<Option<ReturnKind>, String>,
+    /// The function that calls the entry point with the arguments in the slots, for entry points with parameters.
+    wrapper: Option<(FunctionValue<'ctx>, ArgumentSlots)>,
+    /// Whether all measurements of the entry point are terminal, so that its shots can be sampled. This is never
+    /// the case if custom intrinsics are bound, since the runner cannot tell what they do to the state.
+    terminal_measurements: bool,
+}
+
+/// The executable form of a program.
+enum Program<'ctx> {
+    Qir {
+        context: &'ctx Context,
+        // The module is kept alive along with the execution engine that owns it.
+        _module: Module<'ctx>,
+        execution_engine: ExecutionEngine<'ctx>,
+        entry_points: Vec<EntryPoint<'ctx>>,
+    },
+    /// An `OpenQASM` program, which is run by applying its gates through the intrinsics of the backend.
+    Qasm(Circuit),
+}
+
+/// A QIR or `OpenQASM` program that has been compiled once and whose entry points can be executed repeatedly,
+/// with different arguments, without compiling the program again.
+pub struct CompiledProgram<'ctx> {
+    program: Program<'ctx>,
+    failure_policy: FailurePolicy,
+    noise_model: Option<NoiseModel>,
+    simulator: SimulatorKind,
+    tracing: bool,
+    dump_threshold: f64,
+}
+
+impl<'ctx> CompiledProgram<'ctx> {
+    fn new(
+        context: &'ctx Context,
+        module: Module<'ctx>,
+        intrinsics: &Intrinsics,
+    ) -> Result<Self, String> {
+        module
+            .verify()
+            .map_err(|e| format!("Failed to verify module: {}", e.to_string()))?;
+
+        run_basic_passes_on(&module);
+
+        // Wrappers for the entry points with parameters must be added before the module is compiled.
+        let functions: Vec<_> = module_functions(&module)
+            .filter(|f| crate::is_entry_point(*f))
+            .collect();
+        let wrappers: Vec<_> = functions
+            .iter()
+            .map(|function| {
+                (function.count_params() > 0)
+                    .then(|| args::build_entry_point_wrapper(context, &module, *function))
+            })
+            .collect();
+        failure_checks::add_failure_checks(context, &module);
+
+        Target::initialize_native(&InitializationConfig::default())?;
+        let default_triple = TargetMachine::get_default_triple();
+        let target = Target::from_triple(&default_triple).map_err(|e| e.to_string())?;
+        if !target.has_asm_backend() {
+            return Err("Target doesn't have an ASM backend.".to_owned());
+        }
+        if !target.has_target_machine() {
+            return Err("Target doesn't have a target machine.".to_owned());
+        }
+
+        inkwell::support::load_library_permanently("");
+
+        let execution_engine = module
+            .create_jit_execution_engine(OptimizationLevel::None)
+            .map_err(|e| e.to_string())?;
+
+        bind_functions(&module, &execution_engine, intrinsics.functions())?;
+
+        let flow = ValueFlow::new(&module);
+        let target_data = execution_engine.get_target_data();
+        let entry_points = functions
+            .into_iter()
+            .zip(wrappers)
+            .map(|(function, wrapper)| EntryPoint {
+                function,
+                metadata: entry_point_metadata(function),
+                parameters: args::parameters(context, &flow, function),
+                return_kind: return_value::return_kind(context, target_data, &flow, function),
+                wrapper,
+                terminal_measurements: intrinsics.functions().is_empty()
+                    && sampling::has_terminal_measurements(function),
+            })
+            .collect();
+
+        Ok(Self::with_program(Program::Qir {
+            context,
+            _module: module,
+            execution_engine,
+            entry_points,
+        }))
+    }
+
+    fn with_program(program: Program<'ctx>) -> Self {
+        CompiledProgram {
+            program,
+            failure_policy: FailurePolicy::default(),
+            noise_model: None,
+            simulator: SimulatorKind::default(),
+            tracing: false,
+            dump_threshold: 0.0,
+        }
+    }
+
+    /// Sets what to do with the remaining shots of an execution after a shot fails.
+    pub fn set_failure_policy(&mut self, policy: FailurePolicy) {
+        self.failure_policy = policy;
+    }
+
+    /// Sets the noise model with which the shots of an execution are simulated, or `None` to simulate them
+    /// without noise.
+    pub fn set_noise_model(&mut self, model: Option<NoiseModel>) {
+        self.noise_model = model;
+    }
+
+    /// Sets the kind of simulator on which the shots of an execution are run.
+    pub fn set_simulator(&mut self, kind: SimulatorKind) {
+        self.simulator = kind;
+    }
+
+    /// Sets whether the gates and measurements executed by the first shot, which has index 0, are recorded as an
+    /// `OpenQASM` 3 trace. The other shots are not traced.
+    pub fn set_tracing(&mut self, enabled: bool) {
+        self.tracing = enabled;
+    }
+
+    /// Sets the magnitude up to which amplitudes are left out when a shot dumps the state of the simulator.
+    pub fn set_dump_threshold(&mut self, threshold: f64) {
+        self.dump_threshold = threshold;
+    }
+
+    /// Runs the chosen entry point with the given arguments for the given number of shots and returns the
+    /// output of each shot. See `run_bitcode_parallel` for the supported arguments and return values, and
+    /// for how failed shots are reported.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if
+    /// - `entry_point` is not found in the QIR
+    /// - `args` do not match the parameters of the entry point.
+    /// - Entry point has an unsupported return type.
+    /// - An entry point or arguments are given for an `OpenQASM` program.
+    pub fn execute(
+        &self,
+        entry_point: Option<&str>,
+        args: &Arguments,
+        shots: u32,
+    ) -> Result<Vec<ShotResult>, String> {
+        self.run_shots(entry_point, args, 0..shots, None)
+    }
+
+    /// Runs the given range of shots, seeding the simulator before every shot with the sum of the seed and
+    /// the index of the shot if a seed is given. Running stops after a failed shot if the failure policy says so.
+    pub(crate) fn run_shots(
+        &self,
+        entry_point: Option<&str>,
+        args: &Arguments,
+        shots: Range<u32>,
+        seed: Option<u64>,
+    ) -> Result<Vec<ShotResult>, String> {
+        let (context, execution_engine, entry_points) = match &self.program {
+            Program::Qir {
+                context,
+                execution_engine,
+                entry_points,
+                ..
+            } => (*context, execution_engine, entry_points),
+            Program::Qasm(circuit) => {
+                if entry_point.is_some() {
+                    return Err("OpenQASM programs do not have entry points.".to_owned());
+                }
+                if !args.is_empty() {
+                    return Err("OpenQASM programs do not take arguments.".to_owned());
+                }
+                return Ok(self.run_prepared_shots(shots, seed, &[], false, || circuit.run()));
+            }
+        };
+
+        let function =
+            choose_entry_point(entry_points.iter().map(|entry| entry.function), entry_point)?;
+        let entry_point = entry_points
+            .iter()
+            .find(|entry| entry.function == function)
+            .expect("Chosen entry point should be compiled.");
+
+        let params = entry_point.parameters.as_ref().map_err(Clone::clone)?;
+        let args = args::resolve_arguments(params, args)?;
+        let return_kind = entry_point.return_kind.as_ref().map_err(Clone::clone)?;
+        // The arrays created for the arguments must stay alive until all shots are run.
+        let mut arrays = ArgumentArrays::default();
+        let function = match &entry_point.wrapper {
+            Some((wrapper, arg_slots)) => {
+                arg_slots.store(&args, &mut arrays);
+                *wrapper
+            }
+            None => function,
+        };
+
+        Ok(self.run_prepared_shots(
+            shots,
+            seed,
+            &entry_point.metadata,
+            entry_point.terminal_measurements,
+            || {
+                let value = unsafe { run_entry_point(execution_engine, function) };
+                if has_failed() {
+                    return;
+                }
+                if let Some(kind) = &return_kind {
+                    unsafe { return_value::record_return_value(context, kind, &value) };
+                }
+            },
+        ))
+    }
+
+    /// Runs the given range of shots of a program that is ready to run, reporting the given metadata with every
+    /// shot. Shots of programs whose measurements are all terminal are sampled from the state of the first shot,
+    /// unless noise changes the state from shot to shot. If tracing is enabled, only the shot with index 0 is
+    /// traced, which is simulated in full and not used for sampling.
+    fn run_prepared_shots(
+        &self,
+        shots: Range<u32>,
+        seed: Option<u64>,
+        metadata: &[(String, String)],
+        terminal_measurements: bool,
+        shot: impl Fn(),
+    ) -> Vec<ShotResult> {
+        // Collect the output of the shots through a buffer.
+        let buffer = OutputBuffer::new();
+        let _settings = ShotSettings::apply(
+            self,
+            Box::new(buffer.clone()),
+            terminal_measurements
+                && self.noise_model.is_none()
+                && self.simulator == SimulatorKind::Sparse,
+        );
+        let mut results = Vec::new();
+        for index in shots {
+            if let Some(seed) = seed {
+                set_rng_seed(seed.wrapping_add(u64::from(index)));
+            }
+            if self.tracing {
+                set_tracing(index == 0);
+            }
+            __quantum__rt__initialize(null_mut());
+            let failure = run_shot(&shot);
+            let failed = failure.is_some();
+            results.push(ShotResult {
+                metadata: metadata.to_vec(),
+                output: buffer.take(),
+                failure,
+                truncation_error: truncation_error(),
+                resources: resource_counts(),
+                trace: trace_qasm(),
+            });
+            if failed && self.failure_policy == FailurePolicy::Stop {
+                break;
+            }
+        }
+        results
+    }
+}
+
+/// The output sink, noise model, simulator, sampling, tracing and dump threshold of the current thread that
+/// were replaced for running shots, which are restored when dropped, even if a shot panics.
+struct ShotSettings {
+    sink: Option<Box<dyn OutputSink>>,
+    noise_model: Option<NoiseModel>,
+    simulator: SimulatorKind,
+    sampling: bool,
+    tracing: bool,
+    dump_threshold: f64,
+}
+
+impl ShotSettings {
+    /// Replaces the settings of the current thread with those of the given program, collecting the output of the
+    /// shots through the given sink.
+    fn apply(program: &CompiledProgram, sink: Box<dyn OutputSink>, sampling: bool) -> Self {
+        ShotSettings {
+            sink: Some(set_output_sink(sink)),
+            noise_model: set_noise_model(program.noise_model.clone()),
+            simulator: set_simulator_kind(program.simulator),
+            sampling: set_terminal_sampling(sampling),
+            tracing: set_tracing(program.tracing),
+            dump_threshold: set_dump_threshold(program.dump_threshold),
+        }
+    }
+}
+
+impl Drop for ShotSettings {
+    fn drop(&mut self) {
+        if let Some(sink) = self.sink.take() {
+            set_output_sink(sink);
+        }
+        set_noise_model(self.noise_model.take());
+        set_simulator_kind(self.simulator);
+        set_terminal_sampling(self.sampling);
+        set_tracing(self.tracing);
+        set_dump_threshold(self.dump_threshold);
+    }
+}
+
+/// Runs a shot, returning the failure message of the shot if it failed. The functions called by the program capture
+/// their failures, such as the one raised by `__quantum__rt__fail`, after which the program returns at its next
+/// check, so nothing unwinds through the frames of the compiled program.
+fn run_shot(shot: impl FnOnce()) -> Option<String> {
+    let previous = set_capturing(true);
+    let _ = take_failure();
+    catch_failure(shot);
+    set_capturing(previous);
+    take_failure()
+}
+
+unsafe fn run_entry_point<'ctx>(
+    execution_engine: &ExecutionEngine<'ctx>,
+    function: FunctionValue<'ctx>,
+) -> GenericValue<'ctx> {
+    execution_engine.run_function(function, &[])
+}
+
+fn entry_point_metadata(entry_point: FunctionValue) -> Vec<(String, String)> {
+    // TODO: need a cleaner way to get the attr strings for metadata
+    entry_point
+        .attributes(AttributeLoc::Function)
+        .iter()
+        .map(|attr| {
+            (
+                attr.get_string_kind_id()
+                    .to_str()
+                    .expect("Invalid UTF8 data")
+                    .to_string(),
+                attr.get_string_value()
+                    .to_str()
+                    .expect("Invalid UTF8 data")
+                    .to_string(),
+            )
+        })
+        .collect()
+}
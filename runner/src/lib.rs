@@ -4,6 +4,23 @@
 #![deny(clippy::all, clippy::pedantic)]
 #![allow(unused)]
 
+mod args;
+mod failure_checks;
+mod format;
+mod intrinsics;
+mod jit;
+mod qasm;
+mod results;
+mod return_value;
+mod sampling;
+mod value_flow;
+
+pub use args::{Argument, Arguments};
+pub use format::{render_estimates, render_histogram, render_results, OutputFormat};
+pub use intrinsics::{IntrinsicEntry, Intrinsics, PLUGIN_TABLE_SYMBOL};
+pub use jit::{CompiledProgram, FailurePolicy, Runner};
+pub use results::{histogram, ShotResult};
+
 pub use qir_backend::{
     arrays::*, bigints::*, callables::*, exp::*, math::*, output_recording::*, range_support::*,
     result_bool::*, strings::*, tuples::*, *,
@@ -11,132 +28,242 @@ pub use qir_backend::{
 
 use inkwell::{
     attributes::AttributeLoc,
-    context::Context,
     execution_engine::ExecutionEngine,
-    memory_buffer::MemoryBuffer,
     module::Module,
     passes::{PassManager, PassManagerBuilder},
-    targets::{InitializationConfig, Target, TargetMachine},
+    targets::{InitializationConfig, Target},
     values::FunctionValue,
     OptimizationLevel,
 };
-use std::{collections::HashMap, ffi::OsStr, path::Path, ptr::null_mut};
+use intrinsics::Intrinsic;
+use jit::ProgramSource;
+use qir_backend::{
+    noise::NoiseModel,
+    registry::{exported_functions, OutputSchema},
+};
+use std::{collections::HashMap, ffi::OsStr, fs, path::Path, thread};
 
+/// Runs the chosen entry point of the given QIR file, or the given `OpenQASM` file, for the given number of
+/// shots and returns the output of each shot. `OpenQASM` programs have no entry point and record their classical
+/// registers as output.
+///
 /// # Errors
 ///
 /// Will return `Err` if
 /// - `filename` does not exist or the user does not have permission to read it.
-/// - `filename` does not contain a valid bitcode module
-/// - `filename` does not have either a .ll or .bc as an extension
+/// - `filename` does not contain a valid module or `OpenQASM` program
+/// - `filename` does not have a .ll, .bc or .qasm extension
 /// - `entry_point` is not found in the QIR
-/// - Entry point has parameters or a non-void return type.
+/// - Entry point has parameters, whose arguments must be passed with `run_file_parallel` instead.
+/// - Entry point has an unsupported return type.
 pub fn run_file(
     path: impl AsRef<Path>,
     entry_point: Option<&str>,
     shots: u32,
-) -> Result<(), String> {
-    let context = Context::create();
-    let module = load_file(path, &context)?;
-    run_module(&module, entry_point, shots)
+) -> Result<Vec<ShotResult>, String> {
+    Runner::new()
+        .load_file(path)?
+        .execute(entry_point, &Arguments::default(), shots)
 }
 
+/// Runs the chosen entry point of the given QIR file, or the given `OpenQASM` file, for the given number of
+/// shots, spreading the shots over the given number of threads. See `run_bitcode_parallel` for details.
+///
 /// # Errors
 ///
 /// Will return `Err` if
-/// - `bytes` does not contain a valid bitcode module
+/// - `filename` does not exist or the user does not have permission to read it.
+/// - `filename` does not contain a valid module or `OpenQASM` program
+/// - `filename` does not have a .ll, .bc or .qasm extension
 /// - `entry_point` is not found in the QIR
-/// - Entry point has parameters or a non-void return type.
-pub fn run_bitcode(bytes: &[u8], entry_point: Option<&str>, shots: u32) -> Result<(), String> {
-    let context = Context::create();
-    let buffer = MemoryBuffer::create_from_memory_range(bytes, "");
-    let module = Module::parse_bitcode_from_buffer(&buffer, &context).map_err(|e| e.to_string())?;
-    run_module(&module, entry_point, shots)
+/// - `args` do not match the parameters of the entry point.
+/// - Entry point has an unsupported return type.
+/// - A worker thread panics.
+pub fn run_file_parallel(
+    path: impl AsRef<Path>,
+    entry_point: Option<&str>,
+    args: &Arguments,
+    shots: u32,
+    options: &RunOptions,
+) -> Result<Vec<ShotResult>, String> {
+    let path = path.as_ref();
+    let extension = path.extension().and_then(OsStr::to_str);
+    if !matches!(extension, Some("ll" | "bc" | "qasm")) {
+        return Err(format!("Unsupported file extension '{extension:?}'."));
+    }
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let source = match extension {
+        Some("bc") => ProgramSource::Bitcode(&bytes),
+        Some("qasm") => ProgramSource::Qasm(&bytes),
+        _ => ProgramSource::Ir(&bytes),
+    };
+    run_parallel(source, entry_point, args, shots, options)
 }
 
-fn run_module(module: &Module, entry_point: Option<&str>, shots: u32) -> Result<(), String> {
-    module
-        .verify()
-        .map_err(|e| format!("Failed to verify module: {}", e.to_string()))?;
-
-    run_basic_passes_on(module);
+/// Runs the chosen entry point of the given QIR bitcode for the given number of shots and returns the
+/// output of each shot.
+///
+/// # Errors
+///
+/// Will return `Err` if
+/// - `bytes` does not contain a valid bitcode module
+/// - `entry_point` is not found in the QIR
+/// - Entry point has parameters, whose arguments must be passed with `run_bitcode_parallel` instead.
+/// - Entry point has an unsupported return type.
+pub fn run_bitcode(
+    bytes: &[u8],
+    entry_point: Option<&str>,
+    shots: u32,
+) -> Result<Vec<ShotResult>, String> {
+    Runner::new()
+        .load_bitcode(bytes)?
+        .execute(entry_point, &Arguments::default(), shots)
+}
 
-    Target::initialize_native(&InitializationConfig::default())?;
-    let default_triple = TargetMachine::get_default_triple();
-    let target = Target::from_triple(&default_triple).map_err(|e| e.to_string())?;
-    if !target.has_asm_backend() {
-        return Err("Target doesn't have an ASM backend.".to_owned());
-    }
-    if !target.has_target_machine() {
-        return Err("Target doesn't have a target machine.".to_owned());
-    }
+/// Options for running shots with `run_file_parallel` and `run_bitcode_parallel`.
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// The number of threads over which the shots are spread.
+    pub threads: usize,
+    /// The seed from which the seed of every shot is derived, if the shots should be reproducible. The simulator
+    /// is seeded before every shot with the sum of the seed and the index of the shot, so the results do not
+    /// depend on the number of threads.
+    pub seed: Option<u64>,
+    /// Custom intrinsics that are bound in addition to the built-in functions.
+    pub intrinsics: Intrinsics,
+    /// What to do with the remaining shots after a shot fails, which are either run or not reported.
+    pub failure_policy: FailurePolicy,
+    /// The noise model with which the shots are simulated, if any. Every shot is simulated with errors sampled
+    /// from the noise model, except on the density matrix simulator, which applies the noise channels exactly.
+    pub noise_model: Option<NoiseModel>,
+    /// The kind of simulator on which the shots are run. The stabilizer simulator fails any shot that applies a
+    /// non-Clifford gate, the matrix product state simulator reports the error introduced by truncating its bonds
+    /// in the truncation error of each shot, and resource estimation reports the operations counted by each shot
+    /// in its resources instead of simulating them.
+    pub simulator: SimulatorKind,
+    /// Whether the gates and measurements executed by the first shot are recorded as an `OpenQASM` 3 program,
+    /// which is reported in the trace of the shot. The other shots are not traced.
+    pub trace: bool,
+    /// The magnitude up to which amplitudes are left out when a shot dumps the state of the simulator.
+    pub dump_threshold: f64,
+}
 
-    inkwell::support::load_library_permanently("");
-
-    let execution_engine = module
-        .create_jit_execution_engine(OptimizationLevel::None)
-        .map_err(|e| e.to_string())?;
-
-    bind_functions(module, &execution_engine)?;
-
-    let entry_point = choose_entry_point(module_functions(module), entry_point)?;
-    // TODO: need a cleaner way to get the attr strings for metadata
-    let attrs: Vec<(String, String)> = entry_point
-        .attributes(AttributeLoc::Function)
-        .iter()
-        .map(|attr| {
-            (
-                attr.get_string_kind_id()
-                    .to_str()
-                    .expect("Invalid UTF8 data")
-                    .to_string(),
-                attr.get_string_value()
-                    .to_str()
-                    .expect("Invalid UTF8 data")
-                    .to_string(),
-            )
-        })
-        .collect();
-
-    for _ in 1..=shots {
-        println!("START");
-        for attr in &attrs {
-            print!("METADATA\t{}", attr.0);
-            if !attr.1.is_empty() {
-                print!("\t{}", attr.1);
-            }
-            println!();
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            threads: 1,
+            seed: None,
+            intrinsics: Intrinsics::default(),
+            failure_policy: FailurePolicy::default(),
+            noise_model: None,
+            simulator: SimulatorKind::default(),
+            trace: false,
+            dump_threshold: 0.0,
         }
-
-        __quantum__rt__initialize(null_mut());
-        unsafe { run_entry_point(&execution_engine, entry_point)? }
-        println!("END\t0");
     }
-    Ok(())
 }
 
-fn load_file(path: impl AsRef<Path>, context: &Context) -> Result<Module, String> {
-    let path = path.as_ref();
-    let extension = path.extension().and_then(OsStr::to_str);
-
-    match extension {
-        Some("ll") => MemoryBuffer::create_from_file(path)
-            .and_then(|buffer| context.create_module_from_ir(buffer))
-            .map_err(|e| e.to_string()),
-        Some("bc") => Module::parse_bitcode_from_path(path, context).map_err(|e| e.to_string()),
-        _ => Err(format!("Unsupported file extension '{extension:?}'.")),
-    }
+/// Runs the chosen entry point of the given QIR bitcode for the given number of shots, spreading the shots
+/// over the number of threads given in the options. Each thread compiles its own copy of the program and runs
+/// a contiguous range of shots, and the output of the shots is returned in shot order.
+///
+/// The entry point is called with the given arguments, which are either typed and given in parameter order,
+/// or given as text by parameter name and parsed according to the parameter types. Parameters of type `i64`,
+/// `double`, `i1` and `%Array*` are supported, where the element type of an array is taken from how the program
+/// accesses its elements, and every element is checked against it like a scalar argument.
+///
+/// A value returned by the entry point is recorded as untagged output at the end of each shot and released
+/// afterwards. Values of type `i64`, `double`, `i1` and `%Result*` are supported, as well as pointers to tuples
+/// and arrays of those, where tuples and arrays are recorded with their number of items followed by the items.
+/// A returned `%String*` is recorded as a message.
+///
+/// A shot fails if a runtime function fails while running it, e.g., when `__quantum__rt__fail` is called or a gate
+/// is applied to duplicate qubits. The failed shot is reported with the output recorded before the failure and
+/// the failure message, and the simulator is reset before the next shot.
+///
+/// Entry points without branches that only measure with `__quantum__qis__mz__body` after their last gate, and
+/// only record the results as output, are simulated once on each thread when running on the default simulator
+/// without noise or tracing. The outcomes of the remaining shots are then sampled from the state at the first
+/// measurement.
+///
+/// # Errors
+///
+/// Will return `Err` if
+/// - `bytes` does not contain a valid bitcode module
+/// - `entry_point` is not found in the QIR
+/// - `args` do not match the parameters of the entry point.
+/// - Entry point has an unsupported return type.
+/// - A worker thread panics.
+pub fn run_bitcode_parallel(
+    bytes: &[u8],
+    entry_point: Option<&str>,
+    args: &Arguments,
+    shots: u32,
+    options: &RunOptions,
+) -> Result<Vec<ShotResult>, String> {
+    run_parallel(
+        ProgramSource::Bitcode(bytes),
+        entry_point,
+        args,
+        shots,
+        options,
+    )
 }
 
-unsafe fn run_entry_point(
-    execution_engine: &ExecutionEngine,
-    entry_point: FunctionValue,
-) -> Result<(), String> {
-    if entry_point.count_params() == 0 && entry_point.get_type().get_return_type().is_none() {
-        execution_engine.run_function(entry_point, &[]);
-        Ok(())
-    } else {
-        Err("Entry point has parameters or a non-void return type.".to_owned())
-    }
+fn run_parallel(
+    source: ProgramSource,
+    entry_point: Option<&str>,
+    args: &Arguments,
+    shots: u32,
+    options: &RunOptions,
+) -> Result<Vec<ShotResult>, String> {
+    // Initialize the native target once up front, since registering it is not safe to do concurrently.
+    Target::initialize_native(&InitializationConfig::default())?;
+
+    let seed = options.seed;
+    let threads = u32::try_from(options.threads.max(1))
+        .unwrap_or(u32::MAX)
+        .min(shots.max(1));
+    let shots_per_thread = shots / threads;
+    let remainder = shots % threads;
+    let ranges = (0..threads).map(|thread| {
+        let start = thread * shots_per_thread + thread.min(remainder);
+        let end = start + shots_per_thread + u32::from(thread < remainder);
+        start..end
+    });
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = ranges
+            .map(|range| {
+                scope.spawn(move || {
+                    // Every thread compiles its own copy of the program, since compiled programs cannot be shared.
+                    let runner = Runner::with_intrinsics(options.intrinsics.clone());
+                    let mut program = runner.compile(source)?;
+                    program.set_failure_policy(options.failure_policy);
+                    program.set_noise_model(options.noise_model.clone());
+                    program.set_simulator(options.simulator);
+                    program.set_tracing(options.trace);
+                    program.set_dump_threshold(options.dump_threshold);
+                    program.run_shots(entry_point, args, range, seed)
+                })
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(shots as usize);
+        for handle in handles {
+            let thread_results = handle
+                .join()
+                .map_err(|_| "Worker thread panicked during shot execution.".to_owned())??;
+            results.extend(thread_results);
+        }
+        // Threads run their shots independently, so the shots after the first failure are dropped here.
+        if options.failure_policy == FailurePolicy::Stop {
+            if let Some(failed) = results.iter().position(|shot| shot.failure.is_some()) {
+                results.truncate(failed + 1);
+            }
+        }
+        Ok(results)
+    })
 }
 
 fn choose_entry_point<'ctx>(
@@ -200,7 +327,11 @@ extern "C" {
 }
 
 #[allow(clippy::too_many_lines)]
-fn bind_functions(module: &Module, execution_engine: &ExecutionEngine) -> Result<(), String> {
+fn bind_functions(
+    module: &Module,
+    execution_engine: &ExecutionEngine,
+    intrinsics: &[Intrinsic],
+) -> Result<(), String> {
     let mut uses_legacy = vec![];
     let mut declarations: HashMap<String, FunctionValue> = HashMap::default();
     for func in module_functions(module).filter(|f| {
@@ -237,214 +368,60 @@ fn bind_functions(module: &Module, execution_engine: &ExecutionEngine) -> Result
         };
     }
 
-    macro_rules! legacy_output {
-        ($func:ident) => {
-            if let Some(func) = declarations.get(stringify!($func)) {
-                execution_engine.add_global_mapping(
-                    func,
-                    qir_backend::output_recording::legacy::$func as usize,
-                );
-                declarations.remove(stringify!($func));
-                Some(true)
-            } else {
-                None
-            }
+    // The functions exported by the backend are bound based on its registry, where output recording functions
+    // may have a legacy variant with a different number of parameters.
+    let exports = exported_functions();
+    let mut bound = vec![];
+    for (name, func) in &declarations {
+        let mut variants = exports
+            .iter()
+            .filter(|export| export.name == name)
+            .peekable();
+        let Some(expected) = variants.peek().map(|export| export.param_count) else {
+            continue;
         };
+        let export = variants
+            .find(|export| export.param_count == func.count_params())
+            .ok_or_else(|| {
+                format!(
+                    "Function '{name}' has mismatched parameters: expected {expected}, found {}",
+                    func.count_params()
+                )
+            })?;
+        execution_engine.add_global_mapping(func, export.address);
+        if let Some(schema) = export.output_schema {
+            uses_legacy.push(schema == OutputSchema::Legacy);
+        }
+        bound.push(name.clone());
+    }
+    for name in bound {
+        declarations.remove(&name);
     }
 
-    macro_rules! bind_output_record {
-        ($func:ident) => {
-            if let Some(func) = declarations.get(stringify!($func)) {
-                if func.get_params().len() == 1 {
-                    execution_engine.add_global_mapping(
-                        func,
-                        qir_backend::output_recording::legacy::$func as usize,
-                    );
-                    declarations.remove(stringify!($func));
-                    Some(true)
-                } else {
-                    execution_engine.add_global_mapping(func, $func as usize);
-                    declarations.remove(stringify!($func));
-                    Some(false)
-                }
-            } else {
-                None
-            }
-        };
+    bind!(__quantum__rt__array_slice_1d, 3);
+    bind!(__quantum__rt__range_to_string, 1);
+
+    // The checks for failures are added by the runner itself.
+    if let Some(func) = declarations.remove(failure_checks::CHECK_FUNCTION) {
+        execution_engine.add_global_mapping(&func, failure_checks::failed as usize);
     }
 
-    // Legacy output methods
-    uses_legacy.push(legacy_output!(__quantum__rt__array_end_record_output));
-    uses_legacy.push(legacy_output!(__quantum__rt__array_start_record_output));
-    uses_legacy.push(legacy_output!(__quantum__rt__tuple_end_record_output));
-    uses_legacy.push(legacy_output!(__quantum__rt__tuple_start_record_output));
-
-    bind!(__quantum__rt__initialize, 1);
-    bind!(__quantum__qis__arccos__body, 1);
-    bind!(__quantum__qis__arcsin__body, 1);
-    bind!(__quantum__qis__arctan__body, 1);
-    bind!(__quantum__qis__arctan2__body, 2);
-    bind!(__quantum__qis__assertmeasurementprobability__body, 6);
-    bind!(__quantum__qis__assertmeasurementprobability__ctl, 6);
-    bind!(__quantum__qis__ccx__body, 3);
-    bind!(__quantum__qis__cnot__body, 2);
-    bind!(__quantum__qis__cos__body, 1);
-    bind!(__quantum__qis__cosh__body, 1);
-    bind!(__quantum__qis__cx__body, 2);
-    bind!(__quantum__qis__cz__body, 2);
-    bind!(__quantum__qis__drawrandomdouble__body, 2);
-    bind!(__quantum__qis__drawrandomint__body, 2);
-    bind!(__quantum__qis__dumpmachine__body, 1);
-    bind!(__quantum__qis__exp__body, 3);
-    bind!(__quantum__qis__exp__adj, 3);
-    bind!(__quantum__qis__exp__ctl, 2);
-    bind!(__quantum__qis__exp__ctladj, 2);
-    bind!(__quantum__qis__h__body, 1);
-    bind!(__quantum__qis__h__ctl, 2);
-    bind!(__quantum__qis__ieeeremainder__body, 2);
-    bind!(__quantum__qis__infinity__body, 0);
-    bind!(__quantum__qis__isinf__body, 1);
-    bind!(__quantum__qis__isnan__body, 1);
-    bind!(__quantum__qis__isnegativeinfinity__body, 1);
-    bind!(__quantum__qis__log__body, 1);
-    bind!(__quantum__qis__m__body, 1);
-    bind!(__quantum__qis__measure__body, 2);
-    bind!(__quantum__qis__mz__body, 2);
-    bind!(__quantum__qis__nan__body, 0);
-    bind!(__quantum__qis__r__adj, 3);
-    bind!(__quantum__qis__r__body, 3);
-    bind!(__quantum__qis__r__ctl, 2);
-    bind!(__quantum__qis__r__ctladj, 2);
-    bind!(__quantum__qis__read_result__body, 1);
-    bind!(__quantum__qis__reset__body, 1);
-    bind!(__quantum__qis__rx__body, 2);
-    bind!(__quantum__qis__rx__ctl, 2);
-    bind!(__quantum__qis__ry__body, 2);
-    bind!(__quantum__qis__ry__ctl, 2);
-    bind!(__quantum__qis__rz__body, 2);
-    bind!(__quantum__qis__rz__ctl, 2);
-    bind!(__quantum__qis__s__adj, 1);
-    bind!(__quantum__qis__s__body, 1);
-    bind!(__quantum__qis__s__ctl, 2);
-    bind!(__quantum__qis__s__ctladj, 2);
-    bind!(__quantum__qis__sin__body, 1);
-    bind!(__quantum__qis__sinh__body, 1);
-    bind!(__quantum__qis__sqrt__body, 1);
-    bind!(__quantum__qis__swap__body, 2);
-    bind!(__quantum__qis__t__adj, 1);
-    bind!(__quantum__qis__t__body, 1);
-    bind!(__quantum__qis__t__ctl, 2);
-    bind!(__quantum__qis__t__ctladj, 2);
-    bind!(__quantum__qis__tan__body, 1);
-    bind!(__quantum__qis__tanh__body, 1);
-    bind!(__quantum__qis__x__body, 1);
-    bind!(__quantum__qis__x__ctl, 2);
-    bind!(__quantum__qis__y__body, 1);
-    bind!(__quantum__qis__y__ctl, 2);
-    bind!(__quantum__qis__z__body, 1);
-    bind!(__quantum__qis__z__ctl, 2);
-    bind!(__quantum__rt__array_concatenate, 2);
-    bind!(__quantum__rt__array_copy, 2);
-    bind!(__quantum__rt__array_create_1d, 2);
-
-    // New calls
-    bind!(__quantum__rt__array_record_output, 2);
-    bind!(__quantum__rt__tuple_record_output, 2);
-
-    // calls with unlabeled signature variants
-    uses_legacy.push(bind_output_record!(__quantum__rt__bool_record_output));
-    uses_legacy.push(bind_output_record!(__quantum__rt__double_record_output));
-    uses_legacy.push(bind_output_record!(__quantum__rt__int_record_output));
-
-    // results need special handling as they aren't in the std lib
-    uses_legacy.push(
-        if let Some(func) = declarations.get("__quantum__rt__result_record_output") {
-            if func.get_params().len() == 1 {
-                execution_engine.add_global_mapping(
-                    func,
-                    qir_backend::legacy::__quantum__rt__result_record_output as usize,
-                );
-                declarations.remove("__quantum__rt__result_record_output");
-                Some(true)
-            } else {
-                execution_engine
-                    .add_global_mapping(func, __quantum__rt__result_record_output as usize);
-                declarations.remove("__quantum__rt__result_record_output");
-                Some(false)
+    // Custom intrinsics are bound for the declarations that remain after binding the built-in functions.
+    for intrinsic in intrinsics {
+        if let Some(func) = declarations.remove(&intrinsic.name) {
+            if func.count_params() != intrinsic.param_count {
+                return Err(format!(
+                    "Function '{}' has mismatched parameters: expected {}, found {}",
+                    intrinsic.name,
+                    intrinsic.param_count,
+                    func.count_params()
+                ));
             }
-        } else {
-            None
-        },
-    );
+            execution_engine.add_global_mapping(&func, intrinsic.address);
+        }
+    }
 
-    bind!(__quantum__rt__array_get_element_ptr_1d, 2);
-    bind!(__quantum__rt__array_get_size_1d, 1);
-    bind!(__quantum__rt__array_slice_1d, 3);
-    bind!(__quantum__rt__array_update_alias_count, 2);
-    bind!(__quantum__rt__array_update_reference_count, 2);
-    bind!(__quantum__rt__bigint_add, 2);
-    bind!(__quantum__rt__bigint_bitand, 2);
-    bind!(__quantum__rt__bigint_bitnot, 1);
-    bind!(__quantum__rt__bigint_bitor, 2);
-    bind!(__quantum__rt__bigint_bitxor, 2);
-    bind!(__quantum__rt__bigint_create_array, 2);
-    bind!(__quantum__rt__bigint_create_i64, 1);
-    bind!(__quantum__rt__bigint_divide, 2);
-    bind!(__quantum__rt__bigint_equal, 2);
-    bind!(__quantum__rt__bigint_get_data, 1);
-    bind!(__quantum__rt__bigint_get_length, 1);
-    bind!(__quantum__rt__bigint_greater, 2);
-    bind!(__quantum__rt__bigint_greater_eq, 2);
-    bind!(__quantum__rt__bigint_modulus, 2);
-    bind!(__quantum__rt__bigint_multiply, 2);
-    bind!(__quantum__rt__bigint_negate, 1);
-    bind!(__quantum__rt__bigint_power, 2);
-    bind!(__quantum__rt__bigint_shiftleft, 2);
-    bind!(__quantum__rt__bigint_shiftright, 2);
-    bind!(__quantum__rt__bigint_subtract, 2);
-    bind!(__quantum__rt__bigint_to_string, 1);
-    bind!(__quantum__rt__bigint_update_reference_count, 2);
-    bind!(__quantum__rt__bool_to_string, 1);
-    bind!(__quantum__rt__callable_copy, 2);
-    bind!(__quantum__rt__callable_create, 3);
-    bind!(__quantum__rt__callable_invoke, 3);
-    bind!(__quantum__rt__callable_make_adjoint, 1);
-    bind!(__quantum__rt__callable_make_controlled, 1);
-    bind!(__quantum__rt__callable_update_alias_count, 2);
-    bind!(__quantum__rt__callable_update_reference_count, 2);
-    bind!(__quantum__rt__capture_update_alias_count, 2);
-    bind!(__quantum__rt__capture_update_reference_count, 2);
-    bind!(__quantum__rt__double_to_string, 1);
-    bind!(__quantum__rt__fail, 1);
-    bind!(__quantum__rt__int_to_string, 1);
-    bind!(__quantum__rt__memory_allocate, 1);
-    bind!(__quantum__rt__message, 1);
-    bind!(__quantum__rt__pauli_to_string, 1);
-    bind!(__quantum__rt__qubit_allocate, 0);
-    bind!(__quantum__rt__qubit_allocate_array, 1);
-    bind!(__quantum__rt__qubit_release, 1);
-    bind!(__quantum__rt__qubit_release_array, 1);
-    bind!(__quantum__rt__qubit_to_string, 1);
-    bind!(__quantum__rt__range_to_string, 1);
-    bind!(__quantum__rt__result_equal, 2);
-    bind!(__quantum__rt__result_get_one, 0);
-    bind!(__quantum__rt__result_get_zero, 0);
-    bind!(__quantum__rt__result_to_string, 1);
-    bind!(__quantum__rt__result_update_reference_count, 2);
-    bind!(__quantum__rt__string_concatenate, 2);
-    bind!(__quantum__rt__string_create, 1);
-    bind!(__quantum__rt__string_equal, 2);
-    bind!(__quantum__rt__string_get_data, 1);
-    bind!(__quantum__rt__string_get_length, 1);
-    bind!(__quantum__rt__string_update_reference_count, 2);
-    bind!(__quantum__rt__tuple_copy, 2);
-    bind!(__quantum__rt__tuple_create, 1);
-    bind!(__quantum__rt__tuple_update_alias_count, 2);
-    bind!(__quantum__rt__tuple_update_reference_count, 2);
-
-    if !(uses_legacy.iter().filter_map(|&b| b).all(|b| b)
-        || uses_legacy.iter().filter_map(|&b| b).all(|b| !b))
-    {
+    if !(uses_legacy.iter().all(|&b| b) || uses_legacy.iter().all(|&b| !b)) {
         Err("Use of legacy and current output recording functions in the same program is not supported".to_string())
     } else if declarations.is_empty() {
         Ok(())
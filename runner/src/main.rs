@@ -3,22 +3,61 @@
 
 #![deny(clippy::all, clippy::pedantic)]
 
-use std::path::PathBuf;
+use std::{fs, path::PathBuf};
 
-use clap::{arg, value_parser, Command};
+use clap::{arg, value_parser, ArgAction, ArgMatches, Command};
+use qir_runner::{
+    estimate::MeasurementPolicy, noise::NoiseModel, render_estimates, render_histogram,
+    render_results, Arguments, FailurePolicy, Intrinsics, OutputFormat, RunOptions, ShotResult,
+    SimulatorKind,
+};
 // use std::env;
 
 fn main() -> Result<(), String> {
     let cmd = Command::new("qir-runner").args(&[
-        arg!(-f --file <PATH> "(Required) Path to the QIR file to run")
+        arg!(-f --file <PATH> "(Required) Path to the QIR (.ll or .bc) or OpenQASM (.qasm) file to run")
             .value_parser(value_parser!(PathBuf))
             .required(true),
         arg!(-e --entrypoint <NAME> "Name of the entry point function to execute"),
+        arg!(--arg <ARG> "An argument for the entry point parameter with the given name, such as n=5 or xs=[1,2]")
+            .value_name("NAME=VALUE")
+            .action(ArgAction::Append),
         arg!(-s --shots <NUM> "The number of times to repeat the execution of the chosen entry point in the program")
             .value_parser(value_parser!(u32))
             .default_value("1"),
         arg!(-r --rngseed <NUM> "The value to use when seeding the random number generator used for quantum simulation")
-            .value_parser(value_parser!(u64))]);
+            .value_parser(value_parser!(u64)),
+        arg!(-t --threads <NUM> "The number of threads over which the shots are spread")
+            .value_parser(value_parser!(usize))
+            .default_value("1"),
+        arg!(--"on-failure" <POLICY> "Whether to continue with the remaining shots or stop after a shot fails")
+            .value_parser(["continue", "stop"])
+            .default_value("continue"),
+        arg!(--simulator <KIND> "The simulator on which the shots are run, where the stabilizer simulator only supports Clifford gates and the density matrix simulator applies noise exactly, and the mps simulator truncates entanglement to the bond dimension")
+            .value_parser(["sparse", "stabilizer", "density-matrix", "mps"])
+            .default_value("sparse"),
+        arg!(--"bond-dimension" <NUM> "The maximum bond dimension of the matrix product state simulator")
+            .value_parser(value_parser!(usize))
+            .default_value("64"),
+        arg!(--estimate "Count the gates, measurements, qubits and depth of the shots instead of simulating them, and print them as JSON")
+            .conflicts_with_all(["simulator", "noise", "histogram", "format"]),
+        arg!(--measurements <POLICY> "The outcome of every measurement during resource estimation")
+            .value_parser(["zero", "one", "random"])
+            .default_value("zero"),
+        arg!(--noise <PATH> "Path to a TOML or JSON file with the noise model with which the shots are simulated")
+            .value_parser(value_parser!(PathBuf)),
+        arg!(--format <FORMAT> "The format in which the output of the shots is printed")
+            .value_parser(["text", "json", "csv"])
+            .default_value("text"),
+        arg!(--histogram "Print the count and probability of each distinct shot output instead of the individual shots"),
+        arg!(--"trace-qasm" <PATH> "Path to a file to which the gates and measurements executed by the first shot are written as OpenQASM 3, where the other shots are not traced")
+            .value_parser(value_parser!(PathBuf)),
+        arg!(--"dump-threshold" <VALUE> "The magnitude up to which amplitudes are left out when the program dumps the state of the simulator")
+            .value_parser(value_parser!(f64))
+            .default_value("0"),
+        arg!(--plugin <PATH> "Path to a shared library that provides custom intrinsics")
+            .value_parser(value_parser!(PathBuf))
+            .action(ArgAction::Append)]);
 
     let matches = cmd.try_get_matches().map_err(|e| e.to_string());
     match matches {
@@ -27,17 +66,102 @@ fn main() -> Result<(), String> {
             Ok(())
         }
         Ok(matches) => {
-            if let Ok(Some(seed)) = matches.try_get_one::<u64>("rngseed") {
-                qir_backend::set_rng_seed(*seed);
+            let args = matches
+                .get_many::<String>("arg")
+                .unwrap_or_default()
+                .map(|arg| {
+                    arg.split_once('=')
+                        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                        .ok_or_else(|| format!("Argument '{arg}' must have the form NAME=VALUE."))
+                })
+                .collect::<Result<_, _>>()?;
+            let mut intrinsics = Intrinsics::new();
+            for plugin in matches.get_many::<PathBuf>("plugin").unwrap_or_default() {
+                unsafe { intrinsics.load_plugin(plugin)? };
             }
-
-            qir_runner::run_file(
+            let options = RunOptions {
+                threads: *matches.get_one::<usize>("threads").unwrap(),
+                seed: matches.get_one::<u64>("rngseed").copied(),
+                intrinsics,
+                failure_policy: matches
+                    .get_one::<String>("on-failure")
+                    .unwrap()
+                    .parse::<FailurePolicy>()?,
+                noise_model: matches
+                    .get_one::<PathBuf>("noise")
+                    .map(NoiseModel::load)
+                    .transpose()?,
+                simulator: simulator_kind(&matches)?,
+                trace: matches.contains_id("trace-qasm"),
+                dump_threshold: *matches.get_one::<f64>("dump-threshold").unwrap(),
+            };
+            let results = qir_runner::run_file_parallel(
                 matches.get_one::<PathBuf>("file").unwrap(),
                 matches
                     .get_one::<String>("entrypoint")
                     .map(std::string::String::as_str),
+                &Arguments::Named(args),
                 *matches.get_one::<u32>("shots").unwrap(),
-            )
+                &options,
+            )?;
+            print_results(&matches, &results)
         }
     }
 }
+
+/// Returns the simulator chosen in the arguments, which uses the bond dimension from the arguments if it is the
+/// matrix product state simulator, or resource estimation with the measurement policy from the arguments.
+fn simulator_kind(matches: &ArgMatches) -> Result<SimulatorKind, String> {
+    if matches.get_flag("estimate") {
+        return Ok(SimulatorKind::Estimate {
+            measurements: matches
+                .get_one::<String>("measurements")
+                .unwrap()
+                .parse::<MeasurementPolicy>()?,
+        });
+    }
+    let mut simulator = matches
+        .get_one::<String>("simulator")
+        .unwrap()
+        .parse::<SimulatorKind>()?;
+    if let SimulatorKind::Mps { bond_dimension } = &mut simulator {
+        *bond_dimension = *matches.get_one::<usize>("bond-dimension").unwrap();
+        if *bond_dimension == 0 {
+            return Err("The bond dimension must be positive.".to_owned());
+        }
+    }
+    Ok(simulator)
+}
+
+/// Prints the results of the shots in the format chosen in the arguments, or the resources counted for them with
+/// resource estimation, and warns about truncation by the matrix product state simulator. The trace of the first
+/// shot is written to the file given in the arguments.
+fn print_results(matches: &ArgMatches, results: &[ShotResult]) -> Result<(), String> {
+    if let Some(path) = matches.get_one::<PathBuf>("trace-qasm") {
+        let trace = results
+            .first()
+            .and_then(|shot| shot.trace.as_deref())
+            .unwrap_or_default();
+        fs::write(path, trace)
+            .map_err(|e| format!("Failed to write the trace to '{}': {e}", path.display()))?;
+    }
+    let truncation_error = results
+        .iter()
+        .map(|shot| shot.truncation_error)
+        .fold(0.0, f64::max);
+    if truncation_error > 0.0 {
+        eprintln!("Warning: The bond dimension truncated the state with an error of up to {truncation_error:e} per shot.");
+    }
+    let format = matches
+        .get_one::<String>("format")
+        .unwrap()
+        .parse::<OutputFormat>()?;
+    if matches.get_flag("estimate") {
+        print!("{}", render_estimates(results));
+    } else if matches.get_flag("histogram") {
+        print!("{}", render_histogram(results, format)?);
+    } else {
+        print!("{}", render_results(results, format)?);
+    }
+    Ok(())
+}
@@ -0,0 +1,331 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Runs `OpenQASM` 2.0 programs and the subset of `OpenQASM` 3.0 with gates, registers, measurements, resets and
+//! classically controlled `if` statements. Programs are parsed and their gates expanded into the primitive gates
+//! of the backend once, and every shot then applies the expanded circuit through the QIR intrinsics of the
+//! backend, so that shots run on the same simulators and report the same output as QIR programs.
+
+mod parser;
+
+use std::{
+    ffi::{c_double, c_void, CString},
+    mem::size_of,
+    ptr::null_mut,
+};
+
+use qir_backend::{
+    __quantum__qis__cx__body, __quantum__qis__cy__body, __quantum__qis__cz__body,
+    __quantum__qis__h__body, __quantum__qis__h__ctl, __quantum__qis__mz__body,
+    __quantum__qis__r__ctl, __quantum__qis__read_result__body, __quantum__qis__reset__body,
+    __quantum__qis__rx__body, __quantum__qis__rx__ctl, __quantum__qis__ry__body,
+    __quantum__qis__ry__ctl, __quantum__qis__rz__body, __quantum__qis__rz__ctl,
+    __quantum__qis__s__adj, __quantum__qis__s__body, __quantum__qis__s__ctl,
+    __quantum__qis__s__ctladj, __quantum__qis__t__adj, __quantum__qis__t__body,
+    __quantum__qis__t__ctl, __quantum__qis__t__ctladj, __quantum__qis__x__body,
+    __quantum__qis__x__ctl, __quantum__qis__y__body, __quantum__qis__y__ctl,
+    __quantum__qis__z__body, __quantum__qis__z__ctl,
+    arrays::{
+        QirArray, __quantum__rt__array_create_1d, __quantum__rt__array_get_element_ptr_1d,
+        __quantum__rt__array_update_reference_count,
+    },
+    output_recording::__quantum__rt__array_record_output,
+    tuples::{__quantum__rt__tuple_create, __quantum__rt__tuple_update_reference_count},
+    Pauli, __quantum__qis__ccx__body, __quantum__rt__result_record_output,
+};
+
+/// A parsed `OpenQASM` program, whose gates are expanded into primitive operations.
+#[derive(Debug)]
+pub(crate) struct Circuit {
+    /// The classical registers in declaration order, which are recorded as output at the end of every shot.
+    registers: Vec<Register>,
+    instructions: Vec<Instruction>,
+}
+
+/// A quantum or classical register, whose elements are numbered consecutively from its start.
+#[derive(Debug, Clone)]
+struct Register {
+    name: String,
+    start: usize,
+    size: usize,
+    /// Whether the register is a single `qubit` or `bit` declared without a size, which is recorded as a single
+    /// result rather than as an array.
+    scalar: bool,
+}
+
+/// The primitive gates of the backend into which all gates are expanded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Gate {
+    X,
+    Y,
+    Z,
+    H,
+    S,
+    Sdg,
+    T,
+    Tdg,
+    Rx(f64),
+    Ry(f64),
+    Rz(f64),
+}
+
+/// A primitive operation, which may be controlled by any number of qubits.
+#[derive(Debug, Clone, PartialEq)]
+enum Op {
+    Gate {
+        gate: Gate,
+        ctls: Vec<usize>,
+        target: usize,
+    },
+    /// A global phase, which only has an effect when controlled.
+    Phase { angle: f64, ctls: Vec<usize> },
+}
+
+impl Op {
+    fn inverse(self) -> Self {
+        match self {
+            Op::Gate { gate, ctls, target } => Op::Gate {
+                gate: match gate {
+                    Gate::S => Gate::Sdg,
+                    Gate::Sdg => Gate::S,
+                    Gate::T => Gate::Tdg,
+                    Gate::Tdg => Gate::T,
+                    Gate::Rx(theta) => Gate::Rx(-theta),
+                    Gate::Ry(theta) => Gate::Ry(-theta),
+                    Gate::Rz(theta) => Gate::Rz(-theta),
+                    Gate::X | Gate::Y | Gate::Z | Gate::H => gate,
+                },
+                ctls,
+                target,
+            },
+            Op::Phase { angle, ctls } => Op::Phase {
+                angle: -angle,
+                ctls,
+            },
+        }
+    }
+
+    fn controlled(self, controls: &[usize]) -> Self {
+        let add = |ctls: Vec<usize>| controls.iter().copied().chain(ctls).collect();
+        match self {
+            Op::Gate { gate, ctls, target } => Op::Gate {
+                gate,
+                ctls: add(ctls),
+                target,
+            },
+            Op::Phase { angle, ctls } => Op::Phase {
+                angle,
+                ctls: add(ctls),
+            },
+        }
+    }
+}
+
+/// The condition of an `if` statement, which compares the value of the bits, least significant first, to an
+/// integer.
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    bits: Vec<usize>,
+    value: u64,
+    equal: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Instruction {
+    Op(Op),
+    Measure {
+        qubit: usize,
+        bit: usize,
+    },
+    Reset(usize),
+    If {
+        condition: Condition,
+        then: Vec<Instruction>,
+        otherwise: Vec<Instruction>,
+    },
+}
+
+impl Circuit {
+    /// Parses the given `OpenQASM` source.
+    pub(crate) fn parse(source: &str) -> Result<Self, String> {
+        parser::parse(source)
+    }
+
+    /// Runs the circuit as one shot on the simulator of the current thread, which must have been initialized, and
+    /// records every classical register as output.
+    pub(crate) fn run(&self) {
+        run_instructions(&self.instructions);
+        for register in &self.registers {
+            let tag = CString::new(register.name.as_str())
+                .expect("Register names should not contain null bytes.");
+            if register.scalar {
+                __quantum__rt__result_record_output(
+                    result(register.start),
+                    tag.as_ptr().cast_mut(),
+                );
+            } else {
+                let size =
+                    i64::try_from(register.size).expect("Register size should fit in an i64.");
+                __quantum__rt__array_record_output(size, tag.as_ptr().cast_mut());
+                for bit in register.start..register.start + register.size {
+                    __quantum__rt__result_record_output(result(bit), null_mut());
+                }
+            }
+        }
+    }
+}
+
+fn run_instructions(instructions: &[Instruction]) {
+    for instruction in instructions {
+        match instruction {
+            Instruction::Op(Op::Gate { gate, ctls, target }) => apply_gate(*gate, ctls, *target),
+            Instruction::Op(Op::Phase { angle, ctls }) => apply_phase(*angle, ctls),
+            Instruction::Measure { qubit, bit } => {
+                __quantum__qis__mz__body(self::qubit(*qubit), result(*bit));
+            }
+            Instruction::Reset(qubit) => __quantum__qis__reset__body(self::qubit(*qubit)),
+            Instruction::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let value = condition
+                    .bits
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, bit)| __quantum__qis__read_result__body(result(**bit)))
+                    .fold(0_u64, |value, (index, _)| value | (1 << index));
+                if (value == condition.value) == condition.equal {
+                    run_instructions(then);
+                } else {
+                    run_instructions(otherwise);
+                }
+            }
+        }
+    }
+}
+
+fn qubit(id: usize) -> *mut c_void {
+    id as *mut c_void
+}
+
+fn result(id: usize) -> *mut c_void {
+    id as *mut c_void
+}
+
+fn apply_gate(gate: Gate, ctls: &[usize], target: usize) {
+    let target = qubit(target);
+    match (gate, ctls) {
+        (Gate::X, []) => __quantum__qis__x__body(target),
+        (Gate::Y, []) => __quantum__qis__y__body(target),
+        (Gate::Z, []) => __quantum__qis__z__body(target),
+        (Gate::H, []) => __quantum__qis__h__body(target),
+        (Gate::S, []) => __quantum__qis__s__body(target),
+        (Gate::Sdg, []) => __quantum__qis__s__adj(target),
+        (Gate::T, []) => __quantum__qis__t__body(target),
+        (Gate::Tdg, []) => __quantum__qis__t__adj(target),
+        (Gate::Rx(theta), []) => __quantum__qis__rx__body(theta, target),
+        (Gate::Ry(theta), []) => __quantum__qis__ry__body(theta, target),
+        (Gate::Rz(theta), []) => __quantum__qis__rz__body(theta, target),
+        (Gate::X, [ctl]) => __quantum__qis__cx__body(qubit(*ctl), target),
+        (Gate::Y, [ctl]) => __quantum__qis__cy__body(qubit(*ctl), target),
+        (Gate::Z, [ctl]) => __quantum__qis__cz__body(qubit(*ctl), target),
+        (Gate::X, [first, second]) => {
+            __quantum__qis__ccx__body(qubit(*first), qubit(*second), target);
+        }
+        _ => with_controls(ctls, |ctls| unsafe {
+            match gate {
+                Gate::X => __quantum__qis__x__ctl(ctls, target),
+                Gate::Y => __quantum__qis__y__ctl(ctls, target),
+                Gate::Z => __quantum__qis__z__ctl(ctls, target),
+                Gate::H => __quantum__qis__h__ctl(ctls, target),
+                Gate::S => __quantum__qis__s__ctl(ctls, target),
+                Gate::Sdg => __quantum__qis__s__ctladj(ctls, target),
+                Gate::T => __quantum__qis__t__ctl(ctls, target),
+                Gate::Tdg => __quantum__qis__t__ctladj(ctls, target),
+                Gate::Rx(theta) => with_tuple(
+                    RotationArgs {
+                        theta,
+                        qubit: target,
+                    },
+                    |args| {
+                        __quantum__qis__rx__ctl(ctls, args);
+                    },
+                ),
+                Gate::Ry(theta) => with_tuple(
+                    RotationArgs {
+                        theta,
+                        qubit: target,
+                    },
+                    |args| {
+                        __quantum__qis__ry__ctl(ctls, args);
+                    },
+                ),
+                Gate::Rz(theta) => with_tuple(
+                    RotationArgs {
+                        theta,
+                        qubit: target,
+                    },
+                    |args| {
+                        __quantum__qis__rz__ctl(ctls, args);
+                    },
+                ),
+            }
+        }),
+    }
+}
+
+/// Applies a global phase, which is a phase gate on one of the controls when controlled and has no effect
+/// otherwise.
+fn apply_phase(angle: f64, ctls: &[usize]) {
+    let Some(&first) = ctls.first() else {
+        return;
+    };
+    // A controlled rotation about the identity by -2 times the angle applies the phase to the controlled subspace.
+    let args = PauliRotationArgs {
+        pauli: Pauli::I,
+        theta: -2.0 * angle,
+        qubit: qubit(first),
+    };
+    with_controls(ctls, |ctls| {
+        with_tuple(args, |args| unsafe { __quantum__qis__r__ctl(ctls, args) });
+    });
+}
+
+#[repr(C)]
+struct RotationArgs {
+    theta: c_double,
+    qubit: *mut c_void,
+}
+
+#[repr(C)]
+struct PauliRotationArgs {
+    pauli: Pauli,
+    theta: c_double,
+    qubit: *mut c_void,
+}
+
+/// Calls the function with a QIR array of the given control qubits, which is released afterwards.
+fn with_controls(ctls: &[usize], f: impl FnOnce(*const QirArray)) {
+    // The elements of the array are qubit pointers.
+    let array = __quantum__rt__array_create_1d(8, ctls.len() as u64);
+    for (index, ctl) in ctls.iter().enumerate() {
+        unsafe {
+            __quantum__rt__array_get_element_ptr_1d(array, index as u64)
+                .cast::<*mut c_void>()
+                .write_unaligned(qubit(*ctl));
+        }
+    }
+    f(array);
+    unsafe { __quantum__rt__array_update_reference_count(array, -1) };
+}
+
+/// Calls the function with a QIR tuple holding the given arguments, which is released afterwards.
+fn with_tuple<T>(args: T, f: impl FnOnce(*mut *const Vec<u8>)) {
+    let tuple = __quantum__rt__tuple_create(size_of::<T>() as u64);
+    unsafe {
+        tuple.cast::<T>().write_unaligned(args);
+    }
+    f(tuple);
+    unsafe { __quantum__rt__tuple_update_reference_count(tuple, -1) };
+}
@@ -0,0 +1,1182 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::{
+    collections::{HashMap, HashSet},
+    f64::consts::{E, PI, TAU},
+};
+
+use super::{Circuit, Condition, Gate, Instruction, Op, Register};
+
+/// The keywords of `OpenQASM` 3 statements that are not supported, which are reported as such rather than as
+/// unknown gates.
+const UNSUPPORTED_STATEMENTS: &[&str] = &[
+    "angle",
+    "array",
+    "bool",
+    "box",
+    "break",
+    "cal",
+    "complex",
+    "const",
+    "continue",
+    "def",
+    "defcal",
+    "defcalgrammar",
+    "delay",
+    "duration",
+    "end",
+    "extern",
+    "float",
+    "for",
+    "input",
+    "int",
+    "let",
+    "negctrl",
+    "opaque",
+    "output",
+    "pow",
+    "return",
+    "stretch",
+    "switch",
+    "uint",
+    "while",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(u64),
+    Float(f64),
+    Str(String),
+    Symbol(&'static str),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Ident(name) => format!("'{name}'"),
+            Token::Int(value) => format!("'{value}'"),
+            Token::Float(value) => format!("'{value}'"),
+            Token::Str(value) => format!("\"{value}\""),
+            Token::Symbol(symbol) => format!("'{symbol}'"),
+        }
+    }
+}
+
+const SYMBOLS: &[&str] = &[
+    "->", "==", "!=", ";", ",", "(", ")", "[", "]", "{", "}", "=", "+", "-", "*", "/", "^", "@",
+    "!", ":",
+];
+
+/// Splits the source into tokens along with the line on which each token starts, skipping whitespace and
+/// comments.
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        if c == '\n' {
+            line += 1;
+            rest = &rest[1..];
+        } else if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if let Some(comment) = rest.strip_prefix("//") {
+            rest = comment.find('\n').map_or("", |end| &comment[end..]);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            let end = comment
+                .find("*/")
+                .ok_or_else(|| error(line, "Unterminated comment."))?;
+            line += comment[..end].matches('\n').count();
+            rest = &comment[end + 2..];
+        } else if c == 'π' {
+            tokens.push((Token::Ident("pi".to_owned()), line));
+            rest = &rest[c.len_utf8()..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push((Token::Ident(rest[..end].to_owned()), line));
+            rest = &rest[end..];
+        } else if c.is_ascii_digit()
+            || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            let (token, len) = number(rest, line)?;
+            tokens.push((token, line));
+            rest = &rest[len..];
+        } else if c == '"' {
+            let end = rest[1..]
+                .find('"')
+                .ok_or_else(|| error(line, "Unterminated string."))?;
+            tokens.push((Token::Str(rest[1..=end].to_owned()), line));
+            rest = &rest[end + 2..];
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            tokens.push((Token::Symbol(symbol), line));
+            rest = &rest[symbol.len()..];
+        } else {
+            return Err(error(line, &format!("Unexpected character '{c}'.")));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Reads the number at the start of the text, returning it with the length of its text.
+fn number(text: &str, line: usize) -> Result<(Token, usize), String> {
+    let mut len = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    if text[len..].starts_with(['e', 'E']) {
+        let exponent = &text[len + 1..];
+        let sign = usize::from(exponent.starts_with(['+', '-']));
+        let digits = exponent[sign..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(exponent.len() - sign);
+        if digits > 0 {
+            len += 1 + sign + digits;
+        }
+    }
+    let text = &text[..len];
+    if let Ok(value) = text.parse() {
+        Ok((Token::Int(value), len))
+    } else {
+        text.parse()
+            .map(|value| (Token::Float(value), len))
+            .map_err(|_| error(line, &format!("Invalid number '{text}'.")))
+    }
+}
+
+fn error(line: usize, message: &str) -> String {
+    format!("Failed to parse OpenQASM on line {line}: {message}")
+}
+
+/// An arithmetic expression for a gate parameter, which may refer to the parameters of the enclosing gate.
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(f64),
+    Ident(String),
+    Neg(Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Call(String, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, params: &HashMap<String, f64>) -> Result<f64, String> {
+        Ok(match self {
+            Expr::Number(value) => *value,
+            Expr::Ident(name) => match (params.get(name), name.as_str()) {
+                (Some(value), _) => *value,
+                (None, "pi") => PI,
+                (None, "tau") => TAU,
+                (None, "euler") => E,
+                _ => return Err(format!("Unknown identifier '{name}'.")),
+            },
+            Expr::Neg(operand) => -operand.eval(params)?,
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(params)?, rhs.eval(params)?);
+                match *op {
+                    "+" => lhs + rhs,
+                    "-" => lhs - rhs,
+                    "*" => lhs * rhs,
+                    "/" => lhs / rhs,
+                    "^" => lhs.powf(rhs),
+                    _ => {
+                        unreachable!("Only arithmetic operators are parsed as binary expressions.")
+                    }
+                }
+            }
+            Expr::Call(function, operand) => {
+                let operand = operand.eval(params)?;
+                match function.as_str() {
+                    "sin" => operand.sin(),
+                    "cos" => operand.cos(),
+                    "tan" => operand.tan(),
+                    "arcsin" | "asin" => operand.asin(),
+                    "arccos" | "acos" => operand.acos(),
+                    "arctan" | "atan" => operand.atan(),
+                    "exp" => operand.exp(),
+                    "ln" | "log" => operand.ln(),
+                    "sqrt" => operand.sqrt(),
+                    _ => return Err(format!("Unknown function '{function}'.")),
+                }
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Modifier {
+    Inv,
+    Ctrl(usize),
+}
+
+/// A gate applied to named qubits, either in the body of a gate definition or at the top level, where the names
+/// are resolved against the registers.
+#[derive(Debug, Clone)]
+struct GateCall {
+    modifiers: Vec<Modifier>,
+    name: String,
+    params: Vec<Expr>,
+    args: Vec<(String, Option<usize>)>,
+    line: usize,
+}
+
+struct GateDefinition {
+    params: Vec<String>,
+    qubits: Vec<String>,
+    body: Vec<GateCall>,
+}
+
+/// Parses `OpenQASM` 2.0 or 3.0 source into a circuit, expanding all gates into primitive operations.
+pub(super) fn parse(source: &str) -> Result<Circuit, String> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+        qregs: HashMap::new(),
+        cregs: HashMap::new(),
+        registers: Vec::new(),
+        qubits: 0,
+        bits: 0,
+        gates: HashMap::new(),
+    };
+    let mut instructions = Vec::new();
+    parser.version()?;
+    while parser.pos < parser.tokens.len() {
+        parser.statement(&mut instructions)?;
+    }
+    Ok(Circuit {
+        registers: parser.registers,
+        instructions,
+    })
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    qregs: HashMap<String, Register>,
+    cregs: HashMap<String, Register>,
+    /// The classical registers in declaration order, which is the order in which they are recorded as output.
+    registers: Vec<Register>,
+    qubits: usize,
+    bits: usize,
+    gates: HashMap<String, GateDefinition>,
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn error(&self, message: &str) -> String {
+        error(self.line(), message)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .map(|(token, _)| token.clone())
+            .ok_or_else(|| self.error("Unexpected end of file."))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+    }
+
+    fn is_ident(&self, ident: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(name)) if name == ident)
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = self.is_symbol(symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{symbol}'")))
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected("an identifier")),
+        }
+    }
+
+    fn expect_int(&mut self) -> Result<usize, String> {
+        match self.peek() {
+            Some(Token::Int(value)) => {
+                let value =
+                    usize::try_from(*value).map_err(|_| self.error("Integer is too large."))?;
+                self.pos += 1;
+                Ok(value)
+            }
+            _ => Err(self.unexpected("an integer")),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> String {
+        match self.peek() {
+            Some(token) => self.error(&format!("Expected {expected}, found {}.", token.describe())),
+            None => self.error(&format!("Expected {expected}, found the end of file.")),
+        }
+    }
+
+    /// Skips the tokens up to the end of the current statement, for statements that have no effect on simulation.
+    fn skip_statement(&mut self) -> Result<(), String> {
+        while !self.eat_symbol(";") {
+            self.next()?;
+        }
+        Ok(())
+    }
+
+    /// Parses the optional version declaration, which must come first.
+    fn version(&mut self) -> Result<(), String> {
+        if !self.is_ident("OPENQASM") {
+            return Ok(());
+        }
+        self.pos += 1;
+        match self.next()? {
+            Token::Int(2 | 3) => {}
+            Token::Float(version) if (2.0..4.0).contains(&version) => {}
+            token => {
+                return Err(self.error(&format!(
+                    "Unsupported OpenQASM version {}.",
+                    token.describe()
+                )))
+            }
+        }
+        self.expect_symbol(";")
+    }
+
+    fn statement(&mut self, out: &mut Vec<Instruction>) -> Result<(), String> {
+        let line = self.line();
+        let keyword = match self.peek() {
+            Some(Token::Ident(name)) => name.clone(),
+            _ => return Err(self.unexpected("a statement")),
+        };
+        match keyword.as_str() {
+            "include" => {
+                self.pos += 1;
+                match self.next()? {
+                    Token::Str(file) if file == "qelib1.inc" || file == "stdgates.inc" => {}
+                    Token::Str(file) => {
+                        return Err(error(line, &format!("Unsupported include '{file}'.")))
+                    }
+                    _ => return Err(error(line, "Expected a file name after 'include'.")),
+                }
+                self.expect_symbol(";")
+            }
+            "qreg" | "creg" => {
+                self.pos += 1;
+                let name = self.expect_ident()?;
+                self.expect_symbol("[")?;
+                let size = self.expect_int()?;
+                self.expect_symbol("]")?;
+                self.expect_symbol(";")?;
+                self.declare(&name, size, keyword == "creg", false, line)
+            }
+            "qubit" | "bit" => {
+                self.pos += 1;
+                let size = if self.eat_symbol("[") {
+                    let size = self.expect_int()?;
+                    self.expect_symbol("]")?;
+                    Some(size)
+                } else {
+                    None
+                };
+                let name = self.expect_ident()?;
+                if self.is_symbol("=") {
+                    return Err(error(line, "Initializing declarations are not supported."));
+                }
+                self.expect_symbol(";")?;
+                self.declare(
+                    &name,
+                    size.unwrap_or(1),
+                    keyword == "bit",
+                    size.is_none(),
+                    line,
+                )
+            }
+            "gate" => {
+                self.pos += 1;
+                self.gate_definition()
+            }
+            "measure" => {
+                self.pos += 1;
+                let qubits = self.qubit_arg()?;
+                let bits = if self.eat_symbol("->") {
+                    self.bit_arg()?
+                } else {
+                    // A measurement whose outcome is discarded is stored in a bit outside of all registers.
+                    let bits = (self.bits..self.bits + qubits.len()).collect();
+                    self.bits += qubits.len();
+                    bits
+                };
+                self.expect_symbol(";")?;
+                measure(&qubits, &bits, line, out)
+            }
+            "reset" => {
+                self.pos += 1;
+                let qubits = self.qubit_arg()?;
+                self.expect_symbol(";")?;
+                out.extend(qubits.into_iter().map(Instruction::Reset));
+                Ok(())
+            }
+            "barrier" => self.skip_statement(),
+            "if" => {
+                self.pos += 1;
+                self.if_statement(out)
+            }
+            name if self.cregs.contains_key(name) => {
+                let bits = self.bit_arg()?;
+                self.expect_symbol("=")?;
+                if !self.is_ident("measure") {
+                    return Err(error(line, "Only measurements can be assigned to bits."));
+                }
+                self.pos += 1;
+                let qubits = self.qubit_arg()?;
+                self.expect_symbol(";")?;
+                measure(&qubits, &bits, line, out)
+            }
+            name if UNSUPPORTED_STATEMENTS.contains(&name) => {
+                Err(error(line, &format!("Unsupported statement '{name}'.")))
+            }
+            _ => {
+                let call = self.gate_call()?;
+                self.apply(&call, out)
+            }
+        }
+    }
+
+    fn if_statement(&mut self, out: &mut Vec<Instruction>) -> Result<(), String> {
+        self.expect_symbol("(")?;
+        let condition = self.condition()?;
+        self.expect_symbol(")")?;
+        let mut then = Vec::new();
+        self.block(&mut then)?;
+        let mut otherwise = Vec::new();
+        if self.is_ident("else") {
+            self.pos += 1;
+            self.block(&mut otherwise)?;
+        }
+        out.push(Instruction::If {
+            condition,
+            then,
+            otherwise,
+        });
+        Ok(())
+    }
+
+    /// Parses a block in braces or a single statement, as the body of an `if` statement.
+    fn block(&mut self, out: &mut Vec<Instruction>) -> Result<(), String> {
+        if self.eat_symbol("{") {
+            while !self.eat_symbol("}") {
+                if self.peek().is_none() {
+                    return Err(self.unexpected("'}'"));
+                }
+                self.statement(out)?;
+            }
+            Ok(())
+        } else {
+            self.statement(out)
+        }
+    }
+
+    fn declare(
+        &mut self,
+        name: &str,
+        size: usize,
+        classical: bool,
+        scalar: bool,
+        line: usize,
+    ) -> Result<(), String> {
+        if self.qregs.contains_key(name) || self.cregs.contains_key(name) {
+            return Err(error(
+                line,
+                &format!("Register '{name}' is already declared."),
+            ));
+        }
+        let counter = if classical {
+            &mut self.bits
+        } else {
+            &mut self.qubits
+        };
+        let register = Register {
+            name: name.to_owned(),
+            start: *counter,
+            size,
+            scalar,
+        };
+        *counter += size;
+        if classical {
+            self.registers.push(register.clone());
+            self.cregs.insert(name.to_owned(), register);
+        } else {
+            self.qregs.insert(name.to_owned(), register);
+        }
+        Ok(())
+    }
+
+    /// Parses a register or an indexed element of a register.
+    fn arg(&mut self) -> Result<(String, Option<usize>), String> {
+        let name = self.expect_ident()?;
+        let index = if self.eat_symbol("[") {
+            let index = self.expect_int()?;
+            self.expect_symbol("]")?;
+            Some(index)
+        } else {
+            None
+        };
+        Ok((name, index))
+    }
+
+    fn qubit_arg(&mut self) -> Result<Vec<usize>, String> {
+        let line = self.line();
+        let arg = self.arg()?;
+        resolve(&self.qregs, &arg).map_err(|e| error(line, &e))
+    }
+
+    fn bit_arg(&mut self) -> Result<Vec<usize>, String> {
+        let line = self.line();
+        let arg = self.arg()?;
+        resolve(&self.cregs, &arg).map_err(|e| error(line, &e))
+    }
+
+    /// Parses the condition of an `if` statement, which compares a register or a bit to an integer.
+    fn condition(&mut self) -> Result<Condition, String> {
+        let line = self.line();
+        let negated = self.eat_symbol("!");
+        let bits = self.bit_arg()?;
+        if bits.len() > 64 {
+            return Err(error(
+                line,
+                "Conditions on more than 64 bits are not supported.",
+            ));
+        }
+        let (value, equal) = if negated {
+            (0, true)
+        } else if self.eat_symbol("==") {
+            (self.condition_value()?, true)
+        } else if self.eat_symbol("!=") {
+            (self.condition_value()?, false)
+        } else {
+            (0, false)
+        };
+        Ok(Condition { bits, value, equal })
+    }
+
+    fn condition_value(&mut self) -> Result<u64, String> {
+        match self.next()? {
+            Token::Int(value) => Ok(value),
+            Token::Ident(name) if name == "true" => Ok(1),
+            Token::Ident(name) if name == "false" => Ok(0),
+            token => Err(self.error(&format!("Expected an integer, found {}.", token.describe()))),
+        }
+    }
+
+    fn gate_definition(&mut self) -> Result<(), String> {
+        let line = self.line();
+        let name = self.expect_ident()?;
+        if builtin_arity(&name).is_some() || self.gates.contains_key(&name) {
+            return Err(error(line, &format!("Gate '{name}' is already defined.")));
+        }
+        let mut params = Vec::new();
+        if self.eat_symbol("(") && !self.eat_symbol(")") {
+            loop {
+                params.push(self.expect_ident()?);
+                if self.eat_symbol(")") {
+                    break;
+                }
+                self.expect_symbol(",")?;
+            }
+        }
+        let mut qubits = vec![self.expect_ident()?];
+        while self.eat_symbol(",") {
+            qubits.push(self.expect_ident()?);
+        }
+        self.expect_symbol("{")?;
+        let mut body = Vec::new();
+        while !self.eat_symbol("}") {
+            if self.is_ident("barrier") {
+                self.skip_statement()?;
+                continue;
+            }
+            let call = self.gate_call()?;
+            for (qubit, index) in &call.args {
+                if index.is_some() || !qubits.contains(qubit) {
+                    return Err(error(
+                        call.line,
+                        &format!("Gate '{name}' has no qubit argument '{qubit}'."),
+                    ));
+                }
+            }
+            body.push(call);
+        }
+        self.gates.insert(
+            name,
+            GateDefinition {
+                params,
+                qubits,
+                body,
+            },
+        );
+        Ok(())
+    }
+
+    fn gate_call(&mut self) -> Result<GateCall, String> {
+        let line = self.line();
+        let mut modifiers = Vec::new();
+        let name = loop {
+            let name = self.expect_ident()?;
+            match name.as_str() {
+                "inv" => modifiers.push(Modifier::Inv),
+                "ctrl" => {
+                    let count = if self.eat_symbol("(") {
+                        let count = self.expect_int()?;
+                        self.expect_symbol(")")?;
+                        count
+                    } else {
+                        1
+                    };
+                    modifiers.push(Modifier::Ctrl(count));
+                }
+                _ => break name,
+            }
+            self.expect_symbol("@")?;
+        };
+        let mut params = Vec::new();
+        if self.eat_symbol("(") && !self.eat_symbol(")") {
+            loop {
+                params.push(self.expr()?);
+                if self.eat_symbol(")") {
+                    break;
+                }
+                self.expect_symbol(",")?;
+            }
+        }
+        let mut args = Vec::new();
+        if !self.eat_symbol(";") {
+            loop {
+                args.push(self.arg()?);
+                if self.eat_symbol(";") {
+                    break;
+                }
+                self.expect_symbol(",")?;
+            }
+        }
+        Ok(GateCall {
+            modifiers,
+            name,
+            params,
+            args,
+            line,
+        })
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.term()?;
+        while let Some(op) = ["+", "-"].into_iter().find(|op| self.is_symbol(op)) {
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.term()?));
+        }
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.factor()?;
+        while let Some(op) = ["*", "/"].into_iter().find(|op| self.is_symbol(op)) {
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.factor()?));
+        }
+        Ok(lhs)
+    }
+
+    fn factor(&mut self) -> Result<Expr, String> {
+        if self.eat_symbol("-") {
+            return Ok(Expr::Neg(Box::new(self.factor()?)));
+        }
+        if self.eat_symbol("+") {
+            return self.factor();
+        }
+        let base = self.primary()?;
+        if self.eat_symbol("^") {
+            Ok(Expr::Binary("^", Box::new(base), Box::new(self.factor()?)))
+        } else {
+            Ok(base)
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next()? {
+            Token::Int(value) => Ok(Expr::Number(value as f64)),
+            Token::Float(value) => Ok(Expr::Number(value)),
+            Token::Ident(name) if self.is_symbol("(") => {
+                self.pos += 1;
+                let operand = self.expr()?;
+                self.expect_symbol(")")?;
+                Ok(Expr::Call(name, Box::new(operand)))
+            }
+            Token::Ident(name) => Ok(Expr::Ident(name)),
+            Token::Symbol("(") => {
+                let expr = self.expr()?;
+                self.expect_symbol(")")?;
+                Ok(expr)
+            }
+            token => Err(self.error(&format!(
+                "Expected an expression, found {}.",
+                token.describe()
+            ))),
+        }
+    }
+
+    /// Applies a gate at the top level, broadcasting it over the elements of registers passed as arguments.
+    fn apply(&self, call: &GateCall, out: &mut Vec<Instruction>) -> Result<(), String> {
+        let params = call
+            .params
+            .iter()
+            .map(|param| param.eval(&HashMap::new()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| error(call.line, &e))?;
+        let args = call
+            .args
+            .iter()
+            .map(|arg| resolve(&self.qregs, arg))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| error(call.line, &e))?;
+        let size = args
+            .iter()
+            .zip(&call.args)
+            .filter(|(_, (_, index))| index.is_none())
+            .map(|(qubits, _)| qubits.len())
+            .max()
+            .unwrap_or(1);
+        let mut ops = Vec::new();
+        for index in 0..size {
+            let qubits = args
+                .iter()
+                .map(|qubits| match qubits.as_slice() {
+                    [qubit] => Ok(*qubit),
+                    _ => qubits.get(index).copied().ok_or_else(|| {
+                        format!("Registers passed to gate '{}' differ in size.", call.name)
+                    }),
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| error(call.line, &e))?;
+            self.call(&call.modifiers, &call.name, &params, &qubits, &mut ops)
+                .map_err(|e| error(call.line, &e))?;
+        }
+        out.extend(ops.into_iter().map(Instruction::Op));
+        Ok(())
+    }
+
+    /// Expands a gate with modifiers applied to the given qubits into primitive operations.
+    fn call(
+        &self,
+        modifiers: &[Modifier],
+        name: &str,
+        params: &[f64],
+        qubits: &[usize],
+        out: &mut Vec<Op>,
+    ) -> Result<(), String> {
+        let mut distinct = HashSet::new();
+        if !qubits.iter().all(|qubit| distinct.insert(qubit)) {
+            return Err(format!(
+                "Gate '{name}' is applied to the same qubit more than once."
+            ));
+        }
+        match modifiers.split_first() {
+            None => self.expand(name, params, qubits, out),
+            Some((Modifier::Inv, rest)) => {
+                let mut ops = Vec::new();
+                self.call(rest, name, params, qubits, &mut ops)?;
+                out.extend(ops.into_iter().rev().map(Op::inverse));
+                Ok(())
+            }
+            Some((Modifier::Ctrl(count), rest)) => {
+                if qubits.len() < *count {
+                    return Err(format!("Gate '{name}' has fewer qubits than controls."));
+                }
+                let (ctls, targets) = qubits.split_at(*count);
+                let mut ops = Vec::new();
+                self.call(rest, name, params, targets, &mut ops)?;
+                out.extend(ops.into_iter().map(|op| op.controlled(ctls)));
+                Ok(())
+            }
+        }
+    }
+
+    fn expand(
+        &self,
+        name: &str,
+        params: &[f64],
+        qubits: &[usize],
+        out: &mut Vec<Op>,
+    ) -> Result<(), String> {
+        let (param_count, qubit_count) = match (builtin_arity(name), self.gates.get(name)) {
+            (Some(arity), _) => arity,
+            (None, Some(gate)) => (gate.params.len(), gate.qubits.len()),
+            (None, None) => return Err(format!("Unknown gate '{name}'.")),
+        };
+        if params.len() != param_count {
+            return Err(format!(
+                "Gate '{name}' expects {param_count} parameters, but {} were given.",
+                params.len()
+            ));
+        }
+        if qubits.len() != qubit_count {
+            return Err(format!(
+                "Gate '{name}' expects {qubit_count} qubits, but {} were given.",
+                qubits.len()
+            ));
+        }
+
+        let Some(gate) = self.gates.get(name) else {
+            builtin(name, params, qubits, out);
+            return Ok(());
+        };
+        let env: HashMap<String, f64> = gate
+            .params
+            .iter()
+            .cloned()
+            .zip(params.iter().copied())
+            .collect();
+        for call in &gate.body {
+            let params = call
+                .params
+                .iter()
+                .map(|param| param.eval(&env))
+                .collect::<Result<Vec<_>, _>>()?;
+            let qubits: Vec<usize> = call
+                .args
+                .iter()
+                .map(|(arg, _)| {
+                    let position = gate
+                        .qubits
+                        .iter()
+                        .position(|qubit| qubit == arg)
+                        .expect("Qubit arguments in gate bodies should be checked when the gate is defined.");
+                    qubits[position]
+                })
+                .collect();
+            self.call(&call.modifiers, &call.name, &params, &qubits, out)?;
+        }
+        Ok(())
+    }
+}
+
+fn measure(
+    qubits: &[usize],
+    bits: &[usize],
+    line: usize,
+    out: &mut Vec<Instruction>,
+) -> Result<(), String> {
+    if qubits.len() != bits.len() {
+        return Err(error(
+            line,
+            "Measurements must have as many qubits as bits.",
+        ));
+    }
+    out.extend(
+        qubits
+            .iter()
+            .zip(bits)
+            .map(|(&qubit, &bit)| Instruction::Measure { qubit, bit }),
+    );
+    Ok(())
+}
+
+/// Resolves a register or an element of a register to the indices of its qubits or bits.
+fn resolve(
+    registers: &HashMap<String, Register>,
+    (name, index): &(String, Option<usize>),
+) -> Result<Vec<usize>, String> {
+    let register = registers
+        .get(name)
+        .ok_or_else(|| format!("Unknown register '{name}'."))?;
+    match index {
+        None => Ok((register.start..register.start + register.size).collect()),
+        Some(index) if *index < register.size && !register.scalar => {
+            Ok(vec![register.start + index])
+        }
+        Some(index) => Err(format!(
+            "Index {index} is out of range for register '{name}' of size {}.",
+            register.size
+        )),
+    }
+}
+
+/// The number of parameters and qubits of the built-in gates, which include the gates of `qelib1.inc` and
+/// `stdgates.inc`.
+fn builtin_arity(name: &str) -> Option<(usize, usize)> {
+    Some(match name {
+        "id" | "x" | "y" | "z" | "h" | "s" | "sdg" | "t" | "tdg" | "sx" | "sxdg" => (0, 1),
+        "rx" | "ry" | "rz" | "p" | "phase" | "u0" | "u1" => (1, 1),
+        "u2" => (2, 1),
+        "U" | "u" | "u3" => (3, 1),
+        "CX" | "cx" | "cy" | "cz" | "ch" | "swap" => (0, 2),
+        "crx" | "cry" | "crz" | "cp" | "cphase" | "cu1" | "rxx" | "rzz" => (1, 2),
+        "cu3" => (3, 2),
+        "cu" => (4, 2),
+        "ccx" | "cswap" => (0, 3),
+        "gphase" => (1, 0),
+        _ => return None,
+    })
+}
+
+fn controlled(ops: impl IntoIterator<Item = Op>, ctl: usize) -> impl Iterator<Item = Op> {
+    ops.into_iter().map(move |op| op.controlled(&[ctl]))
+}
+
+/// Expands a built-in gate into primitive operations, up to a global phase where the definitions in `qelib1.inc`
+/// and `stdgates.inc` differ.
+fn builtin(name: &str, params: &[f64], qubits: &[usize], out: &mut Vec<Op>) {
+    let gate = |gate, ctls: &[usize], target| Op::Gate {
+        gate,
+        ctls: ctls.to_vec(),
+        target,
+    };
+    let phase = |angle| Op::Phase {
+        angle,
+        ctls: Vec::new(),
+    };
+    // The phase gate with the given angle, as a rotation about Z and a global phase.
+    let p = |angle: f64, target| [phase(angle / 2.0), gate(Gate::Rz(angle), &[], target)];
+    let u3 = |theta: f64, phi: f64, lambda: f64, target| {
+        [
+            phase((phi + lambda) / 2.0),
+            gate(Gate::Rz(lambda), &[], target),
+            gate(Gate::Ry(theta), &[], target),
+            gate(Gate::Rz(phi), &[], target),
+        ]
+    };
+
+    match name {
+        "id" | "u0" => {}
+        "x" => out.push(gate(Gate::X, &[], qubits[0])),
+        "y" => out.push(gate(Gate::Y, &[], qubits[0])),
+        "z" => out.push(gate(Gate::Z, &[], qubits[0])),
+        "h" => out.push(gate(Gate::H, &[], qubits[0])),
+        "s" => out.push(gate(Gate::S, &[], qubits[0])),
+        "sdg" => out.push(gate(Gate::Sdg, &[], qubits[0])),
+        "t" => out.push(gate(Gate::T, &[], qubits[0])),
+        "tdg" => out.push(gate(Gate::Tdg, &[], qubits[0])),
+        "sx" => out.extend([phase(PI / 4.0), gate(Gate::Rx(PI / 2.0), &[], qubits[0])]),
+        "sxdg" => out.extend([phase(-PI / 4.0), gate(Gate::Rx(-PI / 2.0), &[], qubits[0])]),
+        "rx" => out.push(gate(Gate::Rx(params[0]), &[], qubits[0])),
+        "ry" => out.push(gate(Gate::Ry(params[0]), &[], qubits[0])),
+        "rz" => out.push(gate(Gate::Rz(params[0]), &[], qubits[0])),
+        "p" | "phase" | "u1" => out.extend(p(params[0], qubits[0])),
+        "u2" => out.extend(u3(PI / 2.0, params[0], params[1], qubits[0])),
+        "U" | "u" | "u3" => out.extend(u3(params[0], params[1], params[2], qubits[0])),
+        "CX" | "cx" => out.push(gate(Gate::X, &qubits[..1], qubits[1])),
+        "cy" => out.push(gate(Gate::Y, &qubits[..1], qubits[1])),
+        "cz" => out.push(gate(Gate::Z, &qubits[..1], qubits[1])),
+        "ch" => out.push(gate(Gate::H, &qubits[..1], qubits[1])),
+        "swap" => out.extend([
+            gate(Gate::X, &qubits[..1], qubits[1]),
+            gate(Gate::X, &qubits[1..], qubits[0]),
+            gate(Gate::X, &qubits[..1], qubits[1]),
+        ]),
+        "crx" => out.push(gate(Gate::Rx(params[0]), &qubits[..1], qubits[1])),
+        "cry" => out.push(gate(Gate::Ry(params[0]), &qubits[..1], qubits[1])),
+        "crz" => out.push(gate(Gate::Rz(params[0]), &qubits[..1], qubits[1])),
+        "cp" | "cphase" | "cu1" => out.extend(controlled(p(params[0], qubits[1]), qubits[0])),
+        "rxx" => out.extend([
+            gate(Gate::H, &[], qubits[0]),
+            gate(Gate::H, &[], qubits[1]),
+            gate(Gate::X, &qubits[..1], qubits[1]),
+            gate(Gate::Rz(params[0]), &[], qubits[1]),
+            gate(Gate::X, &qubits[..1], qubits[1]),
+            gate(Gate::H, &[], qubits[0]),
+            gate(Gate::H, &[], qubits[1]),
+        ]),
+        "rzz" => {
+            out.push(gate(Gate::X, &qubits[..1], qubits[1]));
+            out.extend(p(params[0], qubits[1]));
+            out.push(gate(Gate::X, &qubits[..1], qubits[1]));
+        }
+        "cu3" => out.extend(controlled(
+            u3(params[0], params[1], params[2], qubits[1]),
+            qubits[0],
+        )),
+        "cu" => {
+            out.push(phase(params[3]).controlled(&qubits[..1]));
+            out.extend(controlled(
+                u3(params[0], params[1], params[2], qubits[1]),
+                qubits[0],
+            ));
+        }
+        "ccx" => out.push(gate(Gate::X, &qubits[..2], qubits[2])),
+        "cswap" => out.extend([
+            gate(Gate::X, &qubits[2..], qubits[1]),
+            gate(Gate::X, &qubits[..2], qubits[2]),
+            gate(Gate::X, &qubits[2..], qubits[1]),
+        ]),
+        "gphase" => out.push(phase(params[0])),
+        _ => unreachable!("Every gate with an arity should be expanded."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, tokenize, Token};
+    use crate::qasm::{Condition, Gate, Instruction, Op};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_tokenize() {
+        let tokens: Vec<Token> = tokenize("rx(-.5e1*π) q[0]; // comment\n/* block\n */ c -> d")
+            .expect("Source should tokenize")
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident("rx".to_owned()),
+                Token::Symbol("("),
+                Token::Symbol("-"),
+                Token::Float(5.0),
+                Token::Symbol("*"),
+                Token::Ident("pi".to_owned()),
+                Token::Symbol(")"),
+                Token::Ident("q".to_owned()),
+                Token::Symbol("["),
+                Token::Int(0),
+                Token::Symbol("]"),
+                Token::Symbol(";"),
+                Token::Ident("c".to_owned()),
+                Token::Symbol("->"),
+                Token::Ident("d".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_qasm2() {
+        let circuit = parse(
+            "OPENQASM 2.0;\ninclude \"qelib1.inc\";\nqreg q[2];\ncreg c[2];\n\
+             gate bell a, b { h a; cx a, b; }\nbell q[0], q[1];\nmeasure q -> c;\nif (c == 3) x q;\n",
+        )
+        .expect("Circuit should parse");
+        assert_eq!(circuit.registers.len(), 1);
+        assert_eq!(
+            circuit.instructions,
+            vec![
+                Instruction::Op(Op::Gate {
+                    gate: Gate::H,
+                    ctls: vec![],
+                    target: 0
+                }),
+                Instruction::Op(Op::Gate {
+                    gate: Gate::X,
+                    ctls: vec![0],
+                    target: 1
+                }),
+                Instruction::Measure { qubit: 0, bit: 0 },
+                Instruction::Measure { qubit: 1, bit: 1 },
+                Instruction::If {
+                    condition: Condition {
+                        bits: vec![0, 1],
+                        value: 3,
+                        equal: true
+                    },
+                    then: vec![
+                        Instruction::Op(Op::Gate {
+                            gate: Gate::X,
+                            ctls: vec![],
+                            target: 0
+                        }),
+                        Instruction::Op(Op::Gate {
+                            gate: Gate::X,
+                            ctls: vec![],
+                            target: 1
+                        }),
+                    ],
+                    otherwise: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_qasm3_modifiers() {
+        let circuit = parse(
+            "OPENQASM 3;\ninclude \"stdgates.inc\";\nqubit[3] q;\nbit b;\n\
+             gate r(theta) a { rz(theta / 2) a; s a; }\nctrl(2) @ inv @ r(pi) q[0], q[1], q[2];\n\
+             b = measure q[2];\nif (!b) { reset q[2]; } else reset q[1];\n",
+        )
+        .expect("Circuit should parse");
+        assert_eq!(
+            circuit.instructions,
+            vec![
+                Instruction::Op(Op::Gate {
+                    gate: Gate::Sdg,
+                    ctls: vec![0, 1],
+                    target: 2
+                }),
+                Instruction::Op(Op::Gate {
+                    gate: Gate::Rz(-PI / 2.0),
+                    ctls: vec![0, 1],
+                    target: 2
+                }),
+                Instruction::Measure { qubit: 2, bit: 0 },
+                Instruction::If {
+                    condition: Condition {
+                        bits: vec![0],
+                        value: 0,
+                        equal: true
+                    },
+                    then: vec![Instruction::Reset(2)],
+                    otherwise: vec![Instruction::Reset(1)],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |source| parse(source).expect_err("Circuit should not parse");
+        assert_eq!(
+            error("qreg q[1];\nfoo q[0];"),
+            "Failed to parse OpenQASM on line 2: Unknown gate 'foo'."
+        );
+        assert_eq!(
+            error("qreg q[2];\ncx q[0], q[0];"),
+            "Failed to parse OpenQASM on line 2: Gate 'cx' is applied to the same qubit more than once."
+        );
+        assert_eq!(
+            error("qreg q[1];\nx q[1];"),
+            "Failed to parse OpenQASM on line 2: Index 1 is out of range for register 'q' of size 1."
+        );
+        assert_eq!(
+            error("OPENQASM 3;\nqubit q;\nfor i in [0:1] { x q; }"),
+            "Failed to parse OpenQASM on line 3: Unsupported statement 'for'."
+        );
+        assert_eq!(
+            error("include \"other.inc\";"),
+            "Failed to parse OpenQASM on line 1: Unsupported include 'other.inc'."
+        );
+    }
+}
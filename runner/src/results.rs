@@ -0,0 +1,116 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use qir_backend::{
+    estimate::ResourceCounts,
+    output_recording::{OutputRecord, OutputValue},
+};
+
+/// The data produced by a single execution (shot) of an entry point.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShotResult {
+    /// The string attributes of the entry point as key/value pairs, where attributes without
+    /// a value have an empty string as value.
+    pub metadata: Vec<(String, String)>,
+    /// The output records of the shot in the order they were produced.
+    pub output: Vec<OutputRecord>,
+    /// The failure message if the shot failed, in which case the output contains the records produced
+    /// before the failure.
+    pub failure: Option<String>,
+    /// The truncation error of the shot, which is the weight of the singular values discarded by the matrix
+    /// product state simulator and zero for all other simulators.
+    pub truncation_error: f64,
+    /// The resources counted during the shot if it was run with resource estimation.
+    pub resources: Option<ResourceCounts>,
+    /// The `OpenQASM` 3 program with the gates and measurements executed by the shot if it was traced.
+    pub trace: Option<String>,
+}
+
+impl ShotResult {
+    /// Returns the exit code reported at the end of the shot, which is 0 for shots that completed and 1 for
+    /// shots that failed.
+    #[must_use]
+    pub fn exit_code(&self) -> i32 {
+        i32::from(self.failure.is_some())
+    }
+
+    /// Returns the recorded values of the shot in order, along with their tags. Values recorded with the
+    /// legacy output recording functions have no tag, and legacy array and tuple markers are skipped.
+    pub fn values(&self) -> impl Iterator<Item = (&OutputValue, Option<&str>)> {
+        self.output.iter().filter_map(|record| match record {
+            OutputRecord::Value { value, tag } => Some((value, tag.as_deref())),
+            OutputRecord::LegacyValue(value) => Some((value, None)),
+            _ => None,
+        })
+    }
+
+    /// Returns the messages emitted by the shot in order, including those recorded via
+    /// `__quantum__rt__message_record_output`.
+    pub fn messages(&self) -> impl Iterator<Item = &str> {
+        self.output.iter().filter_map(|record| match record {
+            OutputRecord::Message(msg) | OutputRecord::Info(msg) => Some(msg.as_str()),
+            _ => None,
+        })
+    }
+}
+
+/// Renders the shot in the text format of the QIR output schema, starting with a `START` line
+/// and ending with an `END` line. The failure message of a failed shot is written on a `FAILURE` line before the
+/// `END` line.
+impl Display for ShotResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "START")?;
+        for (key, value) in &self.metadata {
+            write!(f, "METADATA\t{key}")?;
+            if !value.is_empty() {
+                write!(f, "\t{value}")?;
+            }
+            writeln!(f)?;
+        }
+        for record in &self.output {
+            writeln!(f, "{record}")?;
+        }
+        if let Some(failure) = &self.failure {
+            writeln!(f, "FAILURE\t{}", failure.escape_default())?;
+        }
+        writeln!(f, "END\t{}", self.exit_code())
+    }
+}
+
+/// Groups the shots by their recorded values and failure messages, and returns how often each distinct sequence of
+/// values occurs along with the failure message of its shots, which is `None` for shots that completed.
+#[must_use]
+pub fn histogram(results: &[ShotResult]) -> HashMap<(Vec<OutputValue>, Option<String>), usize> {
+    outcomes(results)
+        .into_iter()
+        .map(|(shot, count)| {
+            let values = shot.values().map(|(value, _)| *value).collect();
+            ((values, shot.failure.clone()), count)
+        })
+        .collect()
+}
+
+/// Groups the shots by their recorded values and failure messages, returning the first shot of each group along
+/// with the size of the group. The groups are ordered by decreasing size and then by first occurrence.
+pub(crate) fn outcomes(results: &[ShotResult]) -> Vec<(&ShotResult, usize)> {
+    let mut indices: HashMap<(Vec<&OutputValue>, Option<&str>), usize> = HashMap::new();
+    let mut outcomes: Vec<(&ShotResult, usize)> = Vec::new();
+    for shot in results {
+        let key = (
+            shot.values().map(|(value, _)| value).collect(),
+            shot.failure.as_deref(),
+        );
+        let index = *indices.entry(key).or_insert_with(|| {
+            outcomes.push((shot, 0));
+            outcomes.len() - 1
+        });
+        outcomes[index].1 += 1;
+    }
+    outcomes.sort_by(|a, b| b.1.cmp(&a.1));
+    outcomes
+}
@@ -0,0 +1,240 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use std::{
+    ffi::{c_void, CStr, CString},
+    ptr::null_mut,
+};
+
+use inkwell::{
+    context::Context,
+    targets::TargetData,
+    types::{AnyTypeEnum, BasicTypeEnum},
+    values::{BasicValueEnum, FunctionValue, GenericValue, PointerValue},
+};
+use qir_backend::{
+    __quantum__rt__result_record_output,
+    arrays::{
+        QirArray, __quantum__rt__array_get_element_ptr_1d, __quantum__rt__array_get_size_1d,
+        __quantum__rt__array_update_reference_count,
+    },
+    output_recording::{record_output, OutputRecord, OutputValue},
+    result_bool::__quantum__rt__result_update_reference_count,
+    strings::{__quantum__rt__string_get_data, __quantum__rt__string_update_reference_count},
+    tuples::__quantum__rt__tuple_update_reference_count,
+};
+
+use crate::value_flow::{returned_values, ValueFlow};
+
+/// The types of entry point return values that can be recorded as output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ReturnKind {
+    Int,
+    Double,
+    Bool,
+    Result,
+    /// A string, which is only supported as the whole return value and is recorded as a message.
+    String,
+    /// A pointer to a tuple, given by the byte offsets and the kinds of its fields.
+    Tuple(Vec<(u64, ReturnKind)>),
+    /// A runtime array with elements of the given kind.
+    Array(Box<ReturnKind>),
+}
+
+/// Determines how the return value of the entry point is recorded, or `None` if the entry point does not
+/// return a value. The element types of returned arrays are not part of the signature, so they are taken from
+/// how the program accesses the elements of the arrays.
+pub(crate) fn return_kind<'ctx>(
+    context: &Context,
+    target_data: &TargetData,
+    flow: &ValueFlow<'ctx>,
+    entry_point: FunctionValue<'ctx>,
+) -> Result<Option<ReturnKind>, String> {
+    let Some(return_type) = entry_point.get_type().get_return_type() else {
+        return Ok(None);
+    };
+    if is_string_type(return_type) {
+        return Ok(Some(ReturnKind::String));
+    }
+    let values = returned_values(entry_point)
+        .into_iter()
+        .filter_map(|value| match value {
+            BasicValueEnum::PointerValue(pointer) => Some(pointer),
+            _ => None,
+        })
+        .collect();
+    value_kind(context, target_data, flow, return_type, values).map(Some)
+}
+
+/// Determines how a value of the given type is recorded, where the given pointers are the values if the type is
+/// a pointer, from which the element types of arrays are determined.
+fn value_kind<'ctx>(
+    context: &Context,
+    target_data: &TargetData,
+    flow: &ValueFlow<'ctx>,
+    ty: BasicTypeEnum<'ctx>,
+    values: Vec<PointerValue<'ctx>>,
+) -> Result<ReturnKind, String> {
+    let unsupported = || "Entry point has an unsupported return type.".to_owned();
+    match ty {
+        BasicTypeEnum::IntType(int_type) if int_type.get_bit_width() == 1 => Ok(ReturnKind::Bool),
+        BasicTypeEnum::IntType(int_type) if int_type.get_bit_width() == 64 => Ok(ReturnKind::Int),
+        BasicTypeEnum::FloatType(float_type) if float_type == context.f64_type() => {
+            Ok(ReturnKind::Double)
+        }
+        BasicTypeEnum::PointerType(pointer_type) => match pointer_type.get_element_type() {
+            AnyTypeEnum::StructType(struct_type) => {
+                match struct_type.get_name().map(CStr::to_bytes) {
+                    Some(b"Result") => Ok(ReturnKind::Result),
+                    Some(b"Array") => {
+                        let (element_type, elements) =
+                            flow.element_type(values).ok_or_else(|| {
+                                "Unable to determine the element type of an array returned by the entry point."
+                                    .to_owned()
+                            })?;
+                        value_kind(context, target_data, flow, element_type, elements)
+                            .map(|element| ReturnKind::Array(Box::new(element)))
+                    }
+                    // Strings within tuples and arrays are not supported, and tuples with opaque types cannot be
+                    // read, since their field types are unknown.
+                    _ if struct_type.is_opaque() => Err(unsupported()),
+                    _ => (0..)
+                        .zip(struct_type.get_field_types())
+                        .map(|(index, field)| {
+                            let offset = target_data
+                                .offset_of_element(&struct_type, index)
+                                .ok_or_else(unsupported)?;
+                            let fields = flow.field_values(values.iter().copied(), index);
+                            Ok((
+                                offset,
+                                value_kind(context, target_data, flow, field, fields)?,
+                            ))
+                        })
+                        .collect::<Result<_, _>>()
+                        .map(ReturnKind::Tuple),
+                }
+            }
+            _ => Err(unsupported()),
+        },
+        _ => Err(unsupported()),
+    }
+}
+
+fn is_string_type(ty: BasicTypeEnum) -> bool {
+    matches!(ty, BasicTypeEnum::PointerType(pointer_type)
+        if matches!(pointer_type.get_element_type(), AnyTypeEnum::StructType(struct_type)
+            if struct_type.get_name().map(CStr::to_bytes) == Some(b"String")))
+}
+
+fn record(value: OutputValue) {
+    record_output(OutputRecord::Value { value, tag: None });
+}
+
+/// Records the value returned by the entry point as output and releases it, along with the strings, tuples and
+/// arrays it refers to. Tuples and arrays are recorded with their number of items followed by the items, and
+/// strings are recorded as messages.
+///
+/// # Safety
+///
+/// The value must have been returned by a function whose return type matches the given kind.
+#[allow(clippy::cast_possible_wrap)]
+pub(crate) unsafe fn record_return_value(
+    context: &Context,
+    kind: &ReturnKind,
+    value: &GenericValue,
+) {
+    match kind {
+        ReturnKind::Int => record(OutputValue::Int(value.as_int(true) as i64)),
+        ReturnKind::Double => record(OutputValue::Double(value.as_float(&context.f64_type()))),
+        ReturnKind::Bool => record(OutputValue::Bool(value.as_int(false) != 0)),
+        ReturnKind::String => {
+            let ptr: *mut c_void = value.into_pointer();
+            let data = CStr::from_ptr(__quantum__rt__string_get_data(ptr.cast::<CString>()));
+            record_output(OutputRecord::Message(data.to_string_lossy().into_owned()));
+            release_pointee(kind, ptr);
+        }
+        _ => {
+            let ptr = value.into_pointer();
+            record_pointee(kind, ptr);
+            release_pointee(kind, ptr);
+        }
+    }
+}
+
+#[allow(clippy::cast_possible_wrap, clippy::cast_possible_truncation)]
+unsafe fn record_pointee(kind: &ReturnKind, ptr: *mut c_void) {
+    match kind {
+        ReturnKind::Result => __quantum__rt__result_record_output(ptr, null_mut()),
+        ReturnKind::Tuple(fields) => {
+            record(OutputValue::Tuple(fields.len() as i64));
+            for (offset, field) in fields {
+                record_item(field, ptr.cast::<u8>().add(*offset as usize));
+            }
+        }
+        ReturnKind::Array(element) => {
+            let array = ptr.cast::<QirArray>();
+            let size = __quantum__rt__array_get_size_1d(array);
+            record(OutputValue::Array(size as i64));
+            for index in 0..size {
+                record_item(
+                    element,
+                    __quantum__rt__array_get_element_ptr_1d(array, index).cast::<u8>(),
+                );
+            }
+        }
+        ReturnKind::Int | ReturnKind::Double | ReturnKind::Bool | ReturnKind::String => {
+            unreachable!("Values of kind {kind:?} are not recorded as pointees.")
+        }
+    }
+}
+
+/// Records a tuple field or array element stored at the given location.
+#[allow(clippy::cast_ptr_alignment)]
+unsafe fn record_item(kind: &ReturnKind, item: *const u8) {
+    match kind {
+        ReturnKind::Int => record(OutputValue::Int(item.cast::<i64>().read_unaligned())),
+        ReturnKind::Double => record(OutputValue::Double(item.cast::<f64>().read_unaligned())),
+        ReturnKind::Bool => record(OutputValue::Bool(*item != 0)),
+        _ => record_pointee(kind, item.cast::<*mut c_void>().read_unaligned()),
+    }
+}
+
+/// Releases the reference held by the caller to the given value, along with the references held by a tuple to
+/// its fields and by an array to its elements.
+#[allow(clippy::cast_possible_truncation, clippy::cast_ptr_alignment)]
+unsafe fn release_pointee(kind: &ReturnKind, ptr: *mut c_void) {
+    if ptr.is_null() {
+        return;
+    }
+    let release_item = |kind: &ReturnKind, item: *const u8| {
+        if !matches!(
+            kind,
+            ReturnKind::Int | ReturnKind::Double | ReturnKind::Bool
+        ) {
+            release_pointee(kind, item.cast::<*mut c_void>().read_unaligned());
+        }
+    };
+    match kind {
+        ReturnKind::Result => __quantum__rt__result_update_reference_count(ptr, -1),
+        ReturnKind::String => __quantum__rt__string_update_reference_count(ptr.cast(), -1),
+        ReturnKind::Tuple(fields) => {
+            for (offset, field) in fields {
+                release_item(field, ptr.cast::<u8>().add(*offset as usize));
+            }
+            __quantum__rt__tuple_update_reference_count(ptr.cast(), -1);
+        }
+        ReturnKind::Array(element) => {
+            let array = ptr.cast::<QirArray>();
+            for index in 0..__quantum__rt__array_get_size_1d(array) {
+                release_item(
+                    element,
+                    __quantum__rt__array_get_element_ptr_1d(array, index).cast::<u8>(),
+                );
+            }
+            __quantum__rt__array_update_reference_count(array, -1);
+        }
+        ReturnKind::Int | ReturnKind::Double | ReturnKind::Bool => {
+            unreachable!("Values of kind {kind:?} are not returned by pointer.")
+        }
+    }
+}
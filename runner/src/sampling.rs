@@ -0,0 +1,93 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+use inkwell::values::{BasicValueEnum, FunctionValue, InstructionOpcode};
+
+use crate::value_flow::instructions;
+
+/// The gates of the backend, which the shots whose measurements are sampled skip. Programs that call any other
+/// quantum intrinsic before their first measurement, which might measure, draw random numbers or inspect the
+/// state, cannot be sampled from the state at the first measurement.
+const GATES: &[&str] = &[
+    "__quantum__qis__ccx__body",
+    "__quantum__qis__cnot__body",
+    "__quantum__qis__cx__body",
+    "__quantum__qis__cy__body",
+    "__quantum__qis__cz__body",
+    "__quantum__qis__exp__adj",
+    "__quantum__qis__exp__body",
+    "__quantum__qis__exp__ctl",
+    "__quantum__qis__exp__ctladj",
+    "__quantum__qis__h__body",
+    "__quantum__qis__h__ctl",
+    "__quantum__qis__r__adj",
+    "__quantum__qis__r__body",
+    "__quantum__qis__r__ctl",
+    "__quantum__qis__r__ctladj",
+    "__quantum__qis__rx__body",
+    "__quantum__qis__rx__ctl",
+    "__quantum__qis__rxx__body",
+    "__quantum__qis__ry__body",
+    "__quantum__qis__ry__ctl",
+    "__quantum__qis__ryy__body",
+    "__quantum__qis__rz__body",
+    "__quantum__qis__rz__ctl",
+    "__quantum__qis__rzz__body",
+    "__quantum__qis__s__adj",
+    "__quantum__qis__s__body",
+    "__quantum__qis__s__ctl",
+    "__quantum__qis__s__ctladj",
+    "__quantum__qis__swap__body",
+    "__quantum__qis__t__adj",
+    "__quantum__qis__t__body",
+    "__quantum__qis__t__ctl",
+    "__quantum__qis__t__ctladj",
+    "__quantum__qis__x__body",
+    "__quantum__qis__x__ctl",
+    "__quantum__qis__y__body",
+    "__quantum__qis__y__ctl",
+    "__quantum__qis__z__body",
+    "__quantum__qis__z__ctl",
+];
+
+/// Checks whether all measurements of the given entry point are terminal, so that the outcomes of every shot
+/// can be sampled from the state at the first measurement. This is the case for programs without branches that
+/// only call runtime functions and the gates of the backend, measure with `__quantum__qis__mz__body` only after
+/// the last gate, and never read a result other than by recording it as output.
+pub(crate) fn has_terminal_measurements(function: FunctionValue) -> bool {
+    let mut measured = false;
+    for inst in instructions(function) {
+        match inst.get_opcode() {
+            InstructionOpcode::Br if inst.get_num_operands() > 1 => return false,
+            InstructionOpcode::Switch
+            | InstructionOpcode::IndirectBr
+            | InstructionOpcode::Invoke
+            | InstructionOpcode::CallBr => return false,
+            InstructionOpcode::Call => {
+                let callee = match inst.get_operand(inst.get_num_operands() - 1) {
+                    Some(operand) => match operand.left() {
+                        Some(BasicValueEnum::PointerValue(callee)) => callee,
+                        _ => return false,
+                    },
+                    None => return false,
+                };
+                let Ok(name) = callee.get_name().to_str() else {
+                    return false;
+                };
+                if name == "__quantum__qis__mz__body" {
+                    measured = true;
+                } else if name.starts_with("__quantum__qis__") {
+                    if measured || !GATES.contains(&name) {
+                        return false;
+                    }
+                } else if name == "__quantum__rt__result_equal"
+                    || !name.starts_with("__quantum__rt__")
+                {
+                    return false;
+                }
+            }
+            _ => (),
+        }
+    }
+    measured
+}
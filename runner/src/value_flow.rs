@@ -0,0 +1,243 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! The element types of runtime arrays are not part of the types of a QIR program, so they are determined from
+//! how the program accesses the elements of its arrays. Arrays and tuples are followed through the functions of
+//! the module, so that elements are found wherever they are accessed, e.g., in a callee or after a phi.
+
+use std::collections::{HashMap, HashSet};
+
+use inkwell::{
+    module::Module,
+    types::BasicTypeEnum,
+    values::{
+        AnyValue, AnyValueEnum, BasicValueEnum, FunctionValue, InstructionOpcode, InstructionValue,
+        PointerValue,
+    },
+};
+
+use crate::module_functions;
+
+/// The runtime functions whose result has the same element or field types as their array or tuple arguments.
+const COPY_FUNCTIONS: &[&str] = &[
+    "__quantum__rt__array_concatenate",
+    "__quantum__rt__array_copy",
+    "__quantum__rt__array_slice_1d",
+    "__quantum__rt__tuple_copy",
+];
+
+/// How pointers flow between the instructions and functions of a module.
+pub(crate) struct ValueFlow<'ctx> {
+    /// The pointers that refer to the same array or tuple as a pointer, or to an array with the same element type.
+    aliases: HashMap<PointerValue<'ctx>, Vec<PointerValue<'ctx>>>,
+    /// Arrays along with the pointers to their elements returned by `__quantum__rt__array_get_element_ptr_1d`.
+    elements: Vec<(PointerValue<'ctx>, PointerValue<'ctx>)>,
+    /// Tuples along with the indices of their fields and the pointers to those fields.
+    fields: Vec<(PointerValue<'ctx>, u64, PointerValue<'ctx>)>,
+    /// Locations along with the pointers stored at or loaded from them.
+    contents: Vec<(PointerValue<'ctx>, PointerValue<'ctx>)>,
+}
+
+impl<'ctx> ValueFlow<'ctx> {
+    pub(crate) fn new(module: &Module<'ctx>) -> Self {
+        let mut flow = ValueFlow {
+            aliases: HashMap::new(),
+            elements: Vec::new(),
+            fields: Vec::new(),
+            contents: Vec::new(),
+        };
+        for function in module_functions(module) {
+            for inst in instructions(function) {
+                flow.add_instruction(module, inst);
+            }
+        }
+        flow
+    }
+
+    fn add_alias(&mut self, first: PointerValue<'ctx>, second: PointerValue<'ctx>) {
+        self.aliases.entry(first).or_default().push(second);
+        self.aliases.entry(second).or_default().push(first);
+    }
+
+    fn add_instruction(&mut self, module: &Module<'ctx>, inst: InstructionValue<'ctx>) {
+        let result = pointer_result(inst);
+        let pointers: Vec<_> = (0..inst.get_num_operands())
+            .map(|index| match operand(inst, index) {
+                Some(BasicValueEnum::PointerValue(pointer)) => Some(pointer),
+                _ => None,
+            })
+            .collect();
+        match inst.get_opcode() {
+            InstructionOpcode::Phi | InstructionOpcode::Select | InstructionOpcode::BitCast => {
+                if let Some(result) = result {
+                    for pointer in pointers.into_iter().flatten() {
+                        self.add_alias(result, pointer);
+                    }
+                }
+            }
+            InstructionOpcode::Call => {
+                let Some((Some(callee), args)) = pointers.split_last() else {
+                    return;
+                };
+                let name = callee.get_name().to_str().unwrap_or_default();
+                if name == "__quantum__rt__array_get_element_ptr_1d" {
+                    if let (Some(Some(array)), Some(element)) = (args.first(), result) {
+                        self.elements.push((*array, element));
+                    }
+                } else if COPY_FUNCTIONS.contains(&name) {
+                    if let Some(result) = result {
+                        for arg in args.iter().flatten() {
+                            self.add_alias(result, *arg);
+                        }
+                    }
+                } else if let Some(function) = module
+                    .get_function(name)
+                    .filter(|function| function.count_basic_blocks() > 0)
+                {
+                    for (index, arg) in (0..).zip(args) {
+                        if let (Some(arg), Some(BasicValueEnum::PointerValue(param))) =
+                            (arg, function.get_nth_param(index))
+                        {
+                            self.add_alias(*arg, param);
+                        }
+                    }
+                    if let Some(result) = result {
+                        for value in returned_values(function) {
+                            if let BasicValueEnum::PointerValue(value) = value {
+                                self.add_alias(result, value);
+                            }
+                        }
+                    }
+                }
+            }
+            // Only the fields of a single tuple are followed, which are addressed by a zero followed by the field
+            // index.
+            InstructionOpcode::GetElementPtr if inst.get_num_operands() == 3 => {
+                let constant = |index| match operand(inst, index) {
+                    Some(BasicValueEnum::IntValue(value)) => value.get_zero_extended_constant(),
+                    _ => None,
+                };
+                if let (Some(Some(tuple)), Some(field), Some(0), Some(index)) =
+                    (pointers.first(), result, constant(1), constant(2))
+                {
+                    self.fields.push((*tuple, index, field));
+                }
+            }
+            InstructionOpcode::Store => {
+                if let [Some(value), Some(location)] = pointers[..] {
+                    self.contents.push((location, value));
+                }
+            }
+            InstructionOpcode::Load => {
+                if let (Some(Some(location)), Some(value)) = (pointers.first(), result) {
+                    self.contents.push((*location, value));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn aliases_of(
+        &self,
+        pointers: impl IntoIterator<Item = PointerValue<'ctx>>,
+    ) -> HashSet<PointerValue<'ctx>> {
+        let mut aliases = HashSet::new();
+        let mut pending: Vec<_> = pointers.into_iter().collect();
+        while let Some(pointer) = pending.pop() {
+            if aliases.insert(pointer) {
+                pending.extend(self.aliases.get(&pointer).into_iter().flatten().copied());
+            }
+        }
+        aliases
+    }
+
+    /// Returns the pointers stored at or loaded from the given locations.
+    fn contents_of(&self, locations: &HashSet<PointerValue<'ctx>>) -> Vec<PointerValue<'ctx>> {
+        self.contents
+            .iter()
+            .filter(|(location, _)| locations.contains(location))
+            .map(|(_, value)| *value)
+            .collect()
+    }
+
+    /// Returns the element type of the given arrays, which is the type their element pointers are cast to, along
+    /// with the pointers held by their elements. Returns `None` if the elements are never accessed through a cast
+    /// or are accessed as different types.
+    pub(crate) fn element_type(
+        &self,
+        arrays: impl IntoIterator<Item = PointerValue<'ctx>>,
+    ) -> Option<(BasicTypeEnum<'ctx>, Vec<PointerValue<'ctx>>)> {
+        let arrays = self.aliases_of(arrays);
+        let elements = self.aliases_of(
+            self.elements
+                .iter()
+                .filter(|(array, _)| arrays.contains(array))
+                .map(|(_, element)| *element),
+        );
+        let mut types = Vec::new();
+        for element in &elements {
+            if self.elements.iter().any(|(_, raw)| raw == element) {
+                continue;
+            }
+            let ty = element.get_type().get_element_type();
+            if !types.contains(&ty) {
+                types.push(ty);
+            }
+        }
+        match types[..] {
+            [ty] => Some((
+                BasicTypeEnum::try_from(ty).ok()?,
+                self.contents_of(&elements),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Returns the pointers held by the field with the given index of the given tuples.
+    pub(crate) fn field_values(
+        &self,
+        tuples: impl IntoIterator<Item = PointerValue<'ctx>>,
+        index: u32,
+    ) -> Vec<PointerValue<'ctx>> {
+        let tuples = self.aliases_of(tuples);
+        let fields = self.aliases_of(
+            self.fields
+                .iter()
+                .filter(|(tuple, field, _)| *field == u64::from(index) && tuples.contains(tuple))
+                .map(|(_, _, pointer)| *pointer),
+        );
+        self.contents_of(&fields)
+    }
+}
+
+pub(crate) fn instructions(function: FunctionValue) -> Vec<InstructionValue> {
+    let mut instructions = Vec::new();
+    for block in function.get_basic_blocks() {
+        let mut instruction = block.get_first_instruction();
+        while let Some(inst) = instruction {
+            instructions.push(inst);
+            instruction = inst.get_next_instruction();
+        }
+    }
+    instructions
+}
+
+pub(crate) fn operand(instruction: InstructionValue, index: u32) -> Option<BasicValueEnum> {
+    instruction.get_operand(index)?.left()
+}
+
+fn pointer_result(instruction: InstructionValue) -> Option<PointerValue> {
+    match instruction.as_any_value_enum() {
+        AnyValueEnum::PointerValue(pointer) => Some(pointer),
+        _ => None,
+    }
+}
+
+/// Returns the values returned by the return instructions of the function.
+pub(crate) fn returned_values(function: FunctionValue) -> Vec<BasicValueEnum> {
+    instructions(function)
+        .into_iter()
+        .filter(|inst| inst.get_opcode() == InstructionOpcode::Return)
+        .filter_map(|inst| operand(inst, 0))
+        .collect()
+}
@@ -0,0 +1,24 @@
+// Teleports the state prepared on q[0] to q[2] and undoes the preparation there, so r is always 0.
+OPENQASM 3.0;
+include "stdgates.inc";
+
+qubit[3] q;
+bit[2] m;
+bit r;
+
+gate prepare(theta) a {
+    ry(theta) a;
+    t a;
+}
+
+prepare(0.7) q[0];
+h q[1];
+cx q[1], q[2];
+cx q[0], q[1];
+h q[0];
+m[0] = measure q[0];
+m[1] = measure q[1];
+if (m[1] == 1) x q[2];
+if (m[0]) z q[2];
+inv @ prepare(0.7) q[2];
+r = measure q[2];
@@ -1,7 +1,17 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT License.
 
-use qir_runner::{run_bitcode, run_file};
+use qir_runner::{
+    __quantum__rt__int_record_output, estimate::MeasurementPolicy, histogram, noise::NoiseModel,
+    render_estimates, render_histogram, render_results, run_bitcode, run_bitcode_parallel,
+    run_file, run_file_parallel, set_output_sink, Argument, Arguments, FailurePolicy, Intrinsics,
+    OutputBuffer, OutputFormat, OutputRecord, OutputValue, RunOptions, Runner, ShotResult,
+    SimulatorKind,
+};
+use std::{
+    ffi::c_void,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 // This group of tests verifies the behavior of QIR execution with a series of quantum gate checks based on the Choi–Jamiołkowski Isomorphism.
 // They will verify the behavior of body, adjoint, controlled, and controlled adjoint specializations of each gate against decompositions thereof,
@@ -17,109 +27,127 @@ mod cji_tests {
     #[test]
     fn test_h_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyH"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyH"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_s_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyS"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyS"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_t_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyT"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyT"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_x_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyX"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyX"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_y_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyY"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyY"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_z_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyZ"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyZ"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_cnot_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyCNOT"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyCNOT"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_cx_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyCX"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyCX"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_cy_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyCY"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyCY"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_cz_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyCZ"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyCZ"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_ccnot_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyCCNOT"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyCCNOT"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_rx_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyRx"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyRx"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_ry_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyRy"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyRy"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_rz_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyRz"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyRz"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_r_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyR"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyR"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_r1_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyR1"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyR1"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_swap_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifySWAP"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifySWAP"), 1)?;
+        Ok(())
     }
 
     #[test]
     fn test_exp_with_cji() -> Result<(), String> {
         let bitcode = include_bytes!("resources/cji.bc");
-        run_bitcode(bitcode, Some("DecompositionTests__VerifyExp"), 1)
+        run_bitcode(bitcode, Some("DecompositionTests__VerifyExp"), 1)?;
+        Ok(())
     }
 }
 
@@ -129,7 +157,10 @@ mod cji_tests {
 #[test]
 fn test_full_qir_simple() -> Result<(), String> {
     let bitcode = include_bytes!("resources/full-qir.bc");
-    run_bitcode(bitcode, Some("QIR_App_Test__Simple"), 1)
+    let results = run_bitcode(bitcode, Some("QIR_App_Test__Simple"), 1)?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].messages().collect::<Vec<_>>(), vec!["42"]);
+    Ok(())
 }
 
 // This test confirms selection of the other named entry point from a file with two entry points
@@ -140,7 +171,11 @@ fn test_full_qir_simple() -> Result<(), String> {
 #[test]
 fn test_full_qir_other() -> Result<(), String> {
     let bitcode = include_bytes!("resources/full-qir.bc");
-    run_bitcode(bitcode, Some("QIR_App_Test__Other"), 1)
+    let results = run_bitcode(bitcode, Some("QIR_App_Test__Other"), 1)?;
+    let messages: Vec<_> = results[0].messages().collect();
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0] == "Zero" || messages[0] == "One");
+    Ok(())
 }
 
 // This tests a simple Bernstein-Vazirani algorithm looking for the encoded pattern |110⟩ in a qubit array.
@@ -150,7 +185,8 @@ fn test_full_qir_other() -> Result<(), String> {
 #[test]
 fn test_bernstein_vazirani() -> Result<(), String> {
     let bitcode = include_bytes!("resources/bv.bc");
-    run_bitcode(bitcode, None, 1)
+    run_bitcode(bitcode, None, 1)?;
+    Ok(())
 }
 
 // This tests support for range operations `__quantum__rt__array_slice_1d` and `__quantum__rt__range_to_string`
@@ -162,7 +198,8 @@ fn test_bernstein_vazirani() -> Result<(), String> {
 #[test]
 fn test_ranges() -> Result<(), String> {
     let bitcode = include_bytes!("resources/ranges.bc");
-    run_bitcode(bitcode, None, 1)
+    run_bitcode(bitcode, None, 1)?;
+    Ok(())
 }
 
 // This test runs a sample Shor's algorithm for integer factorization. It makes use of quantum execution
@@ -171,7 +208,935 @@ fn test_ranges() -> Result<(), String> {
 #[test]
 fn test_shor() -> Result<(), String> {
     let bitcode = include_bytes!("resources/shor.bc");
-    run_bitcode(bitcode, None, 1)
+    run_bitcode(bitcode, None, 1)?;
+    Ok(())
+}
+
+// This test verifies that the values recorded with the labeled output recording functions are returned
+// in order and with their tags for every shot, along with the entry point attributes as metadata.
+#[test]
+fn test_output_recording() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/output.bc");
+    let results = run_bitcode(bitcode, None, 3)?;
+    assert_eq!(results.len(), 3);
+    for shot in &results {
+        assert_eq!(
+            shot.metadata,
+            vec![
+                ("entry_point".to_owned(), String::new()),
+                ("num_required_qubits".to_owned(), "2".to_owned()),
+                ("num_required_results".to_owned(), "2".to_owned()),
+                ("output_labeling_schema".to_owned(), String::new()),
+                ("qir_profiles".to_owned(), "base_profile".to_owned()),
+            ]
+        );
+        assert_eq!(
+            shot.values().collect::<Vec<_>>(),
+            vec![
+                (&OutputValue::Tuple(4), Some("t")),
+                (&OutputValue::Array(2), Some("a")),
+                (&OutputValue::Result(true), Some("r0")),
+                (&OutputValue::Result(false), Some("r1")),
+                (&OutputValue::Int(42), Some("i")),
+                (&OutputValue::Double(3.5), Some("d")),
+                (&OutputValue::Bool(true), Some("b")),
+            ]
+        );
+        assert_eq!(shot.messages().collect::<Vec<_>>(), vec!["Hello"]);
+    }
+    assert_eq!(
+        results[0].to_string(),
+        "START\nMETADATA\tentry_point\nMETADATA\tnum_required_qubits\t2\n\
+         METADATA\tnum_required_results\t2\nMETADATA\toutput_labeling_schema\n\
+         METADATA\tqir_profiles\tbase_profile\nHello\nOUTPUT\tTUPLE\t4\tt\n\
+         OUTPUT\tARRAY\t2\ta\nOUTPUT\tRESULT\t1\tr0\nOUTPUT\tRESULT\t0\tr1\n\
+         OUTPUT\tINT\t42\ti\nOUTPUT\tDOUBLE\t3.5\td\nOUTPUT\tBOOL\ttrue\tb\nEND\t0\n"
+    );
+    Ok(())
+}
+
+// This test verifies that the JSON format nests array and tuple outputs based on their element counts.
+#[test]
+fn test_output_json_format() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/output.bc");
+    let results = run_bitcode(bitcode, None, 2)?;
+    let json: serde_json::Value =
+        serde_json::from_str(&render_results(&results, OutputFormat::Json)?)
+            .map_err(|e| e.to_string())?;
+    let shots = json.as_array().expect("JSON output should be an array");
+    assert_eq!(shots.len(), 2);
+    assert_eq!(
+        shots[0],
+        serde_json::json!({
+            "metadata": {
+                "entry_point": "",
+                "num_required_qubits": "2",
+                "num_required_results": "2",
+                "output_labeling_schema": "",
+                "qir_profiles": "base_profile",
+            },
+            "outputs": [{
+                "type": "TUPLE",
+                "tag": "t",
+                "items": [
+                    {
+                        "type": "ARRAY",
+                        "tag": "a",
+                        "items": [
+                            { "type": "RESULT", "tag": "r0", "value": 1 },
+                            { "type": "RESULT", "tag": "r1", "value": 0 },
+                        ],
+                    },
+                    { "type": "INT", "tag": "i", "value": 42 },
+                    { "type": "DOUBLE", "tag": "d", "value": 3.5 },
+                    { "type": "BOOL", "tag": "b", "value": true },
+                ],
+            }],
+            "messages": ["Hello"],
+        })
+    );
+    Ok(())
+}
+
+// This test verifies that the CSV format has one row per shot and one column per tagged output.
+#[test]
+fn test_output_csv_format() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/output.bc");
+    let results = run_bitcode(bitcode, None, 2)?;
+    assert_eq!(
+        render_results(&results, OutputFormat::Csv)?,
+        "#shot,t,a,r0,r1,i,d,b\n0,4,2,1,0,42,3.5,true\n1,4,2,1,0,42,3.5,true\n"
+    );
+    Ok(())
+}
+
+// This test verifies that the CSV columns of untagged values and shot indices cannot be confused with the
+// columns of tags, and that shots that record a tag more than once cannot be rendered as CSV.
+#[test]
+fn test_output_csv_columns() -> Result<(), String> {
+    let value = |value: i64, tag: Option<&str>| OutputRecord::Value {
+        value: OutputValue::Int(value),
+        tag: tag.map(ToOwned::to_owned),
+    };
+    let shot = ShotResult {
+        output: vec![
+            value(1, Some("0")),
+            value(2, None),
+            value(3, Some("shot")),
+            value(4, Some("#1")),
+        ],
+        ..ShotResult::default()
+    };
+    assert_eq!(
+        render_results(&[shot], OutputFormat::Csv)?,
+        "#shot,0,#1,shot,##1\n0,1,2,3,4\n"
+    );
+    let shot = ShotResult {
+        output: vec![value(1, Some("r")), value(2, Some("r"))],
+        ..ShotResult::default()
+    };
+    assert_eq!(
+        render_results(&[shot], OutputFormat::Csv),
+        Err(
+            "Cannot render the tag 'r' as a column, since a shot records it more than once."
+                .to_owned()
+        )
+    );
+    Ok(())
+}
+
+// This test verifies that the CSV format has a column with the failure message of each shot if a shot failed.
+#[test]
+fn test_output_csv_with_failures() -> Result<(), String> {
+    let shot = |failure: Option<&str>| ShotResult {
+        output: vec![OutputRecord::Value {
+            value: OutputValue::Int(1),
+            tag: Some("i".to_owned()),
+        }],
+        failure: failure.map(ToOwned::to_owned),
+        ..ShotResult::default()
+    };
+    assert_eq!(
+        render_results(
+            &[shot(None), shot(Some("Expected failure"))],
+            OutputFormat::Csv
+        )?,
+        "#shot,#failure,i\n0,,1\n1,Expected failure,1\n"
+    );
+    assert_eq!(
+        render_results(&[shot(None), shot(None)], OutputFormat::Csv)?,
+        "#shot,i\n0,1\n1,1\n"
+    );
+    Ok(())
+}
+
+// This test verifies that shots with identical output are grouped together in the histogram.
+#[test]
+fn test_output_histogram() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/output.bc");
+    let results = run_bitcode(bitcode, None, 4)?;
+    let histogram = histogram(&results);
+    assert_eq!(histogram.len(), 1);
+    assert_eq!(
+        histogram.get(&(
+            vec![
+                OutputValue::Tuple(4),
+                OutputValue::Array(2),
+                OutputValue::Result(true),
+                OutputValue::Result(false),
+                OutputValue::Int(42),
+                OutputValue::Double(3.5),
+                OutputValue::Bool(true),
+            ],
+            None
+        )),
+        Some(&4)
+    );
+    assert_eq!(
+        render_histogram(&results, OutputFormat::Text)?,
+        "4\t1.0\t([1, 0], 42, 3.5, true)\n"
+    );
+    assert_eq!(
+        render_histogram(&results, OutputFormat::Csv)?,
+        "#count,#probability,t,a,r0,r1,i,d,b\n4,1.0,4,2,1,0,42,3.5,true\n"
+    );
+    Ok(())
+}
+
+// This test verifies that failed shots are counted apart from completed shots with the same output in the histogram.
+#[test]
+fn test_output_histogram_with_failures() -> Result<(), String> {
+    let shot = |failure: Option<&str>| ShotResult {
+        output: vec![OutputRecord::Value {
+            value: OutputValue::Int(1),
+            tag: Some("i".to_owned()),
+        }],
+        failure: failure.map(ToOwned::to_owned),
+        ..ShotResult::default()
+    };
+    let results = vec![
+        shot(None),
+        shot(Some("Expected failure")),
+        shot(None),
+        shot(None),
+    ];
+    let histogram = histogram(&results);
+    assert_eq!(histogram.len(), 2);
+    assert_eq!(histogram[&(vec![OutputValue::Int(1)], None)], 3);
+    assert_eq!(
+        histogram[&(
+            vec![OutputValue::Int(1)],
+            Some("Expected failure".to_owned())
+        )],
+        1
+    );
+    assert_eq!(
+        render_histogram(&results, OutputFormat::Text)?,
+        "3\t0.75\t1\n1\t0.25\t1\tFAILURE\tExpected failure\n"
+    );
+    assert_eq!(
+        render_histogram(&results, OutputFormat::Csv)?,
+        "#count,#probability,#failure,i\n3,0.75,,1\n1,0.25,Expected failure,1\n"
+    );
+    Ok(())
+}
+
+// This test verifies that the legacy output recording functions produce untagged values and markers.
+#[test]
+fn test_legacy_output_recording() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/legacy_output.bc");
+    let results = run_bitcode(bitcode, None, 1)?;
+    assert_eq!(
+        results[0].values().collect::<Vec<_>>(),
+        vec![
+            (&OutputValue::Int(5), None),
+            (&OutputValue::Double(7.0), None),
+            (&OutputValue::Bool(true), None),
+            (&OutputValue::Result(false), None),
+        ]
+    );
+    assert_eq!(
+        results[0]
+            .output
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        vec![
+            "RESULT\tTUPLE_START",
+            "RESULT\t5",
+            "RESULT\t7.0",
+            "RESULT\ttrue",
+            "RESULT\t0",
+            "RESULT\tTUPLE_END",
+        ]
+    );
+    assert!(matches!(
+        results[0].output.first(),
+        Some(OutputRecord::LegacyMarker(_))
+    ));
+    Ok(())
+}
+
+// This test verifies that running a program collects its output without writing to the output sink
+// installed by the caller, and that the caller's sink is restored afterwards.
+#[test]
+fn test_output_sink_restored() -> Result<(), String> {
+    let buffer = OutputBuffer::new();
+    let previous = set_output_sink(Box::new(buffer.clone()));
+    let bitcode = include_bytes!("resources/output.bc");
+    let results = run_bitcode(bitcode, None, 1);
+    __quantum__rt__int_record_output(7, std::ptr::null_mut());
+    set_output_sink(previous);
+    assert_eq!(results?[0].values().count(), 7);
+    assert_eq!(
+        buffer.take(),
+        vec![OutputRecord::Value {
+            value: OutputValue::Int(7),
+            tag: None
+        }]
+    );
+    Ok(())
+}
+
+// This test verifies that seeded shots produce the same output in the same order regardless of the
+// number of threads the shots are spread over.
+#[test]
+fn test_parallel_shots_are_reproducible() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/random_bit.bc");
+    let args = Arguments::default();
+    let options = |threads, seed| RunOptions {
+        threads,
+        seed: Some(seed),
+        ..RunOptions::default()
+    };
+    let sequential = run_bitcode_parallel(bitcode, None, &args, 25, &options(1, 42))?;
+    assert_eq!(sequential.len(), 25);
+    for threads in [2, 4, 7, 64] {
+        let parallel = run_bitcode_parallel(bitcode, None, &args, 25, &options(threads, 42))?;
+        assert_eq!(parallel, sequential);
+    }
+    assert_eq!(
+        run_bitcode_parallel(bitcode, None, &args, 25, &options(3, 7))?.len(),
+        25
+    );
+    Ok(())
+}
+
+// This test verifies that the sampled outcomes of a program with terminal measurements follow its distribution.
+#[test]
+fn test_terminal_measurements_are_sampled() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/random_bit.bc");
+    let options = RunOptions {
+        seed: Some(42),
+        ..RunOptions::default()
+    };
+    let results = run_bitcode_parallel(bitcode, None, &Arguments::default(), 200, &options)?;
+    assert!(results.iter().all(|shot| shot.failure.is_none()));
+    let histogram = histogram(&results);
+    assert_eq!(histogram.len(), 2);
+    for outcome in [false, true] {
+        let count = histogram[&(vec![OutputValue::Result(outcome)], None)];
+        assert!(
+            (70..=130).contains(&count),
+            "{outcome} occurred {count} times"
+        );
+    }
+    Ok(())
+}
+
+// This test verifies that a qubit that is first used by a sampled measurement is measured as zero.
+#[test]
+fn test_terminal_measurements_of_unused_qubits() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/unused_qubit.bc");
+    let results = run_bitcode(bitcode, None, 10)?;
+    for shot in &results {
+        assert_eq!(shot.failure, None);
+        assert_eq!(
+            shot.values().map(|(value, _)| value).collect::<Vec<_>>(),
+            vec![&OutputValue::Result(true), &OutputValue::Result(false)]
+        );
+    }
+    Ok(())
+}
+
+// This test verifies that typed arguments are passed to the parameters of the entry point in order.
+#[test]
+fn test_entry_point_positional_arguments() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/params.bc");
+    let args = Arguments::Positional(vec![
+        Argument::Int(-3),
+        Argument::Int(2),
+        Argument::Bool(true),
+        Argument::Array(vec![
+            Argument::Int(10),
+            Argument::Int(20),
+            Argument::Int(30),
+        ]),
+    ]);
+    let results = run_bitcode_parallel(bitcode, None, &args, 2, &RunOptions::default())?;
+    assert_eq!(results.len(), 2);
+    for shot in &results {
+        assert_eq!(
+            shot.values().map(|(value, _)| *value).collect::<Vec<_>>(),
+            vec![
+                OutputValue::Int(-3),
+                OutputValue::Double(2.0),
+                OutputValue::Bool(true),
+                OutputValue::Array(3),
+                OutputValue::Int(10),
+                OutputValue::Int(30),
+            ]
+        );
+    }
+    Ok(())
+}
+
+// This test verifies that arguments given as text by parameter name are parsed according to the
+// parameter types, and that mismatching arguments are reported.
+#[test]
+fn test_entry_point_named_arguments() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/params.bc");
+    let named = |args: &[(&str, &str)]| {
+        Arguments::Named(
+            args.iter()
+                .map(|(name, value)| ((*name).to_owned(), (*value).to_owned()))
+                .collect(),
+        )
+    };
+    let args = named(&[
+        ("xs", "[7, 8]"),
+        ("flag", "false"),
+        ("angle", "0.5"),
+        ("n", "4"),
+    ]);
+    let results = run_bitcode_parallel(bitcode, None, &args, 1, &RunOptions::default())?;
+    assert_eq!(
+        results[0]
+            .values()
+            .map(|(value, _)| *value)
+            .collect::<Vec<_>>(),
+        vec![
+            OutputValue::Int(4),
+            OutputValue::Double(0.5),
+            OutputValue::Bool(false),
+            OutputValue::Array(2),
+            OutputValue::Int(7),
+            OutputValue::Int(8),
+        ]
+    );
+
+    let args = named(&[("n", "4"), ("angle", "0.5"), ("flag", "false")]);
+    assert_eq!(
+        run_bitcode_parallel(bitcode, None, &args, 1, &RunOptions::default()),
+        Err("Missing argument for parameter 'xs'.".to_owned())
+    );
+    let args = named(&[
+        ("n", "4.5"),
+        ("angle", "0.5"),
+        ("flag", "false"),
+        ("xs", "[]"),
+    ]);
+    assert_eq!(
+        run_bitcode_parallel(bitcode, None, &args, 1, &RunOptions::default()),
+        Err("Invalid value '4.5' for parameter 'n'.".to_owned())
+    );
+    let args = named(&[("m", "4")]);
+    assert_eq!(
+        run_bitcode_parallel(bitcode, None, &args, 1, &RunOptions::default()),
+        Err("Entry point has no parameter named 'm'.".to_owned())
+    );
+    let args = named(&[
+        ("n", "4"),
+        ("angle", "0.5"),
+        ("flag", "false"),
+        ("xs", "[]"),
+        ("n", "5"),
+    ]);
+    assert_eq!(
+        run_bitcode_parallel(bitcode, None, &args, 1, &RunOptions::default()),
+        Err("Argument for parameter 'n' is given more than once.".to_owned())
+    );
+    assert_eq!(
+        run_bitcode(bitcode, None, 1),
+        Err("Entry point expects 4 arguments, but 0 were given.".to_owned())
+    );
+    Ok(())
+}
+
+// This test verifies that the element types of array parameters are taken from how the program accesses the
+// elements, also in a callee after a phi, and that every element of an array argument is checked against it.
+#[test]
+fn test_entry_point_array_arguments() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/array_params.bc");
+    let run = |name: &str, args: Vec<Argument>| -> Result<Vec<OutputValue>, String> {
+        let args = Arguments::Positional(args);
+        let results = run_bitcode_parallel(bitcode, Some(name), &args, 1, &RunOptions::default())?;
+        Ok(results[0].values().map(|(value, _)| *value).collect())
+    };
+    assert_eq!(
+        run(
+            "FirstDouble",
+            vec![Argument::Array(vec![Argument::Int(1), Argument::Int(2)])]
+        )?,
+        vec![OutputValue::Double(1.0)]
+    );
+    assert_eq!(
+        run(
+            "FirstDouble",
+            vec![Argument::Array(vec![Argument::Bool(true)])]
+        ),
+        Err("Argument for parameter 'ys' must be of type Double[].".to_owned())
+    );
+    assert_eq!(
+        run(
+            "Nested",
+            vec![Argument::Array(vec![
+                Argument::Array(vec![Argument::Int(1), Argument::Int(2)]),
+                Argument::Array(vec![Argument::Int(3)]),
+            ])]
+        )?,
+        vec![OutputValue::Int(2)]
+    );
+    assert_eq!(
+        run(
+            "Nested",
+            vec![Argument::Array(vec![Argument::Int(1), Argument::Int(2)])]
+        ),
+        Err("Argument for parameter 'rows' must be of type Int[][].".to_owned())
+    );
+    assert_eq!(
+        run("Unused", vec![Argument::Array(Vec::new())]),
+        Err("Unable to determine the element type of array parameter 'zs'.".to_owned())
+    );
+
+    let args = Arguments::Named(vec![("rows".to_owned(), "[[1, 2], [3.5]]".to_owned())]);
+    assert_eq!(
+        run_bitcode_parallel(bitcode, Some("Nested"), &args, 1, &RunOptions::default()),
+        Err("Invalid value '[[1, 2], [3.5]]' for parameter 'rows'.".to_owned())
+    );
+    Ok(())
+}
+
+// This test verifies that values returned by entry points are recorded as untagged output, where tuples
+// and arrays are recorded with their number of items followed by the items and strings are recorded as
+// messages. Element types are taken from the accesses to the elements, also in a callee after a phi.
+#[test]
+fn test_entry_point_return_values() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/returns.bc");
+    let returned = |name: &str| -> Result<Vec<OutputValue>, String> {
+        let results = run_bitcode(bitcode, Some(name), 1)?;
+        Ok(results[0].values().map(|(value, _)| *value).collect())
+    };
+    assert_eq!(returned("ReturnInt")?, vec![OutputValue::Int(-42)]);
+    assert_eq!(returned("ReturnDouble")?, vec![OutputValue::Double(2.5)]);
+    assert_eq!(returned("ReturnBool")?, vec![OutputValue::Bool(true)]);
+    assert_eq!(returned("ReturnResult")?, vec![OutputValue::Result(true)]);
+    assert_eq!(
+        returned("ReturnTuple")?,
+        vec![
+            OutputValue::Tuple(3),
+            OutputValue::Int(7),
+            OutputValue::Bool(false),
+            OutputValue::Double(0.5),
+        ]
+    );
+    assert_eq!(
+        returned("ReturnArray")?,
+        vec![
+            OutputValue::Array(2),
+            OutputValue::Double(1.5),
+            OutputValue::Double(-3.0),
+        ]
+    );
+
+    assert_eq!(
+        returned("ReturnNested")?,
+        vec![
+            OutputValue::Tuple(2),
+            OutputValue::Int(2),
+            OutputValue::Array(1),
+            OutputValue::Int(5),
+        ]
+    );
+    assert_eq!(
+        returned("ReturnUntypedArray"),
+        Err(
+            "Unable to determine the element type of an array returned by the entry point."
+                .to_owned()
+        )
+    );
+
+    let results = run_bitcode(bitcode, Some("ReturnString"), 1)?;
+    assert_eq!(results[0].values().count(), 0);
+    assert_eq!(results[0].messages().collect::<Vec<_>>(), vec!["a\tb c"]);
+
+    let args = Arguments::Positional(vec![Argument::Int(2), Argument::Int(3)]);
+    let results = run_bitcode_parallel(bitcode, Some("Add"), &args, 1, &RunOptions::default())?;
+    assert_eq!(
+        results[0].values().collect::<Vec<_>>(),
+        vec![(&OutputValue::Int(5), None)]
+    );
+    Ok(())
+}
+
+// This test verifies that a compiled program can run its entry points repeatedly with different arguments.
+#[test]
+fn test_compiled_program_executes_repeatedly() -> Result<(), String> {
+    let runner = Runner::new();
+    let program = runner.load_bitcode(include_bytes!("resources/returns.bc"))?;
+    for (a, b) in [(1, 2), (-5, 3), (40, 2)] {
+        let args = Arguments::Positional(vec![Argument::Int(a), Argument::Int(b)]);
+        let results = program.execute(Some("Add"), &args, 2)?;
+        assert_eq!(results.len(), 2);
+        for shot in &results {
+            assert_eq!(
+                shot.values().collect::<Vec<_>>(),
+                vec![(&OutputValue::Int(a + b), None)]
+            );
+        }
+    }
+    assert_eq!(
+        program.execute(Some("ReturnInt"), &Arguments::default(), 1)?[0]
+            .values()
+            .collect::<Vec<_>>(),
+        vec![(&OutputValue::Int(-42), None)]
+    );
+    assert_eq!(
+        program
+            .execute(Some("Missing"), &Arguments::default(), 1)
+            .unwrap_err(),
+        "No matching entry point found."
+    );
+    Ok(())
+}
+
+// This test verifies that a shot that fails is reported with its failure message and that the remaining shots still run.
+#[test]
+fn test_failed_shots_are_reported() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/failures.bc");
+    let results = run_bitcode(bitcode, Some("Fail"), 2)?;
+    assert_eq!(results.len(), 2);
+    for shot in &results {
+        assert_eq!(shot.failure.as_deref(), Some("Expected failure"));
+        assert_eq!(shot.exit_code(), 1);
+        assert_eq!(
+            shot.values().collect::<Vec<_>>(),
+            vec![(&OutputValue::Int(1), Some("i"))]
+        );
+        assert!(shot
+            .to_string()
+            .ends_with("Expected failure\nFAILURE\tExpected failure\nEND\t1\n"));
+    }
+
+    let results = run_bitcode(bitcode, Some("DuplicateQubit"), 1)?;
+    assert_eq!(
+        results[0].failure.as_deref(),
+        Some("Duplicate qubit id '0' found in application.")
+    );
+
+    let json: serde_json::Value =
+        serde_json::from_str(&render_results(&results, OutputFormat::Json)?)
+            .map_err(|e| e.to_string())?;
+    assert_eq!(
+        json[0]["failure"],
+        "Duplicate qubit id '0' found in application."
+    );
+    Ok(())
+}
+
+// This test verifies that the failure policy decides whether shots run after a failed shot.
+#[test]
+fn test_failure_policy() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/failures.bc");
+    let run = |failure_policy| {
+        let options = RunOptions {
+            threads: 2,
+            seed: Some(5),
+            failure_policy,
+            ..RunOptions::default()
+        };
+        run_bitcode_parallel(
+            bitcode,
+            Some("FailOnOne"),
+            &Arguments::default(),
+            20,
+            &options,
+        )
+    };
+
+    let results = run(FailurePolicy::Continue)?;
+    assert_eq!(results.len(), 20);
+    let first_failure = results
+        .iter()
+        .position(|shot| shot.failure.is_some())
+        .expect("Some shots should fail");
+    assert!(results.iter().any(|shot| shot.failure.is_none()));
+    for shot in results.iter().filter(|shot| shot.failure.is_none()) {
+        assert_eq!(
+            shot.values().collect::<Vec<_>>(),
+            vec![(&OutputValue::Int(0), Some("i"))]
+        );
+    }
+
+    let stopped = run(FailurePolicy::Stop)?;
+    assert_eq!(stopped, results[..=first_failure]);
+    Ok(())
+}
+
+// This test verifies that the two-qubit rotations, controlled Y and measure-and-reset gates are bound.
+#[test]
+fn test_two_qubit_gates_and_mresetz() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/gates.bc");
+    let results = run_bitcode(bitcode, None, 1)?;
+    assert_eq!(
+        results[0]
+            .values()
+            .map(|(value, _)| *value)
+            .collect::<Vec<_>>(),
+        [true, true, true, true, true, false].map(OutputValue::Result)
+    );
+    Ok(())
+}
+
+// This test verifies that shots are simulated with the errors of the noise model.
+#[test]
+fn test_noise_model() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/gates.bc");
+    let options = RunOptions {
+        noise_model: Some(NoiseModel::from_toml(
+            "[gates.x]\nbit_flip = 1.0\n[gates.mresetz]\nreadout_error = 1.0\n",
+        )?),
+        ..RunOptions::default()
+    };
+    let results = run_bitcode_parallel(bitcode, None, &Arguments::default(), 2, &options)?;
+    for shot in results {
+        assert_eq!(
+            shot.values().map(|(value, _)| *value).collect::<Vec<_>>(),
+            [true, false, false, false, false, true].map(OutputValue::Result)
+        );
+    }
+    Ok(())
+}
+
+// This test verifies that the density matrix simulator applies the noise model to the same results as the
+// sampled errors when the errors are certain.
+#[test]
+fn test_density_matrix_simulator() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/gates.bc");
+    let options = RunOptions {
+        simulator: SimulatorKind::DensityMatrix,
+        noise_model: Some(NoiseModel::from_toml(
+            "[gates.x]\nbit_flip = 1.0\n[gates.mresetz]\nreadout_error = 1.0\n",
+        )?),
+        ..RunOptions::default()
+    };
+    let results = run_bitcode_parallel(bitcode, None, &Arguments::default(), 2, &options)?;
+    for shot in results {
+        assert_eq!(
+            shot.values().map(|(value, _)| *value).collect::<Vec<_>>(),
+            [true, false, false, false, false, true].map(OutputValue::Result)
+        );
+    }
+    Ok(())
+}
+
+// This test verifies that the expectation intrinsics are bound and return exact values without collapsing the
+// state.
+#[test]
+fn test_expectation() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/expectation.bc");
+    let results = run_bitcode(bitcode, None, 1)?;
+    let values: Vec<f64> = results[0]
+        .values()
+        .map(|(value, _)| match value {
+            OutputValue::Double(value) => *value,
+            _ => panic!("Expected a double, got {value:?}"),
+        })
+        .collect();
+    assert_eq!(values.len(), 2);
+    assert!((values[0] + 1.0).abs() < 1e-10);
+    assert!((values[1] + 2.5).abs() < 1e-10);
+    assert_eq!(results[0].failure, None);
+    Ok(())
+}
+
+// This test verifies that programs run on the matrix product state simulator and report no truncation error
+// when the bond dimension suffices.
+#[test]
+fn test_mps_simulator() -> Result<(), String> {
+    let options = RunOptions {
+        simulator: SimulatorKind::Mps { bond_dimension: 2 },
+        ..RunOptions::default()
+    };
+    let results = run_bitcode_parallel(
+        include_bytes!("resources/gates.bc"),
+        None,
+        &Arguments::default(),
+        2,
+        &options,
+    )?;
+    for shot in results {
+        assert_eq!(
+            shot.values().map(|(value, _)| *value).collect::<Vec<_>>(),
+            [true, true, true, true, true, false].map(OutputValue::Result)
+        );
+        assert!(shot.truncation_error < 1e-10);
+    }
+    Ok(())
+}
+
+// This test verifies that resource estimation counts the operations of each shot, reports measurement outcomes
+// according to the policy and renders the counts as JSON.
+#[test]
+fn test_resource_estimation() -> Result<(), String> {
+    let options = RunOptions {
+        simulator: SimulatorKind::Estimate {
+            measurements: MeasurementPolicy::One,
+        },
+        ..RunOptions::default()
+    };
+    let results = run_bitcode_parallel(
+        include_bytes!("resources/random_bit.bc"),
+        None,
+        &Arguments::default(),
+        2,
+        &options,
+    )?;
+    for shot in &results {
+        assert!(shot.failure.is_none());
+        assert_eq!(
+            shot.values().map(|(value, _)| value).collect::<Vec<_>>(),
+            [&OutputValue::Result(true)]
+        );
+    }
+    assert_eq!(
+        render_estimates(&results[..1]),
+        "[\n  {\n    \"depth\": 2,\n    \"gates\": {\n      \"h\": 1\n    },\n    \"measurement_count\": 1,\n    \"peak_qubits\": 1,\n    \"rotation_count\": 0,\n    \"t_count\": 0\n  }\n]\n"
+    );
+    assert_eq!(results[1].resources, results[0].resources);
+    Ok(())
+}
+
+// This test verifies that the gates and measurements executed by the first shot are traced as OpenQASM 3,
+// including the measurement outcome of the shot, and that the other shots are not traced.
+#[test]
+fn test_qasm_trace() -> Result<(), String> {
+    let options = RunOptions {
+        trace: true,
+        ..RunOptions::default()
+    };
+    let results = run_bitcode_parallel(
+        include_bytes!("resources/random_bit.bc"),
+        None,
+        &Arguments::default(),
+        3,
+        &options,
+    )?;
+    let outcome = match results[0].values().next() {
+        Some((OutputValue::Result(outcome), _)) => u8::from(*outcome),
+        _ => panic!("The shot should record a result."),
+    };
+    assert_eq!(
+        results[0].trace.as_deref(),
+        Some(
+            format!(
+                "OPENQASM 3.0;\ninclude \"stdgates.inc\";\nqubit[1] q;\nbit[1] c;\nh q[0];\nc[0] = measure q[0]; // {outcome}\n"
+            )
+            .as_str()
+        )
+    );
+    assert!(results[1..].iter().all(|shot| shot.trace.is_none()));
+    let results = run_bitcode_parallel(
+        include_bytes!("resources/random_bit.bc"),
+        None,
+        &Arguments::default(),
+        1,
+        &RunOptions::default(),
+    )?;
+    assert_eq!(results[0].trace, None);
+    Ok(())
+}
+
+// This test verifies that OpenQASM programs run on the backend, with classically controlled gates depending on
+// mid-circuit measurements and the classical registers recorded as output in declaration order.
+#[test]
+fn test_qasm_program() -> Result<(), String> {
+    let options = RunOptions {
+        threads: 2,
+        seed: Some(7),
+        ..RunOptions::default()
+    };
+    let results = run_file_parallel(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/resources/teleport.qasm"),
+        None,
+        &Arguments::default(),
+        20,
+        &options,
+    )?;
+    assert_eq!(results.len(), 20);
+    for shot in &results {
+        let values: Vec<_> = shot.values().collect();
+        assert_eq!(values.len(), 4);
+        assert_eq!(values[0], (&OutputValue::Array(2), Some("m")));
+        assert_eq!(values[3], (&OutputValue::Result(false), Some("r")));
+    }
+
+    let runner = Runner::new();
+    let program =
+        runner.load_qasm("OPENQASM 2.0;\nqreg q[1];\ncreg c[1];\nx q;\nmeasure q -> c;\n")?;
+    let results = program.execute(None, &Arguments::default(), 1)?;
+    assert_eq!(
+        results[0].values().collect::<Vec<_>>(),
+        vec![
+            (&OutputValue::Array(1), Some("c")),
+            (&OutputValue::Result(true), None)
+        ]
+    );
+    assert_eq!(
+        program
+            .execute(Some("main"), &Arguments::default(), 1)
+            .err(),
+        Some("OpenQASM programs do not have entry points.".to_owned())
+    );
+    assert_eq!(
+        runner.load_qasm("qreg q[1];\ncz q[0], q[1];").err(),
+        Some(
+            "Failed to parse OpenQASM on line 2: Index 1 is out of range for register 'q' of size 1."
+                .to_owned()
+        )
+    );
+    Ok(())
+}
+
+// This test verifies that Clifford programs run on the stabilizer simulator and that shots applying
+// non-Clifford gates fail with an error naming the gate.
+#[test]
+fn test_stabilizer_simulator() -> Result<(), String> {
+    let options = RunOptions {
+        simulator: SimulatorKind::Stabilizer,
+        ..RunOptions::default()
+    };
+    let results = run_bitcode_parallel(
+        include_bytes!("resources/random_bit.bc"),
+        None,
+        &Arguments::default(),
+        10,
+        &options,
+    )?;
+    assert!(results.iter().all(|shot| shot.failure.is_none()));
+
+    let results = run_bitcode_parallel(
+        include_bytes!("resources/gates.bc"),
+        None,
+        &Arguments::default(),
+        1,
+        &options,
+    )?;
+    assert_eq!(
+        results[0].failure.as_deref(),
+        Some("The stabilizer simulator does not support the non-Clifford gate Rz.")
+    );
+    Ok(())
 }
 
 #[test]
@@ -202,6 +1167,59 @@ fn run_file_errors_on_missing_binding() {
     );
 }
 
+static CUSTOM_INTRINSIC_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn custom_intrinsic() {
+    CUSTOM_INTRINSIC_CALLS.fetch_add(1, Ordering::SeqCst);
+}
+
+// This test verifies that registered custom intrinsics are bound for declarations the runner does not provide.
+#[test]
+fn run_with_custom_intrinsic() -> Result<(), String> {
+    let bitcode = include_bytes!("resources/missing-intrinsic.bc");
+    let mut intrinsics = Intrinsics::new();
+    unsafe {
+        intrinsics.register(
+            "__quantum__qis__mycustomintrinsic__body",
+            0,
+            custom_intrinsic as *const c_void,
+        );
+    }
+    let options = RunOptions {
+        intrinsics: intrinsics.clone(),
+        ..RunOptions::default()
+    };
+    let calls = CUSTOM_INTRINSIC_CALLS.load(Ordering::SeqCst);
+    run_bitcode_parallel(bitcode, None, &Arguments::default(), 3, &options)?;
+    assert_eq!(CUSTOM_INTRINSIC_CALLS.load(Ordering::SeqCst) - calls, 3);
+
+    unsafe {
+        intrinsics.register(
+            "__quantum__qis__mycustomintrinsic__body",
+            1,
+            custom_intrinsic as *const c_void,
+        );
+    }
+    let runner = Runner::with_intrinsics(intrinsics);
+    assert_eq!(
+        runner.load_bitcode(bitcode).err(),
+        Some(
+            "Function '__quantum__qis__mycustomintrinsic__body' has mismatched parameters: expected 1, found 0"
+                .to_owned()
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn load_plugin_errors_on_missing_library() {
+    let mut intrinsics = Intrinsics::new();
+    let result = unsafe { intrinsics.load_plugin("/some/bad/libplugin.so") };
+    assert!(result
+        .unwrap_err()
+        .starts_with("Failed to load plugin '/some/bad/libplugin.so': "));
+}
+
 #[test]
 fn mixed_output_recording_calls_fail() {
     let bitcode = include_bytes!("resources/mixed_output.bc");
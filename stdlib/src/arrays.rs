@@ -4,6 +4,19 @@
 use crate::{strings::convert, update_counts};
 use std::{mem::ManuallyDrop, rc::Rc, usize};
 
+crate::exports! {
+    fn __quantum__rt__array_create_1d(elem_size: u32, count: u64) -> *const QirArray;
+    fn __quantum__rt__array_copy(arr: *const QirArray, force: bool) -> *const QirArray;
+    fn __quantum__rt__array_concatenate(
+        arr1: *const QirArray,
+        arr2: *const QirArray,
+    ) -> *const QirArray;
+    fn __quantum__rt__array_get_size_1d(arr: *const QirArray) -> u64;
+    fn __quantum__rt__array_get_element_ptr_1d(arr: *const QirArray, index: u64) -> *mut i8;
+    fn __quantum__rt__array_update_reference_count(arr: *const QirArray, update: i32);
+    fn __quantum__rt__array_update_alias_count(arr: *const QirArray, update: i32);
+}
+
 #[cfg(not(feature = "fail-support"))]
 #[allow(improper_ctypes)]
 extern "C" {
@@ -19,8 +32,9 @@ pub struct QirArray {
     pub(crate) data: Vec<u8>,
 }
 
-#[no_mangle]
-pub extern "C" fn __quantum__rt__array_create_1d(elem_size: u32, count: u64) -> *const QirArray {
+#[allow(non_snake_case)]
+#[must_use]
+pub fn __quantum__rt__array_create_1d(elem_size: u32, count: u64) -> *const QirArray {
     let elem_size = elem_size
         .try_into()
         .expect("The `elem_size` argument should fit in the `usize` type for this platform.");
@@ -31,11 +45,9 @@ pub extern "C" fn __quantum__rt__array_create_1d(elem_size: u32, count: u64) ->
     Rc::into_raw(Rc::new(QirArray { elem_size, data }))
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__array_copy(
-    arr: *const QirArray,
-    force: bool,
-) -> *const QirArray {
+#[allow(non_snake_case)]
+#[must_use]
+pub unsafe fn __quantum__rt__array_copy(arr: *const QirArray, force: bool) -> *const QirArray {
     // Wrap the array in a `ManuallyDrop` to effectively borrow it and ensure the array
     // won't be dropped, refcount decremented, and cleaned up.
     let rc = ManuallyDrop::new(Rc::from_raw(arr));
@@ -48,8 +60,9 @@ pub unsafe extern "C" fn __quantum__rt__array_copy(
     }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__array_concatenate(
+#[allow(non_snake_case)]
+#[must_use]
+pub unsafe fn __quantum__rt__array_concatenate(
     arr1: *const QirArray,
     arr2: *const QirArray,
 ) -> *const QirArray {
@@ -77,19 +90,18 @@ pub unsafe extern "C" fn __quantum__rt__array_concatenate(
     Rc::into_raw(Rc::new(new_array))
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__array_get_size_1d(arr: *const QirArray) -> u64 {
+#[allow(non_snake_case)]
+#[must_use]
+pub unsafe fn __quantum__rt__array_get_size_1d(arr: *const QirArray) -> u64 {
     let array = &*arr;
     let len = array.data.len() / array.elem_size;
     len.try_into()
         .expect("Length of array should always fit in a 64-bit integer.")
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__array_get_element_ptr_1d(
-    arr: *const QirArray,
-    index: u64,
-) -> *mut i8 {
+#[allow(non_snake_case)]
+#[must_use]
+pub unsafe fn __quantum__rt__array_get_element_ptr_1d(arr: *const QirArray, index: u64) -> *mut i8 {
     let array = &*arr;
     let index: usize = index
         .try_into()
@@ -97,19 +109,13 @@ pub unsafe extern "C" fn __quantum__rt__array_get_element_ptr_1d(
     array.data.as_ptr().add(array.elem_size * index) as *mut i8
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__array_update_reference_count(
-    arr: *const QirArray,
-    update: i32,
-) {
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__array_update_reference_count(arr: *const QirArray, update: i32) {
     update_counts(arr, update, false);
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__array_update_alias_count(
-    arr: *const QirArray,
-    update: i32,
-) {
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__array_update_alias_count(arr: *const QirArray, update: i32) {
     update_counts(arr, update, true);
 }
 
@@ -10,6 +10,26 @@ use crate::{
 };
 use std::{cell::RefCell, mem::ManuallyDrop, rc::Rc};
 
+crate::exports! {
+    fn __quantum__rt__callable_create(
+        func_table: *mut *mut u8,
+        mem_table: *mut *mut u8,
+        cap_tuple: *mut u8,
+    ) -> *const Callable;
+    fn __quantum__rt__callable_invoke(
+        callable: *const Callable,
+        args_tup: *mut u8,
+        res_tup: *mut u8,
+    );
+    fn __quantum__rt__callable_copy(callable: *const Callable, force: bool) -> *const Callable;
+    fn __quantum__rt__callable_make_adjoint(callable: *const Callable);
+    fn __quantum__rt__callable_make_controlled(callable: *const Callable);
+    fn __quantum__rt__callable_update_reference_count(callable: *const Callable, update: i32);
+    fn __quantum__rt__callable_update_alias_count(callable: *const Callable, update: i32);
+    fn __quantum__rt__capture_update_reference_count(callable: *const Callable, update: i32);
+    fn __quantum__rt__capture_update_alias_count(callable: *const Callable, update: i32);
+}
+
 #[derive(Clone)]
 pub struct Callable {
     func_table: *mut *mut u8,
@@ -19,8 +39,8 @@ pub struct Callable {
     ctls_count: RefCell<u32>,
 }
 
-#[no_mangle]
-pub extern "C" fn __quantum__rt__callable_create(
+#[allow(non_snake_case)]
+pub fn __quantum__rt__callable_create(
     func_table: *mut *mut u8,
     mem_table: *mut *mut u8,
     cap_tuple: *mut u8,
@@ -34,9 +54,9 @@ pub extern "C" fn __quantum__rt__callable_create(
     }))
 }
 
-#[no_mangle]
 #[allow(clippy::cast_ptr_alignment)]
-pub unsafe extern "C" fn __quantum__rt__callable_invoke(
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__callable_invoke(
     callable: *const Callable,
     args_tup: *mut u8,
     res_tup: *mut u8,
@@ -101,8 +121,8 @@ pub unsafe extern "C" fn __quantum__rt__callable_invoke(
     }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__callable_copy(
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__callable_copy(
     callable: *const Callable,
     force: bool,
 ) -> *const Callable {
@@ -116,36 +136,33 @@ pub unsafe extern "C" fn __quantum__rt__callable_copy(
     }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__callable_make_adjoint(callable: *const Callable) {
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__callable_make_adjoint(callable: *const Callable) {
     let call = &*callable;
     call.is_adj.replace_with(|&mut old| !old);
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__callable_make_controlled(callable: *const Callable) {
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__callable_make_controlled(callable: *const Callable) {
     let call = &*callable;
     call.ctls_count.replace_with(|&mut old| old + 1);
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__callable_update_reference_count(
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__callable_update_reference_count(
     callable: *const Callable,
     update: i32,
 ) {
     update_counts(callable, update, false);
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__callable_update_alias_count(
-    callable: *const Callable,
-    update: i32,
-) {
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__callable_update_alias_count(callable: *const Callable, update: i32) {
     update_counts(callable, update, true);
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__capture_update_reference_count(
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__capture_update_reference_count(
     callable: *const Callable,
     update: i32,
 ) {
@@ -155,11 +172,8 @@ pub unsafe extern "C" fn __quantum__rt__capture_update_reference_count(
     }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__capture_update_alias_count(
-    callable: *const Callable,
-    update: i32,
-) {
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__capture_update_alias_count(callable: *const Callable, update: i32) {
     let call = &*callable;
     if !call.mem_table.is_null() && !(*(call.mem_table.wrapping_add(1))).is_null() {
         (*call
@@ -0,0 +1,116 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Failures of the exported functions, such as those raised by `__quantum__rt__fail`. While failures are captured
+//! on a thread, the exported functions catch panics instead of unwinding into the program that called them: the
+//! message of the first failure is kept and the failing function returns a zero value, so that the program can
+//! check for the failure and stop. Otherwise a failure panics as usual, which prints its message.
+
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    mem,
+    panic::{self, AssertUnwindSafe},
+};
+
+thread_local! {
+    static CAPTURE: Cell<bool> = Cell::new(false);
+    static FAILURE: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Sets whether failures are captured on the current thread, returning the previous setting.
+#[allow(clippy::must_use_candidate)]
+pub fn set_capturing(enabled: bool) -> bool {
+    CAPTURE.with(|capture| capture.replace(enabled))
+}
+
+/// Returns whether failures are captured on the current thread.
+#[must_use]
+pub fn is_capturing() -> bool {
+    CAPTURE.with(Cell::get)
+}
+
+/// Returns whether a failure has been captured on the current thread and not yet taken.
+#[must_use]
+pub fn has_failed() -> bool {
+    FAILURE.with(|failure| failure.borrow().is_some())
+}
+
+/// Returns the message of the first failure captured on the current thread since the last call, if any.
+#[must_use]
+pub fn take_failure() -> Option<String> {
+    FAILURE.with(RefCell::take)
+}
+
+/// Calls the function and returns its result, or `None` if it panics, in which case the panic is captured as a
+/// failure. If failures are not captured on the current thread, the panic continues to unwind.
+pub fn catch_failure<T>(f: impl FnOnce() -> T) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(value) => Some(value),
+        Err(payload) if !is_capturing() => panic::resume_unwind(payload),
+        Err(payload) => {
+            FAILURE.with(|failure| {
+                failure
+                    .borrow_mut()
+                    .get_or_insert_with(|| failure_message(payload.as_ref()));
+            });
+            None
+        }
+    }
+}
+
+/// Calls an exported function on behalf of its `extern "C"` counterpart, returning a zero value if it fails while
+/// failures are captured. Otherwise the function is called directly, so that a failure panics as it did before
+/// failures could be captured.
+///
+/// # Safety
+///
+/// The all-zero bit pattern must be a valid value of the return type.
+#[doc(hidden)]
+pub unsafe fn guard<T>(f: impl FnOnce() -> T) -> T {
+    if !is_capturing() {
+        return f();
+    }
+    catch_failure(f).unwrap_or_else(|| mem::zeroed())
+}
+
+fn failure_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else {
+        "Failed with an unknown error.".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{catch_failure, guard, has_failed, set_capturing, take_failure};
+    use std::panic;
+
+    #[test]
+    fn test_captured_failure() {
+        let previous = set_capturing(true);
+        assert_eq!(catch_failure(|| 1), Some(1));
+        assert!(!has_failed());
+        let value: *const u8 = unsafe { guard(|| panic::resume_unwind(Box::new("first"))) };
+        assert!(value.is_null());
+        assert_eq!(
+            catch_failure(|| panic::resume_unwind(Box::new("second"))),
+            None::<()>
+        );
+        assert!(has_failed());
+        assert_eq!(take_failure().as_deref(), Some("first"));
+        assert!(!has_failed());
+        set_capturing(previous);
+    }
+
+    #[test]
+    #[should_panic(expected = "uncaptured")]
+    fn test_uncaptured_failure_panics() {
+        let previous = set_capturing(false);
+        unsafe { guard::<()>(|| panic!("uncaptured")) };
+        set_capturing(previous);
+    }
+}
@@ -13,9 +13,11 @@
 pub mod arrays;
 pub mod bigints;
 pub mod callables;
+pub mod failures;
 pub mod math;
 pub mod output_recording;
 pub mod range_support;
+pub mod registry;
 pub mod strings;
 pub mod tuples;
 
@@ -25,6 +27,13 @@ use std::{
     rc::{Rc, Weak},
 };
 
+crate::exports! {
+    fn __quantum__rt__memory_allocate(size: u64) -> *mut u8;
+    #[cfg(feature = "fail-support")]
+    fn __quantum__rt__fail(str: *const CString);
+    fn __quantum__rt__message(str: *const CString);
+}
+
 /// Utility used for managing refcounted items.
 unsafe fn update_counts<T>(raw_rc: *const T, update: i32, is_alias: bool) {
     let mut remaining = update;
@@ -65,8 +74,9 @@ pub enum Pauli {
     Y = 3,
 }
 
-#[no_mangle]
-pub extern "C" fn __quantum__rt__memory_allocate(size: u64) -> *mut u8 {
+#[allow(non_snake_case)]
+#[must_use]
+pub fn __quantum__rt__memory_allocate(size: u64) -> *mut u8 {
     (vec![
         0_u8;
         size.try_into()
@@ -77,15 +87,26 @@ pub extern "C" fn __quantum__rt__memory_allocate(size: u64) -> *mut u8 {
 }
 
 #[cfg(feature = "fail-support")]
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__fail(str: *const CString) {
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__fail(str: *const CString) {
     __quantum__rt__message(str);
-    panic!("{}", (*str).to_str().expect("Unable to convert string"));
+    let msg = (*str).to_str().expect("Unable to convert string");
+    if failures::is_capturing() {
+        // The message is already recorded, so the captured failure bypasses the panic hook, which would print it
+        // again.
+        std::panic::resume_unwind(Box::new(msg.to_owned()));
+    }
+    panic!("{msg}");
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__message(str: *const CString) {
-    println!("{}", (*str).to_str().expect("Unable to convert string"));
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__message(str: *const CString) {
+    output_recording::record_output(output_recording::OutputRecord::Message(
+        (*str)
+            .to_str()
+            .expect("Unable to convert string")
+            .to_owned(),
+    ));
 }
 
 #[cfg(test)]
@@ -2,31 +2,249 @@
 // Licensed under the MIT License.
 
 use std::{
-    ffi::{c_char, c_double, CString},
-    fmt::Display,
+    cell::RefCell,
+    ffi::{c_char, c_double, CStr, CString},
+    fmt::{self, Display},
+    hash::{Hash, Hasher},
     io::{self, Write},
+    mem,
+    rc::Rc,
+    sync::mpsc::Sender,
 };
 
 use crate::strings::double_to_string;
 
+crate::exports! {
+    fn __quantum__rt__array_record_output(val: i64, tag: *mut c_char) => Labeled;
+    fn __quantum__rt__tuple_record_output(val: i64, tag: *mut c_char) => Labeled;
+    fn __quantum__rt__int_record_output(val: i64, tag: *mut c_char) => Labeled;
+    fn __quantum__rt__double_record_output(val: c_double, tag: *mut c_char) => Labeled;
+    fn __quantum__rt__bool_record_output(val: bool, tag: *mut c_char) => Labeled;
+    fn __quantum__rt__message_record_output(str: *const CString);
+}
+
 #[cfg(windows)]
 const LINE_ENDING: &[u8] = b"\r\n";
 #[cfg(not(windows))]
 const LINE_ENDING: &[u8] = b"\n";
 
-fn output(
-    ty: &str,
-    val: &dyn Display,
-    tag: *mut c_char,
-    output: &mut impl Write,
-) -> std::io::Result<()> {
-    output.write_fmt(format_args!("OUTPUT\t{ty}\t{val}"))?;
-    if !tag.is_null() {
-        output.write_all(b"\t")?;
-        unsafe {
-            output.write_all(CString::from_raw(tag).as_bytes())?;
+/// A single value recorded by one of the output recording functions.
+#[derive(Debug, Clone, Copy)]
+pub enum OutputValue {
+    Int(i64),
+    Double(f64),
+    Bool(bool),
+    Result(bool),
+    /// Start of an array with the given number of elements.
+    Array(i64),
+    /// Start of a tuple with the given number of elements.
+    Tuple(i64),
+}
+
+impl OutputValue {
+    /// The type name used for this value in the `OUTPUT` records.
+    #[must_use]
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            OutputValue::Int(_) => "INT",
+            OutputValue::Double(_) => "DOUBLE",
+            OutputValue::Bool(_) => "BOOL",
+            OutputValue::Result(_) => "RESULT",
+            OutputValue::Array(_) => "ARRAY",
+            OutputValue::Tuple(_) => "TUPLE",
+        }
+    }
+}
+
+// Doubles are compared by their bit patterns, which makes the comparison an equivalence relation
+// and allows values to be used as keys, e.g., when grouping shots by their output.
+impl PartialEq for OutputValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (OutputValue::Int(a), OutputValue::Int(b))
+            | (OutputValue::Array(a), OutputValue::Array(b))
+            | (OutputValue::Tuple(a), OutputValue::Tuple(b)) => a == b,
+            (OutputValue::Double(a), OutputValue::Double(b)) => a.to_bits() == b.to_bits(),
+            (OutputValue::Bool(a), OutputValue::Bool(b))
+            | (OutputValue::Result(a), OutputValue::Result(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for OutputValue {}
+
+impl Hash for OutputValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            OutputValue::Int(val) | OutputValue::Array(val) | OutputValue::Tuple(val) => {
+                val.hash(state);
+            }
+            OutputValue::Double(val) => val.to_bits().hash(state),
+            OutputValue::Bool(val) | OutputValue::Result(val) => val.hash(state),
         }
     }
+}
+
+impl Display for OutputValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputValue::Int(val) | OutputValue::Array(val) | OutputValue::Tuple(val) => {
+                write!(f, "{val}")
+            }
+            OutputValue::Double(val) => write!(f, "{}", double_to_string(*val)),
+            OutputValue::Bool(val) => write!(f, "{val}"),
+            OutputValue::Result(val) => write!(f, "{}", i64::from(*val)),
+        }
+    }
+}
+
+/// Markers emitted by the legacy (unlabeled) array and tuple output recording functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegacyMarker {
+    ArrayStart,
+    ArrayEnd,
+    TupleStart,
+    TupleEnd,
+}
+
+impl Display for LegacyMarker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LegacyMarker::ArrayStart => "ARRAY_START",
+            LegacyMarker::ArrayEnd => "ARRAY_END",
+            LegacyMarker::TupleStart => "TUPLE_START",
+            LegacyMarker::TupleEnd => "TUPLE_END",
+        })
+    }
+}
+
+/// A single entry of the program output, in the order it was produced.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputRecord {
+    /// A value recorded with the labeled output recording functions, along with its optional tag.
+    Value {
+        value: OutputValue,
+        tag: Option<String>,
+    },
+    /// A value recorded with the legacy (unlabeled) output recording functions.
+    LegacyValue(OutputValue),
+    /// An array or tuple marker recorded with the legacy output recording functions.
+    LegacyMarker(LegacyMarker),
+    /// A message recorded with `__quantum__rt__message_record_output`.
+    Info(String),
+    /// A message emitted by the program via `__quantum__rt__message`.
+    Message(String),
+}
+
+impl Display for OutputRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputRecord::Value { value, tag } => {
+                write!(f, "OUTPUT\t{}\t{value}", value.type_name())?;
+                if let Some(tag) = tag {
+                    write!(f, "\t{tag}")?;
+                }
+                Ok(())
+            }
+            OutputRecord::LegacyValue(OutputValue::Result(val)) => {
+                write!(f, "RESULT\t{}", if *val { "1" } else { "0" })
+            }
+            OutputRecord::LegacyValue(value) => write!(f, "RESULT\t{value}"),
+            OutputRecord::LegacyMarker(marker) => write!(f, "RESULT\t{marker}"),
+            OutputRecord::Info(msg) => write!(f, "INFO\t{}", msg.escape_default()),
+            OutputRecord::Message(msg) => f.write_str(msg),
+        }
+    }
+}
+
+/// Destination for the records produced by the output recording functions and `__quantum__rt__message`.
+/// Each thread has its own sink, which defaults to [`StdoutSink`] and can be replaced with
+/// [`set_output_sink`].
+pub trait OutputSink {
+    /// Handles a single output record.
+    fn record(&mut self, record: OutputRecord);
+}
+
+/// Sink that writes each record as a line of text to standard output.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdoutSink;
+
+impl OutputSink for StdoutSink {
+    fn record(&mut self, record: OutputRecord) {
+        output(&record, &mut io::stdout()).expect("Failed to write output");
+    }
+}
+
+/// Sink that writes each record as a line of text to the wrapped writer, such as a file.
+#[derive(Debug)]
+pub struct WriterSink<W: Write>(pub W);
+
+impl<W: Write> OutputSink for WriterSink<W> {
+    fn record(&mut self, record: OutputRecord) {
+        output(&record, &mut self.0).expect("Failed to write output");
+    }
+}
+
+/// Sink that collects records into a shared buffer. Clones of the buffer share the same records,
+/// so one clone can be installed as sink while another is used to read the records back.
+#[derive(Debug, Default, Clone)]
+pub struct OutputBuffer(Rc<RefCell<Vec<OutputRecord>>>);
+
+impl OutputBuffer {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes and returns all records collected so far.
+    #[must_use]
+    pub fn take(&self) -> Vec<OutputRecord> {
+        self.0.take()
+    }
+}
+
+impl OutputSink for OutputBuffer {
+    fn record(&mut self, record: OutputRecord) {
+        self.0.borrow_mut().push(record);
+    }
+}
+
+/// Sends each record over the channel. Records are dropped if the receiver has disconnected.
+impl OutputSink for Sender<OutputRecord> {
+    fn record(&mut self, record: OutputRecord) {
+        let _ = self.send(record);
+    }
+}
+
+thread_local! {
+    static OUTPUT_SINK: RefCell<Box<dyn OutputSink>> = RefCell::new(Box::new(StdoutSink));
+}
+
+/// Replaces the output sink of the current thread, returning the previously installed sink.
+#[allow(clippy::must_use_candidate)]
+pub fn set_output_sink(sink: Box<dyn OutputSink>) -> Box<dyn OutputSink> {
+    OUTPUT_SINK.with(|current| current.replace(sink))
+}
+
+/// Records the given entry into the program output by passing it to the output sink of the current thread.
+pub fn record_output(record: OutputRecord) {
+    OUTPUT_SINK.with(|sink| sink.borrow_mut().record(record));
+}
+
+fn record_value(value: OutputValue, tag: *mut c_char) {
+    let tag = if tag.is_null() {
+        None
+    } else {
+        // The tag is owned by the caller, so it is only borrowed here.
+        unsafe { Some(CStr::from_ptr(tag).to_string_lossy().into_owned()) }
+    };
+    record_output(OutputRecord::Value { value, tag });
+}
+
+fn output(record: &OutputRecord, output: &mut impl Write) -> std::io::Result<()> {
+    output.write_fmt(format_args!("{record}"))?;
     output.write_all(LINE_ENDING)?;
     Ok(())
 }
@@ -35,94 +253,104 @@ fn output(
 /// start of an array and how many array elements it has. The second
 /// parameter defines a string label for the array. Depending on
 /// the output schema, the label is included in the output or omitted.
-#[no_mangle]
-pub extern "C" fn __quantum__rt__array_record_output(val: i64, tag: *mut c_char) {
-    output("ARRAY", &val, tag, &mut io::stdout()).expect("Failed to write array output");
+#[allow(non_snake_case)]
+pub fn __quantum__rt__array_record_output(val: i64, tag: *mut c_char) {
+    record_value(OutputValue::Array(val), tag);
 }
 
 /// Inserts a marker in the generated output that indicates the
 /// start of a tuple and how many tuple elements it has. The second
 /// parameter defines a string label for the tuple. Depending on
 /// the output schema, the label is included in the output or omitted.
-#[no_mangle]
-pub extern "C" fn __quantum__rt__tuple_record_output(val: i64, tag: *mut c_char) {
-    output("TUPLE", &val, tag, &mut io::stdout()).expect("Failed to write tuple output");
+#[allow(non_snake_case)]
+pub fn __quantum__rt__tuple_record_output(val: i64, tag: *mut c_char) {
+    record_value(OutputValue::Tuple(val), tag);
 }
 
-#[no_mangle]
-pub extern "C" fn __quantum__rt__int_record_output(val: i64, tag: *mut c_char) {
-    output("INT", &val, tag, &mut io::stdout()).expect("Failed to write int output");
+#[allow(non_snake_case)]
+pub fn __quantum__rt__int_record_output(val: i64, tag: *mut c_char) {
+    record_value(OutputValue::Int(val), tag);
 }
 
-#[no_mangle]
-pub extern "C" fn __quantum__rt__double_record_output(val: c_double, tag: *mut c_char) {
-    output("DOUBLE", &double_to_string(val), tag, &mut io::stdout())
-        .expect("Failed to write double output");
+#[allow(non_snake_case)]
+pub fn __quantum__rt__double_record_output(val: c_double, tag: *mut c_char) {
+    record_value(OutputValue::Double(val), tag);
 }
 
-#[no_mangle]
-pub extern "C" fn __quantum__rt__bool_record_output(val: bool, tag: *mut c_char) {
-    output("BOOL", &val, tag, &mut io::stdout()).expect("Failed to write bool output");
+#[allow(non_snake_case)]
+pub fn __quantum__rt__bool_record_output(val: bool, tag: *mut c_char) {
+    record_value(OutputValue::Bool(val), tag);
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__message_record_output(str: *const CString) {
-    println!(
-        "INFO\t{}",
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__message_record_output(str: *const CString) {
+    record_output(OutputRecord::Info(
         (*str)
             .to_str()
             .expect("Unable to convert input string")
-            .escape_default()
-    );
+            .to_owned(),
+    ));
 }
 
 pub mod legacy {
     use std::{ffi::c_double, ptr::null_mut};
 
-    use crate::strings::double_to_string;
+    use super::{record_output, LegacyMarker, OutputRecord, OutputValue};
+
+    crate::exports! {
+        fn __quantum__rt__array_start_record_output() => Legacy;
+        fn __quantum__rt__array_end_record_output() => Legacy;
+        fn __quantum__rt__tuple_start_record_output() => Legacy;
+        fn __quantum__rt__tuple_end_record_output() => Legacy;
+        fn __quantum__rt__int_record_output(val: i64) => Legacy;
+        fn __quantum__rt__double_record_output(val: c_double) => Legacy;
+        fn __quantum__rt__bool_record_output(val: bool) => Legacy;
+        fn __quantum__rt__array_record_output(val: i64) => Legacy;
+        fn __quantum__rt__tuple_record_output(val: i64) => Legacy;
+    }
 
     #[allow(non_snake_case)]
-    pub extern "C" fn __quantum__rt__array_start_record_output() {
-        println!("RESULT\tARRAY_START");
+    pub fn __quantum__rt__array_start_record_output() {
+        record_output(OutputRecord::LegacyMarker(LegacyMarker::ArrayStart));
     }
 
     #[allow(non_snake_case)]
-    pub extern "C" fn __quantum__rt__array_end_record_output() {
-        println!("RESULT\tARRAY_END");
+    pub fn __quantum__rt__array_end_record_output() {
+        record_output(OutputRecord::LegacyMarker(LegacyMarker::ArrayEnd));
     }
 
     #[allow(non_snake_case)]
-    pub extern "C" fn __quantum__rt__tuple_start_record_output() {
-        println!("RESULT\tTUPLE_START");
+    pub fn __quantum__rt__tuple_start_record_output() {
+        record_output(OutputRecord::LegacyMarker(LegacyMarker::TupleStart));
     }
 
     #[allow(non_snake_case)]
-    pub extern "C" fn __quantum__rt__tuple_end_record_output() {
-        println!("RESULT\tTUPLE_END");
+    pub fn __quantum__rt__tuple_end_record_output() {
+        record_output(OutputRecord::LegacyMarker(LegacyMarker::TupleEnd));
     }
 
     #[allow(non_snake_case)]
-    pub extern "C" fn __quantum__rt__int_record_output(val: i64) {
-        println!("RESULT\t{val}");
+    pub fn __quantum__rt__int_record_output(val: i64) {
+        record_output(OutputRecord::LegacyValue(OutputValue::Int(val)));
     }
 
     #[allow(non_snake_case)]
-    pub extern "C" fn __quantum__rt__double_record_output(val: c_double) {
-        println!("RESULT\t{}", double_to_string(val));
+    pub fn __quantum__rt__double_record_output(val: c_double) {
+        record_output(OutputRecord::LegacyValue(OutputValue::Double(val)));
     }
 
     #[allow(non_snake_case)]
-    pub extern "C" fn __quantum__rt__bool_record_output(val: bool) {
-        println!("RESULT\t{val}");
+    pub fn __quantum__rt__bool_record_output(val: bool) {
+        record_output(OutputRecord::LegacyValue(OutputValue::Bool(val)));
     }
 
     #[allow(non_snake_case)]
-    pub extern "C" fn __quantum__rt__array_record_output(val: i64) {
+    pub fn __quantum__rt__array_record_output(val: i64) {
         super::__quantum__rt__array_record_output(val, null_mut());
     }
 
     #[allow(non_snake_case)]
-    pub extern "C" fn __quantum__rt__tuple_record_output(val: i64) {
+    pub fn __quantum__rt__tuple_record_output(val: i64) {
         super::__quantum__rt__tuple_record_output(val, null_mut());
     }
 }
@@ -136,43 +364,195 @@ mod tests {
     #[test]
     fn test_output_int_untagged() {
         let val: i64 = 42;
-        assert_untagged_output_match("INT", &val, "OUTPUT\tINT\t42");
+        assert_untagged_output_match(
+            || __quantum__rt__int_record_output(val, null_mut()),
+            "OUTPUT\tINT\t42",
+        );
     }
     #[test]
     fn test_output_double_untagged() {
         let val: f64 = 42.4533;
-        let double_str = double_to_string(val);
-        assert_untagged_output_match("DOUBLE", &double_str, "OUTPUT\tDOUBLE\t42.4533");
+        assert_untagged_output_match(
+            || __quantum__rt__double_record_output(val, null_mut()),
+            "OUTPUT\tDOUBLE\t42.4533",
+        );
     }
     #[test]
     fn test_output_double_whole_untagged() {
         let val: c_double = 42.000_000_000_000_001;
-        let double_str = double_to_string(val);
-        assert_untagged_output_match("DOUBLE", &double_str, "OUTPUT\tDOUBLE\t42.0");
+        assert_untagged_output_match(
+            || __quantum__rt__double_record_output(val, null_mut()),
+            "OUTPUT\tDOUBLE\t42.0",
+        );
     }
     #[test]
     fn test_output_bool_true_untagged() {
         let val: bool = true;
-        assert_untagged_output_match("BOOL", &val, "OUTPUT\tBOOL\ttrue");
+        assert_untagged_output_match(
+            || __quantum__rt__bool_record_output(val, null_mut()),
+            "OUTPUT\tBOOL\ttrue",
+        );
     }
     #[test]
     fn test_output_bool_false_untagged() {
         let val: bool = false;
-        assert_untagged_output_match("BOOL", &val, "OUTPUT\tBOOL\tfalse");
+        assert_untagged_output_match(
+            || __quantum__rt__bool_record_output(val, null_mut()),
+            "OUTPUT\tBOOL\tfalse",
+        );
     }
     #[test]
     fn test_output_tuple_untagged() {
         let val: i64 = 42;
-        assert_untagged_output_match("TUPLE", &val, "OUTPUT\tTUPLE\t42");
+        assert_untagged_output_match(
+            || __quantum__rt__tuple_record_output(val, null_mut()),
+            "OUTPUT\tTUPLE\t42",
+        );
     }
     #[test]
     fn test_output_array_untagged() {
         let val: i64 = 42;
-        assert_untagged_output_match("ARRAY", &val, "OUTPUT\tARRAY\t42");
+        assert_untagged_output_match(
+            || __quantum__rt__array_record_output(val, null_mut()),
+            "OUTPUT\tARRAY\t42",
+        );
+    }
+    #[test]
+    fn test_untagged_records() {
+        let buffer = OutputBuffer::new();
+        let previous = set_output_sink(Box::new(buffer.clone()));
+        __quantum__rt__array_record_output(2, null_mut());
+        __quantum__rt__int_record_output(42, null_mut());
+        __quantum__rt__double_record_output(42.4533, null_mut());
+        __quantum__rt__tuple_record_output(1, null_mut());
+        __quantum__rt__bool_record_output(false, null_mut());
+        set_output_sink(previous);
+        let untagged = |value| OutputRecord::Value { value, tag: None };
+        assert_eq!(
+            buffer.take(),
+            vec![
+                untagged(OutputValue::Array(2)),
+                untagged(OutputValue::Int(42)),
+                untagged(OutputValue::Double(42.4533)),
+                untagged(OutputValue::Tuple(1)),
+                untagged(OutputValue::Bool(false)),
+            ]
+        );
+    }
+    #[test]
+    fn test_output_result_tagged() {
+        let tag = CString::new("r0").unwrap();
+        let record = OutputRecord::Value {
+            value: OutputValue::Result(true),
+            tag: Some(tag.to_str().unwrap().to_owned()),
+        };
+        assert_output_match(&record, "OUTPUT\tRESULT\t1\tr0");
+    }
+    #[test]
+    fn test_output_legacy_records() {
+        assert_output_match(
+            &OutputRecord::LegacyValue(OutputValue::Result(false)),
+            "RESULT\t0",
+        );
+        assert_output_match(
+            &OutputRecord::LegacyMarker(LegacyMarker::ArrayStart),
+            "RESULT\tARRAY_START",
+        );
+        assert_output_match(&OutputRecord::Info("a\tb".to_owned()), "INFO\ta\\tb");
+    }
+    #[test]
+    fn test_output_value_equality() {
+        use std::collections::HashSet;
+        assert_eq!(OutputValue::Double(f64::NAN), OutputValue::Double(f64::NAN));
+        assert_ne!(OutputValue::Double(0.0), OutputValue::Double(-0.0));
+        assert_ne!(OutputValue::Array(1), OutputValue::Tuple(1));
+        assert_ne!(OutputValue::Bool(true), OutputValue::Result(true));
+        let values: HashSet<_> = [
+            OutputValue::Int(1),
+            OutputValue::Int(1),
+            OutputValue::Double(1.0),
+            OutputValue::Result(true),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(values.len(), 3);
+    }
+    #[test]
+    fn test_output_buffer_sink() {
+        let tag = CString::new("tag").unwrap();
+        let buffer = OutputBuffer::new();
+        let previous = set_output_sink(Box::new(buffer.clone()));
+        __quantum__rt__int_record_output(3, tag.as_ptr().cast_mut());
+        legacy::__quantum__rt__bool_record_output(true);
+        set_output_sink(previous);
+        assert_eq!(
+            buffer.take(),
+            vec![
+                OutputRecord::Value {
+                    value: OutputValue::Int(3),
+                    tag: Some("tag".to_owned())
+                },
+                OutputRecord::LegacyValue(OutputValue::Bool(true)),
+            ]
+        );
+        // The tag remains owned by the caller.
+        assert_eq!(tag.to_str().unwrap(), "tag");
+        assert!(buffer.take().is_empty());
+    }
+    #[test]
+    fn test_channel_sink() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let previous = set_output_sink(Box::new(sender));
+        __quantum__rt__tuple_record_output(2, null_mut());
+        let msg = CString::new("Message").unwrap();
+        unsafe {
+            crate::__quantum__rt__message(&msg);
+        }
+        // Restoring the previous sink drops the sender, which ends the iteration below.
+        set_output_sink(previous);
+        assert_eq!(
+            receiver.iter().collect::<Vec<_>>(),
+            vec![
+                OutputRecord::Value {
+                    value: OutputValue::Tuple(2),
+                    tag: None
+                },
+                OutputRecord::Message("Message".to_owned()),
+            ]
+        );
+    }
+    #[test]
+    fn test_writer_sink() {
+        let mut sink = WriterSink(Vec::new());
+        sink.record(OutputRecord::LegacyMarker(LegacyMarker::TupleEnd));
+        sink.record(OutputRecord::LegacyValue(OutputValue::Int(1)));
+        let expected = get_string_with_line_ending("RESULT\tTUPLE_END")
+            + &get_string_with_line_ending("RESULT\t1");
+        assert_eq!(get_byte_vec_as_string(&sink.0), expected);
+    }
+    /// A writer whose clones share the written bytes, so that the text written by a sink can be read back.
+    #[derive(Clone, Default)]
+    struct SharedWriter(Rc<RefCell<Vec<u8>>>);
+    impl Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    fn assert_untagged_output_match(record: impl FnOnce(), expected_str: &str) {
+        let out = SharedWriter::default();
+        let previous = set_output_sink(Box::new(WriterSink(out.clone())));
+        record();
+        set_output_sink(previous);
+        let actual = get_byte_vec_as_string(&out.0.borrow());
+        let expected = get_string_with_line_ending(expected_str);
+        assert_eq!(actual, expected);
     }
-    fn assert_untagged_output_match(ty: &str, val: &dyn Display, expected_str: &str) {
+    fn assert_output_match(record: &OutputRecord, expected_str: &str) {
         let mut out: Vec<u8> = Vec::new();
-        output(ty, &val, null_mut(), &mut out).unwrap();
+        output(record, &mut out).unwrap();
         let actual = get_byte_vec_as_string(&out);
         let expected = get_string_with_line_ending(expected_str);
         assert_eq!(actual, expected);
@@ -0,0 +1,112 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! The registry of the functions exported for QIR programs. Every module declares the functions it exports with
+//! `exports!`, which generates both the exported symbols and the entries of the registry, so that runners binding
+//! the declarations of a program based on the registry find exactly the exported functions.
+
+/// The output recording schema that a function belongs to. Programs must not mix both schemas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputSchema {
+    /// Untagged values along with array and tuple start and end markers.
+    Legacy,
+    /// Tagged values, where arrays and tuples are recorded with their number of items.
+    Labeled,
+}
+
+/// A function exported for QIR programs, given by its name, its number of parameters, and its address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExportedFunction {
+    pub name: &'static str,
+    pub param_count: u32,
+    pub address: usize,
+    /// The output recording schema of the function, for output recording functions.
+    pub output_schema: Option<OutputSchema>,
+}
+
+/// Returns the runtime functions exported by this library. A name occurs more than once if the function has
+/// variants with different numbers of parameters.
+#[must_use]
+pub fn exported_functions() -> Vec<ExportedFunction> {
+    [
+        crate::exports::functions(),
+        crate::arrays::exports::functions(),
+        crate::bigints::exports::functions(),
+        crate::callables::exports::functions(),
+        crate::math::exports::functions(),
+        crate::output_recording::exports::functions(),
+        crate::output_recording::legacy::exports::functions(),
+        crate::strings::exports::functions(),
+        crate::tuples::exports::functions(),
+    ]
+    .concat()
+}
+
+#[doc(hidden)]
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub const fn param_count(params: &[&str]) -> u32 {
+    params.len() as u32
+}
+
+/// Declares the functions of the enclosing module that are exported for QIR programs by their signatures. For
+/// every function, an `extern "C"` function of the same name that calls it is exported, which captures a panic of
+/// the function as a failure instead of unwinding into its caller, and the generated `exports` module lists them
+/// with `exports::functions`. Output recording functions give their schema after the signature, as in
+/// `fn __quantum__rt__int_record_output(val: i64, tag: *mut c_char) => Labeled;`.
+#[macro_export]
+macro_rules! exports {
+    ($($(#[$attr:meta])* fn $name:ident($($param:ident: $ty:ty),* $(,)?) $(-> $ret:ty)? $(=> $schema:ident)?;)*) => {
+        pub(crate) mod exports {
+            #[allow(clippy::wildcard_imports, unused_imports)]
+            use super::*;
+
+            $(
+                $(#[$attr])*
+                $crate::export_symbol!([$($schema)?] $name($($param: $ty),*) $(-> $ret)?);
+            )*
+
+            /// Returns the functions exported by the module.
+            #[allow(clippy::vec_init_then_push)]
+            pub(crate) fn functions() -> Vec<$crate::registry::ExportedFunction> {
+                let mut functions = Vec::new();
+                $(
+                    $(#[$attr])*
+                    functions.push($crate::registry::ExportedFunction {
+                        name: stringify!($name),
+                        param_count: $crate::registry::param_count(&[$(stringify!($param)),*]),
+                        address: $name as usize,
+                        output_schema: $crate::export_symbol!(@schema $($schema)?),
+                    });
+                )*
+                functions
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! export_symbol {
+    // The legacy output recording functions share their names with the labeled ones, so they are only exported
+    // through the registry.
+    ([Legacy] $name:ident($($param:ident: $ty:ty),*) $(-> $ret:ty)?) => {
+        #[allow(non_snake_case)]
+        unsafe extern "C" fn $name($($param: $ty),*) $(-> $ret)? {
+            $crate::failures::guard(|| super::$name($($param),*))
+        }
+    };
+    ([$($schema:ident)?] $name:ident($($param:ident: $ty:ty),*) $(-> $ret:ty)?) => {
+        #[no_mangle]
+        #[allow(non_snake_case)]
+        unsafe extern "C" fn $name($($param: $ty),*) $(-> $ret)? {
+            $crate::failures::guard(|| super::$name($($param),*))
+        }
+    };
+    (@schema) => {
+        None
+    };
+    (@schema $schema:ident) => {
+        Some($crate::registry::OutputSchema::$schema)
+    };
+}
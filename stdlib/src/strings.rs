@@ -10,19 +10,35 @@ use std::{
     rc::Rc,
 };
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__string_create(str: *mut c_char) -> *const CString {
+crate::exports! {
+    fn __quantum__rt__string_create(str: *mut c_char) -> *const CString;
+    fn __quantum__rt__string_get_data(str: *const CString) -> *const c_char;
+    fn __quantum__rt__string_get_length(str: *const CString) -> u32;
+    fn __quantum__rt__string_update_reference_count(str: *const CString, update: i32);
+    fn __quantum__rt__string_concatenate(s1: *const CString, s2: *const CString) -> *const CString;
+    fn __quantum__rt__string_equal(s1: *const CString, s2: *const CString) -> bool;
+    fn __quantum__rt__int_to_string(input: i64) -> *const CString;
+    fn __quantum__rt__double_to_string(input: c_double) -> *const CString;
+    fn __quantum__rt__bool_to_string(input: bool) -> *const CString;
+    fn __quantum__rt__pauli_to_string(input: Pauli) -> *const CString;
+    fn __quantum__rt__bigint_to_string(input: *const BigInt) -> *const CString;
+}
+
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__string_create(str: *mut c_char) -> *const CString {
     let cstring = CString::new(CStr::from_ptr(str).to_owned()).expect("Failed to create %String");
     Rc::into_raw(Rc::new(cstring))
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__string_get_data(str: *const CString) -> *const c_char {
+#[allow(non_snake_case)]
+#[must_use]
+pub unsafe fn __quantum__rt__string_get_data(str: *const CString) -> *const c_char {
     (*str).as_bytes_with_nul().as_ptr().cast::<c_char>()
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__string_get_length(str: *const CString) -> u32 {
+#[allow(non_snake_case)]
+#[must_use]
+pub unsafe fn __quantum__rt__string_get_length(str: *const CString) -> u32 {
     (*str)
         .as_bytes()
         .len()
@@ -30,16 +46,14 @@ pub unsafe extern "C" fn __quantum__rt__string_get_length(str: *const CString) -
         .expect("String length is too large for 32-bit integer.")
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__string_update_reference_count(
-    str: *const CString,
-    update: i32,
-) {
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__string_update_reference_count(str: *const CString, update: i32) {
     update_counts(str, update, false);
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__string_concatenate(
+#[allow(non_snake_case)]
+#[must_use]
+pub unsafe fn __quantum__rt__string_concatenate(
     s1: *const CString,
     s2: *const CString,
 ) -> *const CString {
@@ -51,11 +65,9 @@ pub unsafe extern "C" fn __quantum__rt__string_concatenate(
     ))
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__string_equal(
-    s1: *const CString,
-    s2: *const CString,
-) -> bool {
+#[allow(non_snake_case)]
+#[must_use]
+pub unsafe fn __quantum__rt__string_equal(s1: *const CString, s2: *const CString) -> bool {
     *s1 == *s2
 }
 
@@ -73,8 +85,9 @@ where
     }
 }
 
-#[no_mangle]
-pub extern "C" fn __quantum__rt__int_to_string(input: i64) -> *const CString {
+#[allow(non_snake_case)]
+#[must_use]
+pub fn __quantum__rt__int_to_string(input: i64) -> *const CString {
     convert(&input)
 }
 
@@ -88,18 +101,21 @@ pub(crate) fn double_to_string(input: c_double) -> String {
     }
 }
 
-#[no_mangle]
-pub extern "C" fn __quantum__rt__double_to_string(input: c_double) -> *const CString {
+#[allow(non_snake_case)]
+#[must_use]
+pub fn __quantum__rt__double_to_string(input: c_double) -> *const CString {
     convert(&double_to_string(input))
 }
 
-#[no_mangle]
-pub extern "C" fn __quantum__rt__bool_to_string(input: bool) -> *const CString {
+#[allow(non_snake_case)]
+#[must_use]
+pub fn __quantum__rt__bool_to_string(input: bool) -> *const CString {
     convert(&input)
 }
 
-#[no_mangle]
-pub extern "C" fn __quantum__rt__pauli_to_string(input: Pauli) -> *const CString {
+#[allow(non_snake_case)]
+#[must_use]
+pub fn __quantum__rt__pauli_to_string(input: Pauli) -> *const CString {
     match input {
         Pauli::I => convert(&"PauliI"),
         Pauli::X => convert(&"PauliX"),
@@ -108,8 +124,9 @@ pub extern "C" fn __quantum__rt__pauli_to_string(input: Pauli) -> *const CString
     }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__bigint_to_string(input: *const BigInt) -> *const CString {
+#[allow(non_snake_case)]
+#[must_use]
+pub unsafe fn __quantum__rt__bigint_to_string(input: *const BigInt) -> *const CString {
     convert(&*input)
 }
 
@@ -8,9 +8,17 @@ use std::{
     usize,
 };
 
+crate::exports! {
+    fn __quantum__rt__tuple_create(size: u64) -> *mut *const Vec<u8>;
+    fn __quantum__rt__tuple_copy(raw_tup: *mut *const Vec<u8>, force: bool) -> *mut *const Vec<u8>;
+    fn __quantum__rt__tuple_update_reference_count(raw_tup: *mut *const Vec<u8>, update: i32);
+    fn __quantum__rt__tuple_update_alias_count(raw_tup: *mut *const Vec<u8>, update: i32);
+}
+
 #[allow(clippy::cast_ptr_alignment)]
-#[no_mangle]
-pub extern "C" fn __quantum__rt__tuple_create(size: u64) -> *mut *const Vec<u8> {
+#[allow(non_snake_case)]
+#[must_use]
+pub fn __quantum__rt__tuple_create(size: u64) -> *mut *const Vec<u8> {
     let mut mem = vec![
         0_u8;
         <usize as std::convert::TryFrom<u64>>::try_from(size)
@@ -26,8 +34,8 @@ pub extern "C" fn __quantum__rt__tuple_create(size: u64) -> *mut *const Vec<u8>
 }
 
 #[allow(clippy::cast_ptr_alignment)]
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__tuple_copy(
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__tuple_copy(
     raw_tup: *mut *const Vec<u8>,
     force: bool,
 ) -> *mut *const Vec<u8> {
@@ -43,19 +51,16 @@ pub unsafe extern "C" fn __quantum__rt__tuple_copy(
     }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__tuple_update_reference_count(
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__tuple_update_reference_count(
     raw_tup: *mut *const Vec<u8>,
     update: i32,
 ) {
     update_counts(*raw_tup.wrapping_sub(1), update, false);
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn __quantum__rt__tuple_update_alias_count(
-    raw_tup: *mut *const Vec<u8>,
-    update: i32,
-) {
+#[allow(non_snake_case)]
+pub unsafe fn __quantum__rt__tuple_update_alias_count(raw_tup: *mut *const Vec<u8>, update: i32) {
     update_counts(*raw_tup.wrapping_sub(1), update, true);
 }
 